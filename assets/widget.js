@@ -0,0 +1,85 @@
+/*
+ * represence embeddable widget
+ *
+ *   <script src="https://your-server/widget.js" async></script>
+ *
+ * Optional attributes on the script tag:
+ *   data-target="#status"  render into an existing element instead of next to the script
+ *   data-prefix="currently "  text shown before the status
+ *   data-class="my-status"  extra class for the injected element
+ *
+ * The element's `--represence-color` custom property follows the accent color of the activity shown.
+ */
+(function () {
+  var script = document.currentScript;
+  if (!script) return;
+
+  var base = new URL(script.src, location.href);
+  var prefix = script.getAttribute("data-prefix") || "";
+  var target = script.getAttribute("data-target");
+
+  var element = target && document.querySelector(target);
+  if (!element) {
+    element = document.createElement("span");
+    script.parentNode.insertBefore(element, script.nextSibling);
+  }
+  element.classList.add("represence-status");
+  if (script.getAttribute("data-class")) {
+    element.classList.add(script.getAttribute("data-class"));
+  }
+
+  var delay = 1000;
+  var failedSockets = 0;
+
+  function render(data) {
+    try {
+      var presence = JSON.parse(data);
+      if (typeof presence.text === "string") element.textContent = prefix + presence.text;
+      if (typeof presence.color === "string") {
+        element.style.setProperty("--represence-color", presence.color);
+      } else {
+        element.style.removeProperty("--represence-color");
+      }
+    } catch (e) {
+      // Ignore malformed messages
+    }
+  }
+
+  function retry(connect) {
+    setTimeout(connect, delay);
+    delay = Math.min(delay * 2, 30000);
+  }
+
+  function connectSocket() {
+    var protocol = base.protocol === "https:" ? "wss:" : "ws:";
+    var opened = false;
+    var socket = new WebSocket(protocol + "//" + base.host + "/ws/represence");
+    socket.onopen = function () {
+      opened = true;
+      failedSockets = 0;
+      delay = 1000;
+    };
+    socket.onmessage = function (event) { render(event.data); };
+    socket.onclose = function () {
+      // Proxies that block WebSocket upgrades never open, fall back to SSE after a few tries
+      if (!opened && ++failedSockets >= 2 && window.EventSource) {
+        connectEvents();
+      } else {
+        retry(connectSocket);
+      }
+    };
+  }
+
+  function connectEvents() {
+    // EventSource reconnects on its own after transient errors
+    var source = new EventSource(base.origin + "/sse/represence");
+    source.onopen = function () { delay = 1000; };
+    source.onmessage = function (event) { render(event.data); };
+  }
+
+  if (window.WebSocket) {
+    connectSocket();
+  } else if (window.EventSource) {
+    connectEvents();
+  }
+})();
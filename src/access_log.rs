@@ -0,0 +1,130 @@
+use axum::body::HttpBody;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::config::env_var;
+use crate::web_server;
+
+/// `REPRESENCE_ACCESS_LOG=true`: address, request line, status and latency
+const DEFAULT_TEMPLATE: &str = "{ip} \"{method} {path}\" {status} {latency_ms}ms";
+/// Apache common log format
+const COMMON_TEMPLATE: &str = "{ip} - - [{time}] \"{method} {path} {version}\" {status} {bytes}";
+/// Apache combined log format, for log analyzers like GoAccess
+const COMBINED_TEMPLATE: &str = "{ip} - - [{time}] \"{method} {path} {version}\" {status} {bytes} \"{referer}\" \"{user_agent}\"";
+
+/// How each request is written to stdout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// A line with `{ip}`, `{time}`, `{method}`, `{path}`, `{version}`, `{status}`, `{bytes}`,
+    /// `{latency_ms}`, `{referer}` and `{user_agent}` filled in
+    Template(String),
+    /// One JSON object per line
+    Json,
+}
+
+impl std::str::FromStr for AccessLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "true" | "default" => Ok(Self::Template(DEFAULT_TEMPLATE.to_string())),
+            "common" => Ok(Self::Template(COMMON_TEMPLATE.to_string())),
+            "combined" => Ok(Self::Template(COMBINED_TEMPLATE.to_string())),
+            "json" => Ok(Self::Json),
+            template if template.contains('{') => Ok(Self::Template(template.to_string())),
+            _ => Err(format!("unknown access log format '{}', expected default, common, combined, json or a template", s)),
+        }
+    }
+}
+
+/// One served request. The query string is left out, it may carry tokens.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessLogEntry {
+    pub time: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+    pub method: String,
+    pub path: String,
+    pub version: String,
+    pub status: u16,
+    pub bytes: Option<u64>,
+    pub latency_ms: f64,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl AccessLogFormat {
+    /// `REPRESENCE_ACCESS_LOG`, off when unset or `false`
+    pub fn from_env() -> Option<Self> {
+        let value = env_var("REPRESENCE_ACCESS_LOG")?;
+        if value == "false" {
+            return None;
+        }
+        match value.parse() {
+            Ok(format) => Some(format),
+            Err(e) => {
+                eprintln!("Access log disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn render(&self, entry: &AccessLogEntry) -> String {
+        let template = match self {
+            Self::Template(template) => template,
+            Self::Json => return serde_json::to_string(entry).unwrap_or_default(),
+        };
+        let fields = [
+            ("{ip}", entry.ip.map_or_else(|| "-".to_string(), |ip| ip.to_string())),
+            ("{time}", entry.time.format("%d/%b/%Y:%H:%M:%S %z").to_string()),
+            ("{method}", entry.method.clone()),
+            ("{path}", entry.path.clone()),
+            ("{version}", entry.version.clone()),
+            ("{status}", entry.status.to_string()),
+            ("{bytes}", entry.bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string())),
+            ("{latency_ms}", format!("{:.1}", entry.latency_ms)),
+            ("{referer}", entry.referer.clone().unwrap_or_else(|| "-".to_string())),
+            ("{user_agent}", entry.user_agent.clone().unwrap_or_else(|| "-".to_string())),
+        ];
+        fields.iter().fold(template.clone(), |line, (placeholder, value)| line.replace(placeholder, value))
+    }
+}
+
+/// Write a line for every request once its response headers are ready
+pub async fn middleware(State(format): State<Arc<AccessLogFormat>>, request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let time = Utc::now();
+    let ip = web_server::client_ip(&request);
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let version = format!("{:?}", request.version());
+    let referer = header_value(request.headers(), header::REFERER);
+    let user_agent = header_value(request.headers(), header::USER_AGENT);
+
+    let response = next.run(request).await;
+
+    let entry = AccessLogEntry {
+        time,
+        ip,
+        method,
+        path,
+        version,
+        status: response.status().as_u16(),
+        bytes: response.body().size_hint().exact(),
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        referer,
+        user_agent,
+    };
+    println!("{}", format.render(&entry));
+    response
+}
+
+fn header_value(headers: &HeaderMap, name: impl header::AsHeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_string)
+}
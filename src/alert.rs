@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use crate::config::env_var;
+use crate::device;
+use crate::diagnostics::{Diagnostics, DetectorStatus, SharedDiagnostics};
+use crate::history::SharedHistory;
+use crate::web_server::SharedData;
+use crate::OutputData;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// How often the daemon's health is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Where alerts are pushed to
+#[derive(Debug, Clone)]
+pub enum AlertTarget {
+    /// Topic URL on ntfy.sh or a self-hosted server, e.g. `https://ntfy.sh/my-represence`
+    Ntfy { url: String, token: Option<String> },
+    Pushover { token: String, user: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    pub targets: Vec<AlertTarget>,
+    /// Named in the alerts, for several machines sending to one topic
+    pub host: String,
+}
+
+impl AlertConfig {
+    /// `REPRESENCE_NTFY_URL` and/or `REPRESENCE_PUSHOVER_TOKEN` with `REPRESENCE_PUSHOVER_USER`, `None` without either
+    pub fn from_env() -> Option<Self> {
+        let mut targets = Vec::new();
+        if let Some(url) = env_var("REPRESENCE_NTFY_URL") {
+            targets.push(AlertTarget::Ntfy { url, token: env_var("REPRESENCE_NTFY_TOKEN") });
+        }
+        if let (Some(token), Some(user)) = (env_var("REPRESENCE_PUSHOVER_TOKEN"), env_var("REPRESENCE_PUSHOVER_USER")) {
+            targets.push(AlertTarget::Pushover { token, user });
+        }
+        (!targets.is_empty()).then(|| Self { targets, host: device::hostname() })
+    }
+}
+
+/// Ways the daemon can degrade while nobody watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    ScanFailing,
+    VsCodeFailing,
+    HistoryFailing,
+    Stale,
+    PushOnly,
+}
+
+impl Problem {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::ScanFailing => "process scans keep failing",
+            Self::VsCodeFailing => "the VS Code extension stopped answering",
+            Self::HistoryFailing => "writing to the history database fails",
+            Self::Stale => "the update loop is stuck, the presence is marked stale",
+            Self::PushOnly => "the process list is unavailable, presence only changes through pushes",
+        }
+    }
+}
+
+/// What's wrong right now. History failures count when there were new ones since the last check.
+pub fn problems(diagnostics: &Diagnostics, data: &OutputData, new_history_failures: u64, now: DateTime<Utc>) -> Vec<Problem> {
+    let checks = [
+        (Problem::ScanFailing, diagnostics.last_scan.is_some() && diagnostics.scan_health(now).status == DetectorStatus::Degraded),
+        // Only once the extension answered, VS Code without it is fine
+        (Problem::VsCodeFailing, diagnostics.vscode.last_contact.is_some() && diagnostics.vscode_health(now).status == DetectorStatus::Degraded),
+        (Problem::HistoryFailing, new_history_failures > 0),
+        (Problem::Stale, data.stale),
+        (Problem::PushOnly, diagnostics.push_only),
+    ];
+    checks.into_iter().filter(|(_, failing)| *failing).map(|(problem, _)| problem).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub title: String,
+    pub message: String,
+    /// The problem went away, sent with a lower priority
+    pub resolved: bool,
+}
+
+/// An alert for every problem that started or went away between two checks
+pub fn alerts(host: &str, before: &[Problem], after: &[Problem]) -> Vec<Alert> {
+    let started = after.iter().filter(|problem| !before.contains(problem)).map(|problem| Alert {
+        title: format!("represence on {} degraded", host),
+        message: problem.describe().to_string(),
+        resolved: false,
+    });
+    let resolved = before.iter().filter(|problem| !after.contains(problem)).map(|problem| Alert {
+        title: format!("represence on {} recovered", host),
+        message: format!("No longer: {}", problem.describe()),
+        resolved: true,
+    });
+    started.chain(resolved).collect()
+}
+
+/// Check the daemon's health every `CHECK_INTERVAL` and push alerts for changes
+pub fn spawn(config: AlertConfig, shared_data: SharedData, diagnostics: SharedDiagnostics, history: Option<SharedHistory>) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Alerts disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let history_failures = || history.as_ref().map_or(0, |history| history.write_failures());
+        let mut last_failures = history_failures();
+        let mut last: Vec<Problem> = Vec::new();
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let failures = history_failures();
+            let current = problems(&*diagnostics.read().await, &shared_data.load(), failures - last_failures, Utc::now());
+            last_failures = failures;
+
+            for alert in alerts(&config.host, &last, &current) {
+                for target in &config.targets {
+                    if let Err(e) = send(&client, target, &alert).await {
+                        eprintln!("Failed to send alert '{}': {}", alert.message, e);
+                    }
+                }
+            }
+            last = current;
+        }
+    });
+}
+
+async fn send(client: &reqwest::Client, target: &AlertTarget, alert: &Alert) -> reqwest::Result<()> {
+    let request = match target {
+        AlertTarget::Ntfy { url, token } => {
+            let mut request = client
+                .post(url)
+                .header("Title", &alert.title)
+                .header("Priority", if alert.resolved { "default" } else { "high" })
+                .header("Tags", if alert.resolved { "white_check_mark" } else { "warning" })
+                .body(alert.message.clone());
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            request
+        }
+        AlertTarget::Pushover { token, user } => client.post(PUSHOVER_URL).json(&serde_json::json!({
+            "token": token,
+            "user": user,
+            "title": alert.title,
+            "message": alert.message,
+            "priority": if alert.resolved { 0 } else { 1 },
+        })),
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
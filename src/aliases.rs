@@ -0,0 +1,90 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::compose;
+use crate::config::env_json;
+use crate::process_finder::{RunningApp, TieredApp};
+
+/// What an executable is shown as, e.g. `zen-bin` as `Zen`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alias {
+    /// Shown wherever the executable name would be
+    pub name: Option<String>,
+    /// Kind of activity for combined activities and icons, one of [`compose::CATEGORIES`]
+    pub kind: Option<String>,
+    /// Detect the executable with this tier, needed for apps represence doesn't know
+    pub tier: Option<u32>,
+}
+
+/// Display names, kinds and tiers by executable name prefix
+#[derive(Debug, Clone, Default)]
+pub struct Aliases {
+    aliases: HashMap<String, Alias>,
+}
+
+impl Aliases {
+    /// Aliases from the JSON file at `REPRESENCE_ALIASES_FILE` or inline in `REPRESENCE_ALIASES_JSON`, none otherwise
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_ALIASES", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(aliases) => {
+                println!("Loaded aliases for {} app(s) from {}", aliases.aliases.len(), from);
+                aliases
+            }
+            Err(e) => {
+                eprintln!("Ignoring alias file {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// `{"zen-bin": {"name": "Zen", "kind": "browser"}, "gimp": {"name": "GIMP", "tier": 2}}`
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::from_str(source)?))
+    }
+
+    pub fn new(aliases: HashMap<String, Alias>) -> Self {
+        Self { aliases }
+    }
+
+    /// The apps to detect: aliases with a tier ahead of `defaults`, so their tier wins
+    pub fn apps(&self, defaults: Vec<TieredApp>) -> Vec<TieredApp> {
+        let mut apps: Vec<TieredApp> = self
+            .aliases
+            .iter()
+            .filter_map(|(name, alias)| Some(TieredApp { name: name.clone(), tier: alias.tier? }))
+            .collect();
+        // Longest first, `gimp-2` before `gimp`, since the first matching prefix wins
+        apps.sort_by(|a, b| b.name.len().cmp(&a.name.len()).then_with(|| a.name.cmp(&b.name)));
+        apps.extend(defaults);
+        apps
+    }
+
+    /// The alias of the longest matching prefix, like icons
+    pub fn get(&self, name: &str) -> Option<&Alias> {
+        self.aliases.iter().filter(|(key, _)| name.starts_with(key.as_str())).max_by_key(|(key, _)| key.len()).map(|(_, alias)| alias)
+    }
+
+    /// Give running apps their display names and kinds, before any text is made from them
+    pub fn apply(&self, apps: &mut [RunningApp]) {
+        for app in apps {
+            let Some(alias) = self.get(&app.name) else { continue };
+            if let Some(name) = &alias.name {
+                app.display_name = Some(name.clone());
+            }
+            // Unknown kinds are reported by `represence check-config`
+            if let Some(kind) = alias.kind.as_deref().and_then(|kind| compose::CATEGORIES.iter().find(|category| **category == kind)) {
+                app.kind = Some(kind);
+            }
+        }
+    }
+}
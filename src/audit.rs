@@ -0,0 +1,167 @@
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::config::env_var;
+use crate::history;
+use crate::trust::Trust;
+use crate::web_server::{self, AppState, Broadcaster};
+use crate::OutputData;
+
+/// Entries returned by `/api/audit` unless `limit` says otherwise
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// One line of the audit log: the presence exactly as anonymous visitors got it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub presence: OutputData,
+}
+
+/// Append-only JSON Lines file of everything served publicly, apart from the analytics history
+/// so it can be checked for leaks after adding detectors
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Last presence written, repeats aren't logged
+    last: Mutex<Option<OutputData>>,
+}
+
+pub type SharedAuditLog = Arc<AuditLog>;
+
+impl AuditLog {
+    /// The log at `REPRESENCE_AUDIT_LOG`, disabled when unset
+    pub fn from_env() -> Option<io::Result<Self>> {
+        let path = PathBuf::from(env_var("REPRESENCE_AUDIT_LOG")?);
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        Some(Self::open(&path))
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { path: path.to_path_buf(), file: Mutex::new(file), last: Mutex::new(None) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append the public view of `data` unless it is what was logged last, blocking
+    pub fn record(&self, at: DateTime<Utc>, data: &OutputData) -> io::Result<bool> {
+        let presence = Trust::Public.view(Arc::new(data.clone()));
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if last.as_ref() == Some(&*presence) {
+            return Ok(false);
+        }
+
+        let entry = AuditEntry { at, presence: (*presence).clone() };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).write_all(line.as_bytes())?;
+        *last = Some(entry.presence);
+        Ok(true)
+    }
+
+    /// The newest `limit` entries between `from` and `to` in time order, blocking
+    pub fn entries(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: usize) -> io::Result<Vec<AuditEntry>> {
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            // A line cut off by a crash shouldn't hide the rest of the log
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) else {
+                continue;
+            };
+            if from.is_some_and(|from| entry.at < from) || to.is_some_and(|to| entry.at >= to) {
+                continue;
+            }
+            entries.push(entry);
+        }
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.split_off(skip))
+    }
+}
+
+/// Log every broadcast presence as it goes out
+pub fn spawn(audit: SharedAuditLog, broadcaster: &Broadcaster) {
+    let mut rx = broadcaster.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let data = match rx.recv().await {
+                Ok(data) => data,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let audit = audit.clone();
+            let result = tokio::task::spawn_blocking(move || audit.record(Utc::now(), &data)).await;
+            if let Ok(Err(e)) = result {
+                eprintln!("Failed to write audit log: {}", e);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditParams {
+    /// Start (RFC 3339, `YYYY-MM-DD` or Unix seconds), inclusive
+    from: Option<String>,
+    /// End, exclusive
+    to: Option<String>,
+    /// Most recent entries to return, default 100
+    limit: Option<usize>,
+}
+
+/// What anonymous visitors were served and when
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    tag = "history",
+    params(AuditParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Audit entries in time order", body = [AuditEntry]),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "Audit log or the admin token is not configured"),
+    )
+)]
+pub async fn get_audit(State(state): State<AppState>, headers: HeaderMap, Query(params): Query<AuditParams>) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    let Some(audit) = state.audit.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (mut from, mut to) = (None, None);
+    if let Some(value) = params.from {
+        match history::parse_time(&value) {
+            Some(time) => from = Some(time),
+            None => return web_server::bad_request("invalid 'from'"),
+        }
+    }
+    if let Some(value) = params.to {
+        match history::parse_time(&value) {
+            Some(time) => to = Some(time),
+            None => return web_server::bad_request("invalid 'to'"),
+        }
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    match tokio::task::spawn_blocking(move || audit.entries(from, to, limit)).await {
+        Ok(Ok(entries)) => Json(entries).into_response(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to read audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,226 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{BackupArgs, RestoreArgs};
+use crate::config;
+use crate::history::{self, PresenceStore, SqliteHistory, StoreError};
+use crate::pidfile::PidFile;
+use crate::web_server::{self, AppState};
+
+/// Table in the snapshot holding the configuration, dropped again on restore
+const TABLE: &str = "represence_backup";
+
+/// Variables naming configuration files worth carrying over
+const CONFIG_FILES: [&str; 10] = [
+    "REPRESENCE_ICONS_FILE",
+    "REPRESENCE_PHRASES_FILE",
+    "REPRESENCE_COMPOSE_RULES_FILE",
+    "REPRESENCE_DAY_VARIANTS_FILE",
+    "REPRESENCE_IDLE_RULES_FILE",
+    "REPRESENCE_TRANSLATIONS_FILE",
+    "REPRESENCE_PROFILES_FILE",
+    "REPRESENCE_ALIASES_FILE",
+    "REPRESENCE_TEXT_SCRIPT",
+    "REPRESENCE_PRIORITY_SCRIPT",
+];
+
+/// Name of the environment entry in the backup table
+const ENV_ENTRY: &str = "env";
+/// File the environment is restored to, in the working directory
+const ENV_FILE: &str = "represence.env";
+
+/// Every `REPRESENCE_*` variable in effect as a dotenv file, including secrets
+pub fn env_file(vars: impl IntoIterator<Item = (String, String)>) -> String {
+    let mut vars: Vec<(String, String)> = vars.into_iter().filter(|(name, _)| name.starts_with("REPRESENCE_")).collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"\n", name, value)
+        })
+        .collect()
+}
+
+/// Snapshot the history into `path` along with the configuration, blocking. Returns the configuration files included.
+pub fn create(history: &dyn PresenceStore, path: &Path) -> Result<Vec<PathBuf>, StoreError> {
+    history.snapshot(path)?;
+    let conn = Connection::open(path)?;
+    conn.execute_batch(&format!("CREATE TABLE {} (name TEXT PRIMARY KEY, path TEXT, content BLOB NOT NULL)", TABLE))?;
+    let insert = |name: &str, path: Option<&Path>, content: &[u8]| {
+        conn.execute(
+            &format!("INSERT INTO {} (name, path, content) VALUES (?1, ?2, ?3)", TABLE),
+            params![name, path.map(|path| path.to_string_lossy()), content],
+        )
+    };
+
+    insert(ENV_ENTRY, None, env_file(config::env_vars()).as_bytes())?;
+    let mut files = Vec::new();
+    for name in CONFIG_FILES {
+        let file = match config::env_var(name) {
+            Some(file) => PathBuf::from(file),
+            None => continue,
+        };
+        // The path as the server resolves it, the backup may be restored from another directory
+        let file = std::fs::canonicalize(&file).unwrap_or(file);
+        match std::fs::read(&file) {
+            Ok(content) => {
+                insert(name, Some(&file), &content)?;
+                files.push(file);
+            }
+            Err(e) => eprintln!("Leaving {} out of the backup, failed to read {}: {}", name, file.display(), e),
+        }
+    }
+    Ok(files)
+}
+
+/// `represence backup`: snapshot the history database and configuration into one file
+pub fn run_backup(args: BackupArgs) -> Result<(), Box<dyn Error>> {
+    let history = match history::open_from_env() {
+        Some(history) => history?,
+        None => return Err("history is disabled (REPRESENCE_HISTORY=false)".into()),
+    };
+    if args.path.exists() {
+        return Err(format!("{} already exists", args.path.display()).into());
+    }
+    let files = create(history.as_ref(), &args.path)?;
+    eprintln!("Backed up {} and {} configuration file(s) to {}", history.describe(), files.len(), args.path.display());
+    eprintln!("The backup holds every REPRESENCE_* variable, tokens included, keep it private");
+    Ok(())
+}
+
+/// What a restore wrote and what it left alone
+#[derive(Debug, Default)]
+pub struct Restored {
+    pub written: Vec<PathBuf>,
+    /// Existing files kept without `force`
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Put the history from `backup` at `history_path` and its configuration files back where they were, blocking.
+/// The environment goes to `represence.env` in `env_dir`. Existing files are only replaced with `force`.
+pub fn restore(backup: &Path, history_path: &Path, env_dir: &Path, force: bool) -> Result<Restored, Box<dyn Error>> {
+    if history_path.exists() && !force {
+        return Err(format!("{} already exists, restore with --force to replace it", history_path.display()).into());
+    }
+
+    let mut restored = Restored::default();
+    let conn = Connection::open(backup)?;
+    let entries: Vec<(String, Option<String>, Vec<u8>)> = {
+        let table: Option<String> = conn
+            .query_row("SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1", [TABLE], |row| row.get(0))
+            .optional()?;
+        if table.is_none() {
+            return Err(format!("{} isn't a represence backup", backup.display()).into());
+        }
+        let mut statement = conn.prepare(&format!("SELECT name, path, content FROM {}", TABLE))?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    drop(conn);
+
+    let mut write = |path: PathBuf, content: &[u8]| -> std::io::Result<()> {
+        if path.exists() && !force {
+            restored.skipped.push(path);
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)?;
+        restored.written.push(path);
+        Ok(())
+    };
+    for (name, path, content) in &entries {
+        match (name.as_str(), path) {
+            (ENV_ENTRY, _) => write(env_dir.join(ENV_FILE), content)?,
+            (_, Some(path)) => write(PathBuf::from(path), content)?,
+            (_, None) => {}
+        }
+    }
+
+    // Copy next to the target first, so a failure leaves the current database alone
+    let staging = history_path.with_extension("restoring");
+    if let Some(parent) = history_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(backup, &staging)?;
+    Connection::open(&staging)?.execute_batch(&format!("DROP TABLE {}", TABLE))?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", history_path.display(), suffix));
+    }
+    std::fs::rename(&staging, history_path)?;
+    restored.written.push(history_path.to_path_buf());
+    Ok(restored)
+}
+
+/// `represence restore`: put a backup in place, refusing while the server runs
+pub fn run_restore(args: RestoreArgs) -> Result<(), Box<dyn Error>> {
+    // Hold the PID file, so the server doesn't start halfway through
+    let _pid_file = match PidFile::path_from_env() {
+        Some(path) => Some(PidFile::acquire(&path, false).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let restored = restore(&args.path, &SqliteHistory::path_from_env(), Path::new("."), args.force)?;
+    for path in &restored.written {
+        eprintln!("Restored {}", path.display());
+    }
+    for path in &restored.skipped {
+        eprintln!("Kept the existing {}, restore with --force to replace it", path.display());
+    }
+    eprintln!("Review {} and move it to .env or the service's environment", ENV_FILE);
+    Ok(())
+}
+
+/// History database and configuration as one file for `represence restore`, requires the admin token
+#[utoipa::path(
+    get,
+    path = "/api/admin/backup",
+    tag = "history",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "SQLite backup file", content_type = "application/vnd.sqlite3"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "History or the admin token is not configured"),
+    )
+)]
+pub async fn get_backup(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    let Some(history) = state.history.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let path = std::env::temp_dir().join(format!("represence-backup-{}-{}.db", std::process::id(), chrono::Utc::now().timestamp_millis()));
+        let content = create(history.as_ref(), &path).map_err(|e| e.to_string()).and_then(|_| std::fs::read(&path).map_err(|e| e.to_string()));
+        let _ = std::fs::remove_file(&path);
+        content
+    })
+    .await;
+
+    match result {
+        Ok(Ok(content)) => (
+            [
+                (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"represence-backup-{}.db\"", chrono::Local::now().format("%Y-%m-%d")),
+                ),
+                // Holds tokens
+                (header::CACHE_CONTROL, "no-store".to_string()),
+            ],
+            content,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            eprintln!("Backup failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{Local, NaiveTime};
+
+use crate::config::env_list;
+
+/// Processes that never influence the presence, always or during a daily time window
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    entries: Vec<BlockEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct BlockEntry {
+    /// Executable name prefix, like the app list
+    prefix: String,
+    /// Local time window the entry applies in, may wrap past midnight
+    window: Option<(NaiveTime, NaiveTime)>,
+}
+
+impl Blocklist {
+    /// `REPRESENCE_BLOCKLIST` entries are `name` or `name@HH:MM-HH:MM`, e.g. `keepassxc,steam@09:00-17:00`
+    pub fn from_env() -> Self {
+        Self::parse(&env_list("REPRESENCE_BLOCKLIST"))
+    }
+
+    pub fn parse(entries: &[String]) -> Self {
+        let entries = entries
+            .iter()
+            .filter_map(|entry| match BlockEntry::parse(entry) {
+                Some(entry) => Some(entry),
+                None => {
+                    eprintln!("Ignoring blocklist entry '{}', expected name or name@HH:MM-HH:MM", entry);
+                    None
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether a process is hidden right now
+    pub fn blocks(&self, name: &str) -> bool {
+        self.blocks_at(name, Local::now().time())
+    }
+
+    pub fn blocks_at(&self, name: &str, time: NaiveTime) -> bool {
+        self.entries.iter().any(|entry| name.starts_with(&entry.prefix) && entry.applies_at(time))
+    }
+}
+
+impl BlockEntry {
+    fn parse(entry: &str) -> Option<Self> {
+        let (prefix, window) = match entry.split_once('@') {
+            Some((prefix, window)) => {
+                let (start, end) = window.split_once('-')?;
+                let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+                let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+                (prefix, Some((start, end)))
+            }
+            None => (entry, None),
+        };
+
+        let prefix = prefix.trim();
+        (!prefix.is_empty()).then(|| Self { prefix: prefix.to_string(), window })
+    }
+
+    fn applies_at(&self, time: NaiveTime) -> bool {
+        match self.window {
+            None => true,
+            Some((start, end)) if start <= end => start <= time && time < end,
+            Some((start, end)) => time >= start || time < end,
+        }
+    }
+}
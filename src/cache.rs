@@ -0,0 +1,65 @@
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+
+use crate::config::env_parse;
+
+/// For assets requested with a `?v=` cache buster, which changes whenever they should be fetched again
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// `/widget.js` without a cache buster, so a new release shows up within the hour
+const WIDGET_MAX_AGE_SECS: u64 = 3600;
+
+/// `Cache-Control` per route, handlers setting their own keep it
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    /// How long the presence may be served from a cache
+    pub presence_max_age: u64,
+}
+
+impl CachePolicy {
+    /// `REPRESENCE_CACHE_MAX_AGE_SECS`, 5 by default
+    pub fn from_env() -> Self {
+        Self { presence_max_age: env_parse("REPRESENCE_CACHE_MAX_AGE_SECS", 5) }
+    }
+
+    /// The header for a request, `None` to leave the response alone
+    pub fn cache_control(&self, path: &str, query: Option<&str>, authorized: bool) -> Option<String> {
+        let busted = query.is_some_and(|query| url::form_urlencoded::parse(query.as_bytes()).any(|(key, _)| key == "v"));
+        // Responses for friend tokens must not end up in shared caches
+        let scope = match authorized {
+            true => "private",
+            false => "public",
+        };
+        match path {
+            "/widget.js" | "/api/badge.svg" if busted => Some(IMMUTABLE.to_string()),
+            "/widget.js" => Some(format!("public, max-age={}", WIDGET_MAX_AGE_SECS)),
+            "/api/represence" | "/api/represence.txt" | "/api/shields" | "/api/badge.svg" => {
+                Some(format!("{}, max-age={}", scope, self.presence_max_age))
+            }
+            path if path.starts_with("/api/represence/") || path.starts_with("/v1/users/") => {
+                Some(format!("{}, max-age={}", scope, self.presence_max_age))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub async fn middleware(State(policy): State<Arc<CachePolicy>>, request: Request, next: Next) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let query = request.uri().query();
+    let authorized = request.headers().contains_key(header::AUTHORIZATION)
+        || query.is_some_and(|query| url::form_urlencoded::parse(query.as_bytes()).any(|(key, _)| key == "token"));
+    let cache_control = policy.cache_control(request.uri().path(), query, authorized);
+
+    let mut response = next.run(request).await;
+    if let Some(value) = cache_control.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+            response.headers_mut().entry(header::CACHE_CONTROL).or_insert(value);
+        }
+    }
+    response
+}
@@ -0,0 +1,287 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::config::env_var;
+
+/// Occurrences of a recurring event are searched this far at most
+const MAX_RECURRENCE_DAYS: i64 = 366 * 5;
+
+const DEFAULT_MESSAGE: &str = "in a meeting";
+
+/// A busy event from an ICS calendar
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    start: NaiveDateTime,
+    duration: Duration,
+    recurrence: Option<Recurrence>,
+    /// Starts of cancelled occurrences, local time
+    exceptions: Vec<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Recurrence {
+    /// Days between occurrences, 7 × INTERVAL for weekly rules
+    step_days: i64,
+    weekdays: Vec<Weekday>,
+    until: Option<NaiveDateTime>,
+    count: Option<u32>,
+}
+
+impl CalendarEvent {
+    /// Whether an occurrence of the event covers `now`
+    pub fn covers(&self, now: NaiveDateTime) -> bool {
+        let Some(recurrence) = &self.recurrence else {
+            return self.start <= now && now < self.start + self.duration;
+        };
+
+        let time = self.start.time();
+        let first = self.start.date();
+        let mut occurrences = 0;
+        for offset in 0..MAX_RECURRENCE_DAYS {
+            let day = first + Duration::days(offset);
+            let start = day.and_time(time);
+            if start > now || recurrence.until.is_some_and(|until| start > until) {
+                return false;
+            }
+
+            let week = offset.div_euclid(7) * 7;
+            let matches = match recurrence.weekdays.is_empty() {
+                true => offset % recurrence.step_days == 0,
+                false => week % recurrence.step_days == 0 && recurrence.weekdays.contains(&day.weekday()),
+            };
+            if !matches {
+                continue;
+            }
+
+            occurrences += 1;
+            if recurrence.count.is_some_and(|count| occurrences > count) {
+                return false;
+            }
+            if now < start + self.duration && !self.exceptions.contains(&start) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Busy events from an ICS document. Cancelled, free (`TRANSP:TRANSPARENT`) and all-day events are left out.
+pub fn parse_ics(ics: &str) -> Vec<CalendarEvent> {
+    // Long lines are folded with a leading space or tab
+    let unfolded = ics.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+
+    unfolded
+        .split("BEGIN:VEVENT")
+        .skip(1)
+        .filter_map(|block| {
+            let block = block.split("END:VEVENT").next()?;
+            let properties: Vec<(&str, &str, &str)> = block
+                .lines()
+                .filter_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    let (name, params) = key.split_once(';').unwrap_or((key, ""));
+                    Some((name, params, value.trim()))
+                })
+                .collect();
+            let property = |name: &str| properties.iter().find(|(key, _, _)| *key == name).map(|(_, params, value)| (*params, *value));
+
+            if property("STATUS").is_some_and(|(_, status)| status == "CANCELLED")
+                || property("TRANSP").is_some_and(|(_, transp)| transp == "TRANSPARENT")
+            {
+                return None;
+            }
+
+            let start = property("DTSTART").and_then(|(params, value)| parse_date_time(params, value))?;
+            let duration = match property("DTEND").and_then(|(params, value)| parse_date_time(params, value)) {
+                Some(end) => end - start,
+                None => property("DURATION").and_then(|(_, value)| parse_duration(value))?,
+            };
+
+            Some(CalendarEvent {
+                summary: property("SUMMARY").map(|(_, summary)| unescape(summary)).unwrap_or_default(),
+                start,
+                duration,
+                recurrence: property("RRULE").and_then(|(_, rule)| parse_rrule(rule)),
+                exceptions: properties
+                    .iter()
+                    .filter(|(key, _, _)| *key == "EXDATE")
+                    .flat_map(|(_, params, value)| value.split(',').filter_map(|date| parse_date_time(params, date)))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// A date-time in local time. UTC values (`Z`) are converted, `TZID` and floating times are taken as local.
+/// Plain dates (all-day events) give `None`.
+fn parse_date_time(params: &str, value: &str) -> Option<NaiveDateTime> {
+    if params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME") {
+        return None;
+    }
+    match value.strip_suffix('Z') {
+        Some(utc) => {
+            let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+            Some(Utc.from_utc_datetime(&utc).with_timezone(&Local).naive_local())
+        }
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok(),
+    }
+}
+
+/// `PT1H30M`, `P1D` and the like
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('P')?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                duration += match unit {
+                    'W' => Duration::weeks(amount),
+                    'D' => Duration::days(amount),
+                    'H' => Duration::hours(amount),
+                    'M' => Duration::minutes(amount),
+                    'S' => Duration::seconds(amount),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(duration)
+}
+
+/// Daily and weekly rules, anything else counts as a single occurrence
+fn parse_rrule(rule: &str) -> Option<Recurrence> {
+    let part = |name: &str| rule.split(';').find_map(|part| part.strip_prefix(name)?.strip_prefix('='));
+    let interval: i64 = part("INTERVAL").and_then(|interval| interval.parse().ok()).unwrap_or(1).max(1);
+    let step_days = match part("FREQ")? {
+        "DAILY" => interval,
+        "WEEKLY" => interval * 7,
+        _ => return None,
+    };
+
+    let weekdays = part("BYDAY")
+        .map(|days| {
+            days.split(',')
+                .filter_map(|day| match day {
+                    "MO" => Some(Weekday::Mon),
+                    "TU" => Some(Weekday::Tue),
+                    "WE" => Some(Weekday::Wed),
+                    "TH" => Some(Weekday::Thu),
+                    "FR" => Some(Weekday::Fri),
+                    "SA" => Some(Weekday::Sat),
+                    "SU" => Some(Weekday::Sun),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let until = part("UNTIL").and_then(|until| {
+        parse_date_time("", until).or_else(|| NaiveDate::parse_from_str(until, "%Y%m%d").ok()?.and_hms_opt(23, 59, 59))
+    });
+
+    Some(Recurrence { step_days, weekdays, until, count: part("COUNT").and_then(|count| count.parse().ok()) })
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\n", " ").replace("\\N", " ").replace("\\,", ",").replace("\\;", ";").replace("\\\\", "\\")
+}
+
+/// Busy events of the configured calendars and the presence shown during them
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    pub events: Arc<RwLock<Vec<CalendarEvent>>>,
+    /// `{title}` is replaced with the event's summary
+    pub message: String,
+}
+
+impl Calendar {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { events: Arc::default(), message: message.into() }
+    }
+
+    /// Message from `REPRESENCE_CALENDAR_MESSAGE`, e.g. `in a meeting ({title})`
+    pub fn from_env() -> Self {
+        Self::new(env_var("REPRESENCE_CALENDAR_MESSAGE").unwrap_or_else(|| DEFAULT_MESSAGE.to_string()))
+    }
+
+    /// Presence text while an event is going on
+    pub async fn busy_text(&self, now: DateTime<Local>) -> Option<String> {
+        let events = self.events.read().await;
+        let event = events.iter().find(|event| event.covers(now.naive_local()))?;
+        Some(self.message.replace("{title}", &event.summary))
+    }
+}
+
+#[cfg(feature = "calendar")]
+pub mod client {
+    use std::time::Duration;
+
+    use super::{parse_ics, Calendar};
+    use crate::config::{env_list, env_parse};
+
+    const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+    /// ICS feeds and local files from `REPRESENCE_CALENDAR_URLS`, `None` when empty
+    #[derive(Debug, Clone)]
+    pub struct CalendarConfig {
+        pub sources: Vec<String>,
+        pub refresh: Duration,
+    }
+
+    impl CalendarConfig {
+        pub fn from_env() -> Option<Self> {
+            let sources = env_list("REPRESENCE_CALENDAR_URLS");
+            (!sources.is_empty()).then(|| Self {
+                sources,
+                refresh: Duration::from_secs(env_parse("REPRESENCE_CALENDAR_REFRESH_SECS", 900).max(60)),
+            })
+        }
+    }
+
+    /// Re-read the calendars periodically, keeping the last good copy of a feed that fails
+    pub fn spawn(config: CalendarConfig, calendar: Calendar) {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS)).build() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Calendar disabled, failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+
+        tokio::spawn(async move {
+            let mut feeds = vec![Vec::new(); config.sources.len()];
+            loop {
+                for (source, feed) in config.sources.iter().zip(feeds.iter_mut()) {
+                    let ics = match source.starts_with("http://") || source.starts_with("https://") {
+                        true => match client.get(source).send().await.and_then(|response| response.error_for_status()) {
+                            Ok(response) => response.text().await.map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        },
+                        false => tokio::fs::read_to_string(source).await.map_err(|e| e.to_string()),
+                    };
+                    match ics {
+                        Ok(ics) => *feed = parse_ics(&ics),
+                        Err(e) => eprintln!("Failed to read calendar {}: {}", redact(source), e),
+                    }
+                }
+                *calendar.events.write().await = feeds.concat();
+                tokio::time::sleep(config.refresh).await;
+            }
+        });
+    }
+
+    /// Secret ICS links carry their token in the path, only the host is logged
+    fn redact(source: &str) -> String {
+        match url::Url::parse(source) {
+            Ok(url) => format!("{}://{}/…", url.scheme(), url.host_str().unwrap_or_default()),
+            Err(_) => source.to_string(),
+        }
+    }
+}
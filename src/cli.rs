@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config_file::ConfigFile;
+use crate::export::{self, ExportFormat};
+
+/// Like Discord Rich Presence, but for anywhere
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Profile from `REPRESENCE_PROFILES_FILE` to apply, `REPRESENCE_PROFILE` by default
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the presence server (the default)
+    Serve(ServeArgs),
+    /// Dump the presence history as CSV or JSON
+    Export(ExportArgs),
+    /// Snapshot the history database and configuration into one file
+    Backup(BackupArgs),
+    /// Put a backup's history database and configuration in place
+    Restore(RestoreArgs),
+    /// Serve presence from a scripted scenario instead of the running apps
+    Simulate(SimulateArgs),
+    /// Print the running server's presence for a waybar or polybar module on every change
+    Waybar(WaybarArgs),
+    /// Check /proc, the desktop session, the VS Code extension, configuration and the port, and say what to fix
+    Doctor,
+    /// Validate the configuration files, with the line and column of each error
+    CheckConfig(CheckConfigArgs),
+    /// Ask which running apps to show and about privacy, and write a starter `.env`
+    Init(InitArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    /// Stop an instance that is already running instead of refusing to start
+    #[arg(long)]
+    pub replace: bool,
+    /// Fork into the background, logging to `REPRESENCE_LOG_FILE`, for machines without systemd
+    #[arg(long)]
+    pub daemon: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    pub format: ExportFormat,
+    /// Start of the range (RFC 3339, YYYY-MM-DD or unix seconds), inclusive
+    #[arg(long, value_parser = export::parse_time_arg)]
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range, exclusive
+    #[arg(long, value_parser = export::parse_time_arg)]
+    pub to: Option<DateTime<Utc>>,
+    /// Only events produced by apps starting with this name
+    #[arg(long)]
+    pub app: Option<String>,
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct BackupArgs {
+    /// File to create
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// File made by `represence backup` or downloaded from `/api/admin/backup`
+    pub path: PathBuf,
+    /// Replace the history database and configuration files that already exist
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckConfigArgs {
+    /// Kind of the file to check, every file set in the environment is checked without one
+    #[arg(requires = "path")]
+    pub file: Option<ConfigFile>,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// File to write
+    #[arg(short, long, default_value = ".env")]
+    pub output: PathBuf,
+    /// Replace the file if it exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct WaybarArgs {
+    /// WebSocket of the server, `ws://127.0.0.1:$REPRESENCE_PORT/ws/represence` by default
+    #[arg(long)]
+    pub url: Option<String>,
+    /// Friend token for the full details, `REPRESENCE_ADMIN_TOKEN` by default
+    #[arg(long)]
+    pub token: Option<String>,
+    /// Print just the text instead of waybar's JSON, for polybar and other bars
+    #[arg(long)]
+    pub plain: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SimulateArgs {
+    /// Scenario JSON file with the steps to play
+    pub scenario: PathBuf,
+    /// Time acceleration, overrides the scenario's `speed`
+    #[arg(long)]
+    pub speed: Option<f64>,
+    /// Start over after the last step, overrides the scenario's `loop`
+    #[arg(long = "loop")]
+    pub repeat: bool,
+}
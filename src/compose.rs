@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::config::env_json;
+use crate::process_finder::RunningApp;
+use crate::vscode_client::FileInfo;
+
+/// Combines the top app with another running app into one sentence, e.g. editor + media player
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompositionRule {
+    /// Category of the top app
+    pub primary: String,
+    /// Category of the app mentioned second, the highest ranked running one is used
+    pub secondary: String,
+    /// `{primary}` and `{secondary}` are replaced by each app's activity
+    pub template: String,
+}
+
+/// Ordered composition rules, the first one matching the running apps wins
+#[derive(Debug, Clone)]
+pub struct Composer {
+    rules: Vec<CompositionRule>,
+}
+
+impl Default for Composer {
+    fn default() -> Self {
+        let rule = |primary: &str, secondary: &str| CompositionRule {
+            primary: primary.to_string(),
+            secondary: secondary.to_string(),
+            template: "{primary} while {secondary}".to_string(),
+        };
+        Self::new(vec![rule("editor", "media"), rule("terminal", "media")])
+    }
+}
+
+impl Composer {
+    /// Rules from the JSON array in `REPRESENCE_COMPOSE_RULES_FILE` or inline in `REPRESENCE_COMPOSE_RULES_JSON`, the built-in rules otherwise (`[]` turns composing off)
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_COMPOSE_RULES", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(composer) => composer,
+            Err(e) => {
+                eprintln!("Using built-in composition rules, failed to load {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::from_str(source)?))
+    }
+
+    pub fn new(rules: Vec<CompositionRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Combined text for the running apps (highest priority first), `None` when no rule applies
+    pub fn compose(&self, running_apps: &[RunningApp], file_info: Option<&FileInfo>) -> Option<String> {
+        let primary = running_apps.first()?;
+        let primary_category = app_category(primary)?;
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.primary == primary_category)
+            .find_map(|rule| {
+                let secondary = running_apps[1..]
+                    .iter()
+                    .find(|app| app_category(app) == Some(rule.secondary.as_str()))?;
+                Some(
+                    rule.template
+                        .replace("{primary}", &activity(primary, file_info))
+                        .replace("{secondary}", &activity(secondary, file_info)),
+                )
+            })
+    }
+}
+
+/// Every category `category` returns
+pub const CATEGORIES: [&str; 6] = ["editor", "browser", "chat", "media", "game", "terminal"];
+
+/// Kind of activity an app stands for, used to match composition rules
+pub fn category(app_name: &str) -> Option<&'static str> {
+    let category = match app_name {
+        name if name.starts_with("code") => "editor",
+        name if name.starts_with("vlc") || name.starts_with("stremio") || name.starts_with("spotify") => "media",
+        name if name.starts_with("cmus") || name.starts_with("mpd") => "media",
+        "jellyfin" | "plex" | "lastfm" => "media",
+        name if name.starts_with("discord") => "chat",
+        name if name.starts_with("zen") || name.starts_with("chrome") => "browser",
+        name if name.starts_with("steam") => "game",
+        name if name.starts_with("ghostty") => "terminal",
+        _ => return None,
+    };
+    Some(category)
+}
+
+/// The app's kind from the aliases, the built-in one otherwise
+pub fn app_category(app: &RunningApp) -> Option<&'static str> {
+    app.kind.or_else(|| category(&app.name))
+}
+
+/// Short phrase for an app that reads well on either side of a composed sentence
+pub fn activity(app: &RunningApp, file_info: Option<&FileInfo>) -> String {
+    match app.name.as_str() {
+        name if name.starts_with("code") => match file_info {
+            Some(file_info) if !file_info.language_id.is_empty() => format!("writing {}", language_name(&file_info.language_id)),
+            _ => "coding".to_string(),
+        },
+        name if name.starts_with("vlc") => "watching a movie".to_string(),
+        name if name.starts_with("stremio") => "streaming a show".to_string(),
+        "jellyfin" | "plex" => "watching something".to_string(),
+        name if name.starts_with("spotify") || name.starts_with("cmus") || name.starts_with("mpd") => "listening to music".to_string(),
+        "lastfm" => "listening to music".to_string(),
+        name if name.starts_with("discord") => "chatting on Discord".to_string(),
+        name if name.starts_with("zen") || name.starts_with("chrome") => "browsing".to_string(),
+        name if name.starts_with("steam") => "gaming".to_string(),
+        name if name.starts_with("ghostty") => "hacking in the terminal".to_string(),
+        _ => format!("using {}", app.display_name()),
+    }
+}
+
+/// Display name of a VS Code language ID, e.g. `rust` → `Rust`
+fn language_name(language_id: &str) -> String {
+    match language_id {
+        "javascript" => "JavaScript".to_string(),
+        "typescript" => "TypeScript".to_string(),
+        "typescriptreact" => "TSX".to_string(),
+        "javascriptreact" => "JSX".to_string(),
+        "cpp" => "C++".to_string(),
+        "csharp" => "C#".to_string(),
+        "html" | "css" | "json" | "yaml" | "toml" | "sql" => language_id.to_uppercase(),
+        "shellscript" => "shell scripts".to_string(),
+        _ => {
+            let mut chars = language_id.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+    }
+}
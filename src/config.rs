@@ -0,0 +1,77 @@
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// Variables that changed since startup, from a profile or a reloaded `.env`. They're kept here instead of being
+/// set in the process environment, which other threads and C libraries may be reading at the same time.
+static OVERLAY: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// Read a non-empty environment variable, the overlay over the process environment
+pub fn env_var(name: &str) -> Option<String> {
+    let overlay = OVERLAY.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned();
+    overlay.or_else(|| env::var(name).ok()).filter(|value| !value.trim().is_empty())
+}
+
+/// Every variable as [`env_var`] reads it, sorted by name
+pub fn env_vars() -> Vec<(String, String)> {
+    let mut vars: BTreeMap<String, String> = env::vars().collect();
+    vars.extend(OVERLAY.read().unwrap_or_else(|e| e.into_inner()).clone());
+    vars.into_iter().collect()
+}
+
+/// Replace the overlay, an empty value hides a variable set in the process environment
+pub fn set_overlay(vars: BTreeMap<String, String>) {
+    *OVERLAY.write().unwrap_or_else(|e| e.into_inner()) = vars;
+}
+
+/// Parse an environment variable, falling back to `default` when unset or invalid
+pub fn env_parse<T: FromStr>(name: &str, default: T) -> T {
+    env_var(name)
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Read a comma-separated list from an environment variable (same format as REPRESENCE_DOMAIN_ALLOWED)
+pub fn env_list(name: &str) -> Vec<String> {
+    env_var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// IANA timezone name like `Europe/Istanbul` in a configuration file, for `#[serde(deserialize_with)]`
+pub fn deserialize_timezone<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Tz>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(name) => name.parse().map(Some).map_err(|_| serde::de::Error::custom(format!("unknown timezone '{}'", name))),
+        None => Ok(None),
+    }
+}
+
+/// Contents of the JSON configuration inline in `<name>_JSON`, where mounting a file is a hassle, or in the file
+/// `<name>_FILE` points at. Inline wins. Comes with where it's from for messages: the variable or the path.
+pub fn env_source(name: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<(String, std::io::Result<String>)> {
+    let inline = format!("{}_JSON", name);
+    if let Some(source) = lookup(&inline) {
+        return Some((inline, Ok(source)));
+    }
+    let path = lookup(&format!("{}_FILE", name))?;
+    let source = std::fs::read_to_string(&path);
+    Some((path, source))
+}
+
+/// A configuration as parsed, or why it couldn't be read or parsed
+pub type Loaded<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Parse the configuration from [`env_source`], `None` when neither variable is set
+pub fn env_json<T>(name: &str, parse: impl FnOnce(&str) -> serde_json::Result<T>) -> Option<(String, Loaded<T>)> {
+    let (origin, source) = env_source(name, env_var)?;
+    Some((origin, source.map_err(Into::into).and_then(|source| Ok(parse(&source)?))))
+}
@@ -0,0 +1,544 @@
+use axum::extract::Query;
+use axum::response::Json;
+use chrono::{NaiveTime, Timelike};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+
+use crate::aliases::Aliases;
+use crate::compose::{self, Composer};
+use crate::i18n::Translations;
+use crate::icons::Icons;
+use crate::idle_text::IdleRules;
+use crate::phrases::Phrases;
+use crate::profiles::Profiles;
+use crate::variables;
+use crate::variants::DayVariants;
+
+/// Activity kinds an icon can be set for besides the app categories
+const EXTRA_ICON_KINDS: [&str; 3] = ["live", "meeting", "idle"];
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Placeholders phrases can use
+const PHRASE_PLACEHOLDERS: [&str; 2] = ["file", "language"];
+
+const WEEKDAYS: [&str; 14] = [
+    "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday",
+];
+
+/// The JSON files shaping texts and icons, each named by an environment variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum, utoipa::ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigFile {
+    Icons,
+    Phrases,
+    ComposeRules,
+    DayVariants,
+    IdleRules,
+    Translations,
+    Profiles,
+    Aliases,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    /// Loads, but part of it never takes effect
+    Warning,
+}
+
+/// Something wrong with a configuration file. Errors from the parser point at a line and column,
+/// the rest at the entry in the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Like `rules[2]` or `kinds.editr`
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn warning(entry: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, line: None, column: None, entry: Some(entry.into()), message: message.into() }
+    }
+
+    pub fn error(entry: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, ..Self::warning(entry, message) }
+    }
+
+    fn parse(e: &serde_json::Error) -> Self {
+        // The parser's message ends with the position we report on our own
+        let message = e.to_string();
+        let message = message.strip_suffix(&format!(" at line {} column {}", e.line(), e.column())).unwrap_or(&message).to_string();
+        Self { severity: Severity::Error, line: Some(e.line()), column: Some(e.column()), entry: None, message }
+    }
+}
+
+impl fmt::Display for Problem {
+    /// `3:12: error: ...` like a compiler, for editors to jump to
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.entry {
+            Some(entry) => write!(f, "{}: {}: {}", severity, entry, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+impl ConfigFile {
+    pub const ALL: [ConfigFile; 8] = [
+        Self::Icons,
+        Self::Phrases,
+        Self::ComposeRules,
+        Self::DayVariants,
+        Self::IdleRules,
+        Self::Translations,
+        Self::Profiles,
+        Self::Aliases,
+    ];
+
+    /// The variable pointing at the file
+    pub fn var(self) -> &'static str {
+        match self {
+            Self::Icons => "REPRESENCE_ICONS_FILE",
+            Self::Phrases => "REPRESENCE_PHRASES_FILE",
+            Self::ComposeRules => "REPRESENCE_COMPOSE_RULES_FILE",
+            Self::DayVariants => "REPRESENCE_DAY_VARIANTS_FILE",
+            Self::IdleRules => "REPRESENCE_IDLE_RULES_FILE",
+            Self::Translations => "REPRESENCE_TRANSLATIONS_FILE",
+            Self::Profiles => "REPRESENCE_PROFILES_FILE",
+            Self::Aliases => "REPRESENCE_ALIASES_FILE",
+        }
+    }
+
+    /// The variable holding the file's contents inline instead, which wins over the file
+    pub fn inline_var(self) -> String {
+        self.var().replace("_FILE", "_JSON")
+    }
+
+    /// The contents as configured and where they're from, `None` when neither variable is set
+    pub fn source(self, lookup: impl Fn(&str) -> Option<String>) -> Option<(String, std::io::Result<String>)> {
+        crate::config::env_source(self.var().trim_end_matches("_FILE"), lookup)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Icons => "icons",
+            Self::Phrases => "phrases",
+            Self::ComposeRules => "compose-rules",
+            Self::DayVariants => "day-variants",
+            Self::IdleRules => "idle-rules",
+            Self::Translations => "translations",
+            Self::Profiles => "profiles",
+            Self::Aliases => "aliases",
+        }
+    }
+
+    /// Parse `source` the way the server loads it, then look for entries that would never apply
+    pub fn check(self, source: &str) -> Vec<Problem> {
+        let parsed = match self {
+            Self::Icons => Icons::parse(source).map(drop),
+            Self::Phrases => Phrases::parse(source).map(drop),
+            Self::ComposeRules => Composer::parse(source).map(drop),
+            Self::DayVariants => DayVariants::parse(source).map(drop),
+            Self::IdleRules => IdleRules::parse(source).map(drop),
+            Self::Translations => Translations::parse(source).map(drop),
+            Self::Profiles => Profiles::parse(source).map(drop),
+            Self::Aliases => Aliases::parse(source).map(drop),
+        };
+        if let Err(e) = parsed {
+            return vec![Problem::parse(&e)];
+        }
+
+        let document: Value = serde_json::from_str(source).unwrap_or_default();
+        match self {
+            Self::Icons => lint_icons(&document),
+            Self::Phrases => lint_phrases(&document),
+            Self::ComposeRules => lint_compose_rules(&document),
+            Self::DayVariants => lint_day_variants(&document),
+            Self::IdleRules => lint_idle_rules(&document),
+            Self::Translations => lint_translations(&document),
+            Self::Profiles => Vec::new(),
+            Self::Aliases => lint_aliases(&document),
+        }
+    }
+
+    /// JSON Schema of the file, for editors to complete and validate it
+    pub fn schema(self) -> Value {
+        let text = json!({ "type": "string" });
+        let timezone = json!({ "type": "string", "description": "IANA name like Europe/Istanbul, the system timezone otherwise" });
+        let weekdays = json!({ "type": "array", "items": { "enum": WEEKDAYS } });
+        let (description, schema) = match self {
+            Self::Icons => {
+                let icon = json!({
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "emoji": text,
+                        "icon": { "type": "string", "description": "Image URL" },
+                        "color": { "type": "string", "description": "CSS color, e.g. #22c55e" },
+                    },
+                });
+                let kinds: Vec<&str> = compose::CATEGORIES.iter().chain(&EXTRA_ICON_KINDS).copied().collect();
+                (
+                    "Emoji, icon image and accent color per app name prefix and per kind of activity",
+                    json!({
+                        "type": "object",
+                        "additionalProperties": false,
+                        "properties": {
+                            "apps": { "type": "object", "additionalProperties": icon },
+                            "kinds": { "type": "object", "propertyNames": { "enum": kinds }, "additionalProperties": icon },
+                        },
+                    }),
+                )
+            }
+            Self::Phrases => (
+                "Alternative texts per app name prefix, {file} and {language} come from VS Code",
+                json!({ "type": "object", "additionalProperties": { "type": "array", "items": text } }),
+            ),
+            Self::ComposeRules => (
+                "Combine the top app with another running app, the first matching rule wins",
+                json!({
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["primary", "secondary", "template"],
+                        "properties": {
+                            "primary": { "enum": compose::CATEGORIES },
+                            "secondary": { "enum": compose::CATEGORIES },
+                            "template": { "type": "string", "description": "{primary} and {secondary} are replaced by each app's activity" },
+                        },
+                    },
+                }),
+            ),
+            Self::DayVariants => (
+                "Texts per app name prefix on workdays and on weekends",
+                json!({
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["apps"],
+                    "properties": {
+                        "timezone": timezone,
+                        "weekend": weekdays,
+                        "apps": {
+                            "type": "object",
+                            "additionalProperties": {
+                                "type": "object",
+                                "additionalProperties": false,
+                                "properties": { "weekday": text, "weekend": text },
+                            },
+                        },
+                    },
+                }),
+            ),
+            Self::IdleRules => {
+                let time = json!({ "type": "string", "pattern": "^[0-9]{1,2}:[0-9]{2}$", "description": "HH:MM" });
+                (
+                    "Texts for local time windows while nothing is detected, the first matching rule wins",
+                    json!({
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["rules"],
+                        "properties": {
+                            "timezone": timezone,
+                            "rules": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "additionalProperties": false,
+                                    "required": ["from", "to", "text"],
+                                    "properties": { "days": weekdays, "from": time, "to": time, "text": text },
+                                },
+                            },
+                        },
+                    }),
+                )
+            }
+            Self::Translations => (
+                "Status texts per language tag, keyed by the English text or template",
+                json!({ "type": "object", "additionalProperties": { "type": "object", "additionalProperties": text } }),
+            ),
+            Self::Profiles => (
+                "Variables per profile name, applied over the environment with --profile or REPRESENCE_PROFILE",
+                json!({
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "object",
+                        "propertyNames": { "pattern": "^REPRESENCE_", "not": { "const": "REPRESENCE_PROFILE" } },
+                        "additionalProperties": text,
+                    },
+                }),
+            ),
+            Self::Aliases => (
+                "Display name, kind of activity and tier per executable name prefix",
+                json!({
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "properties": {
+                            "name": { "type": "string", "description": "Shown instead of the executable name" },
+                            "kind": { "enum": compose::CATEGORIES },
+                            "tier": { "type": "integer", "minimum": 1, "description": "Detect the app with this tier, 1 ranks highest" },
+                        },
+                    },
+                }),
+            ),
+        };
+
+        let mut schema = schema;
+        schema["title"] = json!(self.var());
+        schema["description"] = json!(description);
+        schema
+    }
+}
+
+/// Every file's schema under `$defs`, by file name
+pub fn schema() -> Value {
+    let defs: serde_json::Map<String, Value> = ConfigFile::ALL.iter().map(|file| (file.name().to_string(), file.schema())).collect();
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": "represence configuration files",
+        "$defs": defs,
+    })
+}
+
+fn lint_icons(document: &Value) -> Vec<Problem> {
+    let kinds = document["kinds"].as_object().into_iter().flatten();
+    kinds
+        .filter(|(kind, _)| !compose::CATEGORIES.contains(&kind.as_str()) && !EXTRA_ICON_KINDS.contains(&kind.as_str()))
+        .map(|(kind, _)| Problem::warning(format!("kinds.{}", kind), "not a kind of activity, so it's never used"))
+        .collect()
+}
+
+fn lint_aliases(document: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for (app, alias) in document.as_object().into_iter().flatten() {
+        if alias.as_object().is_none_or(|alias| alias.is_empty()) {
+            problems.push(Problem::warning(app, "sets nothing, so it changes nothing"));
+        }
+        if let Some(kind) = alias["kind"].as_str().filter(|kind| !compose::CATEGORIES.contains(kind)) {
+            problems.push(Problem::warning(
+                format!("{}.kind", app),
+                format!("'{}' is not a kind of activity, so it's ignored, expected one of {}", kind, compose::CATEGORIES.join(", ")),
+            ));
+        }
+    }
+    problems
+}
+
+fn lint_phrases(document: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for (app, pool) in document.as_object().into_iter().flatten() {
+        let phrases = pool.as_array().map(Vec::as_slice).unwrap_or_default();
+        if phrases.is_empty() {
+            problems.push(Problem::warning(app, "has no phrases, the built-in text is used"));
+        }
+        for (index, phrase) in phrases.iter().enumerate() {
+            for placeholder in placeholders(phrase.as_str().unwrap_or_default()) {
+                if !PHRASE_PLACEHOLDERS.contains(&placeholder) {
+                    problems.push(Problem::warning(format!("{}[{}]", app, index), format!("{{{}}} is never filled in, only {{file}} and {{language}} are", placeholder)));
+                }
+            }
+        }
+    }
+    problems
+}
+
+fn lint_compose_rules(document: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let rules = document.as_array().map(Vec::as_slice).unwrap_or_default();
+    for (index, rule) in rules.iter().enumerate() {
+        let entry = format!("[{}]", index);
+        for side in ["primary", "secondary"] {
+            let category = rule[side].as_str().unwrap_or_default();
+            if !compose::CATEGORIES.contains(&category) {
+                problems.push(Problem::error(&entry, format!("{} '{}' is not a category, expected one of {}", side, category, compose::CATEGORIES.join(", "))));
+            }
+        }
+        let earlier = rules[..index].iter().position(|other| other["primary"] == rule["primary"] && other["secondary"] == rule["secondary"]);
+        if let Some(earlier) = earlier {
+            problems.push(Problem::warning(&entry, format!("never applies, [{}] combines the same categories first", earlier)));
+        }
+        let template = placeholders(rule["template"].as_str().unwrap_or_default());
+        if !template.contains(&"primary") && !template.contains(&"secondary") {
+            problems.push(Problem::warning(&entry, "the template mentions neither {primary} nor {secondary}"));
+        }
+    }
+    problems
+}
+
+fn lint_day_variants(document: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if document["weekend"].as_array().is_some_and(|days| days_of(days).len() == 7) {
+        problems.push(Problem::warning("weekend", "lists every day, weekday texts never show"));
+    }
+    for (app, variant) in document["apps"].as_object().into_iter().flatten() {
+        if variant["weekday"].is_null() && variant["weekend"].is_null() {
+            problems.push(Problem::warning(format!("apps.{}", app), "has neither a weekday nor a weekend text"));
+        }
+    }
+    problems
+}
+
+fn lint_idle_rules(document: &Value) -> Vec<Problem> {
+    let rules = document["rules"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let windows: Vec<(Vec<u32>, Vec<bool>)> = rules.iter().map(|rule| (days_of(rule["days"].as_array().map(Vec::as_slice).unwrap_or_default()), minutes(rule))).collect();
+
+    let mut problems = Vec::new();
+    for (index, (days, window)) in windows.iter().enumerate() {
+        // An earlier rule on all of its days and all of its minutes always wins
+        let shadowed_by = windows[..index].iter().position(|(earlier_days, earlier_window)| {
+            days.iter().all(|day| earlier_days.contains(day)) && window.iter().zip(earlier_window).all(|(minute, earlier)| !minute || *earlier)
+        });
+        if let Some(earlier) = shadowed_by {
+            problems.push(Problem::warning(format!("rules[{}]", index), format!("never applies, rules[{}] covers its whole window first", earlier)));
+        }
+    }
+    problems
+}
+
+fn lint_translations(document: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    for (language, texts) in document.as_object().into_iter().flatten() {
+        for (english, translated) in texts.as_object().into_iter().flatten() {
+            let known = placeholders(english);
+            for placeholder in placeholders(translated.as_str().unwrap_or_default()) {
+                if !known.contains(&placeholder) {
+                    problems.push(Problem::warning(
+                        format!("{}.{}", language, english),
+                        format!("{{{}}} isn't in the English text, so it's never filled in", placeholder),
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Names in `{braces}`
+fn placeholders(text: &str) -> Vec<&str> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .collect()
+}
+
+/// Weekdays as numbers from Monday, every day for an empty list
+fn days_of(days: &[Value]) -> Vec<u32> {
+    if days.is_empty() {
+        return (0..7).collect();
+    }
+    let mut numbers: Vec<u32> = days
+        .iter()
+        .filter_map(|day| day.as_str()?.parse::<chrono::Weekday>().ok())
+        .map(|day| day.num_days_from_monday())
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+/// Minutes of the day a rule's window covers, wrapping past midnight like the rule does
+fn minutes(rule: &Value) -> Vec<bool> {
+    let time = |key: &str| NaiveTime::parse_from_str(rule[key].as_str().unwrap_or_default(), "%H:%M").ok();
+    let (Some(from), Some(to)) = (time("from"), time("to")) else {
+        return vec![false; 1440];
+    };
+    let minute = |time: NaiveTime| (time.num_seconds_from_midnight() / 60) as usize;
+    let (from, to) = (minute(from), minute(to));
+    (0..1440)
+        .map(|m| match from.cmp(&to) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => m >= from && m < to,
+            std::cmp::Ordering::Greater => m >= from || m < to,
+        })
+        .collect()
+}
+
+/// `represence check-config`: check `path` as `file`, or the `REPRESENCE_*` variables and every file they configure.
+/// Warnings are printed, errors fail the command.
+pub fn run(file: Option<ConfigFile>, path: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut errors = 0;
+    let files: Vec<(ConfigFile, String, std::io::Result<String>)> = match (file, path) {
+        (Some(file), Some(path)) => vec![(file, path.display().to_string(), std::fs::read_to_string(path))],
+        _ => {
+            // `.env` is loaded into the environment by now
+            let problems = variables::check(crate::config::env_vars());
+            if problems.is_empty() {
+                println!("environment: ok");
+            }
+            for problem in &problems {
+                println!("environment: {}", problem);
+            }
+            errors += problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+
+            ConfigFile::ALL
+                .iter()
+                .filter_map(|file| {
+                    let (from, source) = file.source(crate::config::env_var)?;
+                    Some((*file, from, source))
+                })
+                .collect()
+        }
+    };
+
+    for (file, path, source) in files {
+        let problems = match source {
+            Ok(source) => file.check(&source),
+            Err(e) => vec![Problem { severity: Severity::Error, line: None, column: None, entry: None, message: e.to_string() }],
+        };
+        if problems.is_empty() {
+            println!("{}: ok ({})", path, file.name());
+        }
+        for problem in &problems {
+            println!("{}:{}", path, problem);
+        }
+        errors += problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+    }
+
+    match errors {
+        0 => Ok(()),
+        _ => Err(format!("{} error(s) in the configuration", errors).into()),
+    }
+}
+
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct SchemaParams {
+    /// Only this file's schema
+    pub file: Option<ConfigFile>,
+}
+
+/// JSON Schema of the configuration files, to point an editor's `$schema` at
+#[utoipa::path(
+    get,
+    path = "/api/config-schema",
+    tag = "diagnostics",
+    params(SchemaParams),
+    responses(
+        (status = 200, description = "JSON Schema, every file under `$defs` unless `file` picks one", body = Object),
+        (status = 400, description = "Unknown file"),
+    )
+)]
+pub async fn get_config_schema(Query(params): Query<SchemaParams>) -> Json<Value> {
+    match params.file {
+        Some(file) => {
+            let mut schema = file.schema();
+            schema["$schema"] = json!(JSON_SCHEMA_DIALECT);
+            Json(schema)
+        }
+        None => Json(schema()),
+    }
+}
@@ -0,0 +1,167 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use futures_util::StreamExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::config::env_parse;
+use crate::diagnostics::SharedDiagnostics;
+use crate::web_server::ClientGuard;
+
+/// Polled by service managers and load balancers, not by anyone looking at the presence
+const PROBES: [&str; 3] = ["/health", "/livez", "/readyz"];
+
+/// How long a request waits for an on-demand scan before answering with what it has
+const ON_DEMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub type SharedConsumers = Arc<Consumers>;
+
+/// Who reads the presence, so the update loop can slow down while nobody does
+#[derive(Debug)]
+pub struct Consumers {
+    last_request: Mutex<Instant>,
+    /// Open WebSocket connections, counted by their handlers
+    pub websocket_clients: Arc<AtomicUsize>,
+    /// Open Server-Sent Events responses and gRPC watches
+    pub event_streams: Arc<AtomicUsize>,
+    request: Notify,
+    /// Presence older than this is refreshed before `/api/represence` answers
+    max_age: Option<Duration>,
+    scan_request: Notify,
+    scanned: Notify,
+}
+
+impl Default for Consumers {
+    fn default() -> Self {
+        Self {
+            last_request: Mutex::new(Instant::now()),
+            websocket_clients: Arc::new(AtomicUsize::new(0)),
+            event_streams: Arc::new(AtomicUsize::new(0)),
+            request: Notify::new(),
+            max_age: None,
+            scan_request: Notify::new(),
+            scanned: Notify::new(),
+        }
+    }
+}
+
+impl Consumers {
+    pub fn shared() -> SharedConsumers {
+        Arc::new(Self::default())
+    }
+
+    /// On-demand scans for presence older than `REPRESENCE_ON_DEMAND_MAX_AGE_SECS`, off when unset
+    pub fn from_env() -> SharedConsumers {
+        let max_age = env_parse("REPRESENCE_ON_DEMAND_MAX_AGE_SECS", 0u64);
+        Self::with_max_age((max_age > 0).then(|| Duration::from_secs(max_age)))
+    }
+
+    pub fn with_max_age(max_age: Option<Duration>) -> SharedConsumers {
+        Arc::new(Self { max_age, ..Self::default() })
+    }
+
+    /// Note an incoming request and wake an update loop that is saving energy
+    pub fn touch(&self) {
+        *self.last_request.lock().unwrap() = Instant::now();
+        self.request.notify_one();
+    }
+
+    /// How long nobody has asked for the presence, zero while a stream is open
+    pub fn idle_for(&self) -> Duration {
+        let streams = self.websocket_clients.load(Ordering::Relaxed) + self.event_streams.load(Ordering::Relaxed);
+        match streams {
+            0 => self.last_request.lock().unwrap().elapsed(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Resolves on the next request
+    pub async fn requested(&self) {
+        self.request.notified().await
+    }
+
+    /// Scan first when the last one is older than the on-demand max age, waiting at most `ON_DEMAND_TIMEOUT`
+    pub async fn refresh(&self, diagnostics: &SharedDiagnostics) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        let last_scan = diagnostics.read().await.last_scan;
+        let age = last_scan.map(|at| (chrono::Utc::now() - at).to_std().unwrap_or_default());
+        if age.is_some_and(|age| age <= max_age) {
+            return;
+        }
+
+        // Registered before asking, so a scan finishing right away still counts
+        let scanned = self.scanned.notified();
+        self.scan_request.notify_one();
+        let _ = tokio::time::timeout(ON_DEMAND_TIMEOUT, scanned).await;
+    }
+
+    /// Resolves when a consumer wants a scan right now
+    pub async fn scan_requested(&self) {
+        self.scan_request.notified().await
+    }
+
+    /// Called by the update loop after every scan
+    pub fn scan_finished(&self) {
+        self.scanned.notify_waiters();
+    }
+}
+
+/// Record every request but health probes, and count event streams for as long as their body is being sent
+pub async fn middleware(State(consumers): State<SharedConsumers>, request: Request, next: Next) -> Response {
+    if !PROBES.contains(&request.uri().path()) {
+        consumers.touch();
+    }
+    let response = next.run(request).await;
+
+    let streaming = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"text/event-stream"));
+    if !streaming {
+        return response;
+    }
+    let guard = ClientGuard::new(&consumers.event_streams);
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().inspect(move |_| {
+            let _ = &guard;
+        }))
+    })
+}
+
+/// Slow scanning down once nobody has been connected for a while
+#[derive(Debug, Clone)]
+pub struct EnergySaving {
+    pub consumers: SharedConsumers,
+    /// Time without consumers before slowing down
+    pub after: Duration,
+    /// Scan interval while saving energy, `None` scans only when a request comes in
+    pub interval: Option<Duration>,
+}
+
+impl EnergySaving {
+    /// `REPRESENCE_ENERGY_SAVING_MINS` (off when unset or 0) and `REPRESENCE_ENERGY_SAVING_INTERVAL_SECS` (0 pauses scanning)
+    pub fn from_env(consumers: &SharedConsumers) -> Option<Self> {
+        let after = env_parse("REPRESENCE_ENERGY_SAVING_MINS", 0u64);
+        let interval = env_parse("REPRESENCE_ENERGY_SAVING_INTERVAL_SECS", 60u64);
+        (after > 0).then(|| Self {
+            consumers: consumers.clone(),
+            after: Duration::from_secs(after * 60),
+            interval: (interval > 0).then(|| Duration::from_secs(interval)),
+        })
+    }
+
+    /// Whether nobody has been around long enough to slow down
+    pub fn active(&self) -> bool {
+        self.consumers.idle_for() >= self.after
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+use crate::config::{env_parse, env_var};
+
+/// Commands for the update loop from outside the HTTP API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control {
+    /// Re-read `.env` and the config files
+    Reload,
+    /// Hide the presence behind the privacy text, or show it again
+    TogglePrivacy,
+    /// Turn privacy mode on or off
+    SetPrivacy(bool),
+    /// Forget cached scan results, scan right away and broadcast even when nothing changed
+    Rescan,
+    /// Show `text` instead of the detected presence for `duration`
+    Override { text: String, duration: Duration },
+    /// Go back to the detected presence before the override runs out
+    EndOverride,
+    /// Apply another profile, or none, and reload
+    SwitchProfile(Option<String>),
+}
+
+pub type Controls = mpsc::Receiver<Control>;
+pub type ControlSender = mpsc::Sender<Control>;
+
+pub fn channel() -> (ControlSender, Controls) {
+    mpsc::channel(8)
+}
+
+/// Shown instead of the presence in privacy mode, `REPRESENCE_PRIVACY_TEXT` or `busy`
+pub fn privacy_text() -> String {
+    env_var("REPRESENCE_PRIVACY_TEXT").unwrap_or_else(|| "busy".to_string())
+}
+
+/// Privacy mode at startup and after switching to a profile that sets it, on with `REPRESENCE_PRIVACY=true`
+pub fn privacy_from_env() -> Option<String> {
+    env_parse("REPRESENCE_PRIVACY", false).then(privacy_text)
+}
+
+/// SIGHUP reloads the config, SIGUSR1 toggles privacy mode and SIGUSR2 rescans
+pub fn spawn_signals(tx: &ControlSender) -> std::io::Result<()> {
+    let signals = [
+        (SignalKind::hangup(), Control::Reload),
+        (SignalKind::user_defined1(), Control::TogglePrivacy),
+        (SignalKind::user_defined2(), Control::Rescan),
+    ];
+    for (kind, control) in signals {
+        let mut stream = signal(kind)?;
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                if tx.send(control.clone()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(())
+}
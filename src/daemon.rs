@@ -0,0 +1,125 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+
+/// How often the log size is checked for rotation
+const ROTATE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where a daemon writes its output, rotated once it grows too big
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub path: PathBuf,
+    /// Size that triggers a rotation
+    pub max_bytes: u64,
+    /// Rotated files kept next to the log, `represence.log.1` being the newest
+    pub keep: u32,
+}
+
+impl LogFile {
+    /// `REPRESENCE_LOG_FILE` (`$XDG_STATE_HOME/represence/represence.log`), rotated at
+    /// `REPRESENCE_LOG_MAX_BYTES` (10 MB) keeping `REPRESENCE_LOG_KEEP` (5) old files
+    pub fn from_env() -> Self {
+        let path = env_var("REPRESENCE_LOG_FILE").map(PathBuf::from).unwrap_or_else(|| {
+            env_var("XDG_STATE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env_var("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("represence")
+                .join("represence.log")
+        });
+        Self::new(path, env_parse("REPRESENCE_LOG_MAX_BYTES", 10 * 1024 * 1024), env_parse("REPRESENCE_LOG_KEEP", 5))
+    }
+
+    pub fn new(path: PathBuf, max_bytes: u64, keep: u32) -> Self {
+        Self { path, max_bytes, keep: keep.max(1) }
+    }
+
+    pub fn open(&self) -> io::Result<File> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)
+    }
+
+    /// Shift the rotated files up by one and start a new log once it reached `max_bytes`,
+    /// returning the new file to write to
+    pub fn rotate(&self) -> io::Result<Option<File>> {
+        if std::fs::metadata(&self.path)?.len() < self.max_bytes {
+            return Ok(None);
+        }
+        for index in (1..self.keep).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                std::fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))?;
+        self.open().map(Some)
+    }
+
+    fn rotated(&self, index: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+}
+
+/// Detach from the terminal with a double fork, continuing only in the background process with
+/// stdout and stderr going to `log`. Must run before any threads are started.
+pub fn daemonize(log: &LogFile) -> io::Result<()> {
+    // Open everything first so mistakes still show up in the terminal
+    let log_file = log.open()?;
+    let dev_null = File::open("/dev/null")?;
+    println!("Starting in the background, logging to {}", log.path.display());
+
+    // SAFETY: no other threads exist yet, the parents exit right away without running destructors
+    unsafe {
+        fork_and_exit_parent()?;
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // The session leader exits too, so we can never get a controlling terminal again
+        fork_and_exit_parent()?;
+    }
+
+    redirect(dev_null.as_raw_fd(), &[libc::STDIN_FILENO])?;
+    redirect(log_file.as_raw_fd(), &[libc::STDOUT_FILENO, libc::STDERR_FILENO])
+}
+
+/// Rotate the log in a background thread, pointing stdout and stderr at the new file each time
+pub fn spawn_rotation(log: LogFile) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(ROTATE_CHECK_INTERVAL);
+        match log.rotate() {
+            Ok(Some(file)) => {
+                if let Err(e) = redirect(file.as_raw_fd(), &[libc::STDOUT_FILENO, libc::STDERR_FILENO]) {
+                    eprintln!("Failed to switch to the rotated log {}: {}", log.path.display(), e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to rotate the log {}: {}", log.path.display(), e),
+        }
+    });
+}
+
+unsafe fn fork_and_exit_parent() -> io::Result<()> {
+    match libc::fork() {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(()),
+        _ => libc::_exit(0),
+    }
+}
+
+fn redirect(fd: i32, targets: &[i32]) -> io::Result<()> {
+    for &target in targets {
+        // SAFETY: both descriptors are open, dup2 replaces the target atomically
+        if unsafe { libc::dup2(fd, target) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+use crate::config::env_parse;
+use crate::web_server::{Broadcaster, SharedData};
+use crate::OutputData;
+
+pub const BUS_NAME: &str = "org.represence.Presence1";
+pub const OBJECT_PATH: &str = "/org/represence/Presence1";
+
+/// `org.represence.Presence1` on the session bus, for status bars and scripts on this machine
+pub struct PresenceInterface {
+    status: String,
+}
+
+#[interface(name = "org.represence.Presence1")]
+impl PresenceInterface {
+    /// The presence text, with `PropertiesChanged` emitted whenever it changes
+    #[zbus(property)]
+    fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    /// Sent with the new text on every change, for clients that don't follow properties
+    #[zbus(signal)]
+    async fn changed(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+/// Publish the presence on the session bus unless `REPRESENCE_DBUS=false`
+pub fn spawn(shared_data: SharedData, broadcaster: &Broadcaster) {
+    if !env_parse("REPRESENCE_DBUS", true) {
+        return;
+    }
+    let updates = broadcaster.subscribe();
+    tokio::spawn(async move {
+        if let Err(e) = serve(shared_data, updates).await {
+            eprintln!("Not publishing presence on D-Bus, the session bus is unavailable: {}", e);
+        }
+    });
+}
+
+async fn serve(shared_data: SharedData, mut updates: broadcast::Receiver<Arc<OutputData>>) -> zbus::Result<()> {
+    let interface = PresenceInterface { status: shared_data.load().text.clone() };
+    let connection = zbus::connection::Builder::session()?.name(BUS_NAME)?.serve_at(OBJECT_PATH, interface)?.build().await?;
+    println!("Publishing presence on the session bus as {}", BUS_NAME);
+    let interface = connection.object_server().interface::<_, PresenceInterface>(OBJECT_PATH).await?;
+
+    loop {
+        let data = match updates.recv().await {
+            Ok(data) => data,
+            Err(RecvError::Lagged(_)) => shared_data.load_full(),
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        let mut presence = interface.get_mut().await;
+        if presence.status == data.text {
+            continue;
+        }
+        presence.status = data.text.clone();
+        presence.status_changed(interface.signal_emitter()).await?;
+        drop(presence);
+        PresenceInterface::changed(interface.signal_emitter(), &data.text).await?;
+    }
+}
@@ -0,0 +1,69 @@
+use std::cmp::max;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+
+use crate::trust::Trust;
+use crate::web_server::{Broadcaster, SharedData};
+use crate::OutputData;
+
+/// Follows the public presence text for services with strict rate limits, like profile and status updates
+pub struct Debounce {
+    shared_data: SharedData,
+    updates: broadcast::Receiver<Arc<OutputData>>,
+    /// How long a text must stay unchanged before it's passed on
+    quiet: Duration,
+    /// Least time between two texts passed on
+    min_interval: Duration,
+    /// The text passed on last and when
+    last: Option<(String, Instant)>,
+}
+
+impl Debounce {
+    pub fn new(shared_data: SharedData, broadcaster: &Broadcaster, quiet: Duration, min_interval: Duration) -> Self {
+        Self { updates: broadcaster.subscribe(), shared_data, quiet, min_interval, last: None }
+    }
+
+    /// The next text that differs from the last one, once it settled. `None` when the server shuts down.
+    pub async fn next(&mut self) -> Option<String> {
+        let mut pending = self.unsent(public_text(self.shared_data.load_full()));
+        let mut changed_at = Instant::now();
+        loop {
+            let update = match &pending {
+                Some(text) => {
+                    let settled = changed_at + self.quiet;
+                    let deadline = self.last.as_ref().map_or(settled, |(_, sent_at)| max(settled, *sent_at + self.min_interval));
+                    match tokio::time::timeout_at(deadline, self.updates.recv()).await {
+                        Ok(update) => update,
+                        Err(_) => {
+                            self.last = Some((text.clone(), Instant::now()));
+                            return pending;
+                        }
+                    }
+                }
+                None => self.updates.recv().await,
+            };
+            let text = match update {
+                Ok(data) => public_text(data),
+                Err(RecvError::Lagged(_)) => public_text(self.shared_data.load_full()),
+                Err(RecvError::Closed) => return None,
+            };
+            if pending.as_ref() != Some(&text) {
+                changed_at = Instant::now();
+                pending = self.unsent(text);
+            }
+        }
+    }
+
+    fn unsent(&self, text: String) -> Option<String> {
+        match &self.last {
+            Some((last, _)) if *last == text => None,
+            _ => Some(text),
+        }
+    }
+}
+
+fn public_text(data: Arc<OutputData>) -> String {
+    Trust::Public.view(data).text.clone()
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{env_parse, env_var};
+
+/// The machine a presence comes from, so multi-device consumers can tell desktop and laptop apart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DeviceInfo {
+    pub hostname: String,
+    /// e.g. `Arch Linux`, from `/etc/os-release`
+    pub os: String,
+    /// Display name from `REPRESENCE_DEVICE_NAME`, e.g. `desktop`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Enabled by `REPRESENCE_DEVICE_INFO=true` or by naming the device with `REPRESENCE_DEVICE_NAME`
+    pub fn from_env() -> Option<Self> {
+        let name = env_var("REPRESENCE_DEVICE_NAME");
+        if name.is_none() && !env_parse("REPRESENCE_DEVICE_INFO", false) {
+            return None;
+        }
+
+        Some(Self {
+            hostname: hostname(),
+            os: os_name(),
+            name,
+        })
+    }
+}
+
+pub fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| env_var("HOSTNAME"))
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// `PRETTY_NAME` from os-release, falling back to the target OS
+fn os_name() -> String {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .find_map(|release| {
+            release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|value| value.trim().trim_matches('"').to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| std::env::consts::OS.to_string())
+}
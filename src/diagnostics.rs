@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Number of recent presence transitions kept in memory for the dashboard
+const RECENT_HISTORY_LEN: usize = 50;
+
+/// Scans run at least every 3s, so a longer gap without success means the scanner is stuck or failing
+pub const SCAN_STALE_AFTER_SECS: i64 = 10;
+/// VS Code is asked every 2s while it runs
+pub const VSCODE_STALE_AFTER_SECS: i64 = 30;
+
+pub type SharedDiagnostics = Arc<RwLock<Diagnostics>>;
+
+/// Runtime state of the detection pipeline, mostly for debugging why the wrong app is shown
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Diagnostics {
+    pub detected_apps: Vec<DetectedApp>,
+    pub last_scan: Option<DateTime<Utc>>,
+    pub last_successful_scan: Option<DateTime<Utc>>,
+    /// How much longer the last update cycle took than its planned sleep
+    pub update_loop_lag_ms: u64,
+    pub vscode: VsCodeStatus,
+    pub recent: VecDeque<Transition>,
+    /// Set once every listener is bound and accepting connections
+    pub listeners_bound: bool,
+    /// The process list is unavailable, presence only changes through pushes
+    pub push_only: bool,
+    /// Nobody is connected, scans are slowed down or paused
+    pub energy_saving: bool,
+    /// When the update loop last came out of energy saving
+    pub last_wake: Option<DateTime<Utc>>,
+    /// Privacy mode hides the presence behind `REPRESENCE_PRIVACY_TEXT`
+    pub privacy: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedApp {
+    pub name: String,
+    pub tier: u32,
+    pub instances: u32,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VsCodeStatus {
+    pub running: bool,
+    pub connected: bool,
+    pub last_contact: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Category of the last error, e.g. `connection` or `parse`
+    pub last_error_kind: Option<&'static str>,
+}
+
+/// Whether a detector has succeeded recently enough
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectorStatus {
+    Ok,
+    Degraded,
+    /// Nothing to detect, e.g. VS Code isn't running
+    Inactive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectorHealth {
+    pub status: DetectorStatus,
+    pub last_success: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Transition {
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Diagnostics {
+    pub fn shared() -> SharedDiagnostics {
+        Arc::new(RwLock::new(Self::default()))
+    }
+
+    pub fn scan_health(&self, now: DateTime<Utc>) -> DetectorHealth {
+        DetectorHealth {
+            status: match self.energy_saving {
+                true => DetectorStatus::Inactive,
+                false => fresh(self.last_successful_scan, now, SCAN_STALE_AFTER_SECS),
+            },
+            last_success: self.last_successful_scan,
+        }
+    }
+
+    pub fn vscode_health(&self, now: DateTime<Utc>) -> DetectorHealth {
+        DetectorHealth {
+            status: match self.vscode.running {
+                true => fresh(self.vscode.last_contact, now, VSCODE_STALE_AFTER_SECS),
+                false => DetectorStatus::Inactive,
+            },
+            last_success: self.vscode.last_contact,
+        }
+    }
+
+    /// Reasons not to route traffic here yet, empty once ready
+    pub fn not_ready_reasons(&self) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+        if self.last_scan.is_none() {
+            reasons.push("first detection pass has not completed");
+        }
+        if !self.listeners_bound {
+            reasons.push("listeners are not bound");
+        }
+        reasons
+    }
+
+    pub fn record_transition(&mut self, text: &str) {
+        if self.recent.len() >= RECENT_HISTORY_LEN {
+            self.recent.pop_back();
+        }
+        self.recent.push_front(Transition {
+            text: text.to_string(),
+            timestamp: Utc::now(),
+        });
+    }
+}
+
+fn fresh(last_success: Option<DateTime<Utc>>, now: DateTime<Utc>, stale_after_secs: i64) -> DetectorStatus {
+    match last_success {
+        Some(at) if (now - at).num_seconds() < stale_after_secs => DetectorStatus::Ok,
+        _ => DetectorStatus::Degraded,
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::{env_parse, env_var};
+use crate::config_file::{ConfigFile, Severity};
+use crate::error::RepresenceError;
+use crate::features;
+use crate::listener::{self, BindAddr};
+use crate::priority::ResolverKind;
+use crate::process_finder::{ProcSource, ProcessInfo, ProcessSource};
+use crate::vscode_client::{self, VsCodeSource};
+
+/// How long a probe of the D-Bus or a running server may take
+const PROBE_TIMEOUT_SECS: u64 = 3;
+
+/// Tools the focus resolver asks for the focused window, in the order it tries them
+const FOCUS_TOOLS: [&str; 3] = ["hyprctl", "swaymsg", "xdotool"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    /// Works, with something missing that the user may want
+    Warn,
+    /// represence won't work as configured
+    Fail,
+}
+
+/// Result of one check, with what to do about it when it didn't pass
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), outcome: Outcome::Ok, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), outcome: Outcome::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), outcome: Outcome::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.outcome {
+            Outcome::Ok => "ok",
+            Outcome::Warn => "warn",
+            Outcome::Fail => "FAIL",
+        };
+        write!(f, "[{:>4}] {}: {}", mark, self.name, self.detail)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n       -> {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// `represence doctor`: check the environment and print what to fix, failing when a check fails
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let checks = checks().await;
+    for check in &checks {
+        println!("{}", check);
+    }
+
+    let failed = checks.iter().filter(|check| check.outcome == Outcome::Fail).count();
+    let warned = checks.iter().filter(|check| check.outcome == Outcome::Warn).count();
+    match failed {
+        0 => {
+            println!("\nAll checks passed{}", if warned > 0 { format!(" with {} warning(s)", warned) } else { String::new() });
+            Ok(())
+        }
+        _ => Err(format!("{} check(s) failed", failed).into()),
+    }
+}
+
+/// Every check, against the environment the server would start with
+pub async fn checks() -> Vec<Check> {
+    let source = ProcSource::from_env();
+    let (proc_check, processes) = proc(&source).await;
+
+    let mut checks = vec![proc_check, display(env_var)];
+    checks.extend(dbus().await);
+    checks.push(vscode(VsCodeSource::default().port, &processes).await);
+    checks.extend(config(env_var));
+    checks.extend(port(env_parse("REPRESENCE_PORT", 3001u16)).await);
+    checks
+}
+
+/// Whether the process list can be read, with the processes for the checks that need them
+pub async fn proc(source: &ProcSource) -> (Check, Vec<ProcessInfo>) {
+    match source.processes().await {
+        Ok(processes) => {
+            let detail = format!("{} processes readable in {}", processes.len(), source.proc_path().display());
+            (Check::ok("processes", detail), processes)
+        }
+        Err(e @ RepresenceError::ProcUnavailable(_)) => (
+            Check::fail(
+                "processes",
+                e.to_string(),
+                "in a container, run with --pid=host or mount the host's /proc (-v /proc:/host/proc:ro) and set REPRESENCE_PROC_PATH=/host/proc",
+            ),
+            Vec::new(),
+        ),
+        Err(e) => (
+            Check::fail("processes", e.to_string(), "set REPRESENCE_PROC_PATH to where procfs is mounted"),
+            Vec::new(),
+        ),
+    }
+}
+
+/// The graphical session, which only `REPRESENCE_PRIORITY=focus` needs
+pub fn display(lookup: impl Fn(&str) -> Option<String>) -> Check {
+    let session = match (lookup("WAYLAND_DISPLAY"), lookup("DISPLAY")) {
+        (Some(wayland), _) => Some(format!("Wayland ({})", wayland)),
+        (None, Some(x11)) => Some(format!("X11 ({})", x11)),
+        (None, None) => None,
+    };
+    let focus = lookup("REPRESENCE_PRIORITY").is_some_and(|priority| priority.trim() == "focus");
+    let tool = FOCUS_TOOLS.iter().find(|tool| on_path(tool, lookup("PATH").as_deref()));
+
+    match (session, focus, tool) {
+        (None, false, _) => Check::ok("display", "no X11 or Wayland session, only REPRESENCE_PRIORITY=focus needs one"),
+        (None, true, _) => Check::fail(
+            "display",
+            "REPRESENCE_PRIORITY=focus, but neither DISPLAY nor WAYLAND_DISPLAY is set",
+            "run represence inside the graphical session, for a systemd user service run `systemctl --user import-environment DISPLAY WAYLAND_DISPLAY`",
+        ),
+        (Some(session), false, _) => Check::ok("display", session),
+        (Some(session), true, Some(tool)) => Check::ok("display", format!("{}, focused window from {}", session, tool)),
+        (Some(session), true, None) => Check::fail(
+            "display",
+            format!("{}, but none of {} is installed for REPRESENCE_PRIORITY=focus", session, FOCUS_TOOLS.join(", ")),
+            "install xdotool on X11, on Wayland the focused window is only known under Hyprland and Sway",
+        ),
+    }
+}
+
+fn on_path(tool: &str, path: Option<&str>) -> bool {
+    path.unwrap_or_default().split(':').filter(|dir| !dir.is_empty()).any(|dir| Path::new(dir).join(tool).is_file())
+}
+
+/// The session bus for publishing and notifications, the system bus for suspend detection
+async fn dbus() -> Vec<Check> {
+    #[allow(unused_mut)]
+    let mut checks = Vec::new();
+    #[cfg(feature = "dbus")]
+    checks.push(match tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), zbus::Connection::session()).await {
+        Ok(Ok(_)) => Check::ok("session bus", "connected"),
+        Ok(Err(e)) => Check::warn(
+            "session bus",
+            e.to_string(),
+            "start represence inside the user session so DBUS_SESSION_BUS_ADDRESS is set, the D-Bus interface and desktop notifications are off without it",
+        ),
+        Err(_) => Check::warn("session bus", "timed out", "check that the session bus in DBUS_SESSION_BUS_ADDRESS is running"),
+    });
+    #[cfg(feature = "logind")]
+    checks.push(match tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), zbus::Connection::system()).await {
+        Ok(Ok(_)) => Check::ok("system bus", "connected"),
+        Ok(Err(e)) => Check::warn(
+            "system bus",
+            e.to_string(),
+            "suspend and resume aren't noticed without it, in a container mount /run/dbus/system_bus_socket",
+        ),
+        Err(_) => Check::warn("system bus", "timed out", "suspend and resume aren't noticed without it"),
+    });
+    checks
+}
+
+/// The VS Code companion extension, which only matters while VS Code runs
+pub async fn vscode(port: u16, processes: &[ProcessInfo]) -> Check {
+    match vscode_client::connect_to_vscode_once(port).await {
+        Ok(file_info) => Check::ok("vscode", format!("extension answers on port {}, editing {}", port, file_info.file_name)),
+        Err(_) if !processes.iter().any(|process| process.name.starts_with("code")) => {
+            Check::ok("vscode", "VS Code isn't running, not checking the extension")
+        }
+        Err(e) => Check::warn(
+            "vscode",
+            format!("VS Code runs, but the extension doesn't answer on port {}: {}", port, e),
+            "install the represence extension (see VSCode Extension in the README) and reload the window",
+        ),
+    }
+}
+
+/// Configuration files and values that the server would ignore or fall back from
+pub fn config(lookup: impl Fn(&str) -> Option<String>) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for file in ConfigFile::ALL {
+        let Some((from, source)) = file.source(&lookup) else { continue };
+        let inline = from == file.inline_var();
+        let name = if inline { from.clone() } else { file.var().to_string() };
+        let problems = match source {
+            Ok(source) => file.check(&source),
+            Err(e) => {
+                checks.push(Check::fail(name, format!("{}: {}", from, e), "fix the path or unset the variable"));
+                continue;
+            }
+        };
+        // Inline contents can't be passed on the command line, without arguments it checks them too
+        let command = match inline {
+            true => "represence check-config".to_string(),
+            false => format!("represence check-config {} {}", file.name(), from),
+        };
+        let errors = problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+        checks.push(match (errors, problems.first()) {
+            (_, None) => Check::ok(name, format!("{} loads", from)),
+            (0, Some(problem)) => Check::warn(name, format!("{}: {}", from, problem), format!("`{}` lists every problem", command)),
+            (_, Some(_)) => Check::fail(
+                name,
+                format!("{}: {} error(s)", from, errors),
+                format!("`{}` shows where, the defaults are used meanwhile", command),
+            ),
+        });
+    }
+
+    for var in ["REPRESENCE_TEXT_SCRIPT", "REPRESENCE_PRIORITY_SCRIPT"] {
+        if let Some(path) = lookup(var).filter(|path| !Path::new(path).is_file()) {
+            checks.push(Check::fail(var, format!("{} doesn't exist", path), "point it at a script file"));
+        }
+    }
+
+    if let Some(priority) = lookup("REPRESENCE_PRIORITY") {
+        if let Err(e) = priority.trim().parse::<ResolverKind>() {
+            checks.push(Check::fail("REPRESENCE_PRIORITY", e, "unset it to rank apps by tier"));
+        }
+    }
+
+    for (feature, var) in features::missing(&lookup) {
+        checks.push(Check::warn(
+            var,
+            format!("set, but represence was built without the {} feature", feature),
+            format!("rebuild with `--features {}` or unset the variable", feature),
+        ));
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::ok("config", "no configuration files to check"));
+    }
+    checks
+}
+
+/// Whether the server could listen on `port`, or already does
+pub async fn port(port: u16) -> Vec<Check> {
+    let addrs = match listener::bind_addrs_from_env(port) {
+        Ok(addrs) => addrs,
+        Err(e) => return vec![Check::fail("REPRESENCE_BIND", e, "use addresses like 127.0.0.1:3001 or unix:/run/represence.sock")],
+    };
+
+    let mut checks = Vec::new();
+    for addr in addrs {
+        let BindAddr::Tcp(addr) = addr else { continue };
+        checks.push(match TcpListener::bind(addr) {
+            Ok(_) => Check::ok("port", format!("{} is free", addr)),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && serves_represence(addr).await => {
+                Check::ok("port", format!("represence is already serving on {}", addr))
+            }
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => Check::fail(
+                "port",
+                format!("{} is taken by another program", addr),
+                "set REPRESENCE_PORT to a free port, or find the program with `ss -ltnp`",
+            ),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Check::fail(
+                "port",
+                format!("not allowed to listen on {}", addr),
+                "ports below 1024 need CAP_NET_BIND_SERVICE, or set REPRESENCE_PORT above 1024",
+            ),
+            Err(e) => Check::fail("port", format!("{}: {}", addr, e), "set REPRESENCE_BIND or REPRESENCE_PORT to an address of this machine"),
+        });
+    }
+    checks
+}
+
+/// Ask whatever listens on `addr` for the health endpoint and see if it answers like represence
+async fn serves_represence(addr: SocketAddr) -> bool {
+    let target = match addr.ip().is_unspecified() {
+        true => SocketAddr::from((Ipv4Addr::LOCALHOST, addr.port())),
+        false => addr,
+    };
+    let probe = async {
+        let mut stream = TcpStream::connect(target).await?;
+        stream.write_all(b"GET /health HTTP/1.0\r\nHost: localhost\r\n\r\n").await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, io::Error>(String::from_utf8_lossy(&response).contains("\"process_scan\""))
+    };
+    matches!(tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), probe).await, Ok(Ok(true)))
+}
@@ -0,0 +1,167 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Json, Response};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::{self, env_parse, env_var};
+use crate::config_file::{ConfigFile, Severity};
+use crate::features;
+use crate::listener::{self, BindAddr};
+use crate::presence;
+use crate::web_server::{self, AppState};
+
+/// Shown instead of a secret
+const MASK: &str = "********";
+
+/// Variables whose name says they hold a secret
+const SECRET_MARKERS: [&str; 5] = ["TOKEN", "SECRET", "PASSWORD", "API_KEY", "DSN"];
+
+/// Lists with a secret in every item: tokens in webhook and ICS paths, `user:token` pairs
+const SECRET_LISTS: [&str; 3] = ["REPRESENCE_WEBHOOK_URLS", "REPRESENCE_CALENDAR_URLS", "REPRESENCE_HUB_USERS"];
+
+/// Inline profiles can set any of the above, they're listed masked under `files` instead
+const INLINE_PROFILES: &str = "REPRESENCE_PROFILES_JSON";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The process environment, e.g. the service's `Environment=`
+    Environment,
+    /// The `.env` file in the working directory
+    Dotenv,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Variable {
+    pub value: String,
+    pub source: Source,
+}
+
+/// `value` as it may be shown: secrets masked, credentials in URLs too
+pub fn mask(name: &str, value: &str) -> String {
+    let bare = name.trim_start_matches("REPRESENCE_");
+    if SECRET_MARKERS.iter().any(|marker| bare.contains(marker)) || SECRET_LISTS.contains(&name) || name == INLINE_PROFILES {
+        return MASK.to_string();
+    }
+    match url::Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some(MASK));
+            url.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Every `REPRESENCE_*` variable, masked, and whether it came from the `.env` file
+pub fn variables(vars: impl IntoIterator<Item = (String, String)>, dotenv: &HashMap<String, String>) -> BTreeMap<String, Variable> {
+    vars.into_iter()
+        .filter(|(name, _)| name.starts_with("REPRESENCE_"))
+        .map(|(name, value)| {
+            // The environment wins over `.env` at startup, so a different value there came from elsewhere
+            let source = match dotenv.get(&name) {
+                Some(from_file) if *from_file == value => Source::Dotenv,
+                _ => Source::Environment,
+            };
+            (name.clone(), Variable { value: mask(&name, &value), source })
+        })
+        .collect()
+}
+
+/// Settings most questions are about, with their defaults filled in
+fn settings(state: &AppState) -> Value {
+    let port = env_parse("REPRESENCE_PORT", 3001u16);
+    let bind: Vec<String> = match listener::bind_addrs_from_env(port) {
+        Ok(addrs) => addrs
+            .iter()
+            .map(|addr| match addr {
+                BindAddr::Tcp(addr) => addr.to_string(),
+                BindAddr::Unix(path) => format!("unix:{}", path.display()),
+            })
+            .collect(),
+        Err(e) => vec![e],
+    };
+    let apps: Vec<Value> = presence::default_apps().iter().map(|app| json!({ "name": app.name, "tier": app.tier })).collect();
+
+    json!({
+        "port": port,
+        "bind": bind,
+        "priority": env_var("REPRESENCE_PRIORITY").unwrap_or_else(|| "tier".to_string()),
+        "proc_path": env_var("REPRESENCE_PROC_PATH").unwrap_or_else(|| "/proc".to_string()),
+        "apps": apps,
+        "history": state.history.as_ref().map(|history| history.describe()),
+    })
+}
+
+/// Configuration files as loaded, or why they weren't
+fn files() -> BTreeMap<&'static str, Value> {
+    ConfigFile::ALL
+        .iter()
+        .filter_map(|file| {
+            let (from, source) = file.source(env_var)?;
+            let loaded = source.map_err(|e| e.to_string()).and_then(|source| {
+                match file.check(&source).into_iter().find(|problem| problem.severity == Severity::Error) {
+                    Some(problem) => Err(format!("ignored, {}", problem)),
+                    None => Ok(serde_json::from_str::<Value>(&source).unwrap_or_default()),
+                }
+            });
+            let loaded = match file {
+                ConfigFile::Profiles => loaded.map(mask_profiles),
+                _ => loaded,
+            };
+            // Inline contents are named by their variable instead of a path
+            let mut entry = match from == file.inline_var() {
+                true => json!({ "variable": from }),
+                false => json!({ "path": from }),
+            };
+            match loaded {
+                Ok(content) => entry["content"] = content,
+                Err(error) => entry["error"] = json!(error),
+            }
+            Some((file.name(), entry))
+        })
+        .collect()
+}
+
+/// Profiles with the values of their variables masked like the variables themselves
+fn mask_profiles(mut profiles: Value) -> Value {
+    for vars in profiles.as_object_mut().into_iter().flat_map(|profiles| profiles.values_mut()) {
+        for (name, value) in vars.as_object_mut().into_iter().flatten() {
+            if let Some(text) = value.as_str() {
+                *value = json!(mask(name, text));
+            }
+        }
+    }
+    profiles
+}
+
+/// The configuration in effect: every variable and where it came from with secrets masked, the main settings
+/// with their defaults, the configuration files as loaded, compiled features and modules. Requires the admin token.
+#[utoipa::path(
+    get,
+    path = "/api/admin/config",
+    tag = "diagnostics",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Effective configuration", body = Object),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+    )
+)]
+pub async fn get_config(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+
+    let dotenv: HashMap<String, String> = dotenvy::dotenv_iter().map(|vars| vars.flatten().collect()).unwrap_or_default();
+    Json(json!({
+        "settings": settings(&state),
+        "variables": variables(config::env_vars(), &dotenv),
+        "files": files(),
+        "features": features::compiled(),
+        "missing_features": features::missing(env_var).into_iter().map(|(feature, var)| json!({ "feature": feature, "variable": var })).collect::<Vec<_>>(),
+        "modules": state.modules.status(),
+    }))
+    .into_response()
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+/// Path parts that say nothing about which app it is, e.g. in `/usr/lib/signal-desktop/resources/app.asar`
+const GENERIC_PARTS: [&str; 3] = ["app", "resources", "dist"];
+
+/// Whether `name` is a shared Electron runtime like distributions ship, e.g. `electron` or `electron37`,
+/// so the app it runs has to be told from its arguments
+pub fn is_runtime(name: &str) -> bool {
+    name.strip_prefix("electron").is_some_and(|version| version.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The app a shared runtime runs, from `--app-id`, the `--app-path` of its helper processes or the app
+/// argument, e.g. `electron /usr/lib/obsidian/app.asar` is `obsidian`. `None` for helpers that don't say.
+pub fn app_name(args: &[String]) -> Option<String> {
+    let mut args = args.iter().skip(1);
+    let mut app_path = None;
+    while let Some(arg) = args.next() {
+        if let Some(id) = arg.strip_prefix("--app-id=") {
+            return app_id(id);
+        }
+        if arg == "--app-id" {
+            return app_id(args.next()?);
+        }
+        match arg.strip_prefix("--app-path=") {
+            Some(path) => app_path = Some(path),
+            // The first argument that isn't a switch is the app, later ones are for the app
+            None if !arg.starts_with('-') => app_path = app_path.or(Some(arg.as_str())),
+            None => {}
+        }
+    }
+    from_path(app_path?)
+}
+
+/// `com.slack.Slack` is `slack`
+fn app_id(id: &str) -> Option<String> {
+    let name = id.rsplit('.').next()?.to_lowercase();
+    (!name.is_empty()).then_some(name)
+}
+
+/// The innermost directory naming the app, `/opt/Slack/resources/app.asar` is `slack`
+fn from_path(path: &str) -> Option<String> {
+    Path::new(path)
+        .iter()
+        .rev()
+        .filter_map(|part| part.to_str())
+        .map(|part| part.strip_suffix(".asar").unwrap_or(part))
+        // Skips the entry script of an unpacked app like `main.js` too
+        .find(|part| !part.is_empty() && !part.contains('.') && !GENERIC_PARTS.contains(part) && *part != "/")
+        .map(str::to_lowercase)
+}
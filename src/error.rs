@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+/// Failures of the detection pipeline, grouped so callers can react to the kind of failure
+#[derive(Debug, Error)]
+pub enum RepresenceError {
+    #[error("failed to connect to {target}: {source}")]
+    Connection {
+        target: String,
+        #[source]
+        source: tokio_tungstenite::tungstenite::Error,
+    },
+
+    #[error("timed out waiting for {0}")]
+    Timeout(&'static str),
+
+    #[error("failed to parse {what}: {source}")]
+    Parse {
+        what: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("unexpected message from {0}")]
+    UnexpectedMessage(&'static str),
+
+    #[error("{0} closed the connection")]
+    Closed(&'static str),
+
+    #[error("failed to scan {path}: {source}")]
+    Scan {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("process list unavailable: {0}")]
+    ProcUnavailable(String),
+}
+
+impl RepresenceError {
+    /// Short category used to tag log lines and diagnostics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Connection { .. } | Self::Timeout(_) | Self::UnexpectedMessage(_) | Self::Closed(_) => "connection",
+            Self::Parse { .. } => "parse",
+            Self::Scan { .. } | Self::ProcUnavailable(_) => "scan",
+        }
+    }
+}
+
+pub type Result<T, E = RepresenceError> = std::result::Result<T, E>;
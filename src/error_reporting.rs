@@ -0,0 +1,53 @@
+use sentry::protocol::Event;
+use std::borrow::Cow;
+
+use crate::config::env_var;
+use crate::error::RepresenceError;
+
+/// Reports panics and detector errors to Sentry or a self-hosted GlitchTip
+#[derive(Debug, Clone)]
+pub struct ErrorReporting {
+    pub dsn: String,
+    /// Tells machines apart in one project, e.g. `laptop` or `server`
+    pub environment: Option<String>,
+}
+
+impl ErrorReporting {
+    /// `REPRESENCE_SENTRY_DSN` with an optional `REPRESENCE_SENTRY_ENVIRONMENT`, `None` without a DSN
+    pub fn from_env() -> Option<Self> {
+        let dsn = env_var("REPRESENCE_SENTRY_DSN")?;
+        Some(Self { dsn, environment: env_var("REPRESENCE_SENTRY_ENVIRONMENT") })
+    }
+
+    /// Start reporting, events are flushed when the guard is dropped. Must run before the runtime's threads start.
+    pub fn init(self) -> Option<sentry::ClientInitGuard> {
+        let dsn = match self.dsn.parse() {
+            Ok(dsn) => dsn,
+            Err(e) => {
+                eprintln!("Error reporting disabled, invalid REPRESENCE_SENTRY_DSN: {}", e);
+                return None;
+            }
+        };
+        let mut options = sentry::ClientOptions::default();
+        options.dsn = Some(dsn);
+        options.release = sentry::release_name!();
+        options.environment = self.environment.map(Cow::Owned);
+        let guard = sentry::init(options);
+        println!("Reporting panics and detector errors to Sentry");
+        Some(guard)
+    }
+}
+
+/// The event for a detector failing, tagged with the detector and the error kind, and the update cycle it failed in
+pub fn detector_event(detector: &str, cycle: u64, error: &RepresenceError) -> Event<'static> {
+    let mut event = sentry::event_from_error(error);
+    event.tags.insert("detector".to_string(), detector.to_string());
+    event.tags.insert("error_kind".to_string(), error.kind().to_string());
+    event.extra.insert("cycle".to_string(), cycle.into());
+    event
+}
+
+/// Send a detector failure, a no-op unless reporting was initialized
+pub fn report(detector: &str, cycle: u64, error: &RepresenceError) {
+    sentry::capture_event(detector_event(detector, cycle, error));
+}
@@ -0,0 +1,208 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::io::{self, Write};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::cli::ExportArgs;
+use crate::history::{self, HistoryEvent, HistoryQuery, PresenceStore};
+use crate::web_server::{self, AppState};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv_row(out: &mut impl Write, event: &HistoryEvent) -> io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{},{},{},{}",
+        event.id,
+        event.timestamp.to_rfc3339(),
+        csv_field(&event.text),
+        csv_field(event.app.as_deref().unwrap_or_default()),
+        csv_field(event.language.as_deref().unwrap_or_default()),
+        event.duration_secs
+    )
+}
+
+/// Write every event matching `query` in time order, paging through the database, blocking
+pub fn write(history: &dyn PresenceStore, mut query: HistoryQuery, format: ExportFormat, out: &mut impl Write) -> io::Result<()> {
+    query.limit = history::MAX_PAGE_SIZE;
+    let mut first = true;
+
+    match format {
+        ExportFormat::Csv => writeln!(out, "id,timestamp,text,app,language,duration_secs")?,
+        ExportFormat::Json => write!(out, "[")?,
+    }
+
+    loop {
+        let page = history.query(&query).map_err(io::Error::other)?;
+
+        for event in &page.events {
+            match format {
+                ExportFormat::Csv => write_csv_row(out, event)?,
+                ExportFormat::Json => {
+                    if !first {
+                        write!(out, ",")?;
+                    }
+                    serde_json::to_writer(&mut *out, event)?;
+                }
+            }
+            first = false;
+        }
+
+        match page.next_cursor.as_deref().and_then(history::Cursor::parse) {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+
+    if let ExportFormat::Json = format {
+        writeln!(out, "]")?;
+    }
+    out.flush()
+}
+
+/// `represence export`: dump history to stdout or a file
+pub fn run(args: ExportArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let history = match history::open_from_env() {
+        Some(history) => history?,
+        None => return Err("history is disabled (REPRESENCE_HISTORY=false)".into()),
+    };
+
+    let query = HistoryQuery {
+        from: args.from,
+        to: args.to,
+        app: args.app,
+        ..Default::default()
+    };
+
+    match args.output {
+        Some(path) => {
+            let mut file = io::BufWriter::new(std::fs::File::create(&path)?);
+            write(history.as_ref(), query, args.format, &mut file)?;
+            eprintln!("Exported history to {}", path.display());
+        }
+        None => write(history.as_ref(), query, args.format, &mut io::stdout().lock())?,
+    }
+
+    Ok(())
+}
+
+/// Parse a `--from`/`--to` argument the same way as the HTTP API
+pub fn parse_time_arg(value: &str) -> Result<DateTime<Utc>, String> {
+    history::parse_time(value).ok_or_else(|| "expected RFC 3339, YYYY-MM-DD or unix seconds".to_string())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportParams {
+    /// `csv` (default) or `json`
+    #[param(value_type = Option<ExportFormat>)]
+    format: Option<ExportFormat>,
+    /// Start of the range (RFC 3339, `YYYY-MM-DD` or unix seconds), inclusive
+    from: Option<String>,
+    /// End of the range, exclusive
+    to: Option<String>,
+    /// Only events produced by apps starting with this name
+    app: Option<String>,
+}
+
+/// Full presence history as a CSV or JSON download, requires the admin token
+#[utoipa::path(
+    get,
+    path = "/api/history/export",
+    tag = "history",
+    params(ExportParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "History as CSV, or a JSON array of events with `format=json`", content_type = "text/csv"),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "History or the admin token is not configured"),
+    )
+)]
+pub async fn export_history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ExportParams>,
+) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+
+    let Some(history) = state.history.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut query = HistoryQuery { app: params.app, ..Default::default() };
+    if let Some(from) = params.from {
+        match history::parse_time(&from) {
+            Some(from) => query.from = Some(from),
+            None => return web_server::bad_request("invalid 'from'"),
+        }
+    }
+    if let Some(to) = params.to {
+        match history::parse_time(&to) {
+            Some(to) => query.to = Some(to),
+            None => return web_server::bad_request("invalid 'to'"),
+        }
+    }
+
+    let format = params.format.unwrap_or_default();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut body = Vec::new();
+        write(history.as_ref(), query, format, &mut body).map(|()| body)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(body)) => (
+            [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"represence-history.{}\"", format.extension()),
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            eprintln!("History export failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,69 @@
+use crate::config::env_var;
+
+/// A cargo feature and the variables that ask for it
+#[derive(Debug, Clone, Copy)]
+pub struct Feature {
+    pub name: &'static str,
+    pub compiled: bool,
+    pub vars: &'static [&'static str],
+}
+
+/// Optional subsystems. Those on by default without configuration (`dbus`, `logind`) and the
+/// `postgres` backend, which `REPRESENCE_HISTORY_URL` reports itself, have no variables.
+pub const FEATURES: &[Feature] = &[
+    Feature { name: "alerts", compiled: cfg!(feature = "alerts"), vars: &["REPRESENCE_NTFY_URL", "REPRESENCE_PUSHOVER_TOKEN"] },
+    Feature { name: "calendar", compiled: cfg!(feature = "calendar"), vars: &["REPRESENCE_CALENDAR_URLS"] },
+    Feature { name: "dbus", compiled: cfg!(feature = "dbus"), vars: &[] },
+    Feature { name: "film-log", compiled: cfg!(feature = "film-log"), vars: &["REPRESENCE_LETTERBOXD_USER", "REPRESENCE_TRAKT_USER"] },
+    Feature { name: "github", compiled: cfg!(feature = "github"), vars: &["REPRESENCE_GITHUB_USER"] },
+    Feature { name: "graphql", compiled: cfg!(feature = "graphql"), vars: &[] },
+    Feature { name: "grpc", compiled: cfg!(feature = "grpc"), vars: &["REPRESENCE_GRPC_PORT"] },
+    Feature { name: "history", compiled: cfg!(feature = "history"), vars: &["REPRESENCE_HISTORY_PATH"] },
+    Feature { name: "influxdb", compiled: cfg!(feature = "influxdb"), vars: &["REPRESENCE_INFLUX_URL"] },
+    Feature { name: "lastfm", compiled: cfg!(feature = "lastfm"), vars: &["REPRESENCE_LASTFM_USER"] },
+    Feature { name: "logind", compiled: cfg!(feature = "logind"), vars: &[] },
+    Feature { name: "mastodon", compiled: cfg!(feature = "mastodon"), vars: &["REPRESENCE_MASTODON_URL"] },
+    Feature { name: "matrix", compiled: cfg!(feature = "matrix"), vars: &["REPRESENCE_MATRIX_HOMESERVER"] },
+    Feature { name: "media-server", compiled: cfg!(feature = "media-server"), vars: &["REPRESENCE_JELLYFIN_URL", "REPRESENCE_PLEX_URL"] },
+    Feature { name: "mqtt", compiled: cfg!(feature = "mqtt"), vars: &["REPRESENCE_MQTT_HOST"] },
+    Feature { name: "nats", compiled: cfg!(feature = "nats"), vars: &["REPRESENCE_NATS_URL"] },
+    Feature { name: "otel", compiled: cfg!(feature = "otel"), vars: &["REPRESENCE_OTLP_ENDPOINT"] },
+    Feature { name: "postgres", compiled: cfg!(feature = "postgres"), vars: &[] },
+    Feature { name: "profile-readme", compiled: cfg!(feature = "profile-readme"), vars: &["REPRESENCE_PROFILE_README_REPO"] },
+    Feature { name: "redis", compiled: cfg!(feature = "redis"), vars: &["REPRESENCE_REDIS_URL"] },
+    Feature { name: "scripting", compiled: cfg!(feature = "scripting"), vars: &["REPRESENCE_TEXT_SCRIPT", "REPRESENCE_PRIORITY_SCRIPT"] },
+    Feature { name: "sentry", compiled: cfg!(feature = "sentry"), vars: &["REPRESENCE_SENTRY_DSN"] },
+    Feature { name: "spotify", compiled: cfg!(feature = "spotify"), vars: &["REPRESENCE_SPOTIFY_CLIENT_ID"] },
+    Feature { name: "telegram", compiled: cfg!(feature = "telegram"), vars: &["REPRESENCE_TELEGRAM_TOKEN"] },
+    Feature { name: "tls", compiled: cfg!(feature = "tls"), vars: &["REPRESENCE_TLS_CERT", "REPRESENCE_ACME_DOMAINS"] },
+    Feature { name: "twitch", compiled: cfg!(feature = "twitch"), vars: &["REPRESENCE_TWITCH_CHANNEL"] },
+    Feature { name: "vlc", compiled: cfg!(feature = "vlc"), vars: &["REPRESENCE_VLC_PASSWORD"] },
+    Feature { name: "wakatime", compiled: cfg!(feature = "wakatime"), vars: &["REPRESENCE_WAKATIME_API_KEY"] },
+    Feature { name: "webhooks", compiled: cfg!(feature = "webhooks"), vars: &["REPRESENCE_WEBHOOK_URLS"] },
+];
+
+/// Names of the features this build has
+pub fn compiled() -> Vec<&'static str> {
+    FEATURES.iter().filter(|feature| feature.compiled).map(|feature| feature.name).collect()
+}
+
+/// Features left out of this build that `lookup` configures, with the variable asking for each
+pub fn missing(lookup: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, &'static str)> {
+    FEATURES
+        .iter()
+        .filter(|feature| !feature.compiled)
+        .filter_map(|feature| feature.vars.iter().find(|var| lookup(var).is_some()).map(|var| (feature.name, *var)))
+        .collect()
+}
+
+/// Warn about configuration this build can't act on
+pub fn warn_missing() {
+    for (feature, var) in missing(env_var) {
+        eprintln!("{} is set, but represence was built without the {} feature, ignoring it", var, feature);
+    }
+}
+
+/// Error for a command that needs `feature`
+pub fn not_built(feature: &str) -> String {
+    format!("represence was built without the {} feature (cargo install represence --features {})", feature, feature)
+}
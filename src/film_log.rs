@@ -0,0 +1,174 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// The film most recently logged on Letterboxd or Trakt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct LastWatched {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+    /// Letterboxd star rating, 0.5 to 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watched_on: Option<NaiveDate>,
+    /// Page of the diary entry or film
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+pub type SharedLastWatched = Arc<RwLock<Option<LastWatched>>>;
+
+/// Latest film diary entry of a Letterboxd RSS feed, list entries are skipped
+pub fn parse_letterboxd_rss(rss: &str) -> Option<LastWatched> {
+    rss.split("<item>").skip(1).find_map(|item| {
+        Some(LastWatched {
+            title: unescape(tag(item, "letterboxd:filmTitle")?),
+            year: tag(item, "letterboxd:filmYear").and_then(|year| year.parse().ok()),
+            rating: tag(item, "letterboxd:memberRating").and_then(|rating| rating.parse().ok()),
+            watched_on: tag(item, "letterboxd:watchedDate").and_then(|date| date.parse().ok()),
+            url: tag(item, "link").map(unescape),
+        })
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TraktHistoryEntry {
+    watched_at: chrono::DateTime<chrono::Utc>,
+    movie: TraktMovie,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraktMovie {
+    title: String,
+    year: Option<u16>,
+    ids: TraktIds,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraktIds {
+    slug: Option<String>,
+}
+
+/// Newest entry of `/users/<user>/history/movies`
+pub fn parse_trakt_history(history: Vec<TraktHistoryEntry>) -> Option<LastWatched> {
+    let entry = history.into_iter().max_by_key(|entry| entry.watched_at)?;
+    Some(LastWatched {
+        title: entry.movie.title,
+        year: entry.movie.year,
+        rating: None,
+        watched_on: Some(entry.watched_at.date_naive()),
+        url: entry.movie.ids.slug.map(|slug| format!("https://trakt.tv/movies/{}", slug)),
+    })
+}
+
+/// Text of the first `<name>` element, CDATA unwrapped
+fn tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    let text = xml[start..end].trim();
+    Some(text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")).unwrap_or(text))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(feature = "film-log")]
+pub mod client {
+    use std::time::Duration;
+
+    use super::{parse_letterboxd_rss, parse_trakt_history, LastWatched, SharedLastWatched, TraktHistoryEntry};
+    use crate::config::{env_parse, env_var};
+
+    const REQUEST_TIMEOUT_SECS: u64 = 15;
+
+    #[derive(Debug, Clone)]
+    pub enum FilmLogConfig {
+        Letterboxd { user: String, refresh: Duration },
+        Trakt { user: String, client_id: String, refresh: Duration },
+    }
+
+    impl FilmLogConfig {
+        /// `REPRESENCE_LETTERBOXD_USER`, or `REPRESENCE_TRAKT_USER` with `REPRESENCE_TRAKT_CLIENT_ID`.
+        /// Refreshed every `REPRESENCE_FILM_LOG_REFRESH_SECS`, an hour by default.
+        pub fn from_env() -> Option<Self> {
+            let refresh = Duration::from_secs(env_parse("REPRESENCE_FILM_LOG_REFRESH_SECS", 3600).max(60));
+            if let Some(user) = env_var("REPRESENCE_LETTERBOXD_USER") {
+                return Some(Self::Letterboxd { user, refresh });
+            }
+            Some(Self::Trakt {
+                user: env_var("REPRESENCE_TRAKT_USER")?,
+                client_id: env_var("REPRESENCE_TRAKT_CLIENT_ID")?,
+                refresh,
+            })
+        }
+
+        pub fn describe(&self) -> String {
+            match self {
+                Self::Letterboxd { user, .. } => format!("letterboxd.com/{}", user),
+                Self::Trakt { user, .. } => format!("trakt.tv/users/{}", user),
+            }
+        }
+
+        fn refresh(&self) -> Duration {
+            match self {
+                Self::Letterboxd { refresh, .. } | Self::Trakt { refresh, .. } => *refresh,
+            }
+        }
+
+        async fn fetch(&self, client: &reqwest::Client) -> Result<Option<LastWatched>, reqwest::Error> {
+            match self {
+                Self::Letterboxd { user, .. } => {
+                    let rss = client.get(format!("https://letterboxd.com/{}/rss/", user)).send().await?.error_for_status()?.text().await?;
+                    Ok(parse_letterboxd_rss(&rss))
+                }
+                Self::Trakt { user, client_id, .. } => {
+                    let history: Vec<TraktHistoryEntry> = client
+                        .get(format!("https://api.trakt.tv/users/{}/history/movies?limit=1", user))
+                        .header("trakt-api-key", client_id)
+                        .header("trakt-api-version", "2")
+                        .send()
+                        .await?
+                        .error_for_status()?
+                        .json()
+                        .await?;
+                    Ok(parse_trakt_history(history))
+                }
+            }
+        }
+    }
+
+    /// Refresh the last logged film on a slow interval, keeping the previous one while the site is unreachable
+    pub fn spawn(config: FilmLogConfig, last_watched: SharedLastWatched) {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Film log disabled, failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+
+        tokio::spawn(async move {
+            loop {
+                match config.fetch(&client).await {
+                    Ok(film) => *last_watched.write().await = film,
+                    Err(e) => eprintln!("Failed to fetch the film log from {}: {}", config.describe(), e),
+                }
+                tokio::time::sleep(config.refresh()).await;
+            }
+        });
+    }
+}
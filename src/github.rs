@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Something done on GitHub recently, shown while no app is detected
+pub type SharedIdleActivity = Arc<RwLock<Option<String>>>;
+
+/// An entry of `/users/<user>/events/public`
+#[derive(Debug, Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    repo: Repo,
+    #[serde(default)]
+    payload: serde_json::Value,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repo {
+    name: String,
+}
+
+impl Event {
+    /// e.g. `just pushed to bilgi42/represence`, `None` for events that make no sentence
+    pub fn describe(&self) -> Option<String> {
+        let repo = &self.repo.name;
+        let payload = |key: &str| self.payload.get(key).and_then(|value| value.as_str());
+
+        let text = match (self.kind.as_str(), payload("action")) {
+            ("PushEvent", _) => format!("just pushed to {}", repo),
+            ("PullRequestEvent", Some("opened")) => format!("just opened a pull request on {}", repo),
+            ("PullRequestEvent", Some("closed"))
+                if self.payload.pointer("/pull_request/merged").and_then(|merged| merged.as_bool()) == Some(true) =>
+            {
+                format!("just merged a pull request on {}", repo)
+            }
+            ("IssuesEvent", Some("opened")) => format!("just opened an issue on {}", repo),
+            ("IssueCommentEvent", _) | ("PullRequestReviewEvent", _) => format!("just commented on {}", repo),
+            ("ReleaseEvent", Some("published")) => match self.payload.pointer("/release/tag_name").and_then(|tag| tag.as_str()) {
+                Some(tag) => format!("just released {} {}", repo, tag),
+                None => format!("just released {}", repo),
+            },
+            ("CreateEvent", _) if payload("ref_type") == Some("repository") => format!("just created {}", repo),
+            ("ForkEvent", _) => format!("just forked {}", repo),
+            ("WatchEvent", _) => format!("just starred {}", repo),
+            _ => return None,
+        };
+        Some(text)
+    }
+}
+
+/// Newest describable event no older than `max_age`, events come newest first
+pub fn recent_activity(events: &[Event], now: DateTime<Utc>, max_age: chrono::Duration) -> Option<String> {
+    events
+        .iter()
+        .take_while(|event| now - event.created_at <= max_age)
+        .find_map(Event::describe)
+}
+
+#[cfg(feature = "github")]
+pub mod client {
+    use std::time::Duration;
+
+    use super::{recent_activity, Event, SharedIdleActivity};
+    use crate::config::{env_parse, env_var};
+
+    const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+    #[derive(Debug, Clone)]
+    pub struct GitHubConfig {
+        pub user: String,
+        /// Raises the rate limit, public events need no scopes
+        pub token: Option<String>,
+        pub poll_interval: Duration,
+        /// How long an event stays interesting
+        pub recent: Duration,
+    }
+
+    impl GitHubConfig {
+        /// `None` unless `REPRESENCE_GITHUB_USER` is set
+        pub fn from_env() -> Option<Self> {
+            Some(Self {
+                user: env_var("REPRESENCE_GITHUB_USER")?,
+                token: env_var("REPRESENCE_GITHUB_TOKEN"),
+                poll_interval: Duration::from_secs(env_parse("REPRESENCE_GITHUB_POLL_SECS", 300).max(60)),
+                recent: Duration::from_secs(env_parse("REPRESENCE_GITHUB_RECENT_MINS", 60) * 60),
+            })
+        }
+    }
+
+    /// Poll the user's public events, revalidating with ETags so unchanged feeds don't count against the rate limit
+    pub fn spawn(config: GitHubConfig, activity: SharedIdleActivity) {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("GitHub activity disabled, failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+
+        let url = format!("https://api.github.com/users/{}/events/public?per_page=30", config.user);
+        let max_age = chrono::Duration::from_std(config.recent).unwrap_or(chrono::Duration::hours(1));
+
+        tokio::spawn(async move {
+            let mut etag: Option<String> = None;
+            let mut events: Vec<Event> = Vec::new();
+
+            loop {
+                let mut request = client.get(&url).header(reqwest::header::ACCEPT, "application/vnd.github+json");
+                if let Some(token) = &config.token {
+                    request = request.bearer_auth(token);
+                }
+                if let Some(etag) = &etag {
+                    request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+                }
+
+                match request.send().await.and_then(|response| response.error_for_status()) {
+                    Ok(response) if response.status() == reqwest::StatusCode::NOT_MODIFIED => {}
+                    Ok(response) => {
+                        let new_etag = response.headers().get(reqwest::header::ETAG).and_then(|value| value.to_str().ok()).map(str::to_string);
+                        match response.json().await {
+                            Ok(fresh) => {
+                                events = fresh;
+                                etag = new_etag;
+                            }
+                            Err(e) => eprintln!("Failed to parse GitHub events: {}", e),
+                        }
+                    }
+                    Err(e) => eprintln!("GitHub events request failed: {}", e),
+                }
+
+                // Re-evaluated every poll so old events age out even when the feed is unchanged
+                *activity.write().await = recent_activity(&events, chrono::Utc::now(), max_age);
+                tokio::time::sleep(config.poll_interval).await;
+            }
+        });
+    }
+}
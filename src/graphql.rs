@@ -0,0 +1,123 @@
+use async_graphql::http::ALL_WEBSOCKET_PROTOCOLS;
+use async_graphql::{Context, Data, EmptyMutation, Error, Object, Result, Schema, Subscription};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
+use axum::extract::{State, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Extension, Router};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::Stream;
+use std::sync::Arc;
+
+use crate::history::{self, HistoryPage, HistoryQuery, SharedHistory};
+use crate::summary::{self, DailySummary, WeeklySummary};
+use crate::trust::{Trust, Visible};
+use crate::web_server::{AppState, ClientGuard};
+use crate::OutputData;
+
+pub type PresenceSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+/// Every request carries the `AppState` and the presence it may see as `Visible` context data
+pub fn schema() -> PresenceSchema {
+    Schema::new(QueryRoot, EmptyMutation, SubscriptionRoot)
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/graphql", get(graphql).post(graphql))
+        .route("/graphql/ws", get(graphql_ws))
+        .layer(Extension(schema()))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// What I'm doing right now
+    async fn presence(&self, ctx: &Context<'_>) -> Arc<OutputData> {
+        ctx.data_unchecked::<Visible>().current()
+    }
+
+    /// Recorded presence transitions in time order, `to` is exclusive. Requires a friend token when there are any.
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        app: Option<String>,
+        limit: Option<u32>,
+        cursor: Option<String>,
+    ) -> Result<HistoryPage> {
+        let history = history_of(ctx)?;
+        let cursor = match cursor {
+            Some(cursor) => Some(history::Cursor::parse(&cursor).ok_or("invalid 'cursor'")?),
+            None => None,
+        };
+        let query = HistoryQuery { from, to, app, limit: limit.unwrap_or(history::DEFAULT_PAGE_SIZE), cursor };
+        match tokio::task::spawn_blocking(move || history.query(&query)).await {
+            Ok(Ok(page)) => Ok(page),
+            Ok(Err(e)) => {
+                eprintln!("History query failed: {}", e);
+                Err(Error::new("history query failed"))
+            }
+            Err(_) => Err(Error::new("history query failed")),
+        }
+    }
+
+    /// Active and coding time per day, the last 30 days unless given. Requires a friend token when there are any.
+    async fn daily_summary(&self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<DailySummary>> {
+        let (from, to) = summary::range(from, to, summary::DEFAULT_DAILY_RANGE_DAYS)?;
+        summary::daily(history_of(ctx)?, from, to).await.map_err(|_| Error::new("summary query failed"))
+    }
+
+    /// Active and coding time per ISO week, the last 12 weeks unless given. Requires a friend token when there are any.
+    async fn weekly_summary(&self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<WeeklySummary>> {
+        let (from, to) = summary::range(from, to, summary::DEFAULT_WEEKLY_RANGE_WEEKS * 7)?;
+        summary::weekly(history_of(ctx)?, from, to).await.map_err(|_| Error::new("summary query failed"))
+    }
+}
+
+/// The history and its summaries, for friends only like the REST routes
+fn history_of(ctx: &Context<'_>) -> Result<SharedHistory> {
+    if ctx.data_unchecked::<Visible>().trust() != Trust::Friend {
+        return Err(Error::new("requires a friend token"));
+    }
+    ctx.data_unchecked::<AppState>().history.clone().ok_or_else(|| Error::new("history is disabled"))
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// The current presence, then every change
+    async fn presence(&self, ctx: &Context<'_>) -> impl Stream<Item = Arc<OutputData>> {
+        ctx.data_unchecked::<Visible>().updates()
+    }
+}
+
+/// Queries over GET or POST
+async fn graphql(
+    State(state): State<AppState>,
+    presence: Visible,
+    Extension(schema): Extension<PresenceSchema>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(request.into_inner().data(state).data(presence)).await.into()
+}
+
+/// Subscriptions over `graphql-transport-ws` or the older `graphql-ws`
+async fn graphql_ws(
+    State(state): State<AppState>,
+    presence: Visible,
+    Extension(schema): Extension<PresenceSchema>,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.protocols(ALL_WEBSOCKET_PROTOCOLS).on_upgrade(move |socket| async move {
+        let _guard = ClientGuard::new(&state.websocket_clients);
+        let mut data = Data::default();
+        data.insert(state);
+        data.insert(presence);
+        GraphQLWebSocket::new(socket, schema, protocol).with_data(data).serve().await
+    })
+}
@@ -0,0 +1,202 @@
+use futures_util::{Stream, StreamExt};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tonic::codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+use tonic_prost::ProstCodec;
+
+use crate::config::env_parse;
+use crate::consumers::SharedConsumers;
+use crate::trust::{TrustConfig, Visible};
+use crate::web_server::{Broadcaster, ClientGuard, SharedData};
+use crate::OutputData;
+
+// The messages and the service from `proto/represence.proto`, written out by hand rather than
+// generated in a build script so building doesn't need `protoc`
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetPresenceRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WatchPresenceRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Presence {
+    #[prost(string, tag = "1")]
+    pub text: String,
+    #[prost(message, optional, tag = "2")]
+    pub device: Option<Device>,
+    #[prost(string, optional, tag = "3")]
+    pub album_art: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub link: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub emoji: Option<String>,
+    #[prost(string, optional, tag = "6")]
+    pub icon: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub color: Option<String>,
+    #[prost(message, repeated, tag = "8")]
+    pub apps: Vec<App>,
+    #[prost(bool, tag = "9")]
+    pub stale: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Device {
+    #[prost(string, tag = "1")]
+    pub hostname: String,
+    #[prost(string, tag = "2")]
+    pub os: String,
+    #[prost(string, optional, tag = "3")]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct App {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint32, tag = "2")]
+    pub instances: u32,
+}
+
+impl From<&OutputData> for Presence {
+    fn from(data: &OutputData) -> Self {
+        Self {
+            text: data.text.clone(),
+            device: data.device.as_ref().map(|device| Device {
+                hostname: device.hostname.clone(),
+                os: device.os.clone(),
+                name: device.name.clone(),
+            }),
+            album_art: data.album_art.clone(),
+            link: data.link.clone(),
+            emoji: data.emoji.clone(),
+            icon: data.icon.clone(),
+            color: data.color.clone(),
+            apps: data.apps.iter().map(|app| App { name: app.name.clone(), instances: app.instances }).collect(),
+            stale: data.stale,
+        }
+    }
+}
+
+pub type PresenceStream = Pin<Box<dyn Stream<Item = Result<Presence, Status>> + Send>>;
+
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub addr: SocketAddr,
+}
+
+impl GrpcConfig {
+    /// `REPRESENCE_GRPC_PORT` on all IPv4 interfaces, `None` when unset
+    pub fn from_env() -> Option<Self> {
+        let port: u16 = env_parse("REPRESENCE_GRPC_PORT", 0);
+        (port != 0).then(|| Self { addr: SocketAddr::from(([0, 0, 0, 0], port)) })
+    }
+}
+
+/// `represence.v1.Represence`, reading the same presence and broadcaster as the HTTP API
+#[derive(Clone)]
+pub struct PresenceService {
+    shared_data: SharedData,
+    broadcaster: Broadcaster,
+    consumers: SharedConsumers,
+    trust: Arc<TrustConfig>,
+}
+
+impl PresenceService {
+    pub fn new(shared_data: SharedData, broadcaster: Broadcaster, consumers: SharedConsumers, trust: TrustConfig) -> Self {
+        Self { shared_data, broadcaster, consumers, trust: Arc::new(trust) }
+    }
+
+    /// Friend tokens are sent as `authorization: Bearer <token>` metadata
+    fn presence<T>(&self, request: &Request<T>) -> Visible {
+        let trust = self.trust.level(&request.metadata().clone().into_headers(), None);
+        Visible::new(trust, &self.shared_data, &self.broadcaster)
+    }
+
+    pub async fn get_presence(&self, request: Request<GetPresenceRequest>) -> Result<Response<Presence>, Status> {
+        self.consumers.touch();
+        let data = self.presence(&request).current();
+        Ok(Response::new(Presence::from(data.as_ref())))
+    }
+
+    pub async fn watch_presence(&self, request: Request<WatchPresenceRequest>) -> Result<Response<PresenceStream>, Status> {
+        let presence = self.presence(&request);
+        // Keeps the update loop at full speed while the stream is open
+        let guard = ClientGuard::new(&self.consumers.event_streams);
+        let presences = presence.updates().map(move |data| {
+            let _ = &guard;
+            Ok(Presence::from(data.as_ref()))
+        });
+        Ok(Response::new(Box::pin(presences) as PresenceStream))
+    }
+}
+
+impl NamedService for PresenceService {
+    const NAME: &'static str = "represence.v1.Represence";
+}
+
+struct GetPresence(PresenceService);
+
+impl UnaryService<GetPresenceRequest> for GetPresence {
+    type Response = Presence;
+    type Future = BoxFuture<Response<Presence>, Status>;
+
+    fn call(&mut self, request: Request<GetPresenceRequest>) -> Self::Future {
+        let service = self.0.clone();
+        Box::pin(async move { service.get_presence(request).await })
+    }
+}
+
+struct WatchPresence(PresenceService);
+
+impl ServerStreamingService<WatchPresenceRequest> for WatchPresence {
+    type Response = Presence;
+    type ResponseStream = PresenceStream;
+    type Future = BoxFuture<Response<PresenceStream>, Status>;
+
+    fn call(&mut self, request: Request<WatchPresenceRequest>) -> Self::Future {
+        let service = self.0.clone();
+        Box::pin(async move { service.watch_presence(request).await })
+    }
+}
+
+impl<B> Service<http::Request<B>> for PresenceService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::Body>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.clone();
+        match request.uri().path() {
+            "/represence.v1.Represence/GetPresence" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default()).unary(GetPresence(service), request).await)
+            }),
+            "/represence.v1.Represence/WatchPresence" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default()).server_streaming(WatchPresence(service), request).await)
+            }),
+            _ => Box::pin(async move { Ok(Status::unimplemented("unknown method").into_http()) }),
+        }
+    }
+}
+
+/// Serve the gRPC API on its own port, HTTP/2 only
+pub fn spawn(config: GrpcConfig, service: PresenceService) {
+    tokio::spawn(async move {
+        let result = tonic::transport::Server::builder().add_service(service).serve(config.addr).await;
+        if let Err(e) = result {
+            eprintln!("gRPC server on {} failed: {}", config.addr, e);
+        }
+    });
+}
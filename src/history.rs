@@ -0,0 +1,735 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+#[cfg(feature = "history")]
+use rusqlite::{params, params_from_iter, Connection};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+#[cfg(feature = "history")]
+use std::path::PathBuf;
+#[cfg(feature = "history")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::config::{env_parse, env_var};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// How often the update loop extends the current event's `ended_at`
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 60;
+
+/// A previous event only runs up to the next one if the daemon was alive in between
+pub(crate) const CONTINUITY_GRACE_MS: i64 = 2 * HEARTBEAT_INTERVAL_SECS as i64 * 1000;
+
+pub type SharedHistory = Arc<dyn PresenceStore>;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[cfg(feature = "history")]
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Postgres(#[from] postgres::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Tls(#[from] native_tls::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Unsupported(&'static str),
+}
+
+/// Where presence history is kept. Calls block, use `spawn_blocking` in async code.
+pub trait PresenceStore: Send + Sync {
+    /// Where the history goes, for logs
+    fn describe(&self) -> String;
+
+    /// Failed writes since opening, for alerting
+    fn write_failures(&self) -> u64;
+
+    /// Append a transition. The previous event is closed at this timestamp if the daemon was alive in between.
+    fn record(&self, timestamp: DateTime<Utc>, text: &str, app: Option<&str>, language: Option<&str>) -> Result<(), StoreError>;
+
+    /// Extend the ongoing event up to `timestamp`, proving the daemon is still running
+    fn heartbeat(&self, timestamp: DateTime<Utc>) -> Result<(), StoreError>;
+
+    /// Query transitions in time order with keyset pagination
+    fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, StoreError>;
+
+    /// Events overlapping `[start, end)` in unix millis, clipped to the range
+    fn spans(&self, start: i64, end: i64) -> Result<Vec<Span>, StoreError>;
+
+    /// Timestamp of the oldest recorded event
+    fn first_event(&self) -> Result<Option<i64>, StoreError>;
+
+    /// Most recent day that has a stored summary
+    fn latest_summary_day(&self) -> Result<Option<NaiveDate>, StoreError>;
+
+    /// Store or replace the totals of a day
+    fn store_summary(&self, totals: &DailyTotals) -> Result<(), StoreError>;
+
+    /// Stored daily aggregates for days in `[from, to]`
+    fn summaries(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyTotals>, StoreError>;
+
+    /// End of the latest stored session in unix millis
+    fn latest_session_end(&self) -> Result<Option<i64>, StoreError>;
+
+    /// Store a session that is over
+    fn store_session(&self, session: &Session) -> Result<(), StoreError>;
+
+    /// Stored sessions overlapping `[start, end)` in unix millis, oldest first
+    fn sessions(&self, start: i64, end: i64) -> Result<Vec<Session>, StoreError>;
+
+    /// Delete raw events that ended before `before` (unix millis), returns how many were removed
+    fn prune(&self, before: i64) -> Result<usize, StoreError>;
+
+    /// Write a consistent SQLite copy of the history to `path`, which must not exist yet
+    fn snapshot(&self, _path: &Path) -> Result<(), StoreError> {
+        Err(StoreError::Unsupported("only SQLite history can be backed up, use the database's own tools"))
+    }
+}
+
+/// Open the history configured in the environment unless `REPRESENCE_HISTORY=false`: the backend
+/// `REPRESENCE_HISTORY_URL` names, SQLite at `REPRESENCE_HISTORY_PATH` without one
+pub fn open_from_env() -> Option<Result<SharedHistory, StoreError>> {
+    if !env_parse("REPRESENCE_HISTORY", true) {
+        return None;
+    }
+
+    let url = env_var("REPRESENCE_HISTORY_URL");
+    if let Some(path) = url.as_deref().and_then(|url| url.strip_prefix("jsonl:")) {
+        let path = Path::new(path);
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        return Some(crate::jsonl::JsonlHistory::open(path).map(|history| Arc::new(history) as SharedHistory));
+    }
+    if url.as_deref() == Some("memory:") {
+        return Some(Ok(Arc::new(MemoryHistory::new())));
+    }
+
+    match url {
+        #[cfg(feature = "postgres")]
+        Some(url) => Some(crate::postgres::PostgresHistory::connect(&url).map(|history| Arc::new(history) as SharedHistory)),
+        #[cfg(not(feature = "postgres"))]
+        Some(_) => Some(Err(StoreError::Unsupported("REPRESENCE_HISTORY_URL needs represence built with the postgres feature"))),
+        #[cfg(feature = "history")]
+        None => {
+            let path = SqliteHistory::path_from_env();
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            Some(SqliteHistory::open(&path).map(|history| Arc::new(history) as SharedHistory).map_err(StoreError::from))
+        }
+        // Only an error when asked for, minimal builds run without history by default
+        #[cfg(not(feature = "history"))]
+        None => env_var("REPRESENCE_HISTORY").or_else(|| env_var("REPRESENCE_HISTORY_PATH")).map(|_| {
+            Err(StoreError::Unsupported(
+                "SQLite history needs represence built with the history feature, or REPRESENCE_HISTORY_URL=jsonl:<path>",
+            ))
+        }),
+    }
+}
+
+/// A recorded presence transition
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct HistoryEvent {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+    /// Process name of the app that produced the text, if any
+    pub app: Option<String>,
+    /// VS Code language ID of the file being edited, if any
+    pub language: Option<String>,
+    /// Seconds spent in this state (so far, for the ongoing one)
+    pub duration_secs: i64,
+}
+
+/// Raw event span used for aggregation
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub start: i64,
+    pub end: i64,
+    pub app: Option<String>,
+    pub language: Option<String>,
+}
+
+/// Continuous activity, with no gap longer than the session gap
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Session {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub duration_secs: i64,
+    /// Time with an app in front, the short breaks in between don't count
+    pub active_secs: i64,
+    /// App in front the longest
+    pub dominant_app: Option<String>,
+    /// Seconds per app
+    pub apps: BTreeMap<String, i64>,
+    /// Still going, activity may extend it
+    pub ongoing: bool,
+}
+
+/// Seconds spent per day, stored once the day is over
+#[derive(Debug, Default, Clone)]
+pub struct DailyTotals {
+    pub day: NaiveDate,
+    /// Time with any detected app in front
+    pub active_secs: i64,
+    /// Time spent in an editor
+    pub coding_secs: i64,
+    pub apps: HashMap<String, i64>,
+    pub languages: HashMap<String, i64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// App name prefix, matched the same way as the tier list
+    pub app: Option<String>,
+    pub limit: u32,
+    /// Return events after this position in the timeline
+    pub cursor: Option<Cursor>,
+}
+
+/// Keyset position `(timestamp, id)`, encoded as `<millis>:<id>`
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    pub(crate) timestamp: i64,
+    pub(crate) id: i64,
+}
+
+impl Cursor {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (timestamp, id) = value.split_once(':')?;
+        Some(Self {
+            timestamp: timestamp.parse().ok()?,
+            id: id.parse().ok()?,
+        })
+    }
+
+    pub(crate) fn after(event: &HistoryEvent) -> String {
+        format!("{}:{}", event.timestamp.timestamp_millis(), event.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct HistoryPage {
+    pub events: Vec<HistoryEvent>,
+    /// Pass as `cursor` to fetch the next page, `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl HistoryPage {
+    /// The page out of up to `limit + 1` events, the extra one only tells whether another page exists
+    pub(crate) fn new(mut events: Vec<HistoryEvent>, limit: u32) -> Self {
+        let has_more = events.len() > limit as usize;
+        events.truncate(limit as usize);
+        let next_cursor = if has_more {
+            events.last().map(Cursor::after)
+        } else {
+            None
+        };
+        Self { events, next_cursor }
+    }
+}
+
+/// Persistent log of presence transitions in SQLite
+#[cfg(feature = "history")]
+pub struct SqliteHistory {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+    /// Failed writes since opening, for alerting
+    write_failures: AtomicU64,
+}
+
+#[cfg(feature = "history")]
+impl SqliteHistory {
+    /// `REPRESENCE_HISTORY_PATH`, `~/.local/share/represence/history.db` by default
+    pub fn path_from_env() -> PathBuf {
+        env_var("REPRESENCE_HISTORY_PATH").map(PathBuf::from).unwrap_or_else(default_path)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS events (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 timestamp INTEGER NOT NULL,
+                 text TEXT NOT NULL,
+                 app TEXT
+             );
+             CREATE INDEX IF NOT EXISTS events_timestamp ON events (timestamp);
+             CREATE TABLE IF NOT EXISTS daily_summaries (
+                 day TEXT PRIMARY KEY,
+                 active_secs INTEGER NOT NULL,
+                 coding_secs INTEGER NOT NULL,
+                 apps TEXT NOT NULL,
+                 languages TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS sessions (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 started_at INTEGER NOT NULL,
+                 ended_at INTEGER NOT NULL,
+                 dominant_app TEXT,
+                 active_secs INTEGER NOT NULL,
+                 apps TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS sessions_started_at ON sessions (started_at);",
+        )?;
+
+        // Columns added after the first release of the events table
+        for (column, definition) in [("language", "TEXT"), ("ended_at", "INTEGER")] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('events') WHERE name = ?1")?
+                .exists([column])?;
+            if !exists {
+                conn.execute(&format!("ALTER TABLE events ADD COLUMN {} {}", column, definition), [])?;
+            }
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path: path.to_path_buf(),
+            write_failures: AtomicU64::new(0),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn track<T>(&self, result: Result<T, rusqlite::Error>) -> Result<T, StoreError> {
+        if result.is_err() {
+            self.write_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(result?)
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "history")]
+impl PresenceStore for SqliteHistory {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn write_failures(&self) -> u64 {
+        self.write_failures.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, timestamp: DateTime<Utc>, text: &str, app: Option<&str>, language: Option<&str>) -> Result<(), StoreError> {
+        let now = timestamp.timestamp_millis();
+        let mut conn = self.conn();
+        let result = conn.transaction().and_then(|tx| {
+            tx.execute(
+                "UPDATE events SET ended_at = ?1
+                 WHERE id = (SELECT MAX(id) FROM events) AND ended_at >= ?2",
+                params![now, now - CONTINUITY_GRACE_MS],
+            )?;
+            tx.execute(
+                "INSERT INTO events (timestamp, text, app, language, ended_at) VALUES (?1, ?2, ?3, ?4, ?1)",
+                params![now, text, app, language],
+            )?;
+            tx.commit()
+        });
+        self.track(result)
+    }
+
+    fn heartbeat(&self, timestamp: DateTime<Utc>) -> Result<(), StoreError> {
+        self.track(self.conn().execute(
+            "UPDATE events SET ended_at = ?1 WHERE id = (SELECT MAX(id) FROM events)",
+            params![timestamp.timestamp_millis()],
+        ))?;
+        Ok(())
+    }
+
+    fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, StoreError> {
+        let limit = query.limit.clamp(1, MAX_PAGE_SIZE);
+
+        let mut conditions = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+
+        if let Some(from) = query.from {
+            conditions.push("timestamp >= ?");
+            values.push(from.timestamp_millis().into());
+        }
+        if let Some(to) = query.to {
+            conditions.push("timestamp < ?");
+            values.push(to.timestamp_millis().into());
+        }
+        if let Some(app) = &query.app {
+            conditions.push("substr(app, 1, length(?)) = ?");
+            values.push(app.clone().into());
+            values.push(app.clone().into());
+        }
+        if let Some(cursor) = query.cursor {
+            conditions.push("(timestamp > ? OR (timestamp = ? AND id > ?))");
+            values.push(cursor.timestamp.into());
+            values.push(cursor.timestamp.into());
+            values.push(cursor.id.into());
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let sql = format!(
+            "SELECT id, timestamp, text, app, language, COALESCE(ended_at, timestamp) FROM events
+             {}
+             ORDER BY timestamp, id
+             LIMIT {}",
+            where_clause,
+            limit + 1
+        );
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&sql)?;
+        let events = statement
+            .query_map(params_from_iter(values), |row| {
+                let timestamp: i64 = row.get(1)?;
+                let ended_at: i64 = row.get(5)?;
+                Ok(HistoryEvent {
+                    id: row.get(0)?,
+                    timestamp: millis_to_datetime(timestamp),
+                    text: row.get(2)?,
+                    app: row.get(3)?,
+                    language: row.get(4)?,
+                    duration_secs: (ended_at - timestamp).max(0) / 1000,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(HistoryPage::new(events, limit))
+    }
+
+    fn spans(&self, start: i64, end: i64) -> Result<Vec<Span>, StoreError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT timestamp, COALESCE(ended_at, timestamp), app, language FROM events
+             WHERE timestamp < ?2 AND COALESCE(ended_at, timestamp) > ?1
+             ORDER BY timestamp, id",
+        )?;
+        let spans = statement
+            .query_map(params![start, end], |row| {
+                let event_start: i64 = row.get(0)?;
+                let event_end: i64 = row.get(1)?;
+                Ok(Span {
+                    start: event_start.max(start),
+                    end: event_end.min(end),
+                    app: row.get(2)?,
+                    language: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(spans)
+    }
+
+    fn first_event(&self) -> Result<Option<i64>, StoreError> {
+        Ok(self.conn().query_row("SELECT MIN(timestamp) FROM events", [], |row| row.get(0))?)
+    }
+
+    fn latest_summary_day(&self) -> Result<Option<NaiveDate>, StoreError> {
+        let day: Option<String> = self
+            .conn()
+            .query_row("SELECT MAX(day) FROM daily_summaries", [], |row| row.get(0))?;
+        Ok(day.and_then(|day| day.parse().ok()))
+    }
+
+    fn store_summary(&self, totals: &DailyTotals) -> Result<(), StoreError> {
+        let result = self.conn().execute(
+            "INSERT OR REPLACE INTO daily_summaries (day, active_secs, coding_secs, apps, languages)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                totals.day.to_string(),
+                totals.active_secs,
+                totals.coding_secs,
+                serde_json::to_string(&totals.apps).unwrap_or_default(),
+                serde_json::to_string(&totals.languages).unwrap_or_default(),
+            ],
+        );
+        self.track(result)?;
+        Ok(())
+    }
+
+    fn summaries(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyTotals>, StoreError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT day, active_secs, coding_secs, apps, languages FROM daily_summaries
+             WHERE day >= ?1 AND day <= ?2 ORDER BY day",
+        )?;
+        let rows = statement
+            .query_map(params![from.to_string(), to.to_string()], |row| {
+                let day: String = row.get(0)?;
+                let apps: String = row.get(3)?;
+                let languages: String = row.get(4)?;
+                Ok(DailyTotals {
+                    day: day.parse().unwrap_or_default(),
+                    active_secs: row.get(1)?,
+                    coding_secs: row.get(2)?,
+                    apps: serde_json::from_str(&apps).unwrap_or_default(),
+                    languages: serde_json::from_str(&languages).unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    fn latest_session_end(&self) -> Result<Option<i64>, StoreError> {
+        Ok(self.conn().query_row("SELECT MAX(ended_at) FROM sessions", [], |row| row.get(0))?)
+    }
+
+    fn store_session(&self, session: &Session) -> Result<(), StoreError> {
+        let result = self.conn().execute(
+            "INSERT INTO sessions (started_at, ended_at, dominant_app, active_secs, apps) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                session.start.timestamp_millis(),
+                session.end.timestamp_millis(),
+                session.dominant_app,
+                session.active_secs,
+                serde_json::to_string(&session.apps).unwrap_or_default(),
+            ],
+        );
+        self.track(result)?;
+        Ok(())
+    }
+
+    fn sessions(&self, start: i64, end: i64) -> Result<Vec<Session>, StoreError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT started_at, ended_at, dominant_app, active_secs, apps FROM sessions
+             WHERE started_at < ?2 AND ended_at > ?1 ORDER BY started_at",
+        )?;
+        let sessions = statement
+            .query_map(params![start, end], |row| {
+                let started_at: i64 = row.get(0)?;
+                let ended_at: i64 = row.get(1)?;
+                let apps: String = row.get(4)?;
+                Ok(Session {
+                    start: millis_to_datetime(started_at),
+                    end: millis_to_datetime(ended_at),
+                    duration_secs: (ended_at - started_at) / 1000,
+                    dominant_app: row.get(2)?,
+                    active_secs: row.get(3)?,
+                    apps: serde_json::from_str(&apps).unwrap_or_default(),
+                    ongoing: false,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(sessions)
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<(), StoreError> {
+        self.conn().execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    fn prune(&self, before: i64) -> Result<usize, StoreError> {
+        Ok(self.conn().execute(
+            "DELETE FROM events WHERE COALESCE(ended_at, timestamp) < ?1",
+            params![before],
+        )?)
+    }
+}
+
+/// An event as the in-memory history keeps it
+#[derive(Debug, Clone)]
+pub(crate) struct StoredEvent {
+    pub id: i64,
+    pub timestamp: i64,
+    pub text: String,
+    pub app: Option<String>,
+    pub language: Option<String>,
+    pub ended_at: i64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Tables {
+    pub events: Vec<StoredEvent>,
+    pub summaries: BTreeMap<NaiveDate, DailyTotals>,
+    pub sessions: Vec<Session>,
+    /// Last ID handed out, pruned events keep theirs taken
+    pub last_id: i64,
+}
+
+/// History kept in memory only, gone on restart. For tests and throwaway instances.
+#[derive(Default)]
+pub struct MemoryHistory {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add an event as is, its end already known
+    pub(crate) fn insert(&self, timestamp: i64, text: String, app: Option<String>, language: Option<String>, ended_at: i64) {
+        let mut tables = self.tables();
+        tables.last_id += 1;
+        let id = tables.last_id;
+        tables.events.push(StoredEvent { id, timestamp, text, app, language, ended_at });
+    }
+}
+
+impl PresenceStore for MemoryHistory {
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn write_failures(&self) -> u64 {
+        0
+    }
+
+    fn record(&self, timestamp: DateTime<Utc>, text: &str, app: Option<&str>, language: Option<&str>) -> Result<(), StoreError> {
+        let now = timestamp.timestamp_millis();
+        if let Some(last) = self.tables().events.last_mut().filter(|last| last.ended_at >= now - CONTINUITY_GRACE_MS) {
+            last.ended_at = now;
+        }
+        self.insert(now, text.to_string(), app.map(str::to_string), language.map(str::to_string), now);
+        Ok(())
+    }
+
+    fn heartbeat(&self, timestamp: DateTime<Utc>) -> Result<(), StoreError> {
+        if let Some(last) = self.tables().events.last_mut() {
+            last.ended_at = timestamp.timestamp_millis();
+        }
+        Ok(())
+    }
+
+    fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, StoreError> {
+        let limit = query.limit.clamp(1, MAX_PAGE_SIZE);
+        let from = query.from.map(|from| from.timestamp_millis());
+        let to = query.to.map(|to| to.timestamp_millis());
+
+        let tables = self.tables();
+        let mut events: Vec<&StoredEvent> = tables.events.iter().filter(|event| {
+            from.is_none_or(|from| event.timestamp >= from)
+                && to.is_none_or(|to| event.timestamp < to)
+                && query.app.as_ref().is_none_or(|prefix| event.app.as_ref().is_some_and(|app| app.starts_with(prefix.as_str())))
+                && query.cursor.is_none_or(|cursor| (event.timestamp, event.id) > (cursor.timestamp, cursor.id))
+        }).collect();
+        events.sort_by_key(|event| (event.timestamp, event.id));
+
+        let events = events
+            .into_iter()
+            .take(limit as usize + 1)
+            .map(|event| HistoryEvent {
+                id: event.id,
+                timestamp: millis_to_datetime(event.timestamp),
+                text: event.text.clone(),
+                app: event.app.clone(),
+                language: event.language.clone(),
+                duration_secs: (event.ended_at - event.timestamp).max(0) / 1000,
+            })
+            .collect();
+        Ok(HistoryPage::new(events, limit))
+    }
+
+    fn spans(&self, start: i64, end: i64) -> Result<Vec<Span>, StoreError> {
+        let tables = self.tables();
+        let mut events: Vec<&StoredEvent> =
+            tables.events.iter().filter(|event| event.timestamp < end && event.ended_at > start).collect();
+        events.sort_by_key(|event| (event.timestamp, event.id));
+        Ok(events
+            .into_iter()
+            .map(|event| Span {
+                start: event.timestamp.max(start),
+                end: event.ended_at.min(end),
+                app: event.app.clone(),
+                language: event.language.clone(),
+            })
+            .collect())
+    }
+
+    fn first_event(&self) -> Result<Option<i64>, StoreError> {
+        Ok(self.tables().events.iter().map(|event| event.timestamp).min())
+    }
+
+    fn latest_summary_day(&self) -> Result<Option<NaiveDate>, StoreError> {
+        Ok(self.tables().summaries.keys().next_back().copied())
+    }
+
+    fn store_summary(&self, totals: &DailyTotals) -> Result<(), StoreError> {
+        self.tables().summaries.insert(totals.day, totals.clone());
+        Ok(())
+    }
+
+    fn summaries(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyTotals>, StoreError> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        Ok(self.tables().summaries.range(from..=to).map(|(_, totals)| totals.clone()).collect())
+    }
+
+    fn latest_session_end(&self) -> Result<Option<i64>, StoreError> {
+        Ok(self.tables().sessions.iter().map(|session| session.end.timestamp_millis()).max())
+    }
+
+    fn store_session(&self, session: &Session) -> Result<(), StoreError> {
+        self.tables().sessions.push(Session { ongoing: false, ..session.clone() });
+        Ok(())
+    }
+
+    fn sessions(&self, start: i64, end: i64) -> Result<Vec<Session>, StoreError> {
+        let mut sessions: Vec<Session> = self
+            .tables()
+            .sessions
+            .iter()
+            .filter(|session| session.start.timestamp_millis() < end && session.end.timestamp_millis() > start)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.start);
+        Ok(sessions)
+    }
+
+    fn prune(&self, before: i64) -> Result<usize, StoreError> {
+        let mut tables = self.tables();
+        let count = tables.events.len();
+        tables.events.retain(|event| event.ended_at >= before);
+        Ok(count - tables.events.len())
+    }
+}
+
+#[cfg(feature = "history")]
+fn default_path() -> PathBuf {
+    let data_dir = env_var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env_var("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    data_dir.join("represence").join("history.db")
+}
+
+pub fn millis_to_datetime(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis).single().unwrap_or_default()
+}
+
+/// Parse an RFC 3339 timestamp, a `YYYY-MM-DD` date (UTC midnight) or unix seconds
+pub fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    value.parse::<i64>().ok().and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+}
@@ -0,0 +1,301 @@
+use axum::extract::{Extension, Path, Query, State, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use crate::config::env_list;
+use crate::subscription::{Subscription, SubscriptionParams};
+use crate::trust::{Trust, Visible};
+use crate::web_server::{self, AppState, Broadcaster, ClientGuard, SharedData, WireFormat};
+use crate::OutputData;
+
+/// Longest status text an agent may push
+pub const MAX_TEXT_LEN: usize = 512;
+
+/// What an agent pushes to its hub, the presence and the text the hub shows visitors without a friend token
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PushedPresence {
+    #[serde(flatten)]
+    pub presence: OutputData,
+    /// Left out of the presence everywhere else, a hub can't make the text coarse itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coarse_text: Option<String>,
+}
+
+impl PushedPresence {
+    pub fn new(presence: &OutputData) -> Self {
+        Self { presence: presence.clone(), coarse_text: presence.coarse_text.clone() }
+    }
+
+    pub fn into_presence(self) -> OutputData {
+        OutputData { coarse_text: self.coarse_text, ..self.presence }
+    }
+}
+
+/// Presence channel for a single hub user
+pub struct UserChannel {
+    token: String,
+    pub shared_data: SharedData,
+    pub broadcaster: Broadcaster,
+}
+
+/// Presence for multiple users on one deployment, each fed by their own agent
+#[derive(Default)]
+pub struct Hub {
+    users: HashMap<String, UserChannel>,
+}
+
+impl Hub {
+    /// Users come from `REPRESENCE_HUB_USERS` as comma-separated `user:token` pairs
+    pub fn from_env() -> Self {
+        let mut users = HashMap::new();
+
+        for entry in env_list("REPRESENCE_HUB_USERS") {
+            let Some((user, token)) = entry.split_once(':') else {
+                eprintln!("Ignoring hub user '{}', expected user:token", entry);
+                continue;
+            };
+
+            let (tx, _rx) = broadcast::channel(32);
+            users.insert(user.trim().to_string(), UserChannel {
+                token: token.trim().to_string(),
+                shared_data: web_server::shared_data(OutputData::new("offline")),
+                broadcaster: tx,
+            });
+        }
+
+        Self { users }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn user(&self, name: &str) -> Option<&UserChannel> {
+        self.users.get(name)
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/represence/{user}", get(get_user_presence).post(push_user_presence))
+        .route("/ws/represence/{user}", get(user_websocket_handler))
+        .route("/sse/represence/{user}", get(user_sse_handler))
+}
+
+fn unknown_user() -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "unknown user" }))).into_response()
+}
+
+/// Current presence of a hub user
+#[utoipa::path(
+    get,
+    path = "/api/represence/{user}",
+    tag = "hub",
+    params(("user" = String, Path, description = "Hub user name")),
+    responses(
+        (status = 200, description = "Current presence", body = OutputData),
+        (status = 404, description = "Unknown user"),
+    )
+)]
+pub async fn get_user_presence(Path(user): Path<String>, State(state): State<AppState>, Extension(trust): Extension<Trust>) -> Response {
+    let Some(channel) = state.hub.user(&user) else {
+        return unknown_user();
+    };
+
+    Json(Visible::new(trust, &channel.shared_data, &channel.broadcaster).current()).into_response()
+}
+
+/// Agents push their presence here with `Authorization: Bearer <token>`
+#[utoipa::path(
+    post,
+    path = "/api/represence/{user}",
+    tag = "hub",
+    params(("user" = String, Path, description = "Hub user name")),
+    request_body = PushedPresence,
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Presence accepted"),
+        (status = 401, description = "Missing or invalid agent token"),
+        (status = 404, description = "Unknown user"),
+        (status = 413, description = "Status text too long"),
+    )
+)]
+pub async fn push_user_presence(
+    Path(user): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(pushed): Json<PushedPresence>,
+) -> Response {
+    let Some(channel) = state.hub.user(&user) else {
+        return unknown_user();
+    };
+
+    let authorized = web_server::bearer_token(&headers)
+        .is_some_and(|token| web_server::constant_time_eq(token, &channel.token));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let data = pushed.into_presence();
+    if data.text.len() > MAX_TEXT_LEN || data.coarse_text.as_ref().is_some_and(|text| text.len() > MAX_TEXT_LEN) {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    if **channel.shared_data.load() != data {
+        web_server::publish(&channel.shared_data, &channel.broadcaster, data);
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Live presence updates for a hub user
+#[utoipa::path(
+    get,
+    path = "/ws/represence/{user}",
+    tag = "hub",
+    params(("user" = String, Path, description = "Hub user name"), SubscriptionParams),
+    responses(
+        (status = 101, description = "WebSocket upgrade, sends OutputData messages limited to the subscribed fields, or JSON Patch deltas with `mode=patch`, as JSON or MessagePack with the `represence.msgpack` subprotocol"),
+        (status = 400, description = "Unknown event"),
+    )
+)]
+pub async fn user_websocket_handler(
+    ws: WebSocketUpgrade,
+    Path(user): Path<String>,
+    Query(params): Query<SubscriptionParams>,
+    State(state): State<AppState>,
+    Extension(trust): Extension<Trust>,
+) -> Response {
+    let Some(channel) = state.hub.user(&user) else {
+        return unknown_user();
+    };
+    let subscription = match Subscription::from_params(&params) {
+        Ok(subscription) => subscription,
+        Err(e) => return web_server::bad_request(&e),
+    };
+
+    let presence = Visible::new(trust, &channel.shared_data, &channel.broadcaster);
+    let ws = ws.protocols(WireFormat::PROTOCOLS);
+    let format = WireFormat::negotiated(&ws);
+    let guard = ClientGuard::new(&state.websocket_clients);
+    ws.on_upgrade(move |socket| web_server::websocket_connection(socket, presence, subscription, format, guard))
+}
+
+/// Live presence updates for a hub user as Server-Sent Events
+#[utoipa::path(
+    get,
+    path = "/sse/represence/{user}",
+    tag = "hub",
+    params(("user" = String, Path, description = "Hub user name")),
+    responses((status = 200, description = "Event stream of OutputData JSON", content_type = "text/event-stream"))
+)]
+pub async fn user_sse_handler(Path(user): Path<String>, State(state): State<AppState>, Extension(trust): Extension<Trust>) -> Response {
+    let Some(channel) = state.hub.user(&user) else {
+        return unknown_user();
+    };
+
+    web_server::sse_stream(&Visible::new(trust, &channel.shared_data, &channel.broadcaster))
+        .await
+        .into_response()
+}
+
+#[cfg(feature = "http-client")]
+pub mod agent {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+    use tokio::sync::broadcast::Receiver;
+
+    use super::PushedPresence;
+    use crate::config::env_var;
+    use crate::web_server::{Broadcaster, SharedData};
+    use crate::OutputData;
+
+    const REQUEST_TIMEOUT_SECS: u64 = 10;
+    const INITIAL_BACKOFF_MS: u64 = 500;
+    const MAX_BACKOFF_SECS: u64 = 60;
+
+    /// Where this instance reports its own presence when acting as an agent for a hub
+    #[derive(Debug, Clone)]
+    pub struct AgentConfig {
+        pub url: String,
+        pub user: String,
+        pub token: String,
+    }
+
+    impl AgentConfig {
+        pub fn from_env() -> Option<Self> {
+            Some(Self {
+                url: env_var("REPRESENCE_HUB_URL")?.trim_end_matches('/').to_string(),
+                user: env_var("REPRESENCE_HUB_USER")?,
+                token: env_var("REPRESENCE_HUB_TOKEN")?,
+            })
+        }
+    }
+
+    /// Push the current presence, then every local change to the hub.
+    /// Failed pushes are retried with exponential backoff, with whatever presence is the latest by then.
+    pub fn spawn(config: AgentConfig, shared_data: &SharedData, broadcaster: &Broadcaster) {
+        let mut rx = broadcaster.subscribe();
+        let client = reqwest::Client::new();
+        let endpoint = format!("{}/api/represence/{}", config.url, config.user);
+        // The hub shows this user offline until it hears from us, don't wait for the next change
+        let mut pending = Some(shared_data.load_full());
+        let shared_data = shared_data.clone();
+
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+            loop {
+                let data = match pending.take() {
+                    Some(data) => data,
+                    None => match rx.recv().await {
+                        Ok(data) => data,
+                        Err(RecvError::Lagged(_)) => shared_data.load_full(),
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+
+                let result = client
+                    .post(&endpoint)
+                    .bearer_auth(&config.token)
+                    .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+                    .json(&PushedPresence::new(&data))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+
+                match result {
+                    Ok(_) => backoff = Duration::from_millis(INITIAL_BACKOFF_MS),
+                    Err(e) => {
+                        eprintln!("Failed to push presence to hub, retrying in {:?}: {}", backoff, e);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(Duration::from_secs(MAX_BACKOFF_SECS));
+                        pending = Some(latest(&mut rx, &shared_data).unwrap_or(data));
+                    }
+                }
+            }
+        });
+    }
+
+    /// The newest update waiting in `rx`, if any
+    fn latest(rx: &mut Receiver<Arc<OutputData>>, shared_data: &SharedData) -> Option<Arc<OutputData>> {
+        let mut latest = None;
+        loop {
+            match rx.try_recv() {
+                Ok(data) => latest = Some(data),
+                Err(TryRecvError::Lagged(_)) => latest = Some(shared_data.load_full()),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return latest,
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::env_json;
+use crate::OutputData;
+
+/// A built-in status text before it is filled in, e.g. `editing {file} in Visual Studio Code` with `file`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Message {
+    pub template: String,
+    pub args: Vec<(String, String)>,
+}
+
+impl Message {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self { template: template.into(), args: Vec::new() }
+    }
+
+    pub fn with_arg(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.args.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Fill `template`, the translated one or the message's own, with the arguments
+    pub fn render(&self, template: &str) -> String {
+        self.args
+            .iter()
+            .fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(&self.template))
+    }
+}
+
+/// Query parameter picking the language instead of `Accept-Language`
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct LangParams {
+    /// Language tag like `es` or `pt-BR`
+    pub lang: Option<String>,
+}
+
+/// Status texts per language, keyed by the English template (`editing {file} in Visual Studio Code`)
+/// or, for texts without arguments like phrases and composed activities, the exact English text
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Translations {
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    /// Translations from the JSON file at `REPRESENCE_TRANSLATIONS_FILE` or inline in `REPRESENCE_TRANSLATIONS_JSON`, e.g. `{"es": {"idle": "inactivo"}}`
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_TRANSLATIONS", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(translations) => {
+                println!("Loaded translations for {} language(s) from {}", translations.locales.len(), from);
+                translations
+            }
+            Err(e) => {
+                eprintln!("Ignoring translation file {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        let translations: Self = serde_json::from_str(source)?;
+        Ok(Self::new(translations.locales))
+    }
+
+    /// Language tags are matched case-insensitively
+    pub fn new(locales: HashMap<String, HashMap<String, String>>) -> Self {
+        Self { locales: locales.into_iter().map(|(tag, texts)| (tag.to_ascii_lowercase(), texts)).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locales.is_empty()
+    }
+
+    /// Texts for `?lang=`, or the best language we have from `Accept-Language` in order of preference.
+    /// `es-MX` falls back to `es`, and `?lang=en` asks for the original texts.
+    pub fn negotiate(&self, params: &LangParams, headers: &HeaderMap) -> Option<&HashMap<String, String>> {
+        if self.is_empty() {
+            return None;
+        }
+        let wanted: Vec<(String, f32)> = match &params.lang {
+            Some(lang) => vec![(lang.clone(), 1.0)],
+            None => headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .map(parse_accept_language)
+                .unwrap_or_default(),
+        };
+
+        wanted.iter().find_map(|(tag, _)| {
+            let tag = tag.to_ascii_lowercase();
+            let primary = tag.split('-').next().unwrap_or_default();
+            self.locales.get(&tag).or_else(|| self.locales.get(primary))
+        })
+    }
+
+    /// The presence in the negotiated language, `None` to serve it as it is
+    pub fn translate(&self, data: &OutputData, params: &LangParams, headers: &HeaderMap) -> Option<OutputData> {
+        let texts = self.negotiate(params, headers)?;
+        let text = match &data.message {
+            Some(message) => message.render(texts.get(&message.template)?),
+            None => texts.get(&data.text)?.clone(),
+        };
+        Some(OutputData { text, ..data.clone() })
+    }
+}
+
+/// Language tags by descending quality, `*` and `q=0` left out
+fn parse_accept_language(value: &str) -> Vec<(String, f32)> {
+    let mut tags: Vec<(String, f32)> = value
+        .split(',')
+        .filter_map(|part| {
+            let mut fields = part.split(';');
+            let tag = fields.next()?.trim();
+            let quality = fields
+                .find_map(|field| field.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+    // Stable, so equal qualities keep the client's order
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::compose;
+use crate::config::env_json;
+use crate::process_finder::RunningApp;
+
+/// Emoji, icon image and accent color for an app or a kind of activity
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Icon {
+    #[serde(default)]
+    pub emoji: Option<String>,
+    /// Image URL, e.g. for Discord RPC assets or a website widget
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// CSS color, e.g. `#22c55e`, for widgets and the SVG badge to tint themselves with
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Icons and colors by app name prefix, and by activity kind for apps without their own: the `compose` categories
+/// (`editor`, `browser`, `chat`, `media`, `game`, `terminal`) plus `live`, `meeting` and `idle`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Icons {
+    #[serde(default)]
+    apps: HashMap<String, Icon>,
+    #[serde(default)]
+    kinds: HashMap<String, Icon>,
+}
+
+/// What the presence shows, to look its icon up
+#[derive(Debug, Clone, Copy)]
+pub enum Activity<'a> {
+    Live,
+    Meeting,
+    App(&'a RunningApp),
+    Idle,
+}
+
+impl Icons {
+    /// Icons from the JSON file at `REPRESENCE_ICONS_FILE` or inline in `REPRESENCE_ICONS_JSON`, none otherwise
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_ICONS", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(icons) => {
+                println!("Loaded icons for {} app(s) and {} activity kind(s) from {}", icons.apps.len(), icons.kinds.len(), from);
+                icons
+            }
+            Err(e) => {
+                eprintln!("Ignoring icon file {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(source)
+    }
+
+    pub fn new(apps: HashMap<String, Icon>, kinds: HashMap<String, Icon>) -> Self {
+        Self { apps, kinds }
+    }
+
+    /// The most specific icon configured, the longest matching app prefix before the activity kind
+    pub fn icon(&self, activity: Activity) -> Icon {
+        let kind = match activity {
+            Activity::Live => Some("live"),
+            Activity::Meeting => Some("meeting"),
+            Activity::App(app) => compose::app_category(app),
+            Activity::Idle => Some("idle"),
+        };
+        let app = match activity {
+            Activity::App(app) => self.apps.iter().filter(|(key, _)| app.name.starts_with(key.as_str())).max_by_key(|(key, _)| key.len()).map(|(_, icon)| icon),
+            _ => None,
+        };
+        let kind = kind.and_then(|kind| self.kinds.get(kind));
+
+        // An app entry with just an emoji still gets the kind's image
+        let pick = |field: fn(&Icon) -> &Option<String>| app.and_then(|icon| field(icon).clone()).or_else(|| kind.and_then(|icon| field(icon).clone()));
+        Icon { emoji: pick(|icon| &icon.emoji), icon: pick(|icon| &icon.icon), color: pick(|icon| &icon.color) }
+    }
+}
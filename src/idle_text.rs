@@ -0,0 +1,104 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::config::{self, env_json};
+
+/// Fallback text for a daily time window, e.g. `probably sleeping` from 01:00 to 08:00
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleRule {
+    /// Days the window starts on, every day when empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub from: NaiveTime,
+    /// May be earlier than `from` to wrap past midnight, equal to `from` for the whole day
+    #[serde(deserialize_with = "deserialize_time")]
+    pub to: NaiveTime,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdleRulesFile {
+    /// IANA name like `Europe/Istanbul`, the system timezone otherwise
+    #[serde(default, deserialize_with = "config::deserialize_timezone")]
+    timezone: Option<Tz>,
+    rules: Vec<IdleRule>,
+}
+
+/// Time-of-day texts shown instead of `idle` when nothing is detected, the first matching rule wins
+#[derive(Debug, Clone, Default)]
+pub struct IdleRules {
+    rules: Vec<IdleRule>,
+    timezone: Option<Tz>,
+}
+
+impl IdleRules {
+    /// Rules from the JSON file in `REPRESENCE_IDLE_RULES_FILE` or inline in `REPRESENCE_IDLE_RULES_JSON`, none otherwise
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_IDLE_RULES", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("Ignoring idle rules, failed to load {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        let file: IdleRulesFile = serde_json::from_str(source)?;
+        Ok(Self::new(file.rules, file.timezone))
+    }
+
+    pub fn new(rules: Vec<IdleRule>, timezone: Option<Tz>) -> Self {
+        Self { rules, timezone }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Text for right now, `None` when no rule covers the current time
+    pub fn text(&self) -> Option<&str> {
+        self.text_at(Utc::now())
+    }
+
+    pub fn text_at(&self, now: DateTime<Utc>) -> Option<&str> {
+        let local = match self.timezone {
+            Some(timezone) => timezone.from_utc_datetime(&now.naive_utc()).naive_local(),
+            None => now.with_timezone(&chrono::Local).naive_local(),
+        };
+        self.rules.iter().find(|rule| rule.covers(local)).map(|rule| rule.text.as_str())
+    }
+}
+
+impl IdleRule {
+    fn covers(&self, local: NaiveDateTime) -> bool {
+        let time = local.time();
+        let start_day = match (self.from <= self.to, time >= self.from, time < self.to) {
+            _ if self.from == self.to => local.date(),
+            (true, true, true) => local.date(),
+            (false, true, _) => local.date(),
+            // Past midnight in a wrapping window, which started the day before
+            (false, false, true) => local.date() - Duration::days(1),
+            _ => return false,
+        };
+        self.days.is_empty() || self.days.contains(&start_day.weekday())
+    }
+}
+
+fn deserialize_time<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&time, "%H:%M").map_err(|_| serde::de::Error::custom(format!("expected HH:MM, got '{}'", time)))
+}
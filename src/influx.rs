@@ -0,0 +1,131 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::config::{env_parse, env_var};
+use crate::device;
+use crate::diagnostics::SharedDiagnostics;
+use crate::web_server::SharedData;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// How often the front app is sampled to attribute time between writes
+const SAMPLE_INTERVAL_SECS: u64 = 1;
+
+/// Lines kept while the endpoint is unreachable, oldest are dropped first
+const MAX_BUFFERED_LINES: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    /// Full write URL, e.g. `http://influxdb:8086/api/v2/write?org=home&bucket=represence`
+    pub url: String,
+    pub token: Option<String>,
+    pub measurement: String,
+    pub interval: Duration,
+    pub host: String,
+}
+
+impl InfluxConfig {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            url: env_var("REPRESENCE_INFLUX_URL")?,
+            token: env_var("REPRESENCE_INFLUX_TOKEN"),
+            measurement: env_var("REPRESENCE_INFLUX_MEASUREMENT").unwrap_or_else(|| "represence".to_string()),
+            interval: Duration::from_secs(env_parse("REPRESENCE_INFLUX_INTERVAL_SECS", 60u64).max(1)),
+            host: device::hostname(),
+        })
+    }
+}
+
+fn escape_measurement(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ")
+}
+
+/// Escape a tag key or tag value
+fn escape_key(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/// Quote a string field value
+fn escape_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Write the current presence and per-app time to a line-protocol endpoint on an interval
+pub fn spawn(config: InfluxConfig, shared_data: SharedData, diagnostics: SharedDiagnostics) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("InfluxDB export disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let measurement = escape_measurement(&config.measurement);
+        let host = escape_key(&config.host);
+        let mut buffer: VecDeque<String> = VecDeque::new();
+        let mut app_time: HashMap<String, Duration> = HashMap::new();
+        let mut sample = tokio::time::interval(Duration::from_secs(SAMPLE_INTERVAL_SECS));
+        let mut last_sample = Instant::now();
+        let mut last_write = Instant::now();
+
+        loop {
+            sample.tick().await;
+
+            // Attribute the time since the last sample to whichever app is in front
+            let front_app = diagnostics.read().await.detected_apps.first().map(|app| app.name.clone());
+            if let Some(app) = front_app {
+                *app_time.entry(app).or_default() += last_sample.elapsed();
+            }
+            last_sample = Instant::now();
+
+            if last_write.elapsed() < config.interval {
+                continue;
+            }
+            last_write = Instant::now();
+
+            let timestamp = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+            let text = shared_data.load().text.clone();
+            buffer.push_back(format!(
+                "{},host={} status={},idle={} {}",
+                measurement,
+                host,
+                escape_string(&text),
+                text == "idle",
+                timestamp
+            ));
+            for (app, duration) in app_time.drain() {
+                buffer.push_back(format!(
+                    "{}_app,host={},app={} seconds={} {}",
+                    measurement,
+                    host,
+                    escape_key(&app),
+                    duration.as_secs_f64(),
+                    timestamp
+                ));
+            }
+            while buffer.len() > MAX_BUFFERED_LINES {
+                buffer.pop_front();
+            }
+
+            let body = buffer.iter().map(String::as_str).collect::<Vec<_>>().join("\n");
+            let mut request = client
+                .post(&config.url)
+                .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(body);
+            if let Some(token) = &config.token {
+                request = request.header(reqwest::header::AUTHORIZATION, format!("Token {}", token));
+            }
+
+            match request.send().await.and_then(|response| response.error_for_status()) {
+                Ok(_) => buffer.clear(),
+                // Keep the lines and send them with the next batch
+                Err(e) => eprintln!("Failed to write to InfluxDB: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,132 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::blocklist::Blocklist;
+use crate::cli::InitArgs;
+use crate::presence;
+use crate::process_finder::{self, ProcSource, ProcessCache, RunningApp};
+
+/// What the wizard asked, turned into settings by [`render`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Answers {
+    /// Running apps that should never show up
+    pub hidden: Vec<String>,
+    pub min_uptime_secs: u64,
+    /// Start in privacy mode
+    pub privacy: bool,
+    /// `None` keeps the default `busy`
+    pub privacy_text: Option<String>,
+    /// Listen on loopback only
+    pub local_only: bool,
+}
+
+/// Ask about `apps` and the privacy settings. Empty answers and the end of the input take the defaults.
+pub fn ask(input: &mut impl BufRead, output: &mut impl Write, apps: &[RunningApp]) -> io::Result<Answers> {
+    let mut answers = Answers::default();
+
+    if apps.is_empty() {
+        writeln!(output, "None of the apps represence knows are running, start a few and run init again to pick them.")?;
+    } else {
+        writeln!(output, "These running apps would show up, most important first:")?;
+        for app in apps {
+            writeln!(output, "  {} (tier {})", app.name, app.tier)?;
+        }
+        for app in apps {
+            if !confirm(input, output, &format!("Show {}?", app.name), true)? {
+                answers.hidden.push(app.name.clone());
+            }
+        }
+    }
+
+    answers.min_uptime_secs = loop {
+        let answer = prompt(input, output, "Seconds an app must run before it shows, so a quick look doesn't [0]:")?;
+        match answer.parse() {
+            Ok(secs) => break secs,
+            Err(_) if answer.is_empty() => break 0,
+            Err(_) => writeln!(output, "'{}' isn't a number of seconds", answer)?,
+        }
+    };
+    answers.privacy = confirm(input, output, "Start in privacy mode, publishing a fixed text until you turn it off?", false)?;
+    let text = prompt(input, output, "Text shown in privacy mode [busy]:")?;
+    answers.privacy_text = (!text.is_empty()).then_some(text);
+    answers.local_only = confirm(input, output, "Only reachable from this machine, e.g. behind a reverse proxy?", false)?;
+    Ok(answers)
+}
+
+fn prompt(input: &mut impl BufRead, output: &mut impl Write, question: &str) -> io::Result<String> {
+    write!(output, "{} ", question)?;
+    output.flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+fn confirm(input: &mut impl BufRead, output: &mut impl Write, question: &str, default: bool) -> io::Result<bool> {
+    let options = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match prompt(input, output, &format!("{} {}", question, options))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            answer => writeln!(output, "'{}' isn't yes or no", answer)?,
+        }
+    }
+}
+
+/// A commented `.env` with the answers set and the next steps as comments
+pub fn render(answers: &Answers, port: u16) -> String {
+    let mut env = String::from("# Written by `represence init`, every variable is described in the README\n\n");
+
+    env.push_str("# Apps that never show up, name prefixes, optionally only during a time window like steam@09:00-17:00\n");
+    match answers.hidden.is_empty() {
+        true => env.push_str("# REPRESENCE_BLOCKLIST=keepassxc,1password\n"),
+        false => env.push_str(&format!("REPRESENCE_BLOCKLIST={}\n", answers.hidden.join(","))),
+    }
+
+    env.push_str("\n# Seconds an app must have been running before it shows\n");
+    env.push_str(&format!("REPRESENCE_MIN_UPTIME_SECS={}\n", answers.min_uptime_secs));
+
+    env.push_str("\n# Privacy mode publishes a fixed text instead of what's detected, toggle it with `pkill -USR1 represence`\n");
+    env.push_str(&format!("REPRESENCE_PRIVACY={}\n", answers.privacy));
+    match &answers.privacy_text {
+        Some(text) => env.push_str(&format!("REPRESENCE_PRIVACY_TEXT={}\n", quote(text))),
+        None => env.push_str("# REPRESENCE_PRIVACY_TEXT=busy\n"),
+    }
+
+    env.push_str("\n# Where the API listens\n");
+    env.push_str(&format!("REPRESENCE_PORT={}\n", port));
+    match answers.local_only {
+        true => env.push_str(&format!("REPRESENCE_BIND=127.0.0.1:{0},[::1]:{0}\n", port)),
+        false => env.push_str(&format!("# REPRESENCE_BIND=127.0.0.1:{0},[::1]:{0}\n", port)),
+    }
+
+    env.push_str("\n# Token for the admin endpoints and full details, pick a long random one\n");
+    env.push_str("# REPRESENCE_ADMIN_TOKEN=\n");
+    env
+}
+
+/// A value as `.env` reads it back literally: single quotes, or double quotes with escapes when it has one
+fn quote(value: &str) -> String {
+    match value.contains('\'') {
+        false => format!("'{}'", value),
+        true => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$")),
+    }
+}
+
+/// `represence init`: scan the running apps, ask about them and privacy, and write a starter `.env`
+pub async fn run(args: InitArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.output.exists() && !args.force {
+        return Err(format!("{} already exists, pass --force to replace it", args.output.display()).into());
+    }
+
+    // What would show without any configuration, so an existing blocklist doesn't hide apps from the question
+    let source = ProcSource::from_env();
+    let mut cache = ProcessCache::new(Duration::ZERO);
+    let (apps, _) =
+        process_finder::get_running_apps_optimized(&source, &presence::default_apps(), &Blocklist::default(), &mut cache).await?;
+
+    let answers = ask(&mut io::stdin().lock(), &mut io::stdout(), &apps)?;
+    std::fs::write(&args.output, render(&answers, crate::config::env_parse("REPRESENCE_PORT", 3001u16)))?;
+    println!("Wrote {}, start represence in this directory to use it and run `represence doctor` to check the setup", args.output.display());
+    Ok(())
+}
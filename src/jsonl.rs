@@ -0,0 +1,258 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::history::{
+    millis_to_datetime, DailyTotals, HistoryPage, HistoryQuery, MemoryHistory, PresenceStore, Session, Span, StoreError,
+};
+
+/// One line of the file. Writes are appended as they happen and replayed on open.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    /// A transition, closing the previous event like `record` does
+    Record {
+        timestamp: i64,
+        text: String,
+        app: Option<String>,
+        language: Option<String>,
+    },
+    Heartbeat {
+        timestamp: i64,
+    },
+    /// An event with its end already known, as compaction writes them
+    Event {
+        timestamp: i64,
+        ended_at: i64,
+        text: String,
+        app: Option<String>,
+        language: Option<String>,
+    },
+    Summary {
+        day: NaiveDate,
+        active_secs: i64,
+        coding_secs: i64,
+        apps: HashMap<String, i64>,
+        languages: HashMap<String, i64>,
+    },
+    Session {
+        start: i64,
+        end: i64,
+        dominant_app: Option<String>,
+        active_secs: i64,
+        apps: BTreeMap<String, i64>,
+    },
+    Prune {
+        before: i64,
+    },
+}
+
+/// Presence history as JSON lines in a flat file, easy to read, grep and sync. Everything is kept in
+/// memory while running, so it suits months of history rather than years.
+pub struct JsonlHistory {
+    memory: MemoryHistory,
+    path: PathBuf,
+    file: Mutex<File>,
+    write_failures: AtomicU64,
+}
+
+impl JsonlHistory {
+    /// Replay `path` and compact it, heartbeats and pruned events don't survive the rewrite
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        let memory = MemoryHistory::new();
+        match File::open(path) {
+            Ok(file) => {
+                for (number, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // A line cut short by a crash loses that write, not the history
+                    match serde_json::from_str(&line) {
+                        Ok(entry) => apply(&memory, entry)?,
+                        Err(e) => eprintln!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        compact(&memory, path)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            memory,
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            write_failures: AtomicU64::new(0),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry once it has been applied in memory
+    fn append(&self, entry: &Entry) -> Result<(), StoreError> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let result = writeln!(file, "{}", line(entry)).and_then(|()| file.flush());
+        if result.is_err() {
+            self.write_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(result?)
+    }
+}
+
+fn line(entry: &Entry) -> String {
+    serde_json::to_string(entry).unwrap_or_default()
+}
+
+fn apply(memory: &MemoryHistory, entry: Entry) -> Result<(), StoreError> {
+    match entry {
+        Entry::Record { timestamp, text, app, language } => {
+            memory.record(millis_to_datetime(timestamp), &text, app.as_deref(), language.as_deref())
+        }
+        Entry::Heartbeat { timestamp } => memory.heartbeat(millis_to_datetime(timestamp)),
+        Entry::Event { timestamp, ended_at, text, app, language } => {
+            memory.insert(timestamp, text, app, language, ended_at);
+            Ok(())
+        }
+        Entry::Summary { day, active_secs, coding_secs, apps, languages } => {
+            memory.store_summary(&DailyTotals { day, active_secs, coding_secs, apps, languages })
+        }
+        Entry::Session { start, end, dominant_app, active_secs, apps } => memory.store_session(&Session {
+            start: millis_to_datetime(start),
+            end: millis_to_datetime(end),
+            duration_secs: (end - start) / 1000,
+            active_secs,
+            dominant_app,
+            apps,
+            ongoing: false,
+        }),
+        Entry::Prune { before } => memory.prune(before).map(|_| ()),
+    }
+}
+
+/// Rewrite the file with what's left in memory, through a temporary file so a crash keeps the old one
+fn compact(memory: &MemoryHistory, path: &Path) -> Result<(), StoreError> {
+    let staging = path.with_extension("jsonl.compacting");
+    let mut out = BufWriter::new(File::create(&staging)?);
+    {
+        let tables = memory.tables();
+        for event in &tables.events {
+            let entry = Entry::Event {
+                timestamp: event.timestamp,
+                ended_at: event.ended_at,
+                text: event.text.clone(),
+                app: event.app.clone(),
+                language: event.language.clone(),
+            };
+            writeln!(out, "{}", line(&entry))?;
+        }
+        for totals in tables.summaries.values() {
+            writeln!(out, "{}", line(&summary_entry(totals)))?;
+        }
+        for session in &tables.sessions {
+            writeln!(out, "{}", line(&session_entry(session)))?;
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&staging, path)?;
+    Ok(())
+}
+
+fn summary_entry(totals: &DailyTotals) -> Entry {
+    Entry::Summary {
+        day: totals.day,
+        active_secs: totals.active_secs,
+        coding_secs: totals.coding_secs,
+        apps: totals.apps.clone(),
+        languages: totals.languages.clone(),
+    }
+}
+
+fn session_entry(session: &Session) -> Entry {
+    Entry::Session {
+        start: session.start.timestamp_millis(),
+        end: session.end.timestamp_millis(),
+        dominant_app: session.dominant_app.clone(),
+        active_secs: session.active_secs,
+        apps: session.apps.clone(),
+    }
+}
+
+impl PresenceStore for JsonlHistory {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn write_failures(&self) -> u64 {
+        self.write_failures.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, timestamp: DateTime<Utc>, text: &str, app: Option<&str>, language: Option<&str>) -> Result<(), StoreError> {
+        self.memory.record(timestamp, text, app, language)?;
+        self.append(&Entry::Record {
+            timestamp: timestamp.timestamp_millis(),
+            text: text.to_string(),
+            app: app.map(str::to_string),
+            language: language.map(str::to_string),
+        })
+    }
+
+    fn heartbeat(&self, timestamp: DateTime<Utc>) -> Result<(), StoreError> {
+        self.memory.heartbeat(timestamp)?;
+        self.append(&Entry::Heartbeat { timestamp: timestamp.timestamp_millis() })
+    }
+
+    fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, StoreError> {
+        self.memory.query(query)
+    }
+
+    fn spans(&self, start: i64, end: i64) -> Result<Vec<Span>, StoreError> {
+        self.memory.spans(start, end)
+    }
+
+    fn first_event(&self) -> Result<Option<i64>, StoreError> {
+        self.memory.first_event()
+    }
+
+    fn latest_summary_day(&self) -> Result<Option<NaiveDate>, StoreError> {
+        self.memory.latest_summary_day()
+    }
+
+    fn store_summary(&self, totals: &DailyTotals) -> Result<(), StoreError> {
+        self.memory.store_summary(totals)?;
+        self.append(&summary_entry(totals))
+    }
+
+    fn summaries(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyTotals>, StoreError> {
+        self.memory.summaries(from, to)
+    }
+
+    fn latest_session_end(&self) -> Result<Option<i64>, StoreError> {
+        self.memory.latest_session_end()
+    }
+
+    fn store_session(&self, session: &Session) -> Result<(), StoreError> {
+        self.memory.store_session(session)?;
+        self.append(&session_entry(session))
+    }
+
+    fn sessions(&self, start: i64, end: i64) -> Result<Vec<Session>, StoreError> {
+        self.memory.sessions(start, end)
+    }
+
+    fn prune(&self, before: i64) -> Result<usize, StoreError> {
+        let removed = self.memory.prune(before)?;
+        if removed > 0 {
+            self.append(&Entry::Prune { before })?;
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,240 @@
+use axum::extract::ws::{Message, WebSocket};
+use axum::extract::{Path, State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::{env_parse, env_var};
+use crate::trust::Visible;
+use crate::web_server::{AppState, ClientGuard};
+use crate::OutputData;
+
+/// Interval clients are told to heartbeat at, same as the real Lanyard API
+const HEARTBEAT_INTERVAL_MS: u64 = 30_000;
+
+// Lanyard socket op codes
+const OP_EVENT: u8 = 0;
+const OP_HELLO: u8 = 1;
+const OP_INITIALIZE: u8 = 2;
+const OP_HEARTBEAT: u8 = 3;
+
+#[derive(Debug, Clone)]
+pub struct LanyardConfig {
+    /// Only this user ID is answered when set, any ID is accepted otherwise
+    pub user_id: Option<String>,
+}
+
+impl LanyardConfig {
+    pub fn from_env() -> Option<Self> {
+        if !env_parse("REPRESENCE_LANYARD", false) {
+            return None;
+        }
+
+        Some(Self {
+            user_id: env_var("REPRESENCE_LANYARD_USER_ID"),
+        })
+    }
+
+    fn monitors(&self, user_id: &str) -> bool {
+        self.user_id.as_deref().is_none_or(|id| id == user_id)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketMessage {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+}
+
+pub fn routes(config: LanyardConfig) -> Router<AppState> {
+    let config = Arc::new(config);
+    let socket_config = config.clone();
+
+    Router::new()
+        .route(
+            "/v1/users/{id}",
+            get(move |path, visible| get_user(path, visible, config.clone())),
+        )
+        .route(
+            "/socket",
+            get(move |ws, state, presence| socket_handler(ws, state, presence, socket_config.clone())),
+        )
+}
+
+/// Map represence data onto a Lanyard presence object
+fn presence(data: &OutputData, user_id: &str) -> Value {
+    let is_idle = data.text == "idle";
+
+    let activities = if is_idle {
+        Vec::new()
+    } else {
+        // Lanyard widgets render custom statuses (type 4) from `state`
+        vec![json!({
+            "id": "custom",
+            "type": 4,
+            "name": "Custom Status",
+            "state": data.text,
+        })]
+    };
+
+    json!({
+        "discord_user": {
+            "id": user_id,
+            "username": user_id,
+            "global_name": null,
+            "avatar": null,
+            "discriminator": "0",
+        },
+        "discord_status": if is_idle { "idle" } else { "online" },
+        "activities": activities,
+        "listening_to_spotify": false,
+        "spotify": null,
+        "active_on_discord_web": false,
+        "active_on_discord_desktop": true,
+        "active_on_discord_mobile": false,
+        "kv": {},
+    })
+}
+
+async fn get_user(
+    Path(user_id): Path<String>,
+    visible: Visible,
+    config: Arc<LanyardConfig>,
+) -> Response {
+    if !config.monitors(&user_id) {
+        let body = json!({
+            "success": false,
+            "error": {
+                "code": "user_not_monitored",
+                "message": "User is not being monitored by Lanyard",
+            },
+        });
+        return (StatusCode::NOT_FOUND, Json(body)).into_response();
+    }
+
+    let data = visible.current();
+    Json(json!({ "success": true, "data": presence(&data, &user_id) })).into_response()
+}
+
+async fn socket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    presence: Visible,
+    config: Arc<LanyardConfig>,
+) -> Response {
+    let guard = ClientGuard::new(&state.websocket_clients);
+    ws.on_upgrade(move |socket| socket_connection(socket, presence, config, guard))
+}
+
+/// Subscription requested by the client in its Initialize message
+enum Subscription {
+    One(String),
+    Many(Vec<String>),
+}
+
+async fn socket_connection(
+    socket: WebSocket,
+    presence: Visible,
+    config: Arc<LanyardConfig>,
+    _guard: ClientGuard,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    // The current presence is sent on Initialize, only the changes after it come from here
+    let mut updates = Box::pin(presence.updates().skip(1));
+    let mut subscription: Option<Subscription> = None;
+    let mut seq = 0u64;
+
+    let hello = json!({ "op": OP_HELLO, "d": { "heartbeat_interval": HEARTBEAT_INTERVAL_MS } });
+    if sender.send(Message::Text(hello.to_string().into())).await.is_err() {
+        return;
+    }
+
+    loop {
+        let event = tokio::select! {
+            msg = receiver.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let Ok(message) = serde_json::from_str::<SocketMessage>(&text) else {
+                    continue;
+                };
+
+                match message.op {
+                    OP_INITIALIZE => {
+                        subscription = parse_subscription(&message.d, &config);
+                        let data = presence.current();
+                        subscription.as_ref().map(|sub| ("INIT_STATE", event_payloads(sub, &data, true)))
+                    }
+                    // Heartbeats only keep the connection alive, no ack is sent
+                    OP_HEARTBEAT => None,
+                    _ => None,
+                }
+            }
+            update = updates.next() => {
+                let Some(data) = update else {
+                    break;
+                };
+                subscription.as_ref().map(|sub| ("PRESENCE_UPDATE", event_payloads(sub, &data, false)))
+            }
+        };
+
+        let Some((event_type, payloads)) = event else {
+            continue;
+        };
+
+        for d in payloads {
+            seq += 1;
+            let message = json!({ "op": OP_EVENT, "seq": seq, "t": event_type, "d": d });
+            if sender.send(Message::Text(message.to_string().into())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn parse_subscription(d: &Value, config: &LanyardConfig) -> Option<Subscription> {
+    if let Some(id) = d.get("subscribe_to_id").and_then(Value::as_str) {
+        return config.monitors(id).then(|| Subscription::One(id.to_string()));
+    }
+
+    let ids: Vec<String> = d
+        .get("subscribe_to_ids")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|id| config.monitors(id))
+        .map(str::to_string)
+        .collect();
+
+    (!ids.is_empty()).then_some(Subscription::Many(ids))
+}
+
+/// Build the `d` payloads for an event: a single presence, a map of presences on
+/// multi-user init, or one tagged presence per user on multi-user updates
+fn event_payloads(subscription: &Subscription, data: &OutputData, initial: bool) -> Vec<Value> {
+    match subscription {
+        Subscription::One(id) => vec![presence(data, id)],
+        Subscription::Many(ids) if initial => vec![ids
+            .iter()
+            .map(|id| (id.clone(), presence(data, id)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()],
+        Subscription::Many(ids) => ids
+            .iter()
+            .map(|id| {
+                let mut value = presence(data, id);
+                value["user_id"] = json!(id);
+                value
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::now_playing::{NowPlaying, Track};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Key the scrobble is stored under, also the name the activity ranks as
+pub const APP: &str = "lastfm";
+
+#[derive(Debug, Clone)]
+pub struct LastFmConfig {
+    pub user: String,
+    pub api_key: String,
+    pub poll_interval: Duration,
+}
+
+impl LastFmConfig {
+    /// `REPRESENCE_LASTFM_USER` and `REPRESENCE_LASTFM_API_KEY`, `None` unless both are set
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            user: env_var("REPRESENCE_LASTFM_USER")?,
+            api_key: env_var("REPRESENCE_LASTFM_API_KEY")?,
+            poll_interval: Duration::from_secs(env_parse("REPRESENCE_LASTFM_POLL_SECS", 30).max(1)),
+        })
+    }
+}
+
+/// Response of `user.getRecentTracks`
+#[derive(Debug, Deserialize)]
+pub struct RecentTracks {
+    recenttracks: TrackList,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackList {
+    #[serde(default)]
+    track: OneOrMany,
+}
+
+/// A single track comes as an object rather than a list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    Many(Vec<Scrobble>),
+    One(Scrobble),
+}
+
+impl Default for OneOrMany {
+    fn default() -> Self {
+        Self::Many(Vec::new())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Scrobble {
+    name: String,
+    artist: Text,
+    #[serde(default)]
+    image: Vec<Image>,
+    #[serde(rename = "@attr")]
+    attr: Option<Attr>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Text {
+    #[serde(rename = "#text")]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    #[serde(rename = "#text")]
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Attr {
+    nowplaying: Option<String>,
+}
+
+/// The track marked as playing right now, past scrobbles don't count
+pub fn now_playing_track(recent: RecentTracks) -> Option<Track> {
+    let scrobbles = match recent.recenttracks.track {
+        OneOrMany::Many(scrobbles) => scrobbles,
+        OneOrMany::One(scrobble) => vec![scrobble],
+    };
+    let scrobble = scrobbles
+        .into_iter()
+        .find(|scrobble| scrobble.attr.as_ref().and_then(|attr| attr.nowplaying.as_deref()) == Some("true"))?;
+
+    let artist = Some(scrobble.artist.text).filter(|artist| !artist.is_empty());
+    // Sizes go from small to extralarge, the URLs are empty when the track has no cover
+    let album_art = scrobble.image.into_iter().rev().find(|image| !image.url.is_empty()).map(|image| image.url);
+    Some(Track { album_art, ..Track::new(scrobble.name, artist) })
+}
+
+/// Poll the account's recent tracks, a scrobble from another device counts while no local player is playing
+pub fn spawn(config: LastFmConfig, now_playing: NowPlaying) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Last.fm disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut failing = false;
+        loop {
+            let track = match recent_tracks(&client, &config).await {
+                Ok(recent) => {
+                    if failing {
+                        println!("Last.fm API reachable again");
+                        failing = false;
+                    }
+                    now_playing_track(recent)
+                }
+                Err(e) => {
+                    if !failing {
+                        // Without the URL, it holds the API key
+                        eprintln!("Last.fm API request failed: {}", e.without_url());
+                        failing = true;
+                    }
+                    None
+                }
+            };
+            now_playing.set_fallback(APP, "Last.fm", track).await;
+            tokio::time::sleep(config.poll_interval).await;
+        }
+    });
+}
+
+async fn recent_tracks(client: &reqwest::Client, config: &LastFmConfig) -> reqwest::Result<RecentTracks> {
+    client
+        .get(API_URL)
+        .query(&[
+            ("method", "user.getrecenttracks"),
+            ("user", config.user.as_str()),
+            ("api_key", config.api_key.as_str()),
+            ("format", "json"),
+            ("limit", "1"),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
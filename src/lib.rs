@@ -0,0 +1,163 @@
+//! Like Discord Rich Presence, but for anywhere: detects what you're doing and serves it over HTTP.
+
+use serde::{Deserialize, Serialize};
+
+pub mod access_log;
+#[cfg(feature = "alerts")]
+pub mod alert;
+pub mod aliases;
+pub mod audit;
+#[cfg(feature = "history")]
+pub mod backup;
+pub mod blocklist;
+pub mod cache;
+pub mod calendar;
+pub mod cli;
+pub mod compose;
+pub mod config;
+pub mod config_file;
+pub mod consumers;
+pub mod control;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod debounce;
+pub mod device;
+pub mod diagnostics;
+pub mod doctor;
+pub mod effective_config;
+pub mod electron;
+pub mod error;
+#[cfg(feature = "sentry")]
+pub mod error_reporting;
+pub mod export;
+pub mod features;
+pub mod film_log;
+pub mod github;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod history;
+pub mod hub;
+pub mod i18n;
+pub mod icons;
+pub mod idle_text;
+#[cfg(feature = "influxdb")]
+pub mod influx;
+pub mod init;
+pub mod jsonl;
+pub mod lanyard;
+#[cfg(feature = "lastfm")]
+pub mod lastfm;
+pub mod listener;
+pub mod live;
+#[cfg(feature = "mastodon")]
+pub mod mastodon;
+#[cfg(feature = "matrix")]
+pub mod matrix;
+#[cfg(feature = "media-server")]
+pub mod media_server;
+pub mod modules;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "dbus")]
+pub mod notify;
+pub mod now_playing;
+pub mod openapi;
+pub mod phrases;
+pub mod pidfile;
+pub mod players;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod presence;
+pub mod priority;
+pub mod process_finder;
+pub mod profile_readme;
+pub mod profiles;
+pub mod proxy;
+pub mod rate_limit;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod remote;
+pub mod script;
+pub mod sessions;
+pub mod simulate;
+pub mod sleep;
+#[cfg(feature = "spotify")]
+pub mod spotify;
+pub mod stale;
+pub mod streaks;
+pub mod subscription;
+pub mod summary;
+pub mod systemd;
+#[cfg(feature = "telegram")]
+pub mod telegram;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod terminal;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod trust;
+pub mod uptime;
+pub mod variables;
+pub mod variants;
+#[cfg(feature = "vlc")]
+pub mod vlc;
+pub mod vscode_client;
+#[cfg(feature = "wakatime")]
+pub mod wakatime;
+pub mod waybar;
+pub mod web_server;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject), graphql(name = "Presence"))]
+pub struct OutputData {
+    pub text: String,
+    /// Where this presence comes from, only present when enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<device::DeviceInfo>,
+    /// Cover of the track playing in the app shown, when a player integration knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album_art: Option<String>,
+    /// Film most recently logged on Letterboxd or Trakt, when configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_watched: Option<film_log::LastWatched>,
+    /// Where to watch along, set while live streaming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Emoji configured for the app or kind of activity shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// Icon image URL configured for the app or kind of activity shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Accent color configured for the app or kind of activity shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Detected apps with how many of their processes run, highest priority first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub apps: Vec<process_finder::AppInstances>,
+    /// The built-in text before it was filled in, so the API can serve it in other languages
+    #[serde(skip)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub message: Option<i18n::Message>,
+    /// The text for visitors without a friend token, never sent out as part of the presence, agents push it to their hub next to it
+    #[serde(skip)]
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub coarse_text: Option<String>,
+    /// Set when the update loop stopped making progress and this may be out of date
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+}
+
+impl OutputData {
+    /// Presence without device information
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into(), device: None, album_art: None, last_watched: None, link: None, emoji: None, icon: None, color: None, apps: Vec::new(), message: None, coarse_text: None, stale: false }
+    }
+}
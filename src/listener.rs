@@ -0,0 +1,139 @@
+use axum::Router;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+
+use crate::config::env_list;
+use crate::systemd;
+
+#[derive(Debug, Clone)]
+pub enum BindAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl BindAddr {
+    /// Parse `127.0.0.1:3001`, `[::1]:3001` or `unix:/run/represence.sock`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(Self::Unix(path.into())),
+            Some(_) => Err(format!("missing path in bind address '{}'", value)),
+            None => value
+                .parse()
+                .map(Self::Tcp)
+                .map_err(|_| format!("invalid bind address '{}'", value)),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    pub fn bind(addr: &BindAddr) -> io::Result<Self> {
+        let listener = match addr {
+            BindAddr::Tcp(addr) => Self::Tcp(TcpListener::bind(addr)?),
+            BindAddr::Unix(path) => {
+                // A socket file left behind by a previous run would make bind fail
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                Self::Unix(UnixListener::bind(path)?)
+            }
+        };
+
+        match &listener {
+            Self::Tcp(listener) => listener.set_nonblocking(true)?,
+            Self::Unix(listener) => listener.set_nonblocking(true)?,
+        }
+
+        Ok(listener)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Tcp(listener) => listener
+                .local_addr()
+                .map(|addr| format!("http://{}", addr))
+                .unwrap_or_else(|_| "tcp".to_string()),
+            Self::Unix(listener) => listener
+                .local_addr()
+                .ok()
+                .and_then(|addr| addr.as_pathname().map(|path| format!("unix:{}", path.display())))
+                .unwrap_or_else(|| "unix".to_string()),
+        }
+    }
+}
+
+/// Bind addresses from `REPRESENCE_BIND`, defaulting to all IPv4 interfaces on `port`
+pub fn bind_addrs_from_env(port: u16) -> Result<Vec<BindAddr>, String> {
+    let addrs = env_list("REPRESENCE_BIND");
+    if addrs.is_empty() {
+        return Ok(vec![BindAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], port)))]);
+    }
+
+    addrs.iter().map(|addr| BindAddr::parse(addr)).collect()
+}
+
+/// Sockets passed by systemd take precedence, otherwise bind the configured addresses
+pub fn bind_all(port: u16) -> Result<Vec<Listener>, Box<dyn std::error::Error>> {
+    let activated = systemd::take_listeners().map_err(|e| format!("socket activation: {}", e))?;
+    if !activated.is_empty() {
+        println!("Using {} listener(s) from systemd socket activation", activated.len());
+        return Ok(activated);
+    }
+
+    let mut listeners = Vec::new();
+    for addr in bind_addrs_from_env(port)? {
+        let listener = Listener::bind(&addr)
+            .map_err(|e| format!("failed to bind {:?}: {}", addr, e))?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+/// Split listeners into TCP and Unix sockets (TLS only applies to TCP)
+#[cfg(feature = "tls")]
+pub fn partition(listeners: Vec<Listener>) -> (Vec<TcpListener>, Vec<Listener>) {
+    let mut tcp = Vec::new();
+    let mut other = Vec::new();
+    for listener in listeners {
+        match listener {
+            Listener::Tcp(listener) => tcp.push(listener),
+            unix => other.push(unix),
+        }
+    }
+    (tcp, other)
+}
+
+/// Serve plain HTTP on every listener until one of them fails
+pub async fn serve(listeners: Vec<Listener>, app: Router) -> io::Result<()> {
+    let mut servers = JoinSet::new();
+
+    for listener in listeners {
+        let app = app.clone();
+        match listener {
+            Listener::Tcp(listener) => {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                servers.spawn(async move {
+                    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+                });
+            }
+            Listener::Unix(listener) => {
+                // Unix sockets have no peer IP, so per-IP features treat these requests as anonymous
+                let listener = tokio::net::UnixListener::from_std(listener)?;
+                servers.spawn(async move { axum::serve(listener, app).await });
+            }
+        }
+    }
+
+    while let Some(result) = servers.join_next().await {
+        result.map_err(io::Error::other)??;
+    }
+
+    Ok(())
+}
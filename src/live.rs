@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// A live stream on this account, overrides every other activity while it lasts
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveStream {
+    /// e.g. `Twitch`
+    pub platform: String,
+    pub title: String,
+    /// Channel page, published as `link`
+    pub url: String,
+}
+
+impl LiveStream {
+    /// e.g. `live on Twitch: building represence`
+    pub fn text(&self) -> String {
+        format!("live on {}: {}", self.platform, self.title)
+    }
+}
+
+pub type SharedLive = Arc<RwLock<Option<LiveStream>>>;
+
+#[cfg(feature = "twitch")]
+pub mod twitch {
+    use serde::Deserialize;
+    use std::time::{Duration, Instant};
+
+    use super::{LiveStream, SharedLive};
+    use crate::config::{env_parse, env_var};
+    use crate::diagnostics::SharedDiagnostics;
+    use crate::now_playing;
+
+    const REQUEST_TIMEOUT_SECS: u64 = 10;
+    const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+    const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams";
+
+    /// Streaming software whose process makes the channel worth checking
+    const STREAMING_APP: &str = "obs";
+
+    #[derive(Debug, Clone)]
+    pub struct TwitchConfig {
+        pub client_id: String,
+        pub client_secret: String,
+        /// Channel login, e.g. `bilgi42`
+        pub channel: String,
+        pub poll_interval: Duration,
+    }
+
+    impl TwitchConfig {
+        /// `None` unless `REPRESENCE_TWITCH_CLIENT_ID`, `REPRESENCE_TWITCH_CLIENT_SECRET` and `REPRESENCE_TWITCH_CHANNEL` are set
+        pub fn from_env() -> Option<Self> {
+            Some(Self {
+                client_id: env_var("REPRESENCE_TWITCH_CLIENT_ID")?,
+                client_secret: env_var("REPRESENCE_TWITCH_CLIENT_SECRET")?,
+                channel: env_var("REPRESENCE_TWITCH_CHANNEL")?.to_lowercase(),
+                poll_interval: Duration::from_secs(env_parse("REPRESENCE_TWITCH_POLL_SECS", 60).max(10)),
+            })
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+        expires_in: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Streams {
+        data: Vec<Stream>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Stream {
+        #[serde(rename = "type")]
+        kind: String,
+        title: String,
+    }
+
+    struct Client {
+        http: reqwest::Client,
+        config: TwitchConfig,
+        app_token: Option<(String, Instant)>,
+    }
+
+    impl Client {
+        /// App access token from the client credentials flow, renewed before it expires
+        async fn app_token(&mut self) -> Result<String, reqwest::Error> {
+            if let Some((token, expires)) = &self.app_token {
+                if Instant::now() < *expires {
+                    return Ok(token.clone());
+                }
+            }
+
+            let response: TokenResponse = self.http
+                .post(TOKEN_URL)
+                .form(&[
+                    ("client_id", self.config.client_id.as_str()),
+                    ("client_secret", self.config.client_secret.as_str()),
+                    ("grant_type", "client_credentials"),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let expires = Instant::now() + Duration::from_secs(response.expires_in.saturating_sub(300));
+            self.app_token = Some((response.access_token.clone(), expires));
+            Ok(response.access_token)
+        }
+
+        async fn live_stream(&mut self) -> Result<Option<LiveStream>, reqwest::Error> {
+            let token = self.app_token().await?;
+            let streams: Streams = self.http
+                .get(STREAMS_URL)
+                .query(&[("user_login", self.config.channel.as_str())])
+                .header("Client-Id", &self.config.client_id)
+                .bearer_auth(token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            Ok(streams.data.into_iter().find(|stream| stream.kind == "live").map(|stream| LiveStream {
+                platform: "Twitch".to_string(),
+                title: stream.title,
+                url: format!("https://twitch.tv/{}", self.config.channel),
+            }))
+        }
+    }
+
+    /// Check whether the channel is live while OBS is running
+    pub fn spawn(config: TwitchConfig, diagnostics: SharedDiagnostics, live: SharedLive) {
+        let http = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(http) => http,
+            Err(e) => {
+                eprintln!("Twitch disabled, failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+
+        let poll_interval = config.poll_interval;
+        let mut client = Client { http, config, app_token: None };
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match now_playing::is_running(&diagnostics, STREAMING_APP).await {
+                    true => match client.live_stream().await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            // Keep the last answer, a flaky API shouldn't end the stream early
+                            eprintln!("Twitch API request failed: {}", e);
+                            live.read().await.clone()
+                        }
+                    },
+                    false => None,
+                };
+                *live.write().await = stream;
+                tokio::time::sleep(poll_interval).await;
+            }
+        });
+    }
+}
@@ -1,340 +1,510 @@
-use tokio::fs;
-use tokio::task::JoinSet;
-use std::time::SystemTime;
-use serde::{Deserialize, Serialize};
+use clap::Parser;
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use std::time::Duration;
-use std::env;
-use std::collections::HashMap;
-
-mod vscode_client;
-mod web_server;
-
-// Adaptive timing constants for better responsiveness
-const MAX_CONCURRENT_TASKS: usize = 50;
-const FAST_UPDATE_INTERVAL_SECS: u64 = 1; // When changes detected
-const SLOW_UPDATE_INTERVAL_SECS: u64 = 3; // When idle
-const PROCESS_CACHE_TTL_SECS: u64 = 1; // Reduced cache TTL
-const VSCODE_CHECK_INTERVAL_SECS: u64 = 2; // Much faster VSCode checks
-const IDLE_THRESHOLD_COUNT: u32 = 3; // Switch to slow mode after 3 unchanged cycles
-
-#[derive(Debug, Clone)]
-pub struct TieredApp {
-    name: String,
-    tier: u32,
-}
+use tokio::sync::{watch, RwLock};
 
-#[derive(Debug, Clone)]
-pub struct RunningApp {
-    name: String,
-    tier: u32,
-}
+use represence::aliases::Aliases;
+use represence::blocklist::Blocklist;
+use represence::calendar::Calendar;
+use represence::compose::Composer;
+use represence::consumers::{Consumers, EnergySaving};
+use represence::daemon::LogFile;
+use represence::diagnostics::SharedDiagnostics;
+use represence::film_log::SharedLastWatched;
+use represence::github::SharedIdleActivity;
+use represence::history::SharedHistory;
+use represence::icons::Icons;
+use represence::idle_text::IdleRules;
+use represence::live::SharedLive;
+use represence::modules::{self, ModuleKind, Modules};
+use represence::now_playing::NowPlaying;
+use represence::phrases::Phrases;
+use represence::pidfile::PidFile;
+use represence::presence::Pipeline;
+use represence::process_finder::ProcSource;
+use represence::profiles::Profiles;
+use represence::remote::{SharedRemote, SshHosts};
+use represence::stale::{self, StaleGuard};
+use represence::terminal::TerminalInspector;
+use represence::uptime::MinUptime;
+use represence::variants::DayVariants;
+use represence::vscode_client::{FileInfo, VsCodeSource};
+use represence::web_server::{Broadcaster, SharedData};
+use represence::{cli, config, config_file, control, daemon, device, diagnostics, doctor, export, features, history, init, listener, players, priority, simulate, streaks, summary, systemd, waybar, web_server, OutputData};
+#[cfg(feature = "alerts")]
+use represence::alert;
+#[cfg(feature = "history")]
+use represence::backup;
+#[cfg(feature = "calendar")]
+use represence::calendar::client as calendar;
+#[cfg(feature = "dbus")]
+use represence::{dbus, notify};
+#[cfg(feature = "sentry")]
+use represence::error_reporting;
+#[cfg(feature = "film-log")]
+use represence::film_log::client as film_log;
+#[cfg(feature = "github")]
+use represence::github::client as github;
+#[cfg(feature = "grpc")]
+use represence::grpc;
+#[cfg(feature = "http-client")]
+use represence::hub;
+#[cfg(feature = "influxdb")]
+use represence::influx;
+#[cfg(feature = "lastfm")]
+use represence::lastfm;
+#[cfg(feature = "twitch")]
+use represence::live::twitch;
+#[cfg(feature = "mastodon")]
+use represence::mastodon;
+#[cfg(feature = "matrix")]
+use represence::matrix;
+#[cfg(feature = "media-server")]
+use represence::media_server;
+#[cfg(feature = "mqtt")]
+use represence::mqtt;
+#[cfg(feature = "nats")]
+use represence::nats;
+#[cfg(feature = "profile-readme")]
+use represence::profile_readme::client as profile_readme;
+#[cfg(feature = "redis")]
+use represence::redis;
+#[cfg(feature = "spotify")]
+use represence::spotify;
+#[cfg(feature = "telegram")]
+use represence::telegram;
+#[cfg(feature = "otel")]
+use represence::telemetry;
+#[cfg(feature = "tls")]
+use represence::tls;
+#[cfg(feature = "vlc")]
+use represence::vlc;
+#[cfg(feature = "wakatime")]
+use represence::wakatime;
+#[cfg(feature = "webhooks")]
+use represence::webhook;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = cli::Cli::parse();
+
+    // Load environment variables from .env file
+    dotenvy::dotenv().ok();
+
+    // A profile's variables go over the environment before anything reads it
+    let profiles = Profiles::from_env();
+    if let Some(name) = cli.profile.clone().or_else(|| config::env_var("REPRESENCE_PROFILE")) {
+        profiles.activate(Some(&name))?;
+    }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct OutputData {
-    pub text: String,
+    match cli.command {
+        Some(cli::Command::Export(args)) => export::run(args),
+        #[cfg(feature = "history")]
+        Some(cli::Command::Backup(args)) => backup::run_backup(args),
+        #[cfg(feature = "history")]
+        Some(cli::Command::Restore(args)) => backup::run_restore(args),
+        #[cfg(not(feature = "history"))]
+        Some(cli::Command::Backup(_) | cli::Command::Restore(_)) => Err(features::not_built("history").into()),
+        Some(cli::Command::Simulate(args)) => {
+            let mut scenario = simulate::Scenario::load(&args.scenario)?;
+            scenario.speed = args.speed.unwrap_or(scenario.speed);
+            scenario.repeat |= args.repeat;
+            runtime()?.block_on(serve(Some(scenario), profiles))
+        }
+        Some(cli::Command::Serve(args)) => serve_single(args, profiles),
+        Some(cli::Command::Waybar(args)) => runtime()?.block_on(waybar::run(args)),
+        Some(cli::Command::Doctor) => runtime()?.block_on(doctor::run()),
+        Some(cli::Command::CheckConfig(args)) => config_file::run(args.file, args.path.as_deref()),
+        Some(cli::Command::Init(args)) => runtime()?.block_on(init::run(args)),
+        None => serve_single(cli::ServeArgs::default(), profiles),
+    }
 }
 
-// Enhanced cache structure with change detection
-#[derive(Debug)]
-struct ProcessCache {
-    processes: HashMap<String, RunningApp>,
-    last_updated: SystemTime,
-    last_process_count: usize,
-    process_list_hash: u64,
+/// Started by hand rather than with `#[tokio::main]`, forking a running runtime would leave its threads behind
+fn runtime() -> std::io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build()
 }
 
-impl ProcessCache {
-    fn new() -> Self {
-        Self {
-            processes: HashMap::new(),
-            last_updated: SystemTime::UNIX_EPOCH,
-            last_process_count: 0,
-            process_list_hash: 0,
+/// Serve while holding the PID file, so a second instance refuses to start, in the background with `--daemon`
+fn serve_single(args: cli::ServeArgs, profiles: Profiles) -> Result<(), Box<dyn std::error::Error>> {
+    // A daemon always leaves its PID behind, for scripts to signal or stop it
+    let path = PidFile::path_from_env().or_else(|| args.daemon.then(PidFile::default_path));
+    let mut pid_file = match path {
+        Some(path) => Some(PidFile::acquire(&path, args.replace).map_err(|e| e.to_string())?),
+        None => None,
+    };
+
+    if args.daemon {
+        let log = LogFile::from_env();
+        daemon::daemonize(&log)?;
+        if let Some(pid_file) = &mut pid_file {
+            pid_file.write_pid()?;
         }
+        daemon::spawn_rotation(log);
     }
 
-    fn is_expired(&self) -> bool {
-        self.last_updated.elapsed().unwrap_or(Duration::MAX) > Duration::from_secs(PROCESS_CACHE_TTL_SECS)
+    // After forking, reporting runs on a thread of its own
+    #[cfg(feature = "sentry")]
+    let _reporting = error_reporting::ErrorReporting::from_env().and_then(|reporting| reporting.init());
+    #[cfg(feature = "otel")]
+    let _telemetry = telemetry::TelemetryConfig::from_env().and_then(|config| config.init());
+
+    runtime()?.block_on(serve(None, profiles))
+}
+
+/// Run the presence server until a listener fails, playing `scenario` instead of scanning when given
+async fn serve(scenario: Option<simulate::Scenario>, profiles: Profiles) -> Result<(), Box<dyn std::error::Error>> {
+    // Configured integrations this build can't run
+    features::warn_missing();
+    if let Some(profile) = profiles.status().active {
+        println!("Using profile {}", profile);
     }
 
-    // Calculate a simple hash of running process names for change detection
-    fn calculate_process_hash(processes: &[RunningApp]) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        for app in processes {
-            app.name.hash(&mut hasher);
-            app.tier.hash(&mut hasher);
+    // Get port from environment variable or default to 3001
+    let port = config::env_parse("REPRESENCE_PORT", 3001u16);
+    
+    // Describe this machine in the payload if enabled
+    let device = device::DeviceInfo::from_env();
+
+    // Initialize shared data
+    let shared_data = web_server::shared_data(OutputData {
+        device: device.clone(),
+        ..OutputData::new("starting...")
+    });
+
+    // Clone shared data for the background task
+    let data_for_task = shared_data.clone();
+    let diagnostics = diagnostics::Diagnostics::shared();
+
+    // Open the presence history database, simulations never open (or create) it. Postgres connects blocking.
+    let history = match scenario.is_none().then(|| tokio::task::block_in_place(history::open_from_env)).flatten() {
+        Some(Ok(history)) => {
+            println!("Recording presence history to {}", history.describe());
+            Some(history)
         }
-        hasher.finish()
-    }
+        Some(Err(e)) => {
+            eprintln!("History disabled, failed to open database: {}", e);
+            None
+        }
+        None => None,
+    };
+    let history_for_task = history.clone();
 
-    fn has_processes_changed(&self, new_processes: &[RunningApp]) -> bool {
-        let new_hash = Self::calculate_process_hash(new_processes);
-        let new_count = new_processes.len();
-        
-        new_hash != self.process_list_hash || new_count != self.last_process_count
+    // Roll completed days into daily summaries
+    if let Some(history) = history.clone() {
+        summary::spawn(history);
     }
 
-    fn update_with_change_detection(&mut self, new_processes: Vec<RunningApp>) -> bool {
-        let has_changed = self.has_processes_changed(&new_processes);
-        
-        self.processes.clear();
-        for app in &new_processes {
-            self.processes.insert(app.name.clone(), app.clone());
+    // "day 42 of coding streak" while coding
+    let streak_config = streaks::StreakConfig::from_env();
+    let streak = match (&history, streak_config.in_text) {
+        (Some(history), true) => {
+            println!("Mentioning coding streaks of days with at least {} minute(s) of coding", streak_config.min_minutes);
+            let streak: streaks::SharedStreak = Arc::new(RwLock::new(0));
+            streaks::spawn(history.clone(), streak_config, streak.clone());
+            Some(streak)
         }
-        
-        self.last_updated = SystemTime::now();
-        self.process_list_hash = Self::calculate_process_hash(&new_processes);
-        self.last_process_count = new_processes.len();
-        
-        has_changed
+        _ => None,
+    };
+
+    // Presence pushed by the represence instance on a server we're connected to over SSH
+    let remote: SharedRemote = Arc::new(RwLock::new(None));
+
+    // Create and start web server
+    // Requests and open streams, to slow scanning down while nobody looks
+    let consumers = Consumers::from_env();
+    // Detectors and publishers that can be turned off without a restart
+    let modules = Modules::from_env();
+    // Reloads, privacy mode and profile switches for the update loop, from signals, Telegram and the API
+    let (control_tx, controls) = control::channel();
+    let profiles = profiles.with_controls(control_tx.clone());
+
+    let (app, broadcaster) = web_server::create_server(shared_data.clone(), diagnostics.clone(), history, remote.clone(), consumers.clone(), modules.clone(), profiles.clone()).await;
+
+    // The same presence over gRPC, on a port of its own
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_config) = grpc::GrpcConfig::from_env() {
+        println!("gRPC API listening on {}", grpc_config.addr);
+        let service = grpc::PresenceService::new(shared_data.clone(), broadcaster.clone(), consumers.clone(), represence::trust::TrustConfig::from_env());
+        grpc::spawn(grpc_config, service);
     }
-}
 
-/// Optimized function to get running applications with resource limits and caching
-pub async fn get_running_apps_optimized(
-    apps_to_check: &[TieredApp],
-    cache: &mut ProcessCache
-) -> (Vec<RunningApp>, bool) {
-    // Return cached results if still valid
-    if !cache.is_expired() {
-        let cached_results: Vec<RunningApp> = cache.processes.values()
-            .filter(|app| apps_to_check.iter().any(|check| app.name.starts_with(&check.name)))
-            .cloned()
-            .collect();
-        return (cached_results, false); // No change, using cache
+    // The file open in VS Code, for integrations that track coding activity
+    let (editor_tx, editor_rx) = watch::channel(None);
+
+    // Keep simulated presence away from everything outside this server
+    if scenario.is_none() {
+        spawn_integrations(shared_data.clone(), diagnostics.clone(), history_for_task.clone(), &broadcaster, &modules, editor_rx);
     }
 
-    let mut running_apps = Vec::new();
-    let mut tasks = JoinSet::new();
-    let semaphore = Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_TASKS));
-    
-    // Read /proc directory
-    let mut proc_dir = match fs::read_dir("/proc").await {
-        Ok(dir) => dir,
-        Err(_) => return (Vec::new(), false),
-    };
-    
-    let apps_to_check = apps_to_check.to_vec(); // Convert slice to owned vec for move
-    
-    // Process entries with concurrency limit
-    while let Ok(Some(entry)) = proc_dir.next_entry().await {
-        let path = entry.path();
-        
-        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            if name.chars().all(|c| c.is_ascii_digit()) {
-                let apps_to_check_clone = apps_to_check.clone();
-                let semaphore_clone = semaphore.clone();
-                
-                tasks.spawn(async move {
-                    let _permit = semaphore_clone.acquire().await.ok()?;
-                    
-                    // Fast path: only read what we need
-                    let exe_path = path.join("exe");
-                    
-                    if let Ok(exe_target) = fs::read_link(&exe_path).await {
-                        if let Some(app_name) = exe_target.file_name().and_then(|n| n.to_str()) {
-                            // Check if this app matches any from our list
-                            for check_app in &apps_to_check_clone {
-                                if app_name.starts_with(&check_app.name) {
-                                    return Some(RunningApp {
-                                        name: app_name.to_string(),
-                                        tier: check_app.tier,
-                                    });
-                                }
-                            }
-                        }
-                    }
-                    None
-                });
+    // Start background task to update presence data
+    match scenario {
+        Some(scenario) => {
+            println!("Simulating {} scenario step(s) at {}x speed", scenario.steps.len(), scenario.speed);
+            tokio::spawn(simulate::run(scenario, data_for_task, broadcaster, diagnostics.clone(), editor_tx, device));
+        }
+        None => {
+            let source = ProcSource::from_env();
+            if source.proc_path() != Path::new("/proc") {
+                println!("Scanning processes in {}", source.proc_path().display());
+            }
+            let blocklist = Blocklist::from_env();
+            if !blocklist.is_empty() {
+                println!("Hiding {} blocklisted process pattern(s)", blocklist.len());
+            }
+            let terminal = TerminalInspector::from_env(source.proc_path());
+            let priority = priority::from_env(source.proc_path());
+            #[cfg(feature = "scripting")]
+            let text_script = represence::script::rhai::RhaiScript::from_env()
+                .map(|script| Box::new(script) as Box<dyn represence::script::TextScript>);
+            #[cfg(not(feature = "scripting"))]
+            let text_script = None;
+
+            modules.register(modules::VSCODE, ModuleKind::Detector);
+            // Tracks from the music player APIs, polled while the player runs
+            let now_playing = NowPlaying::default();
+            modules.register(modules::NOW_PLAYING, ModuleKind::Detector);
+            if let Some(mpd_config) = players::MpdConfig::from_env() {
+                players::spawn_mpd(mpd_config, now_playing.clone());
+            }
+            players::spawn_cmus(diagnostics.clone(), now_playing.clone());
+            // Last logged film, refreshed slowly for widgets
+            let last_watched: SharedLastWatched = Arc::new(RwLock::new(None));
+            #[cfg(feature = "film-log")]
+            if let Some(film_log_config) = film_log::FilmLogConfig::from_env() {
+                println!("Publishing the last film logged on {}", film_log_config.describe());
+                modules.register(modules::FILM_LOG, ModuleKind::Detector);
+                film_log::spawn(film_log_config, last_watched.clone());
+            }
+            // Recent GitHub activity fills idle breaks
+            let idle_activity: SharedIdleActivity = Arc::new(RwLock::new(None));
+            #[cfg(feature = "github")]
+            if let Some(github_config) = github::GitHubConfig::from_env() {
+                println!("Showing recent GitHub activity of {} while idle", github_config.user);
+                modules.register(modules::GITHUB, ModuleKind::Detector);
+                github::spawn(github_config, idle_activity.clone());
+            }
+
+            // Busy calendar events take precedence over app detection
+            let calendar = Calendar::from_env();
+            #[cfg(feature = "calendar")]
+            if let Some(calendar_config) = calendar::CalendarConfig::from_env() {
+                println!("Reading {} calendar(s) for busy events", calendar_config.sources.len());
+                modules.register(modules::CALENDAR, ModuleKind::Detector);
+                calendar::spawn(calendar_config, calendar.clone());
+            }
+
+            // Publish `asleep` before the machine suspends
+            #[cfg(feature = "logind")]
+            let asleep = represence::sleep::logind::spawn();
+            #[cfg(not(feature = "logind"))]
+            let asleep = None;
+
+            // Live streams override every app
+            let live: SharedLive = Arc::new(RwLock::new(None));
+            #[cfg(feature = "twitch")]
+            if let Some(twitch_config) = twitch::TwitchConfig::from_env() {
+                println!("Checking whether twitch.tv/{} is live while OBS runs", twitch_config.channel);
+                modules.register(modules::TWITCH, ModuleKind::Detector);
+                twitch::spawn(twitch_config, diagnostics.clone(), live.clone());
+            }
+            #[cfg(feature = "media-server")]
+            if let Some(media_server_config) = media_server::MediaServerConfig::from_env() {
+                println!("Watching {} media server(s) for streams", media_server_config.servers.len());
+                media_server::spawn(media_server_config, now_playing.clone());
+            }
+            #[cfg(feature = "vlc")]
+            if let Some(vlc_config) = vlc::VlcConfig::from_env() {
+                println!("Reading titles from the VLC web interface at {}", vlc_config.url);
+                vlc::spawn(vlc_config, diagnostics.clone(), now_playing.clone());
+            }
+            #[cfg(feature = "spotify")]
+            if let Some(spotify_config) = spotify::SpotifyConfig::from_env() {
+                println!("Showing the current Spotify track");
+                spotify::spawn(spotify_config, diagnostics.clone(), now_playing.clone());
+            }
+            #[cfg(feature = "lastfm")]
+            if let Some(lastfm_config) = lastfm::LastFmConfig::from_env() {
+                println!("Showing what {} scrobbles on Last.fm while no player runs here", lastfm_config.user);
+                lastfm::spawn(lastfm_config, now_playing.clone());
             }
+
+            // Don't keep serving the last presence forever if the update loop gets stuck
+            if let Some(guard) = StaleGuard::from_env() {
+                stale::spawn(guard, shared_data.clone(), broadcaster.clone(), diagnostics.clone());
+            }
+
+            // SIGHUP, SIGUSR1 and SIGUSR2 for scripts without the HTTP API
+            if let Err(e) = control::spawn_signals(&control_tx) {
+                eprintln!("Ignoring signals, failed to listen for them: {}", e);
+            }
+            // Remote control from my phone
+            #[cfg(feature = "telegram")]
+            if let Some(telegram_config) = telegram::TelegramConfig::from_env() {
+                println!("Answering Telegram commands from account {}", telegram_config.user_id);
+                telegram::spawn(telegram_config, shared_data.clone(), control_tx.clone());
+            }
+
+            let pipeline = Pipeline::new(source, Some(VsCodeSource::default()), data_for_task, broadcaster, diagnostics.clone(), history_for_task, editor_tx)
+                .with_blocklist(blocklist)
+                .with_min_uptime(MinUptime::from_env())
+                .with_device(device)
+                .with_phrases(Phrases::from_env())
+                .with_composer(Composer::from_env())
+                .with_priority(priority)
+                .with_text_script(text_script)
+                .with_icons(Icons::from_env())
+                .with_terminal_inspector(terminal)
+                .with_remote(SshHosts::from_env(), Some(remote))
+                .with_now_playing(Some(now_playing))
+                .with_last_watched(Some(last_watched))
+                .with_live(Some(live))
+                .with_idle_activity(Some(idle_activity))
+                .with_calendar(Some(calendar))
+                .with_streak(streak)
+                .with_modules(modules.clone())
+                .with_idle_rules(IdleRules::from_env())
+                .with_day_variants(DayVariants::from_env())
+                .with_aliases(Aliases::from_env())
+                .with_sleep(asleep)
+                .with_energy_saving(EnergySaving::from_env(&consumers))
+                .with_consumers(Some(consumers.clone()))
+                .with_controls(Some(controls))
+                .with_profiles(profiles)
+                .with_privacy(control::privacy_from_env())
+                .with_settle_window(Duration::from_millis(config::env_parse("REPRESENCE_SETTLE_MS", 300)));
+            tokio::spawn(pipeline.run());
         }
     }
     
-    // Collect results with better error handling
-    while let Some(result) = tasks.join_next().await {
-        match result {
-            Ok(Some(running_app)) => running_apps.push(running_app),
-            Ok(None) => continue,
-            Err(_) => continue, // Ignore task panics
-        }
+    // Prefer sockets passed in by systemd socket activation over binding our own
+    let listeners = listener::bind_all(port)?;
+
+    for listener in &listeners {
+        println!("Represence server listening on {}", listener.describe());
     }
-    
-    // Sort by tier only (first come first serve within tier)
-    running_apps.sort_by(|a, b| a.tier.cmp(&b.tier));
-    
-    // Update cache and detect changes
-    let has_changed = cache.update_with_change_detection(running_apps.clone());
-    
-    (running_apps, has_changed)
-}
+    diagnostics.write().await.listeners_bound = true;
+    println!("API endpoint: /api/represence, health check: /health");
+    println!("Optimized for fast response times (1-3s adaptive polling)");
 
-/// Check if VS Code is running (optimized)
-fn is_vscode_running(apps: &[RunningApp]) -> bool {
-    apps.iter().any(|app| app.name.starts_with("code"))
-}
+    systemd::notify_ready();
 
-/// Generate text for an application based on its type and context (optimized with string interpolation)
-fn generate_app_text(app: &RunningApp, vscode_file_info: Option<&vscode_client::FileInfo>) -> String {
-    match app.name.as_str() {
-        name if name.starts_with("code") => {
-            match vscode_file_info {
-                Some(file_info) => format!("editing {} in Visual Studio Code", file_info.file_name),
-                None => "VS Code".to_string(),
-            }
-        }
-        name if name.starts_with("zen") => "browsing with Zen browser".to_string(),
-        name if name.starts_with("chrome") => "probably on her work account on Chrome".to_string(),
-        name if name.starts_with("discord") => "yapping on Discord".to_string(),
-        name if name.starts_with("steam") => "gaming on Steam".to_string(),
-        name if name.starts_with("vlc") => "watching a movie (will probably log it in letterboxd/bilgi42".to_string(),
-        name if name.starts_with("stremio") => "legally streaming some content in stremio".to_string(),
-        name if name.starts_with("ghostty") => "using the best terminal emulator (ghostty)".to_string(),
-        _ => app.name.clone()
+    // Terminate TLS ourselves on TCP listeners when certificates or ACME domains are configured
+    #[cfg(feature = "tls")]
+    if let Some(tls_config) = tls::TlsConfig::from_env() {
+        let (tcp, unix) = listener::partition(listeners);
+        tokio::try_join!(
+            tls::serve(tls_config, tcp, app.clone()),
+            listener::serve(unix, app),
+        )?;
+        return Ok(());
     }
+
+    listener::serve(listeners, app).await?;
+
+    Ok(())
 }
 
-/// Optimized presence data updater with adaptive timing and smart change detection
-async fn update_presence_data(shared_data: web_server::SharedData, broadcaster: web_server::Broadcaster) {
-    let apps_to_check = vec![
-        // Tier 1 - The ones you wanna flex the most
-        TieredApp { name: "code".to_string(), tier: 1 },
-        TieredApp { name: "discord".to_string(), tier: 1 },
-        
-        // Tier 2 - The apps that you'll use in your off-days (and sometimes on your work days)
-        TieredApp { name: "zen".to_string(), tier: 2 },
-        TieredApp { name: "chrome".to_string(), tier: 2 },
-        TieredApp { name: "steam".to_string(), tier: 2 },
-        
-        // Tier 3 - Less common applications
-        TieredApp { name: "vlc".to_string(), tier: 3 },
-        TieredApp { name: "stremio".to_string(), tier: 3 },
-        
-        // Tier 4 - Terminal emulators
-        TieredApp { name: "ghostty".to_string(), tier: 4 },
-    ];
-
-    let mut process_cache = ProcessCache::new();
-    let mut last_vscode_check = SystemTime::UNIX_EPOCH;
-    let mut cached_vscode_info: Option<vscode_client::FileInfo> = None;
-    let mut idle_count = 0u32;
-    let mut last_output_text = String::new();
-
-    loop {
-        let (running_apps, processes_changed) = get_running_apps_optimized(&apps_to_check, &mut process_cache).await;
-        
-        // Adaptive VSCode checks - faster when VSCode is running
-        let mut vscode_file_info: Option<vscode_client::FileInfo> = None;
-        
-        if is_vscode_running(&running_apps) {
-            let should_check_vscode = last_vscode_check.elapsed()
-                .unwrap_or(Duration::MAX) > Duration::from_secs(VSCODE_CHECK_INTERVAL_SECS);
-            
-            if should_check_vscode {
-                // Use timeout for VSCode connection to prevent hanging
-                match tokio::time::timeout(
-                    Duration::from_secs(1), // Reduced timeout for faster response
-                    vscode_client::connect_to_vscode_once(3847)
-                ).await {
-                    Ok(Ok(file_info)) => {
-                        cached_vscode_info = Some(file_info.clone());
-                        vscode_file_info = Some(file_info);
-                        last_vscode_check = SystemTime::now();
-                    }
-                    Ok(Err(_)) | Err(_) => {
-                        // Use cached info if available, otherwise fallback
-                        vscode_file_info = cached_vscode_info.clone();
-                    }
-                }
-            } else {
-                // Use cached VSCode info
-                vscode_file_info = cached_vscode_info.clone();
-            }
-        } else {
-            // Clear cached VSCode info if VSCode is not running
-            cached_vscode_info = None;
-        }
+/// Start the integrations that push presence to other services
+// Every integration is optional, so any argument can go unused
+#[allow(unused_variables)]
+fn spawn_integrations(
+    shared_data: SharedData,
+    diagnostics: SharedDiagnostics,
+    history: Option<SharedHistory>,
+    broadcaster: &Broadcaster,
+    modules: &Modules,
+    editor_rx: watch::Receiver<Option<FileInfo>>,
+) {
+    // Start outbound webhook delivery if any URLs are configured
+    #[cfg(feature = "webhooks")]
+    if let Some(webhook_config) = webhook::WebhookConfig::from_env() {
+        println!("Publishing presence changes to {} webhook(s)", webhook_config.urls.len());
+        webhook::spawn(webhook_config, &modules.publisher("webhooks", &shared_data, broadcaster));
+    }
 
-        // Generate output text for the most relevant application
-        let output_text = match running_apps.first() {
-            Some(app) => generate_app_text(app, vscode_file_info.as_ref()),
-            None => "idle".to_string(),
-        };
-
-        // Check if output actually changed
-        let output_changed = output_text != last_output_text;
-        
-        if output_changed {
-            let output = OutputData { text: output_text.clone() };
-            last_output_text = output_text;
-            idle_count = 0; // Reset idle counter on change
-
-            // Update shared data efficiently
-            {
-                let mut data = shared_data.write().await;
-                *data = output.clone();
-                
-                // Broadcast the change
-                let _ = broadcaster.send(output);
-            }
-        } else if processes_changed {
-            // Processes changed but output is the same, reset idle counter
-            idle_count = 0;
-        } else {
-            // No changes detected
-            idle_count += 1;
-        }
+    // Start MQTT publishing (with Home Assistant discovery) if a broker is configured
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt_config) = mqtt::MqttConfig::from_env() {
+        println!("Publishing presence to MQTT broker {}:{}", mqtt_config.host, mqtt_config.port);
+        mqtt::spawn(mqtt_config, shared_data.clone(), &modules.publisher("mqtt", &shared_data, broadcaster));
+    }
 
-        // Adaptive sleep timing based on activity
-        let sleep_duration = if idle_count >= IDLE_THRESHOLD_COUNT {
-            Duration::from_secs(SLOW_UPDATE_INTERVAL_SECS) // Slow polling when idle
-        } else {
-            Duration::from_secs(FAST_UPDATE_INTERVAL_SECS) // Fast polling when active
-        };
+    // Keep the presence in Redis for services that don't hold a connection to us
+    #[cfg(feature = "redis")]
+    if let Some(redis_config) = redis::RedisConfig::from_env() {
+        println!("Publishing presence to Redis channel {} and key {}", redis_config.channel, redis_config.key);
+        redis::spawn(redis_config, shared_data.clone(), &modules.publisher("redis", &shared_data, broadcaster));
+    }
 
-        tokio::time::sleep(sleep_duration).await;
+    // Mirror the public text into the Matrix status message
+    #[cfg(feature = "matrix")]
+    if let Some(matrix_config) = matrix::MatrixConfig::from_env() {
+        println!("Syncing presence to the Matrix status of {}", matrix_config.user_id);
+        matrix::spawn(matrix_config, shared_data.clone(), &modules.publisher("matrix", &shared_data, broadcaster));
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load environment variables from .env file
-    dotenvy::dotenv().ok();
-    
-    // Get port from environment variable or default to 3001
-    let port = env::var("REPRESENCE_PORT")
-        .unwrap_or_else(|_| "3001".to_string())
-        .parse::<u16>()
-        .unwrap_or(3001);
-    
-    // Initialize shared data
-    let shared_data = Arc::new(RwLock::new(OutputData {
-        text: "starting...".to_string(),
-    }));
+    // Keep a Mastodon profile field on the same status, slowly
+    #[cfg(feature = "mastodon")]
+    if let Some(mastodon_config) = mastodon::MastodonConfig::from_env() {
+        println!("Updating the '{}' profile field on {}", mastodon_config.field, mastodon_config.instance);
+        mastodon::spawn(mastodon_config, shared_data.clone(), &modules.publisher("mastodon", &shared_data, broadcaster));
+    }
 
-    // Clone shared data for the background task
-    let data_for_task = shared_data.clone();
+    // Commit the status into a GitHub profile README for viewers without JavaScript
+    #[cfg(feature = "profile-readme")]
+    if let Some(readme_config) = profile_readme::ProfileReadmeConfig::from_env() {
+        println!("Committing the status to {} in {} at most every {} minute(s)", readme_config.path, readme_config.repo, readme_config.interval.as_secs() / 60);
+        profile_readme::spawn(readme_config, shared_data.clone(), &modules.publisher("profile-readme", &shared_data, broadcaster));
+    }
 
-    // Create and start web server
-    let (app, broadcaster) = web_server::create_server(shared_data).await;
+    // Publish to NATS, optionally stored in a JetStream stream
+    #[cfg(feature = "nats")]
+    if let Some(nats_config) = nats::NatsConfig::from_env() {
+        match &nats_config.stream {
+            Some(stream) => println!("Publishing presence to NATS subject {} in stream {}", nats_config.subject, stream),
+            None => println!("Publishing presence to NATS subject {}", nats_config.subject),
+        }
+        nats::spawn(nats_config, shared_data.clone(), &modules.publisher("nats", &shared_data, broadcaster));
+    }
 
-    // Start background task to update presence data
-    tokio::spawn(async move {
-        update_presence_data(data_for_task, broadcaster).await;
-    });
-    
-    println!("Represence server starting on http://0.0.0.0:{}", port);
-    println!("API endpoint: http://0.0.0.0:{}/api/represence", port);
-    println!("Health check: http://0.0.0.0:{}/health", port);
-    println!("Optimized for fast response times (1-3s adaptive polling)");
+    // Local status bars and scripts listen on the session bus
+    #[cfg(feature = "dbus")]
+    dbus::spawn(shared_data.clone(), &modules.publisher("dbus", &shared_data, broadcaster));
 
-    let bind_addr = format!("0.0.0.0:{}", port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    axum::serve(listener, app).await?;
+    // Desktop notifications for transitions that change what visitors see
+    #[cfg(feature = "dbus")]
+    if let Some(notify_config) = notify::NotifyConfig::from_env() {
+        println!("Sending desktop notifications for {} kind(s) of transition", notify_config.kinds.len());
+        notify::spawn(notify_config, shared_data.clone(), diagnostics.clone());
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    // Push alerts when the daemon degrades, it usually runs unattended
+    #[cfg(feature = "alerts")]
+    if let Some(alert_config) = alert::AlertConfig::from_env() {
+        println!("Sending alerts to {} target(s) when represence degrades", alert_config.targets.len());
+        alert::spawn(alert_config, shared_data.clone(), diagnostics.clone(), history);
+    }
+
+    // Write presence samples to InfluxDB for Grafana dashboards
+    #[cfg(feature = "influxdb")]
+    if let Some(influx_config) = influx::InfluxConfig::from_env() {
+        let endpoint = influx_config.url.split('?').next().unwrap_or_default();
+        println!("Writing presence samples to {} every {}s", endpoint, influx_config.interval.as_secs());
+        influx::spawn(influx_config, shared_data.clone(), diagnostics);
+    }
+
+    // Report our presence to a hub if this instance is configured as an agent
+    #[cfg(feature = "http-client")]
+    if let Some(agent_config) = hub::agent::AgentConfig::from_env() {
+        println!("Pushing presence to hub {} as {}", agent_config.url, agent_config.user);
+        hub::agent::spawn(agent_config, &shared_data, &modules.publisher("hub-agent", &shared_data, broadcaster));
+    }
+
+    // Forward coding activity to WakaTime or Wakapi
+    #[cfg(feature = "wakatime")]
+    if let Some(wakatime_config) = wakatime::WakaTimeConfig::from_env() {
+        println!("Sending coding heartbeats to {}", wakatime_config.api_url);
+        wakatime::spawn(wakatime_config, editor_rx);
+    }
+}
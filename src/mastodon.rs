@@ -0,0 +1,111 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::debounce::Debounce;
+use crate::web_server::{Broadcaster, SharedData};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Mastodon's limit for profile field values
+const MAX_VALUE_CHARS: usize = 255;
+
+#[derive(Debug, Clone)]
+pub struct MastodonConfig {
+    /// Instance URL, e.g. `https://mastodon.social`
+    pub instance: String,
+    /// Access token with the `read:accounts` and `write:accounts` scopes
+    pub token: String,
+    /// Name of the profile field holding the presence
+    pub field: String,
+    pub debounce: Duration,
+    pub min_interval: Duration,
+}
+
+impl MastodonConfig {
+    /// `REPRESENCE_MASTODON_URL` and `REPRESENCE_MASTODON_TOKEN`, `None` unless both are set
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            instance: env_var("REPRESENCE_MASTODON_URL")?.trim_end_matches('/').to_string(),
+            token: env_var("REPRESENCE_MASTODON_TOKEN")?,
+            field: env_var("REPRESENCE_MASTODON_FIELD").unwrap_or_else(|| "now".to_string()),
+            debounce: Duration::from_secs(env_parse("REPRESENCE_MASTODON_DEBOUNCE_SECS", 300)),
+            min_interval: Duration::from_secs(env_parse("REPRESENCE_MASTODON_MIN_INTERVAL_SECS", 900)),
+        })
+    }
+}
+
+/// A profile metadata field as the account's `source` has it, before links are rendered
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProfileField {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    source: Source,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    fields: Vec<ProfileField>,
+}
+
+/// Form parameters for `update_credentials`. Fields are replaced as a whole, so the others are sent back unchanged.
+pub fn field_params(fields: &[ProfileField], name: &str, value: &str) -> Vec<(String, String)> {
+    let value: String = value.chars().take(MAX_VALUE_CHARS).collect();
+    let mut fields = fields.to_vec();
+    match fields.iter_mut().find(|field| field.name == name) {
+        Some(field) => field.value = value,
+        None => fields.push(ProfileField { name: name.to_string(), value }),
+    }
+    fields
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, field)| [(format!("fields_attributes[{}][name]", i), field.name), (format!("fields_attributes[{}][value]", i), field.value)])
+        .collect()
+}
+
+/// Put the public presence text into the profile field whenever it settles on a new one
+pub fn spawn(config: MastodonConfig, shared_data: SharedData, broadcaster: &Broadcaster) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Mastodon profile updates disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let mut texts = Debounce::new(shared_data, broadcaster, config.debounce, config.min_interval);
+    tokio::spawn(async move {
+        while let Some(text) = texts.next().await {
+            if let Err(e) = update_field(&client, &config, &text).await {
+                eprintln!("Failed to update the Mastodon profile field: {}", e);
+            }
+        }
+    });
+}
+
+async fn update_field(client: &reqwest::Client, config: &MastodonConfig, text: &str) -> reqwest::Result<()> {
+    // Read the fields right before writing, they may have been edited by hand
+    let account: Account = client
+        .get(format!("{}/api/v1/accounts/verify_credentials", config.instance))
+        .bearer_auth(&config.token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    client
+        .patch(format!("{}/api/v1/accounts/update_credentials", config.instance))
+        .bearer_auth(&config.token)
+        .form(&field_params(&account.source.fields, &config.field, text))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
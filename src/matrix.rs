@@ -0,0 +1,100 @@
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::debounce::Debounce;
+use crate::web_server::{Broadcaster, SharedData};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Used when a rate-limited response doesn't say how long to wait
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct MatrixConfig {
+    /// e.g. `https://matrix.org`
+    pub homeserver: String,
+    /// Full user ID, e.g. `@me:matrix.org`
+    pub user_id: String,
+    pub access_token: String,
+    /// How long the text must stay the same before it's sent
+    pub debounce: Duration,
+    /// Least time between two updates
+    pub min_interval: Duration,
+}
+
+impl MatrixConfig {
+    /// `REPRESENCE_MATRIX_HOMESERVER`, `REPRESENCE_MATRIX_USER` and `REPRESENCE_MATRIX_TOKEN`, `None` unless all are set
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            homeserver: env_var("REPRESENCE_MATRIX_HOMESERVER")?,
+            user_id: env_var("REPRESENCE_MATRIX_USER")?,
+            access_token: env_var("REPRESENCE_MATRIX_TOKEN")?,
+            debounce: Duration::from_secs(env_parse("REPRESENCE_MATRIX_DEBOUNCE_SECS", 30)),
+            min_interval: Duration::from_secs(env_parse("REPRESENCE_MATRIX_MIN_INTERVAL_SECS", 60)),
+        })
+    }
+
+    /// `PUT /_matrix/client/v3/presence/{userId}/status`
+    pub fn status_url(&self) -> Option<Url> {
+        let mut url = Url::parse(&self.homeserver).ok()?;
+        url.path_segments_mut().ok()?.pop_if_empty().extend(["_matrix", "client", "v3", "presence", &self.user_id, "status"]);
+        Some(url)
+    }
+}
+
+/// Body of a `M_LIMIT_EXCEEDED` response
+#[derive(Debug, Deserialize)]
+struct RateLimited {
+    retry_after_ms: Option<u64>,
+}
+
+/// Set the account's status message to the public presence text whenever it settles on a new one
+pub fn spawn(config: MatrixConfig, shared_data: SharedData, broadcaster: &Broadcaster) {
+    let url = match config.status_url() {
+        Some(url) => url,
+        None => {
+            eprintln!("Matrix status sync disabled, invalid REPRESENCE_MATRIX_HOMESERVER: {}", config.homeserver);
+            return;
+        }
+    };
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Matrix status sync disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let mut texts = Debounce::new(shared_data, broadcaster, config.debounce, config.min_interval);
+    tokio::spawn(async move {
+        while let Some(text) = texts.next().await {
+            if let Err(e) = set_status(&client, &url, &config.access_token, &text).await {
+                eprintln!("Failed to update Matrix status: {}", e);
+            }
+        }
+    });
+}
+
+async fn set_status(client: &reqwest::Client, url: &Url, token: &str, text: &str) -> reqwest::Result<()> {
+    let body = serde_json::json!({ "presence": "online", "status_msg": text });
+    let mut attempt = 1;
+    loop {
+        let response = client.put(url.clone()).bearer_auth(token).json(&body).send().await?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt == MAX_ATTEMPTS {
+            response.error_for_status()?;
+            return Ok(());
+        }
+        let retry_after = match response.json::<RateLimited>().await {
+            Ok(RateLimited { retry_after_ms: Some(ms) }) => Duration::from_millis(ms),
+            _ => DEFAULT_RETRY_AFTER,
+        };
+        tokio::time::sleep(retry_after).await;
+        attempt += 1;
+    }
+}
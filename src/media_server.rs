@@ -0,0 +1,234 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::now_playing::{NowPlaying, Track};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// A media server whose sessions are checked for the configured user
+#[derive(Debug, Clone)]
+pub enum MediaServer {
+    Jellyfin { url: String, api_key: String, user: String },
+    Plex { url: String, token: String, user: String },
+}
+
+impl MediaServer {
+    /// Key the track is stored under, also the name the activity ranks as
+    pub fn app(&self) -> &'static str {
+        match self {
+            Self::Jellyfin { .. } => "jellyfin",
+            Self::Plex { .. } => "plex",
+        }
+    }
+
+    fn player(&self) -> &'static str {
+        match self {
+            Self::Jellyfin { .. } => "Jellyfin",
+            Self::Plex { .. } => "Plex",
+        }
+    }
+
+    async fn track(&self, client: &reqwest::Client) -> Result<Option<Track>, reqwest::Error> {
+        match self {
+            Self::Jellyfin { url, api_key, user } => {
+                let sessions: Vec<JellyfinSession> = client
+                    .get(format!("{}/Sessions?activeWithinSeconds=120", url))
+                    .header("X-Emby-Token", api_key)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(jellyfin_track(sessions, user))
+            }
+            Self::Plex { url, token, user } => {
+                let sessions: PlexSessions = client
+                    .get(format!("{}/status/sessions", url))
+                    .header("X-Plex-Token", token)
+                    .header(reqwest::header::ACCEPT, "application/json")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(plex_track(sessions, user))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MediaServerConfig {
+    pub servers: Vec<MediaServer>,
+    pub poll_interval: Duration,
+}
+
+impl MediaServerConfig {
+    /// Jellyfin with `REPRESENCE_JELLYFIN_URL`, `REPRESENCE_JELLYFIN_API_KEY` and `REPRESENCE_JELLYFIN_USER`,
+    /// Plex with `REPRESENCE_PLEX_URL`, `REPRESENCE_PLEX_TOKEN` and `REPRESENCE_PLEX_USER`. `None` when neither is complete.
+    pub fn from_env() -> Option<Self> {
+        let url = |name: &str| env_var(name).map(|url| url.trim_end_matches('/').to_string());
+        let jellyfin = (|| {
+            Some(MediaServer::Jellyfin {
+                url: url("REPRESENCE_JELLYFIN_URL")?,
+                api_key: env_var("REPRESENCE_JELLYFIN_API_KEY")?,
+                user: env_var("REPRESENCE_JELLYFIN_USER")?,
+            })
+        })();
+        let plex = (|| {
+            Some(MediaServer::Plex {
+                url: url("REPRESENCE_PLEX_URL")?,
+                token: env_var("REPRESENCE_PLEX_TOKEN")?,
+                user: env_var("REPRESENCE_PLEX_USER")?,
+            })
+        })();
+
+        let servers: Vec<MediaServer> = jellyfin.into_iter().chain(plex).collect();
+        (!servers.is_empty()).then(|| Self {
+            servers,
+            poll_interval: Duration::from_secs(env_parse("REPRESENCE_MEDIA_SERVER_POLL_SECS", 15).max(1)),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinSession {
+    user_name: Option<String>,
+    now_playing_item: Option<JellyfinItem>,
+    play_state: Option<JellyfinPlayState>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinItem {
+    name: String,
+    #[serde(rename = "Type")]
+    kind: String,
+    series_name: Option<String>,
+    parent_index_number: Option<u32>,
+    index_number: Option<u32>,
+    album_artist: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinPlayState {
+    #[serde(default)]
+    is_paused: bool,
+}
+
+/// What `user` streams unpaused, on any device
+pub fn jellyfin_track(sessions: Vec<JellyfinSession>, user: &str) -> Option<Track> {
+    let item = sessions
+        .into_iter()
+        .filter(|session| session.user_name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(user)))
+        .filter(|session| !session.play_state.as_ref().is_some_and(|state| state.is_paused))
+        .find_map(|session| session.now_playing_item)?;
+
+    Some(match item.kind.as_str() {
+        "Audio" => Track::new(item.name, item.album_artist),
+        "Episode" => Track::video(episode_title(item.series_name.as_deref(), item.parent_index_number, item.index_number, &item.name)),
+        _ => Track::video(item.name),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlexSessions {
+    #[serde(rename = "MediaContainer")]
+    container: PlexContainer,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexContainer {
+    #[serde(rename = "Metadata", default)]
+    metadata: Vec<PlexItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlexItem {
+    #[serde(rename = "type")]
+    kind: String,
+    title: String,
+    /// Show of an episode, artist of a track
+    grandparent_title: Option<String>,
+    parent_index: Option<u32>,
+    index: Option<u32>,
+    #[serde(rename = "User")]
+    user: Option<PlexNamed>,
+    #[serde(rename = "Player")]
+    player: Option<PlexPlayer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexNamed {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlexPlayer {
+    state: String,
+}
+
+/// What `user` streams unpaused, on any device
+pub fn plex_track(sessions: PlexSessions, user: &str) -> Option<Track> {
+    let item = sessions.container.metadata.into_iter().find(|item| {
+        item.user.as_ref().is_some_and(|named| named.title.eq_ignore_ascii_case(user))
+            && item.player.as_ref().is_none_or(|player| player.state == "playing")
+    })?;
+
+    Some(match item.kind.as_str() {
+        "track" => Track::new(item.title, item.grandparent_title),
+        "episode" => Track::video(episode_title(item.grandparent_title.as_deref(), item.parent_index, item.index, &item.title)),
+        _ => Track::video(item.title),
+    })
+}
+
+/// e.g. `Severance S01E03`, the episode name when the show is unknown
+fn episode_title(show: Option<&str>, season: Option<u32>, episode: Option<u32>, name: &str) -> String {
+    match (show, season, episode) {
+        (Some(show), Some(season), Some(episode)) => format!("{} S{:02}E{:02}", show, season, episode),
+        (Some(show), _, _) => format!("{}: {}", show, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Poll the media servers, streams count as an activity even when they play on another device
+pub fn spawn(config: MediaServerConfig, now_playing: NowPlaying) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Media server integration disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut failing = vec![false; config.servers.len()];
+        loop {
+            for (server, failing) in config.servers.iter().zip(failing.iter_mut()) {
+                let track = match server.track(&client).await {
+                    Ok(track) => {
+                        *failing = false;
+                        track
+                    }
+                    Err(e) => {
+                        if !*failing {
+                            eprintln!("{} sessions request failed: {}", server.player(), e);
+                            *failing = true;
+                        }
+                        None
+                    }
+                };
+                now_playing.set_standalone(server.app(), server.player(), track).await;
+            }
+            tokio::time::sleep(config.poll_interval).await;
+        }
+    });
+}
@@ -0,0 +1,180 @@
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Notify;
+use utoipa::ToSchema;
+
+use crate::config::env_list;
+use crate::web_server::{self, AppState, Broadcaster, SharedData};
+
+/// Detectors the pipeline reads, by module name
+pub const VSCODE: &str = "vscode";
+pub const NOW_PLAYING: &str = "now-playing";
+pub const CALENDAR: &str = "calendar";
+pub const TWITCH: &str = "twitch";
+pub const GITHUB: &str = "github";
+pub const FILM_LOG: &str = "film-log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleKind {
+    /// Feeds the presence
+    Detector,
+    /// Sends the presence elsewhere
+    Publisher,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ModuleStatus {
+    pub name: String,
+    pub kind: ModuleKind,
+    pub enabled: bool,
+}
+
+struct Module {
+    kind: ModuleKind,
+    enabled: Arc<AtomicBool>,
+    /// Woken when the module is turned back on
+    turned_on: Arc<Notify>,
+}
+
+/// Running detectors and publishers, each of which can be turned off without a restart
+#[derive(Clone, Default)]
+pub struct Modules {
+    modules: Arc<RwLock<BTreeMap<String, Module>>>,
+    /// Registered turned off, from `REPRESENCE_DISABLED_MODULES`
+    disabled: Arc<HashSet<String>>,
+}
+
+impl Modules {
+    /// Modules listed in `REPRESENCE_DISABLED_MODULES` start turned off
+    pub fn from_env() -> Self {
+        Self::new(env_list("REPRESENCE_DISABLED_MODULES"))
+    }
+
+    pub fn new(disabled: impl IntoIterator<Item = String>) -> Self {
+        Self { modules: Default::default(), disabled: Arc::new(disabled.into_iter().collect()) }
+    }
+
+    /// Add a module to the registry, returns the flag telling whether it's on
+    pub fn register(&self, name: &str, kind: ModuleKind) -> Arc<AtomicBool> {
+        self.insert(name, kind).0
+    }
+
+    fn insert(&self, name: &str, kind: ModuleKind) -> (Arc<AtomicBool>, Arc<Notify>) {
+        let enabled = Arc::new(AtomicBool::new(!self.disabled.contains(name)));
+        let turned_on = Arc::new(Notify::new());
+        self.modules
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), Module { kind, enabled: enabled.clone(), turned_on: turned_on.clone() });
+        (enabled, turned_on)
+    }
+
+    /// Whether `name` runs. Modules that were never registered aren't held back.
+    pub fn enabled(&self, name: &str) -> bool {
+        let modules = self.modules.read().unwrap_or_else(|e| e.into_inner());
+        modules.get(name).is_none_or(|module| module.enabled.load(Ordering::Relaxed))
+    }
+
+    /// Turn a module on or off, `None` when there is no such module
+    pub fn set(&self, name: &str, enabled: bool) -> Option<ModuleStatus> {
+        let modules = self.modules.read().unwrap_or_else(|e| e.into_inner());
+        let module = modules.get(name)?;
+        if !module.enabled.swap(enabled, Ordering::Relaxed) && enabled {
+            module.turned_on.notify_one();
+        }
+        Some(ModuleStatus { name: name.to_string(), kind: module.kind, enabled })
+    }
+
+    pub fn status(&self) -> Vec<ModuleStatus> {
+        let modules = self.modules.read().unwrap_or_else(|e| e.into_inner());
+        modules
+            .iter()
+            .map(|(name, module)| ModuleStatus { name: name.clone(), kind: module.kind, enabled: module.enabled.load(Ordering::Relaxed) })
+            .collect()
+    }
+
+    /// Register a publisher and hand it a broadcaster that only passes updates while it's on.
+    /// Turning it back on sends the current presence, so it catches up on what it missed.
+    pub fn publisher(&self, name: &str, shared_data: &SharedData, broadcaster: &Broadcaster) -> Broadcaster {
+        let (enabled, turned_on) = self.insert(name, ModuleKind::Publisher);
+        let (tx, _) = broadcast::channel(32);
+        let gated = tx.clone();
+        let mut updates = broadcaster.subscribe();
+        let shared_data = shared_data.clone();
+        tokio::spawn(async move {
+            loop {
+                let data = tokio::select! {
+                    update = updates.recv() => match update {
+                        Ok(data) => data,
+                        Err(RecvError::Lagged(_)) => shared_data.load_full(),
+                        Err(RecvError::Closed) => return,
+                    },
+                    // A presence missed while off is already in the current one
+                    _ = turned_on.notified() => shared_data.load_full(),
+                };
+                if enabled.load(Ordering::Relaxed) {
+                    let _ = tx.send(data);
+                }
+            }
+        });
+        gated
+    }
+}
+
+/// Running detectors and publishers and whether each is on, requires the admin token
+#[utoipa::path(
+    get,
+    path = "/api/admin/modules",
+    tag = "diagnostics",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Modules", body = Vec<ModuleStatus>),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+    )
+)]
+pub async fn get_modules(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    Json(state.modules.status()).into_response()
+}
+
+/// Turn modules on or off until the next restart, e.g. `{"matrix": false}`, requires the admin token
+#[utoipa::path(
+    patch,
+    path = "/api/admin/modules",
+    tag = "diagnostics",
+    security(("bearer" = [])),
+    request_body = BTreeMap<String, bool>,
+    responses(
+        (status = 200, description = "Modules after the change", body = Vec<ModuleStatus>),
+        (status = 400, description = "Unknown module, nothing was changed"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+    )
+)]
+pub async fn patch_modules(State(state): State<AppState>, headers: HeaderMap, Json(changes): Json<BTreeMap<String, bool>>) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    let known: HashSet<String> = state.modules.status().into_iter().map(|module| module.name).collect();
+    let unknown: Vec<&String> = changes.keys().filter(|name| !known.contains(*name)).collect();
+    if !unknown.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "unknown modules", "modules": unknown }))).into_response();
+    }
+
+    for (name, enabled) in &changes {
+        if state.modules.set(name, *enabled).is_some() {
+            println!("Module {} turned {} over the API", name, if *enabled { "on" } else { "off" });
+        }
+    }
+    Json(state.modules.status()).into_response()
+}
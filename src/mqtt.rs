@@ -0,0 +1,165 @@
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::{env_parse, env_var};
+use crate::web_server::{Broadcaster, SharedData};
+
+const KEEP_ALIVE_SECS: u64 = 30;
+const RECONNECT_DELAY_SECS: u64 = 5;
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Base topic, state goes to `{topic}/state` and availability to `{topic}/availability`
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    /// Home Assistant discovery prefix, `None` disables discovery messages
+    pub discovery_prefix: Option<String>,
+}
+
+impl MqttConfig {
+    /// Load MQTT settings from the environment, `None` when no broker is configured
+    pub fn from_env() -> Option<Self> {
+        let host = env_var("REPRESENCE_MQTT_HOST")?;
+
+        let qos = match env_parse("REPRESENCE_MQTT_QOS", 1u8) {
+            0 => QoS::AtMostOnce,
+            2 => QoS::ExactlyOnce,
+            _ => QoS::AtLeastOnce,
+        };
+
+        let discovery_prefix = if env_parse("REPRESENCE_MQTT_DISCOVERY", true) {
+            Some(env_var("REPRESENCE_MQTT_DISCOVERY_PREFIX").unwrap_or_else(|| "homeassistant".to_string()))
+        } else {
+            None
+        };
+
+        Some(Self {
+            host,
+            port: env_parse("REPRESENCE_MQTT_PORT", 1883),
+            client_id: env_var("REPRESENCE_MQTT_CLIENT_ID").unwrap_or_else(|| "represence".to_string()),
+            username: env_var("REPRESENCE_MQTT_USERNAME"),
+            password: env_var("REPRESENCE_MQTT_PASSWORD"),
+            topic: env_var("REPRESENCE_MQTT_TOPIC")
+                .map(|topic| topic.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| "represence".to_string()),
+            qos,
+            retain: env_parse("REPRESENCE_MQTT_RETAIN", true),
+            discovery_prefix,
+        })
+    }
+
+    fn state_topic(&self) -> String {
+        format!("{}/state", self.topic)
+    }
+
+    fn availability_topic(&self) -> String {
+        format!("{}/availability", self.topic)
+    }
+}
+
+/// Connect to the broker and publish every presence change, announcing the
+/// Home Assistant sensor on each (re)connect so it survives broker and HA restarts
+pub fn spawn(config: MqttConfig, shared_data: SharedData, broadcaster: &Broadcaster) {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(KEEP_ALIVE_SECS));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    options.set_last_will(LastWill::new(config.availability_topic(), "offline", QoS::AtLeastOnce, true));
+
+    let (client, mut eventloop) = AsyncClient::new(options, 16);
+
+    // Event loop task: drives the connection and re-announces after reconnects
+    {
+        let client = client.clone();
+        let config = config.clone();
+        let shared_data = shared_data.clone();
+        let ha_status_topic = config.discovery_prefix.as_ref().map(|prefix| format!("{}/status", prefix));
+
+        tokio::spawn(async move {
+            loop {
+                let announce = match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => true,
+                    // Home Assistant publishes "online" here after it restarts
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        Some(&publish.topic) == ha_status_topic.as_ref() && publish.payload.as_ref() == b"online"
+                    }
+                    Ok(_) => false,
+                    Err(e) => {
+                        eprintln!("MQTT connection error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+                        false
+                    }
+                };
+
+                if announce {
+                    // Publishing awaits channel capacity, so it must not block the event loop
+                    let client = client.clone();
+                    let config = config.clone();
+                    let shared_data = shared_data.clone();
+                    let ha_status_topic = ha_status_topic.clone();
+                    tokio::spawn(async move {
+                        if let Some(topic) = ha_status_topic {
+                            let _ = client.subscribe(topic, QoS::AtLeastOnce).await;
+                        }
+                        announce_and_publish(&client, &config, &shared_data).await;
+                    });
+                }
+            }
+        });
+    }
+
+    // Publisher task: forwards broadcast updates to the state topic
+    let mut rx = broadcaster.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let data = match rx.recv().await {
+                Ok(data) => data,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            if let Ok(payload) = serde_json::to_vec(&data) {
+                if let Err(e) = client.publish(config.state_topic(), config.qos, config.retain, payload).await {
+                    eprintln!("MQTT publish failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+async fn announce_and_publish(client: &AsyncClient, config: &MqttConfig, shared_data: &SharedData) {
+    if let Some(prefix) = &config.discovery_prefix {
+        let discovery = serde_json::json!({
+            "name": "Presence",
+            "unique_id": format!("{}_status", config.client_id),
+            "object_id": format!("{}_status", config.client_id),
+            "state_topic": config.state_topic(),
+            "value_template": "{{ value_json.text }}",
+            "json_attributes_topic": config.state_topic(),
+            "availability_topic": config.availability_topic(),
+            "icon": "mdi:account-circle",
+            "device": {
+                "identifiers": [config.client_id],
+                "name": "Represence",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        let topic = format!("{}/sensor/{}/status/config", prefix, config.client_id);
+        let _ = client.publish(topic, QoS::AtLeastOnce, true, discovery.to_string()).await;
+    }
+
+    let _ = client.publish(config.availability_topic(), QoS::AtLeastOnce, true, "online").await;
+
+    let payload = serde_json::to_vec(&**shared_data.load());
+    if let Ok(payload) = payload {
+        let _ = client.publish(config.state_topic(), config.qos, config.retain, payload).await;
+    }
+}
@@ -0,0 +1,99 @@
+use async_nats::jetstream::{self, stream};
+use async_nats::{Client, ConnectOptions};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::env_var;
+use crate::web_server::{Broadcaster, SharedData};
+use crate::OutputData;
+
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    /// e.g. `nats://localhost:4222`, credentials may go in the URL
+    pub url: String,
+    pub token: Option<String>,
+    /// Every presence change is published here as JSON
+    pub subject: String,
+    /// Publish through JetStream into this stream, created for the subject when missing. `None` publishes core NATS messages.
+    pub stream: Option<String>,
+}
+
+impl NatsConfig {
+    /// Load NATS settings from the environment, `None` without `REPRESENCE_NATS_URL`
+    pub fn from_env() -> Option<Self> {
+        let url = env_var("REPRESENCE_NATS_URL")?;
+        Some(Self {
+            url,
+            token: env_var("REPRESENCE_NATS_TOKEN"),
+            subject: env_var("REPRESENCE_NATS_SUBJECT").unwrap_or_else(|| "represence.presence".to_string()),
+            stream: env_var("REPRESENCE_NATS_STREAM"),
+        })
+    }
+
+    /// The stream created when it doesn't exist yet, capturing just our subject
+    pub fn stream_config(&self) -> Option<stream::Config> {
+        let name = self.stream.clone()?;
+        Some(stream::Config { name, subjects: vec![self.subject.clone()], ..Default::default() })
+    }
+}
+
+/// Where messages go, JetStream waits for the server to store each one
+enum Publisher {
+    Core(Client),
+    JetStream(jetstream::Context),
+}
+
+impl Publisher {
+    async fn publish(&self, subject: &str, data: &OutputData) -> Result<(), async_nats::Error> {
+        let payload = serde_json::to_vec(data)?;
+        match self {
+            Self::Core(client) => client.publish(subject.to_string(), payload.into()).await?,
+            Self::JetStream(context) => {
+                context.publish(subject.to_string(), payload.into()).await?.await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Connect to the server and publish the current presence, then every change. The client reconnects by itself.
+pub fn spawn(config: NatsConfig, shared_data: SharedData, broadcaster: &Broadcaster) {
+    let mut rx = broadcaster.subscribe();
+    tokio::spawn(async move {
+        let publisher = match connect(&config).await {
+            Ok(publisher) => publisher,
+            Err(e) => {
+                eprintln!("NATS publishing disabled: {}", e);
+                return;
+            }
+        };
+
+        let mut data = shared_data.load_full();
+        loop {
+            if let Err(e) = publisher.publish(&config.subject, &data).await {
+                eprintln!("NATS publish failed: {}", e);
+            }
+            data = match rx.recv().await {
+                Ok(data) => data,
+                Err(RecvError::Lagged(_)) => shared_data.load_full(),
+                Err(RecvError::Closed) => break,
+            };
+        }
+    });
+}
+
+async fn connect(config: &NatsConfig) -> Result<Publisher, async_nats::Error> {
+    let mut options = ConnectOptions::new().name("represence").retry_on_initial_connect();
+    if let Some(token) = &config.token {
+        options = options.token(token.clone());
+    }
+    let client = options.connect(config.url.as_str()).await?;
+
+    match config.stream_config() {
+        Some(stream_config) => {
+            let context = jetstream::new(client);
+            context.get_or_create_stream(stream_config).await?;
+            Ok(Publisher::JetStream(context))
+        }
+        None => Ok(Publisher::Core(client)),
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use zbus::zvariant::Value;
+use zbus::{Connection, Proxy};
+
+use crate::config::env_list;
+use crate::diagnostics::{Diagnostics, SharedDiagnostics};
+use crate::trust::Trust;
+use crate::web_server::SharedData;
+
+/// How often the state is compared with the last check
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Transitions worth a desktop notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoticeKind {
+    /// Privacy mode turned on or off
+    Privacy,
+    /// The VS Code extension stopped answering while VS Code runs, or answers again
+    VsCode,
+    /// The update loop got stuck and the presence is marked stale, or recovered
+    Stale,
+    /// The process list became unavailable
+    PushOnly,
+    /// Any change of what visitors without a friend token see
+    Status,
+}
+
+impl FromStr for NoticeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "privacy" => Ok(Self::Privacy),
+            "vscode" => Ok(Self::VsCode),
+            "stale" => Ok(Self::Stale),
+            "push-only" => Ok(Self::PushOnly),
+            "status" => Ok(Self::Status),
+            _ => Err(format!("unknown notification '{}', expected privacy, vscode, stale, push-only, status or all", s)),
+        }
+    }
+}
+
+/// What the notifier compares between checks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub privacy: bool,
+    pub vscode_running: bool,
+    pub vscode_connected: bool,
+    pub stale: bool,
+    pub push_only: bool,
+    pub public_text: String,
+}
+
+impl Snapshot {
+    pub fn new(diagnostics: &Diagnostics, shared_data: &SharedData) -> Self {
+        let data = shared_data.load_full();
+        Self {
+            privacy: diagnostics.privacy,
+            vscode_running: diagnostics.vscode.running,
+            vscode_connected: diagnostics.vscode.connected,
+            stale: data.stale,
+            push_only: diagnostics.push_only,
+            public_text: Trust::Public.view(data).text.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notice {
+    pub summary: String,
+    pub body: String,
+}
+
+impl Notice {
+    fn new(summary: &str, body: impl Into<String>) -> Self {
+        Self { summary: summary.to_string(), body: body.into() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotifyConfig {
+    pub kinds: Vec<NoticeKind>,
+}
+
+impl NotifyConfig {
+    /// `REPRESENCE_NOTIFY`, comma-separated kinds or `all`, `None` when unset
+    pub fn from_env() -> Option<Self> {
+        let entries = env_list("REPRESENCE_NOTIFY");
+        let kinds: Vec<NoticeKind> = match entries.iter().any(|entry| entry == "all") {
+            true => vec![NoticeKind::Privacy, NoticeKind::VsCode, NoticeKind::Stale, NoticeKind::PushOnly, NoticeKind::Status],
+            false => entries
+                .iter()
+                .filter_map(|entry| match entry.parse() {
+                    Ok(kind) => Some(kind),
+                    Err(e) => {
+                        eprintln!("Ignoring {}", e);
+                        None
+                    }
+                })
+                .collect(),
+        };
+        (!kinds.is_empty()).then_some(Self { kinds })
+    }
+
+    /// Notifications for the selected transitions between two checks
+    pub fn notices(&self, before: &Snapshot, after: &Snapshot) -> Vec<Notice> {
+        let mut notices = Vec::new();
+        for kind in &self.kinds {
+            let notice = match kind {
+                NoticeKind::Privacy if !before.privacy && after.privacy => {
+                    Some(Notice::new("Privacy mode enabled", format!("Publishing '{}' instead of your activity", after.public_text)))
+                }
+                NoticeKind::Privacy if before.privacy && !after.privacy => {
+                    Some(Notice::new("Privacy mode disabled", format!("Your activity is public again: {}", after.public_text)))
+                }
+                NoticeKind::VsCode if before.vscode_connected && !after.vscode_connected && after.vscode_running => {
+                    Some(Notice::new("VS Code integration lost connection", "Files aren't shown until the extension answers again"))
+                }
+                NoticeKind::VsCode if !before.vscode_connected && after.vscode_connected && before.vscode_running => {
+                    Some(Notice::new("VS Code integration reconnected", after.public_text.clone()))
+                }
+                NoticeKind::Stale if !before.stale && after.stale => {
+                    Some(Notice::new("Presence is stale", format!("The update loop is stuck, '{}' may be out of date", after.public_text)))
+                }
+                NoticeKind::Stale if before.stale && !after.stale => Some(Notice::new("Presence is updating again", after.public_text.clone())),
+                NoticeKind::PushOnly if !before.push_only && after.push_only => {
+                    Some(Notice::new("Process list unavailable", "The presence only changes through pushes"))
+                }
+                NoticeKind::Status if before.public_text != after.public_text => Some(Notice::new("Public status changed", after.public_text.clone())),
+                _ => None,
+            };
+            notices.extend(notice);
+        }
+        notices
+    }
+}
+
+/// Check for transitions every `CHECK_INTERVAL` and show them as desktop notifications
+pub fn spawn(config: NotifyConfig, shared_data: SharedData, diagnostics: SharedDiagnostics) {
+    tokio::spawn(async move {
+        let notifications = match notifications().await {
+            Ok(notifications) => notifications,
+            Err(e) => {
+                eprintln!("Desktop notifications disabled, no notification service on the session bus: {}", e);
+                return;
+            }
+        };
+        let mut last = Snapshot::new(&*diagnostics.read().await, &shared_data);
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let current = Snapshot::new(&*diagnostics.read().await, &shared_data);
+            for notice in config.notices(&last, &current) {
+                let hints: HashMap<&str, Value> = HashMap::new();
+                let result: zbus::Result<u32> = notifications
+                    .call("Notify", &("represence", 0u32, "", &notice.summary, &notice.body, Vec::<&str>::new(), hints, -1i32))
+                    .await;
+                if let Err(e) = result {
+                    eprintln!("Failed to show notification '{}': {}", notice.summary, e);
+                }
+            }
+            last = current;
+        }
+    });
+}
+
+async fn notifications() -> zbus::Result<Proxy<'static>> {
+    let connection = Connection::session().await?;
+    Proxy::new_owned(connection, "org.freedesktop.Notifications", "/org/freedesktop/Notifications", "org.freedesktop.Notifications").await
+}
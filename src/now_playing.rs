@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::diagnostics::SharedDiagnostics;
+
+/// What a player is playing right now
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub title: String,
+    pub artist: Option<String>,
+    /// Cover image URL, published as `album_art`
+    pub album_art: Option<String>,
+    /// Films and shows are watched rather than listened to
+    pub video: bool,
+}
+
+impl Track {
+    pub fn new(title: impl Into<String>, artist: Option<String>) -> Self {
+        Self { title: title.into(), artist, album_art: None, video: false }
+    }
+
+    pub fn video(title: impl Into<String>) -> Self {
+        Self { video: true, ..Self::new(title, None) }
+    }
+
+    /// e.g. `listening to Teardrop by Massive Attack on Spotify` or `watching Dune on VLC`
+    pub fn text(&self, player: &str) -> String {
+        let verb = if self.video { "watching" } else { "listening to" };
+        match &self.artist {
+            Some(artist) => format!("{} {} by {} on {}", verb, self.title, artist, player),
+            None => format!("{} {} on {}", verb, self.title, player),
+        }
+    }
+}
+
+/// Tracks reported by the player integrations, keyed by the executable name prefix of the player
+#[derive(Debug, Clone, Default)]
+pub struct NowPlaying {
+    state: Arc<RwLock<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    tracks: HashMap<String, (String, Track)>,
+    /// Background players that run all the time but aren't playing
+    idle: HashSet<String>,
+    /// Sources without a local process, like a media server streaming to the TV
+    standalone: HashSet<String>,
+    /// Standalone sources that only count while no other player plays, like scrobbles that may come from one
+    fallback: HashSet<String>,
+}
+
+impl NowPlaying {
+    /// Replace what `app` plays, shown as playing on `player`. `None` when it's stopped or paused.
+    pub async fn set(&self, app: &str, player: &str, track: Option<Track>) {
+        let mut state = self.state.write().await;
+        match track {
+            Some(track) => state.tracks.insert(app.to_string(), (player.to_string(), track)),
+            None => state.tracks.remove(app),
+        };
+    }
+
+    /// Like `set` for a source that counts as a running app while it plays, with no local process
+    pub async fn set_standalone(&self, app: &str, player: &str, track: Option<Track>) {
+        let mut state = self.state.write().await;
+        match track {
+            Some(track) => {
+                state.standalone.insert(app.to_string());
+                state.tracks.insert(app.to_string(), (player.to_string(), track));
+            }
+            None => {
+                state.standalone.remove(app);
+                state.tracks.remove(app);
+            }
+        }
+    }
+
+    /// Like `set_standalone` for a source that steps aside while another player plays
+    pub async fn set_fallback(&self, app: &str, player: &str, track: Option<Track>) {
+        let mut state = self.state.write().await;
+        match track {
+            Some(track) => {
+                state.fallback.insert(app.to_string());
+                state.tracks.insert(app.to_string(), (player.to_string(), track));
+            }
+            None => {
+                state.fallback.remove(app);
+                state.tracks.remove(app);
+            }
+        }
+    }
+
+    /// Standalone sources playing right now
+    pub async fn standalone_apps(&self) -> Vec<String> {
+        self.state.read().await.standalone.iter().cloned().collect()
+    }
+
+    /// Fallback sources playing right now
+    pub async fn fallback_apps(&self) -> Vec<String> {
+        self.state.read().await.fallback.iter().cloned().collect()
+    }
+
+    /// Hide a daemon like MPD from detection while it has nothing to play
+    pub async fn set_idle(&self, app: &str, idle: bool) {
+        let mut state = self.state.write().await;
+        match idle {
+            true => state.idle.insert(app.to_string()),
+            false => state.idle.remove(app),
+        };
+    }
+
+    /// Whether a running app is a background player with nothing playing
+    pub async fn is_idle(&self, app_name: &str) -> bool {
+        self.state.read().await.idle.iter().any(|app| app_name.starts_with(app.as_str()))
+    }
+
+    /// Presence text and track for a running app, if a player integration knows what it plays
+    pub async fn for_app(&self, app_name: &str) -> Option<(String, Track)> {
+        let state = self.state.read().await;
+        state.tracks
+            .iter()
+            .find(|(app, _)| app_name.starts_with(app.as_str()))
+            .map(|(_, (player, track))| (track.text(player), track.clone()))
+    }
+}
+
+/// Whether the process scan currently sees an app, so integrations only poll while it runs
+pub async fn is_running(diagnostics: &SharedDiagnostics, app: &str) -> bool {
+    diagnostics.read().await.detected_apps.iter().any(|detected| detected.name.starts_with(app))
+}
@@ -0,0 +1,93 @@
+use axum::response::{Html, Json};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::{audit, config_file, effective_config, export, hub, modules, profiles, remote, sessions, streaks, summary, web_server};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "represence",
+        description = "Like Discord Rich Presence, but for anywhere. Current activity over REST, WebSocket and SSE."
+    ),
+    paths(
+        web_server::get_presence,
+        web_server::push_presence,
+        remote::push_remote_presence,
+        web_server::get_presence_text,
+        web_server::get_shields,
+        web_server::get_badge,
+        web_server::get_history,
+        sessions::get_sessions,
+        summary::get_daily_summary,
+        summary::get_weekly_summary,
+        summary::get_heatmap,
+        streaks::get_streaks,
+        export::export_history,
+        audit::get_audit,
+        modules::get_modules,
+        modules::patch_modules,
+        effective_config::get_config,
+        profiles::get_profile,
+        profiles::put_profile,
+        config_file::get_config_schema,
+        web_server::websocket_handler,
+        web_server::sse_handler,
+        web_server::health_check,
+        web_server::livez,
+        web_server::readyz,
+        web_server::dashboard_data,
+        hub::get_user_presence,
+        hub::push_user_presence,
+        hub::user_websocket_handler,
+        hub::user_sse_handler,
+    ),
+    modifiers(&BearerAuth, &OptionalPaths),
+    tags(
+        (name = "presence", description = "Current presence"),
+        (name = "streaming", description = "Live presence updates"),
+        (name = "history", description = "Persisted presence history"),
+        (name = "hub", description = "Multi-user hub routes"),
+        (name = "diagnostics", description = "Health and debugging"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// Backups are a SQLite file
+#[cfg(feature = "history")]
+#[derive(OpenApi)]
+#[openapi(paths(crate::backup::get_backup))]
+struct BackupApi;
+
+/// Paths of routes only built with their feature
+struct OptionalPaths;
+
+impl Modify for OptionalPaths {
+    #[allow(unused_variables)]
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        #[cfg(feature = "history")]
+        openapi.merge(BackupApi::openapi());
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Swagger UI, loaded from a CDN to keep the binary small
+pub async fn docs() -> Html<&'static str> {
+    Html(include_str!("../assets/docs.html"))
+}
@@ -0,0 +1,78 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::path::Path;
+
+use crate::config::env_json;
+use crate::process_finder::RunningApp;
+use crate::vscode_client::FileInfo;
+
+/// Alternative status texts per app, one picked at random each time the app starts
+#[derive(Debug, Clone, Default)]
+pub struct Phrases {
+    /// App name prefix → candidate phrases
+    pools: HashMap<String, Vec<String>>,
+    /// Phrase picked for each pool while its app keeps running
+    chosen: HashMap<String, usize>,
+}
+
+impl Phrases {
+    /// Pools from the JSON file at `REPRESENCE_PHRASES_FILE` or inline in `REPRESENCE_PHRASES_JSON`, e.g. `{"discord": ["yapping on Discord", "lurking in Discord"]}`
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_PHRASES", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(phrases) => {
+                println!("Loaded phrase pools for {} app(s) from {}", phrases.pools.len(), from);
+                phrases
+            }
+            Err(e) => {
+                eprintln!("Ignoring phrase file {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::from_str(source)?))
+    }
+
+    pub fn new(pools: HashMap<String, Vec<String>>) -> Self {
+        let pools = pools.into_iter().filter(|(_, phrases)| !phrases.is_empty()).collect();
+        Self { pools, chosen: HashMap::new() }
+    }
+
+    /// Forget the picks of apps that stopped, so their next session gets a new phrase
+    pub fn retain_running(&mut self, running_apps: &[RunningApp]) {
+        self.chosen.retain(|key, _| running_apps.iter().any(|app| app.name.starts_with(key.as_str())));
+    }
+
+    /// The phrase for `app`, or `None` to use the built-in text.
+    /// `{file}` and `{language}` are filled from VS Code, phrases using them need a known file.
+    pub fn phrase(&mut self, app: &RunningApp, file_info: Option<&FileInfo>) -> Option<String> {
+        // The longest matching prefix is the most specific pool
+        let (key, pool) = self
+            .pools
+            .iter()
+            .filter(|(key, _)| app.name.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())?;
+
+        let index = *self
+            .chosen
+            .entry(key.clone())
+            .or_insert_with(|| (RandomState::new().hash_one(&app.name) % pool.len() as u64) as usize);
+        let phrase = &pool[index];
+
+        match file_info {
+            Some(file_info) => Some(phrase.replace("{file}", &file_info.file_name).replace("{language}", &file_info.language_id)),
+            None if phrase.contains("{file}") || phrase.contains("{language}") => None,
+            None => Some(phrase.clone()),
+        }
+    }
+}
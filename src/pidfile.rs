@@ -0,0 +1,116 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::config::{env_parse, env_var};
+
+/// How long `--replace` waits for the old instance to shut down
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum PidFileError {
+    #[error("represence is already running (PID {0}), stop it first or start with --replace")]
+    AlreadyRunning(u32),
+
+    #[error("the running instance (PID {0}) didn't exit within {1:?}")]
+    ReplaceTimeout(u32, Duration),
+
+    #[error("failed to lock {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Locked file with the PID of the running server, so a second one refuses to start instead of
+/// fighting over the port and publishing conflicting presence. The lock goes away with the
+/// process, a PID file left behind by a crash doesn't block the next start.
+pub struct PidFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl PidFile {
+    /// `REPRESENCE_PID_FILE`, or `represence.pid` in `$XDG_RUNTIME_DIR` (the temp dir without it).
+    /// `None` with `REPRESENCE_SINGLE_INSTANCE=false`, for several instances on different ports.
+    pub fn path_from_env() -> Option<PathBuf> {
+        if !env_parse("REPRESENCE_SINGLE_INSTANCE", true) {
+            return None;
+        }
+        Some(Self::default_path())
+    }
+
+    pub fn default_path() -> PathBuf {
+        env_var("REPRESENCE_PID_FILE").map(PathBuf::from).unwrap_or_else(|| {
+            env_var("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir).join("represence.pid")
+        })
+    }
+
+    /// Lock `path` and write our PID to it. With `replace`, a running instance is sent SIGTERM
+    /// and we wait for it to exit.
+    pub fn acquire(path: &Path, replace: bool) -> Result<Self, PidFileError> {
+        let io_error = |source| PidFileError::Io { path: path.display().to_string(), source };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(io_error)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = read_pid(&mut file).unwrap_or_default();
+                if !replace || pid == 0 {
+                    return Err(PidFileError::AlreadyRunning(pid));
+                }
+                println!("Stopping the running instance (PID {})", pid);
+                // SAFETY: kill has no memory safety requirements
+                if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+                    return Err(io_error(io::Error::last_os_error()));
+                }
+                let started = Instant::now();
+                loop {
+                    match file.try_lock() {
+                        Ok(()) => break,
+                        Err(TryLockError::WouldBlock) if started.elapsed() < REPLACE_TIMEOUT => {
+                            std::thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(TryLockError::WouldBlock) => return Err(PidFileError::ReplaceTimeout(pid, REPLACE_TIMEOUT)),
+                        Err(TryLockError::Error(e)) => return Err(io_error(e)),
+                    }
+                }
+            }
+            Err(TryLockError::Error(e)) => return Err(io_error(e)),
+        }
+
+        let mut pid_file = Self { path: path.to_path_buf(), file };
+        pid_file.write_pid().map_err(io_error)?;
+        Ok(pid_file)
+    }
+
+    /// Write our PID again, after forking into the background. The lock is shared with the child.
+    pub fn write_pid(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        writeln!(self.file, "{}", std::process::id())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use crate::config::{env_parse, env_var};
+use crate::diagnostics::SharedDiagnostics;
+use crate::now_playing::{self, NowPlaying, Track};
+
+const POLL_INTERVAL_SECS: u64 = 5;
+const QUERY_TIMEOUT_SECS: u64 = 2;
+const DEFAULT_MPD_ADDRESS: &str = "localhost:6600";
+
+#[derive(Debug, Clone)]
+pub struct MpdConfig {
+    pub address: String,
+    pub password: Option<String>,
+}
+
+impl MpdConfig {
+    /// On unless `REPRESENCE_MPD=false`, `REPRESENCE_MPD_ADDRESS` and `REPRESENCE_MPD_PASSWORD` match mpd.conf
+    pub fn from_env() -> Option<Self> {
+        env_parse("REPRESENCE_MPD", true).then(|| Self {
+            address: env_var("REPRESENCE_MPD_ADDRESS").unwrap_or_else(|| DEFAULT_MPD_ADDRESS.to_string()),
+            password: env_var("REPRESENCE_MPD_PASSWORD"),
+        })
+    }
+}
+
+/// Ask MPD over its TCP protocol for the playing song, `None` while stopped or paused
+pub async fn query_mpd(config: &MpdConfig) -> std::io::Result<Option<Track>> {
+    let stream = TcpStream::connect(&config.address).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // The server greets with `OK MPD <version>`
+    expect_ok(&mut lines).await?;
+    if let Some(password) = &config.password {
+        writer.write_all(format!("password \"{}\"\n", password.replace('\\', "\\\\").replace('"', "\\\"")).as_bytes()).await?;
+        response(&mut lines).await?;
+    }
+
+    writer.write_all(b"status\n").await?;
+    let status = response(&mut lines).await?;
+    if field(&status, "state") != Some("play") {
+        return Ok(None);
+    }
+
+    writer.write_all(b"currentsong\n").await?;
+    let song = response(&mut lines).await?;
+    let _ = writer.write_all(b"close\n").await;
+
+    // Radio streams only have the station name
+    let title = field(&song, "Title")
+        .or_else(|| field(&song, "Name"))
+        .or_else(|| field(&song, "file").map(file_stem));
+    Ok(title.map(|title| Track::new(title, field(&song, "Artist").map(str::to_string))))
+}
+
+/// Lines of one response up to `OK`, an `ACK` error fails
+async fn response(lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>) -> std::io::Result<Vec<String>> {
+    let mut response = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        if line == "OK" {
+            return Ok(response);
+        }
+        if line.starts_with("ACK") {
+            return Err(std::io::Error::other(line));
+        }
+        response.push(line);
+    }
+    Err(std::io::ErrorKind::UnexpectedEof.into())
+}
+
+async fn expect_ok(lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>) -> std::io::Result<()> {
+    match lines.next_line().await? {
+        Some(line) if line.starts_with("OK MPD") => Ok(()),
+        _ => Err(std::io::Error::other("not an MPD server")),
+    }
+}
+
+/// Value of a `key: value` line
+fn field<'a>(lines: &'a [String], key: &str) -> Option<&'a str> {
+    lines.iter().find_map(|line| line.strip_prefix(key)?.strip_prefix(": ")).filter(|value| !value.is_empty())
+}
+
+fn file_stem(path: &str) -> &str {
+    Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(path)
+}
+
+/// Parse `cmus-remote -Q`, `None` unless it's playing
+pub fn parse_cmus(output: &str) -> Option<Track> {
+    let value = |key: &str| output.lines().find_map(|line| line.strip_prefix(key)).filter(|value| !value.is_empty());
+    if value("status ") != Some("playing") {
+        return None;
+    }
+    let title = value("tag title ").or_else(|| value("stream ")).or_else(|| value("file ").map(file_stem))?;
+    Some(Track::new(title, value("tag artist ").map(str::to_string)))
+}
+
+async fn query_cmus() -> Option<Track> {
+    let output = tokio::time::timeout(Duration::from_secs(QUERY_TIMEOUT_SECS), Command::new("cmus-remote").arg("-Q").output())
+        .await
+        .ok()?
+        .ok()?;
+    parse_cmus(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Poll MPD, hiding it from detection while it isn't playing
+pub fn spawn_mpd(config: MpdConfig, now_playing: NowPlaying) {
+    tokio::spawn(async move {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(QUERY_TIMEOUT_SECS), query_mpd(&config)).await {
+                Ok(Ok(track)) => {
+                    now_playing.set_idle("mpd", track.is_none()).await;
+                    now_playing.set("mpd", "MPD", track).await;
+                }
+                // Not reachable, so nothing is known about a running mpd
+                _ => {
+                    now_playing.set_idle("mpd", false).await;
+                    now_playing.set("mpd", "MPD", None).await;
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+/// Poll `cmus-remote` while cmus is running
+pub fn spawn_cmus(diagnostics: SharedDiagnostics, now_playing: NowPlaying) {
+    tokio::spawn(async move {
+        loop {
+            let track = match now_playing::is_running(&diagnostics, "cmus").await {
+                true => query_cmus().await,
+                false => None,
+            };
+            now_playing.set("cmus", "cmus", track).await;
+            tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        }
+    });
+}
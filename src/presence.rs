@@ -0,0 +1,1073 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+#[cfg(feature = "otel")]
+use opentelemetry::KeyValue;
+
+use crate::aliases::Aliases;
+use crate::blocklist::Blocklist;
+use crate::calendar::Calendar;
+use crate::compose::{self, Composer};
+use crate::consumers::{EnergySaving, SharedConsumers};
+use crate::control::{self, Control, Controls};
+use crate::device::DeviceInfo;
+use crate::diagnostics::{self, SharedDiagnostics};
+use crate::error::RepresenceError;
+use crate::film_log::SharedLastWatched;
+use crate::github::SharedIdleActivity;
+use crate::live::SharedLive;
+use crate::modules::{self, Modules};
+use crate::history::{self, SharedHistory};
+use crate::icons::{Activity, Icons};
+use crate::i18n::Message;
+use crate::idle_text::IdleRules;
+use crate::now_playing::NowPlaying;
+use crate::phrases::Phrases;
+use crate::priority::{PriorityResolver, TierResolver};
+use crate::profiles::Profiles;
+use crate::process_finder::{get_running_apps_optimized, AppInstances, ProcessCache, ProcessSource, RunningApp, TieredApp};
+use crate::remote::{SharedRemote, SshHosts};
+use crate::script::{ScriptApp, ScriptContext, TextScript};
+use crate::sleep::{SleepState, ASLEEP_TEXT};
+use crate::streaks::{self, SharedStreak};
+use crate::systemd::Watchdog;
+#[cfg(feature = "otel")]
+use crate::telemetry::{self, Measurement, Stage};
+use crate::terminal::TerminalInspector;
+use crate::uptime::MinUptime;
+use crate::variants::DayVariants;
+use crate::vscode_client::{EditorSource, FileInfo};
+use crate::web_server::{self, Broadcaster, SharedData};
+use crate::OutputData;
+
+// Adaptive timing constants for better responsiveness
+const FAST_UPDATE_INTERVAL_SECS: u64 = 1; // When changes detected
+const SLOW_UPDATE_INTERVAL_SECS: u64 = 3; // When idle
+const IDLE_THRESHOLD_COUNT: u32 = 3; // Switch to slow mode after 3 unchanged cycles
+
+/// Media server streams and scrobbles rank with the local media players
+const STANDALONE_TIER: u32 = 3;
+
+/// Shown while the process list is unavailable and nothing was pushed yet
+const PUSH_ONLY_TEXT: &str = "offline";
+
+/// The apps represence looks for, highest priority first
+pub fn default_apps() -> Vec<TieredApp> {
+    vec![
+        // Tier 1 - The ones you wanna flex the most
+        TieredApp { name: "code".to_string(), tier: 1 },
+        TieredApp { name: "discord".to_string(), tier: 1 },
+
+        // Tier 2 - The apps that you'll use in your off-days (and sometimes on your work days)
+        TieredApp { name: "zen".to_string(), tier: 2 },
+        TieredApp { name: "chrome".to_string(), tier: 2 },
+        TieredApp { name: "steam".to_string(), tier: 2 },
+        TieredApp { name: "obs".to_string(), tier: 2 },
+        TieredApp { name: "ssh".to_string(), tier: 2 },
+
+        // Tier 3 - Less common applications
+        TieredApp { name: "vlc".to_string(), tier: 3 },
+        TieredApp { name: "stremio".to_string(), tier: 3 },
+        TieredApp { name: "spotify".to_string(), tier: 3 },
+        TieredApp { name: "cmus".to_string(), tier: 3 },
+        TieredApp { name: "mpd".to_string(), tier: 3 },
+
+        // Tier 4 - Terminal emulators
+        TieredApp { name: "ghostty".to_string(), tier: 4 },
+    ]
+}
+
+/// Check if VS Code is running (optimized)
+fn is_vscode_running(apps: &[RunningApp]) -> bool {
+    apps.iter().any(|app| app.name.starts_with("code"))
+}
+
+/// Generate text for an application based on its type and context (optimized with string interpolation)
+pub fn generate_app_text(app: &RunningApp, vscode_file_info: Option<&FileInfo>) -> String {
+    app_message(app, vscode_file_info).to_string()
+}
+
+/// The built-in text for an app as a template, for translations
+pub fn app_message(app: &RunningApp, vscode_file_info: Option<&FileInfo>) -> Message {
+    let template = match app.name.as_str() {
+        name if name.starts_with("code") => {
+            match vscode_file_info {
+                Some(file_info) => return Message::new("editing {file} in Visual Studio Code").with_arg("file", file_info.file_name.clone()),
+                None => "VS Code",
+            }
+        }
+        name if name.starts_with("zen") => "browsing with Zen browser",
+        name if name.starts_with("chrome") => "probably on her work account on Chrome",
+        name if name.starts_with("discord") => "yapping on Discord",
+        name if name.starts_with("steam") => "gaming on Steam",
+        name if name.starts_with("obs") => "setting up scenes in OBS",
+        name if name.starts_with("vlc") => "watching a movie (will probably log it in letterboxd/bilgi42",
+        name if name.starts_with("stremio") => "legally streaming some content in stremio",
+        name if name.starts_with("spotify") => "listening to music on Spotify",
+        name if name.starts_with("cmus") => "listening to music in cmus",
+        name if name.starts_with("mpd") => "listening to music with MPD",
+        name if name.starts_with("ghostty") => "using the best terminal emulator (ghostty)",
+        _ => return Message::new("{app}").with_arg("app", app.display_name().to_string()),
+    };
+    Message::new(template)
+}
+
+/// Detection → text generation → broadcast, one scan per `step`
+pub struct Pipeline<S: ProcessSource, E: EditorSource> {
+    source: S,
+    editor_source: Option<E>,
+    apps_to_check: Vec<TieredApp>,
+    blocklist: Blocklist,
+    min_uptime: MinUptime,
+    /// When each app was first seen, for sources that don't know start times
+    first_seen: HashMap<String, tokio::time::Instant>,
+    shared_data: SharedData,
+    broadcaster: Broadcaster,
+    diagnostics: SharedDiagnostics,
+    history: Option<SharedHistory>,
+    editor: watch::Sender<Option<FileInfo>>,
+    process_cache: ProcessCache,
+    last_vscode_check: SystemTime,
+    cached_vscode_info: Option<FileInfo>,
+    idle_count: u32,
+    last_output_text: String,
+    /// Where and how often systemd wants a ping, `None` without `WatchdogSec`
+    watchdog: Option<Watchdog>,
+    last_history_heartbeat: Instant,
+    scan_failing: bool,
+    /// Update cycles so far, for error reports
+    cycle: u64,
+    device: Option<DeviceInfo>,
+    phrases: Phrases,
+    composer: Composer,
+    priority: Box<dyn PriorityResolver>,
+    text_script: Option<Box<dyn TextScript>>,
+    icons: Icons,
+    current_app: Option<String>,
+    terminal: Option<TerminalInspector>,
+    ssh_hosts: SshHosts,
+    remote: Option<SharedRemote>,
+    now_playing: Option<NowPlaying>,
+    last_watched: Option<SharedLastWatched>,
+    live: Option<SharedLive>,
+    idle_activity: Option<SharedIdleActivity>,
+    calendar: Option<Calendar>,
+    streak: Option<SharedStreak>,
+    /// Detectors turned off at runtime are left out
+    modules: Modules,
+    idle_rules: IdleRules,
+    day_variants: DayVariants,
+    aliases: Aliases,
+    asleep: Option<SleepState>,
+    energy_saving: Option<EnergySaving>,
+    saving_energy: bool,
+    consumers: Option<SharedConsumers>,
+    settle_window: Duration,
+    /// A change is being held back for the settle window
+    settling: bool,
+    /// What was published last, to also republish changes beside the text
+    last_published: Option<Arc<OutputData>>,
+    controls: Option<Controls>,
+    profiles: Profiles,
+    /// Text published instead of the presence while privacy mode is on
+    privacy: Option<String>,
+    /// Text published instead of the detected presence until it runs out
+    overridden: Option<(String, tokio::time::Instant)>,
+}
+
+impl<S: ProcessSource, E: EditorSource> Pipeline<S, E> {
+    /// `editor_source: None` never asks an editor which file is open
+    pub fn new(
+        source: S,
+        editor_source: Option<E>,
+        shared_data: SharedData,
+        broadcaster: Broadcaster,
+        diagnostics: SharedDiagnostics,
+        history: Option<SharedHistory>,
+        editor: watch::Sender<Option<FileInfo>>,
+    ) -> Self {
+        let process_cache = ProcessCache::new(source.cache_ttl());
+        Self {
+            source,
+            editor_source,
+            apps_to_check: default_apps(),
+            blocklist: Blocklist::default(),
+            min_uptime: MinUptime::default(),
+            first_seen: HashMap::new(),
+            shared_data,
+            broadcaster,
+            diagnostics,
+            history,
+            editor,
+            process_cache,
+            last_vscode_check: SystemTime::UNIX_EPOCH,
+            cached_vscode_info: None,
+            idle_count: 0,
+            last_output_text: String::new(),
+            watchdog: Watchdog::from_env(),
+            last_history_heartbeat: Instant::now(),
+            scan_failing: false,
+            cycle: 0,
+            device: None,
+            phrases: Phrases::default(),
+            composer: Composer::default(),
+            priority: Box::new(TierResolver),
+            text_script: None,
+            icons: Icons::default(),
+            current_app: None,
+            terminal: None,
+            ssh_hosts: SshHosts::default(),
+            remote: None,
+            now_playing: None,
+            last_watched: None,
+            live: None,
+            idle_activity: None,
+            calendar: None,
+            streak: None,
+            modules: Modules::default(),
+            idle_rules: IdleRules::default(),
+            day_variants: DayVariants::default(),
+            aliases: Aliases::default(),
+            asleep: None,
+            energy_saving: None,
+            saving_energy: false,
+            consumers: None,
+            settle_window: Duration::ZERO,
+            settling: false,
+            last_published: None,
+            controls: None,
+            profiles: Profiles::default(),
+            privacy: None,
+            overridden: None,
+        }
+    }
+
+    /// Hide processes from detection, always or during time windows
+    pub fn with_blocklist(mut self, blocklist: Blocklist) -> Self {
+        self.blocklist = blocklist;
+        self
+    }
+
+    /// Only report apps once they have been running for a while
+    pub fn with_min_uptime(mut self, min_uptime: MinUptime) -> Self {
+        self.min_uptime = min_uptime;
+        self
+    }
+
+    /// Attach device metadata to every published presence
+    pub fn with_device(mut self, device: Option<DeviceInfo>) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Use configured phrase pools instead of the built-in texts where available
+    pub fn with_phrases(mut self, phrases: Phrases) -> Self {
+        self.phrases = phrases;
+        self
+    }
+
+    /// Replace the built-in rules for combining the top app with a secondary one
+    pub fn with_composer(mut self, composer: Composer) -> Self {
+        self.composer = composer;
+        self
+    }
+
+    /// Ping the service manager at least this often, even while saving energy
+    pub fn with_watchdog(mut self, watchdog: Option<Watchdog>) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Decide which running app is shown, tiers by default
+    pub fn with_priority(mut self, priority: Box<dyn PriorityResolver>) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Let a script rewrite the generated text
+    pub fn with_text_script(mut self, text_script: Option<Box<dyn TextScript>>) -> Self {
+        self.text_script = text_script;
+        self
+    }
+
+    /// Emoji and icon images published with the text
+    pub fn with_icons(mut self, icons: Icons) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    /// Report the command running inside a terminal emulator when it's the app shown
+    pub fn with_terminal_inspector(mut self, terminal: Option<TerminalInspector>) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Name allowed SSH hosts and show what a remote agent pushed during an SSH session
+    pub fn with_remote(mut self, ssh_hosts: SshHosts, remote: Option<SharedRemote>) -> Self {
+        self.ssh_hosts = ssh_hosts;
+        self.remote = remote;
+        self
+    }
+
+    /// Show the track reported by player integrations while their app is shown
+    pub fn with_now_playing(mut self, now_playing: Option<NowPlaying>) -> Self {
+        self.now_playing = now_playing;
+        self
+    }
+
+    /// Include the last film logged on Letterboxd or Trakt in every presence
+    pub fn with_last_watched(mut self, last_watched: Option<SharedLastWatched>) -> Self {
+        self.last_watched = last_watched;
+        self
+    }
+
+    /// Show a live stream instead of any app while it lasts
+    pub fn with_live(mut self, live: Option<SharedLive>) -> Self {
+        self.live = live;
+        self
+    }
+
+    /// Show recent activity from elsewhere, like a GitHub push, instead of `idle`
+    pub fn with_idle_activity(mut self, idle_activity: Option<SharedIdleActivity>) -> Self {
+        self.idle_activity = idle_activity;
+        self
+    }
+
+    /// Show a meeting message during busy calendar events instead of any app
+    pub fn with_calendar(mut self, calendar: Option<Calendar>) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
+    /// Mention the coding streak while coding
+    pub fn with_streak(mut self, streak: Option<SharedStreak>) -> Self {
+        self.streak = streak;
+        self
+    }
+
+    /// Registry of detectors that can be turned off while running
+    pub fn with_modules(mut self, modules: Modules) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Time-of-day texts like `probably sleeping` instead of `idle`
+    pub fn with_idle_rules(mut self, idle_rules: IdleRules) -> Self {
+        self.idle_rules = idle_rules;
+        self
+    }
+
+    /// Different texts for an app on workdays and weekends
+    pub fn with_day_variants(mut self, day_variants: DayVariants) -> Self {
+        self.day_variants = day_variants;
+        self
+    }
+
+    /// Display names and kinds for executables, and tiers for apps that aren't built in
+    pub fn with_aliases(mut self, aliases: Aliases) -> Self {
+        self.set_aliases(aliases);
+        self
+    }
+
+    fn set_aliases(&mut self, aliases: Aliases) {
+        self.apps_to_check = aliases.apps(default_apps());
+        self.aliases = aliases;
+        // Processes already matched against the old app list get matched again
+        self.process_cache = ProcessCache::new(self.source.cache_ttl());
+    }
+
+    /// Publish `asleep` before a suspend and scan from scratch after resuming
+    pub fn with_sleep(mut self, asleep: Option<SleepState>) -> Self {
+        self.asleep = asleep;
+        self
+    }
+
+    /// Scan slowly, or only on request, while nobody reads the presence
+    pub fn with_energy_saving(mut self, energy_saving: Option<EnergySaving>) -> Self {
+        self.energy_saving = energy_saving;
+        self
+    }
+
+    /// Scan right away when a consumer asks for fresh presence
+    pub fn with_consumers(mut self, consumers: Option<SharedConsumers>) -> Self {
+        self.consumers = consumers;
+        self
+    }
+
+    /// Wait this long after a change for other detectors to catch up before broadcasting it
+    pub fn with_settle_window(mut self, settle_window: Duration) -> Self {
+        self.settle_window = settle_window;
+        self
+    }
+
+    /// Profiles to switch between, the active one stays on top of reloads
+    pub fn with_profiles(mut self, profiles: Profiles) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Start in privacy mode with `Some(text)`
+    pub fn with_privacy(mut self, privacy: Option<String>) -> Self {
+        self.privacy = privacy;
+        self
+    }
+
+    /// Take commands like reloads and rescans from signals, handled between scans
+    pub fn with_controls(mut self, controls: Option<Controls>) -> Self {
+        self.controls = controls;
+        self
+    }
+
+    /// Re-read the settings that can change without a restart
+    fn reload_settings(&mut self) {
+        self.blocklist = Blocklist::from_env();
+        self.min_uptime = MinUptime::from_env();
+        self.device = DeviceInfo::from_env();
+        self.phrases = Phrases::from_env();
+        self.composer = Composer::from_env();
+        self.icons = Icons::from_env();
+        self.ssh_hosts = SshHosts::from_env();
+        self.idle_rules = IdleRules::from_env();
+        self.day_variants = DayVariants::from_env();
+        self.set_aliases(Aliases::from_env());
+    }
+
+    /// Apply `control`, the next scan shows the result
+    pub fn control(&mut self, control: Control) {
+        match control {
+            Control::Reload => {
+                self.profiles.reload();
+                self.reload_settings();
+                if self.privacy.is_some() {
+                    self.privacy = Some(control::privacy_text());
+                }
+                println!("Reloaded configuration");
+            }
+            Control::SwitchProfile(name) => match self.profiles.activate(name.as_deref()) {
+                Ok(()) => {
+                    self.reload_settings();
+                    // Privacy mode turned on by hand stays on, unless the profile decides
+                    self.privacy = match self.profiles.sets("REPRESENCE_PRIVACY") {
+                        true => control::privacy_from_env(),
+                        false => self.privacy.as_ref().map(|_| control::privacy_text()),
+                    };
+                    println!("Switched to profile {}", name.as_deref().unwrap_or("(none)"));
+                }
+                Err(e) => eprintln!("Not switching profiles: {}", e),
+            },
+            Control::TogglePrivacy => {
+                self.privacy = match self.privacy {
+                    Some(_) => None,
+                    None => Some(control::privacy_text()),
+                };
+                match self.privacy {
+                    Some(_) => println!("Privacy mode on, hiding the presence"),
+                    None => println!("Privacy mode off"),
+                }
+            }
+            Control::SetPrivacy(on) => {
+                self.privacy = on.then(control::privacy_text);
+                match on {
+                    true => println!("Privacy mode on, hiding the presence"),
+                    false => println!("Privacy mode off"),
+                }
+            }
+            Control::Rescan => println!("Rescanning"),
+            Control::Override { text, duration } => {
+                println!("Showing '{}' for {}s", text, duration.as_secs());
+                self.overridden = Some((text, tokio::time::Instant::now() + duration));
+            }
+            Control::EndOverride => {
+                if self.overridden.take().is_some() {
+                    println!("Override ended");
+                }
+            }
+        }
+        // Scan from scratch and broadcast even when the presence stays the same
+        self.process_cache = ProcessCache::new(self.source.cache_ttl());
+        self.cached_vscode_info = None;
+        self.last_vscode_check = SystemTime::UNIX_EPOCH;
+        self.last_published = None;
+    }
+
+    /// Run forever with adaptive polling
+    pub async fn run(mut self) {
+        loop {
+            if self.asleep.as_ref().is_some_and(|asleep| *asleep.borrow()) {
+                self.sleep_until_resume().await;
+            }
+
+            let cycle_start = Instant::now();
+            let sleep_duration = self.step().await;
+            if let (Some(consumers), false) = (&self.consumers, self.settling) {
+                consumers.scan_finished();
+            }
+            let sleep_duration = self.wait(sleep_duration).await;
+
+            // Time spent scanning plus any oversleep from a busy runtime
+            let lag = cycle_start.elapsed().saturating_sub(sleep_duration);
+            self.diagnostics.write().await.update_loop_lag_ms = lag.as_millis() as u64;
+        }
+    }
+
+    /// Scan once, publish any change and return how long to wait before the next scan
+    pub async fn step(&mut self) -> Duration {
+        self.cycle += 1;
+        #[cfg(feature = "otel")]
+        return telemetry::measure(Stage::ScanCycle, vec![KeyValue::new("represence.cycle", self.cycle as i64)], self.scan()).await;
+        #[cfg(not(feature = "otel"))]
+        self.scan().await
+    }
+
+    async fn scan(&mut self) -> Duration {
+        let (mut running_apps, processes_changed) = match get_running_apps_optimized(&self.source, &self.apps_to_check, &self.blocklist, &mut self.process_cache).await {
+            Ok(result) => {
+                if self.scan_failing {
+                    println!("Process scan works again, leaving push-only mode");
+                    self.diagnostics.write().await.push_only = false;
+                    self.scan_failing = false;
+                }
+                result
+            }
+            Err(e) => {
+                // Log once per outage rather than every cycle
+                if !self.scan_failing {
+                    eprintln!("[{}] {}", e.kind(), e);
+                    #[cfg(feature = "sentry")]
+                    crate::error_reporting::report("process_scan", self.cycle, &e);
+                    eprintln!("Falling back to push-only mode: POST presence to /api/represence with REPRESENCE_ADMIN_TOKEN, or run represence where the apps are as a hub agent");
+                    self.scan_failing = true;
+                    self.start_push_only().await;
+                }
+                return self.push_only_step().await;
+            }
+        };
+
+        self.aliases.apply(&mut running_apps);
+        let running_apps = self.hide_young_apps(running_apps);
+        let running_apps = self.hide_idle_players(running_apps).await;
+        let running_apps = self.add_standalone_players(running_apps).await;
+        let running_apps = self.priority.resolve(running_apps, self.current_app.as_deref()).await;
+        self.current_app = running_apps.first().map(|app| app.name.clone());
+
+        // Adaptive VSCode checks - faster when VSCode is running
+        let mut vscode_file_info: Option<FileInfo> = None;
+        let vscode_running = is_vscode_running(&running_apps) && self.modules.enabled(modules::VSCODE);
+        let mut vscode_result: Option<Result<(), RepresenceError>> = None;
+
+        if let (true, Some(editor_source)) = (vscode_running, &self.editor_source) {
+            let should_check_vscode = self.last_vscode_check.elapsed()
+                .unwrap_or(Duration::MAX) >= editor_source.check_interval();
+
+            if should_check_vscode {
+                #[cfg(feature = "otel")]
+                let round_trip = Measurement::start(Stage::VsCode);
+                // Use timeout for VSCode connection to prevent hanging
+                match tokio::time::timeout(
+                    Duration::from_secs(1), // Reduced timeout for faster response
+                    editor_source.file_info()
+                ).await {
+                    Ok(Ok(file_info)) => {
+                        self.cached_vscode_info = Some(file_info.clone());
+                        vscode_file_info = Some(file_info);
+                        self.last_vscode_check = SystemTime::now();
+                        vscode_result = Some(Ok(()));
+                    }
+                    Ok(Err(e)) => {
+                        // Use cached info if available, otherwise fallback
+                        vscode_file_info = self.cached_vscode_info.clone();
+                        vscode_result = Some(Err(e));
+                    }
+                    Err(_) => {
+                        vscode_file_info = self.cached_vscode_info.clone();
+                        vscode_result = Some(Err(RepresenceError::Timeout("VS Code")));
+                    }
+                }
+                #[cfg(feature = "otel")]
+                round_trip.finish(&[KeyValue::new("outcome", match &vscode_result {
+                    Some(Err(e)) => e.kind(),
+                    _ => "ok",
+                })]);
+            } else {
+                // Use cached VSCode info
+                vscode_file_info = self.cached_vscode_info.clone();
+            }
+        } else {
+            // Clear cached VSCode info if VSCode is not running
+            self.cached_vscode_info = None;
+        }
+
+        // What runs inside the terminal while it's the app shown
+        let terminal_command = match (running_apps.first(), &self.terminal) {
+            (Some(app), Some(terminal)) if compose::app_category(app) == Some("terminal") => {
+                terminal.foreground_command(&app.name).await
+            }
+            _ => None,
+        };
+
+        // Where an SSH session in front is connected to
+        let remote_text = match running_apps.first().and_then(|app| app.remote_host.as_deref()) {
+            Some(host) => {
+                let remote = match &self.remote {
+                    Some(remote) => remote.read().await.clone(),
+                    None => None,
+                };
+                Some(self.ssh_hosts.text(host, remote.as_ref(), tokio::time::Instant::now()))
+            }
+            None => None,
+        };
+
+        // Track playing in the app shown
+        let playing = match (running_apps.first(), self.now_playing()) {
+            (Some(app), Some(now_playing)) => now_playing.for_app(&app.name).await,
+            _ => None,
+        };
+        let (playing_text, album_art) = match playing {
+            Some((text, track)) => (Some(text), track.album_art),
+            None => (None, None),
+        };
+
+        let live = match &self.live {
+            Some(live) if self.modules.enabled(modules::TWITCH) => live.read().await.clone(),
+            _ => None,
+        };
+        let meeting = match &self.calendar {
+            Some(calendar) if self.modules.enabled(modules::CALENDAR) => calendar.busy_text(chrono::Local::now()).await,
+            _ => None,
+        };
+        let idle_activity = match (&self.idle_activity, running_apps.is_empty()) {
+            (Some(activity), true) if self.modules.enabled(modules::GITHUB) => activity.read().await.clone(),
+            _ => None,
+        };
+
+        let icon = self.icons.icon(match (&live, &meeting, running_apps.first()) {
+            (Some(_), _, _) => Activity::Live,
+            (None, Some(_), _) => Activity::Meeting,
+            (None, None, Some(app)) => Activity::App(app),
+            (None, None, None) => Activity::Idle,
+        });
+
+        // Everything a text script gets to see, gathered before the text chain below consumes it
+        let script_context = self.text_script.as_ref().map(|_| ScriptContext {
+            apps: running_apps.iter().map(ScriptApp::from).collect(),
+            file: vscode_file_info.clone(),
+            terminal_command: terminal_command.clone(),
+            remote: remote_text.clone(),
+            playing: playing_text.clone(),
+            meeting: meeting.clone(),
+            live: live.as_ref().map(|stream| stream.text()),
+            idle_activity: idle_activity.clone(),
+            ..ScriptContext::now()
+        });
+
+        // What visitors without a friend token see instead, the app without its file, command, host or track
+        let detailed = vscode_file_info.is_some() || terminal_command.is_some() || remote_text.is_some() || playing_text.is_some();
+        let coarse_text = match (&live, &meeting, running_apps.first()) {
+            (None, None, Some(app)) if detailed => {
+                Some(self.day_variants.text(&app.name).map_or_else(|| generate_app_text(app, None), str::to_string))
+            }
+            _ => None,
+        };
+
+        let streak_text = match (&self.streak, &live, &meeting, running_apps.first()) {
+            (Some(streak), None, None, Some(app)) if compose::app_category(app) == Some("editor") => streaks::text(*streak.read().await),
+            _ => None,
+        };
+
+        // Generate output text for the most relevant application, a live stream and then meetings beat everything
+        self.phrases.retain_running(&running_apps);
+        let output_text = match (&live, meeting, running_apps.first()) {
+            (Some(stream), _, _) => stream.text(),
+            (None, Some(meeting), _) => meeting,
+            (None, None, Some(app)) => self.composer.compose(&running_apps, vscode_file_info.as_ref())
+                .or_else(|| terminal_command.map(|command| format!("running {} in {}", command, app.display_name())))
+                .or(remote_text)
+                .or(playing_text)
+                .or_else(|| self.day_variants.text(&app.name).map(str::to_string))
+                .or_else(|| self.phrases.phrase(app, vscode_file_info.as_ref()))
+                .unwrap_or_else(|| generate_app_text(app, vscode_file_info.as_ref())),
+            (None, None, None) => idle_activity
+                .or_else(|| self.idle_rules.text().map(str::to_string))
+                .unwrap_or_else(|| "idle".to_string()),
+        };
+        let (output_text, coarse_text) = match streak_text {
+            Some(streak_text) => (
+                format!("{} ({})", output_text, streak_text),
+                coarse_text.map(|coarse_text| format!("{} ({})", coarse_text, streak_text)),
+            ),
+            None => (output_text, coarse_text),
+        };
+        let output_text = match (&self.text_script, script_context) {
+            (Some(script), Some(context)) => script.text(&ScriptContext { text: output_text.clone(), ..context }).unwrap_or(output_text),
+            _ => output_text,
+        };
+        // Built-in texts keep their template for translations, anything else is translated by its exact text
+        let message = match running_apps.first() {
+            Some(app) => app_message(app, vscode_file_info.as_ref()),
+            None => Message::new("idle"),
+        };
+        let message = (message.to_string() == output_text).then_some(message);
+        // Always set, so a pushed presence without it (from an older agent) can be told apart and kept from the public
+        let coarse_text = Some(coarse_text.unwrap_or_else(|| output_text.clone()));
+
+        // Publish the file being edited while VS Code is the app shown
+        let editing = match (running_apps.first(), &vscode_file_info) {
+            (Some(app), Some(file_info)) if app.name.starts_with("code") => Some(file_info.clone()),
+            _ => None,
+        };
+        self.editor.send_if_modified(|current| {
+            let modified = *current != editing;
+            *current = editing;
+            modified
+        });
+
+        let last_watched = match &self.last_watched {
+            Some(last_watched) if self.modules.enabled(modules::FILM_LOG) => last_watched.read().await.clone(),
+            _ => None,
+        };
+
+        let output = OutputData {
+            text: output_text.clone(),
+            device: self.device.clone(),
+            album_art,
+            last_watched,
+            link: live.map(|stream| stream.url),
+            emoji: icon.emoji,
+            icon: icon.icon,
+            color: icon.color,
+            message,
+            coarse_text,
+            apps: running_apps.iter().map(AppInstances::from).collect(),
+            stale: false,
+        };
+        if self.overridden.as_ref().is_some_and(|(_, until)| tokio::time::Instant::now() >= *until) {
+            println!("Override ran out");
+            self.overridden = None;
+        }
+        // Privacy mode publishes one fixed text whatever is detected, an override another one
+        let fixed = self.privacy.as_ref().or(self.overridden.as_ref().map(|(text, _)| text));
+        let shown_fixed = fixed.is_some();
+        let (output_text, output) = match fixed {
+            Some(text) => (text.clone(), OutputData { device: self.device.clone(), ..OutputData::new(text.clone()) }),
+            None => (output_text, output),
+        };
+
+        // Check if output actually changed, extra fields are republished without counting as a transition
+        let output_changed = output_text != self.last_output_text;
+        let details_changed = self.last_published.as_deref() != Some(&output);
+
+        // Hold a change back briefly and scan again, so detectors catching up with each other
+        // (a new process, then its VS Code file) end up in one broadcast
+        if (output_changed || details_changed) && !self.settle_window.is_zero() && !self.settling {
+            self.settling = true;
+            self.last_vscode_check = SystemTime::UNIX_EPOCH;
+            return self.settle_window;
+        }
+        self.settling = false;
+
+        // Record what the pipeline saw this cycle for the dashboard
+        {
+            let mut diag = self.diagnostics.write().await;
+            diag.detected_apps = running_apps.iter()
+                .map(|app| diagnostics::DetectedApp { name: app.name.clone(), tier: app.tier, instances: app.instances })
+                .collect();
+            diag.last_scan = Some(chrono::Utc::now());
+            diag.privacy = self.privacy.is_some();
+            if !self.scan_failing {
+                diag.last_successful_scan = diag.last_scan;
+            }
+            diag.vscode.running = vscode_running;
+            match vscode_result {
+                Some(Ok(())) => {
+                    diag.vscode.connected = true;
+                    diag.vscode.last_contact = Some(chrono::Utc::now());
+                    diag.vscode.last_error = None;
+                    diag.vscode.last_error_kind = None;
+                }
+                Some(Err(e)) => {
+                    // Report once until the extension answers again
+                    #[cfg(feature = "sentry")]
+                    if diag.vscode.last_error.is_none() {
+                        crate::error_reporting::report("vscode", self.cycle, &e);
+                    }
+                    diag.vscode.connected = false;
+                    diag.vscode.last_error = Some(e.to_string());
+                    diag.vscode.last_error_kind = Some(e.kind());
+                }
+                None if !vscode_running => diag.vscode.connected = false,
+                None => {}
+            }
+            if output_changed {
+                diag.record_transition(&output_text);
+            }
+        }
+
+        // Persist transitions for the history API, heartbeats keep the ongoing event's end current
+        if let Some(history) = self.history.clone() {
+            if output_changed {
+                let text = output_text.clone();
+                // The public summaries are built from these, a fixed text must not give away what's behind it
+                let app = running_apps.first().filter(|_| !shown_fixed).map(|app| app.name.clone());
+                let language = self.editor.borrow().as_ref().filter(|_| !shown_fixed).map(|file_info| file_info.language_id.clone());
+                self.last_history_heartbeat = Instant::now();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = history.record(chrono::Utc::now(), &text, app.as_deref(), language.as_deref()) {
+                        eprintln!("Failed to record history: {}", e);
+                    }
+                });
+            } else if self.last_history_heartbeat.elapsed() >= Duration::from_secs(history::HEARTBEAT_INTERVAL_SECS) {
+                self.last_history_heartbeat = Instant::now();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = history.heartbeat(chrono::Utc::now()) {
+                        eprintln!("Failed to record history heartbeat: {}", e);
+                    }
+                });
+            }
+        }
+
+        if output_changed || details_changed {
+            self.last_output_text = output_text;
+            self.idle_count = 0; // Reset idle counter on change
+
+            // Swap in the new snapshot and broadcast it, readers never wait on this
+            let output = Arc::new(output);
+            self.last_published = Some(output.clone());
+            web_server::publish(&self.shared_data, &self.broadcaster, output);
+        } else if processes_changed {
+            // Processes changed but output is the same, reset idle counter
+            self.idle_count = 0;
+        } else {
+            // No changes detected
+            self.idle_count += 1;
+        }
+
+        // Let systemd know the update loop is still making progress
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.ping();
+        }
+
+        // Adaptive sleep timing based on activity
+        if self.idle_count >= IDLE_THRESHOLD_COUNT {
+            Duration::from_secs(SLOW_UPDATE_INTERVAL_SECS) // Slow polling when idle
+        } else {
+            Duration::from_secs(FAST_UPDATE_INTERVAL_SECS) // Fast polling when active
+        }
+    }
+
+    /// Drop apps that haven't been running for their minimum uptime yet
+    fn hide_young_apps(&mut self, running_apps: Vec<RunningApp>) -> Vec<RunningApp> {
+        if self.min_uptime.is_zero() {
+            return running_apps;
+        }
+
+        let now = tokio::time::Instant::now();
+        self.first_seen.retain(|name, _| running_apps.iter().any(|app| &app.name == name));
+
+        running_apps
+            .into_iter()
+            .filter(|app| {
+                let required = self.min_uptime.for_app(&app.name);
+                let first_seen = *self.first_seen.entry(app.name.clone()).or_insert(now);
+                required.is_zero() || self.source.age(app.started).unwrap_or(now - first_seen) >= required
+            })
+            .collect()
+    }
+
+    /// Tracks from the player APIs, unless they're turned off
+    fn now_playing(&self) -> Option<&NowPlaying> {
+        self.now_playing.as_ref().filter(|_| self.modules.enabled(modules::NOW_PLAYING))
+    }
+
+    /// Drop background players that have nothing to play
+    async fn hide_idle_players(&self, running_apps: Vec<RunningApp>) -> Vec<RunningApp> {
+        let Some(now_playing) = self.now_playing() else {
+            return running_apps;
+        };
+        let mut playing = Vec::with_capacity(running_apps.len());
+        for app in running_apps {
+            if !now_playing.is_idle(&app.name).await {
+                playing.push(app);
+            }
+        }
+        playing
+    }
+
+    /// Count streams from media servers as running media apps, and scrobbles from elsewhere when no player runs here
+    async fn add_standalone_players(&self, mut running_apps: Vec<RunningApp>) -> Vec<RunningApp> {
+        let Some(now_playing) = self.now_playing() else {
+            return running_apps;
+        };
+        let mut names = now_playing.standalone_apps().await;
+        let player_running = running_apps.iter().any(|app| compose::app_category(app) == Some("media"))
+            || names.iter().any(|name| compose::category(name) == Some("media"));
+        if !player_running {
+            names.extend(now_playing.fallback_apps().await);
+        }
+        for name in names {
+            if !running_apps.iter().any(|app| app.name == name) {
+                running_apps.push(RunningApp { name, tier: STANDALONE_TIER, started: 0, remote_host: None, instances: 1, display_name: None, kind: None });
+            }
+        }
+        // Stable, so the start time order within a tier stays
+        running_apps.sort_by_key(|app| app.tier);
+        running_apps
+    }
+
+    /// Stop claiming to know what's running, pushed presence takes over until the scan works again
+    async fn start_push_only(&mut self) {
+        let output = Arc::new(OutputData { device: self.device.clone(), ..OutputData::new(PUSH_ONLY_TEXT) });
+        self.last_output_text = output.text.clone();
+        self.last_published = Some(output.clone());
+        self.editor.send_replace(None);
+
+        let mut diag = self.diagnostics.write().await;
+        diag.push_only = true;
+        diag.detected_apps.clear();
+        diag.record_transition(&output.text);
+
+        web_server::publish(&self.shared_data, &self.broadcaster, output);
+    }
+
+    /// Sleep until the next scan, returning the planned sleep. Wakes early on suspend and resume,
+    /// on-demand scan requests, controls and, while nobody is connected, the next request.
+    async fn wait(&mut self, duration: Duration) -> Duration {
+        let saving = self.energy_saving.clone().filter(EnergySaving::active);
+        if saving.is_some() != self.saving_energy {
+            self.saving_energy = saving.is_some();
+            match self.saving_energy {
+                true => println!("Nobody is connected, saving energy until the next request"),
+                false => println!("Consumers are back, polling normally"),
+            }
+        }
+        // A held back change still goes out once the settle window closes
+        let duration = match &saving {
+            Some(saving) if !self.settling => saving.interval.unwrap_or(Duration::MAX),
+            _ => duration,
+        };
+        // Wake up when an override runs out
+        let duration = match &self.overridden {
+            Some((_, until)) => duration.min(until.saturating_duration_since(tokio::time::Instant::now())),
+            None => duration,
+        };
+        // Only while waiting, so the stale guard still catches a scan that gets stuck
+        self.diagnostics.write().await.energy_saving = saving.is_some();
+
+        // Scans may be minutes apart while saving energy, systemd still has to hear from us in between
+        let watchdog_tick = match (&saving, &self.watchdog) {
+            (Some(_), Some(watchdog)) => Some(watchdog.interval / 2),
+            _ => None,
+        };
+        let started = tokio::time::Instant::now();
+        let (suspend_watch_ended, control) = loop {
+            let left = duration.saturating_sub(started.elapsed());
+            let nap = watchdog_tick.map_or(left, |tick| left.min(tick));
+            let requested = async {
+                match &saving {
+                    Some(saving) => saving.consumers.requested().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let scan_requested = async {
+                match &self.consumers {
+                    Some(consumers) => consumers.scan_requested().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let sleep_changed = async {
+                match &mut self.asleep {
+                    Some(asleep) => asleep.changed().await.is_err(),
+                    None => std::future::pending().await,
+                }
+            };
+            let controlled = async {
+                match &mut self.controls {
+                    Some(controls) => controls.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let woke = tokio::select! {
+                _ = tokio::time::sleep(nap) => None,
+                _ = requested => Some((false, None)),
+                _ = scan_requested => Some((false, None)),
+                ended = sleep_changed => Some((ended, None)),
+                control = controlled => Some((false, Some(control))),
+            };
+            match woke {
+                Some(woke) => break woke,
+                None if nap >= left => break (false, None),
+                None => {
+                    if let Some(watchdog) = &self.watchdog {
+                        watchdog.ping();
+                    }
+                }
+            }
+        };
+        match control {
+            Some(Some(control)) => self.control(control),
+            // Every sender is gone
+            Some(None) => self.controls = None,
+            None => {}
+        }
+        // Keep polling without suspend watching once it ended
+        if suspend_watch_ended {
+            self.asleep = None;
+        }
+        if saving.is_some() {
+            let mut diag = self.diagnostics.write().await;
+            diag.energy_saving = false;
+            diag.last_wake = Some(chrono::Utc::now());
+        }
+        duration
+    }
+
+    /// Publish `asleep`, wait for the resume and forget everything cached from before the suspend
+    async fn sleep_until_resume(&mut self) {
+        let output = Arc::new(OutputData { device: self.device.clone(), ..OutputData::new(ASLEEP_TEXT) });
+        self.last_output_text = output.text.clone();
+        self.last_published = Some(output.clone());
+        self.editor.send_replace(None);
+        self.diagnostics.write().await.record_transition(&output.text);
+        if let Some(history) = self.history.clone() {
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = history.record(chrono::Utc::now(), ASLEEP_TEXT, None, None) {
+                    eprintln!("Failed to record history: {}", e);
+                }
+            });
+        }
+        web_server::publish(&self.shared_data, &self.broadcaster, output);
+
+        if let Some(asleep) = &mut self.asleep {
+            if asleep.wait_for(|asleep| !*asleep).await.is_err() {
+                self.asleep = None;
+            }
+        }
+
+        self.process_cache = ProcessCache::new(self.source.cache_ttl());
+        self.cached_vscode_info = None;
+        self.last_vscode_check = SystemTime::UNIX_EPOCH;
+        self.idle_count = 0;
+        self.last_history_heartbeat = Instant::now();
+    }
+
+    /// Keep retrying the scan slowly while presence is pushed in
+    async fn push_only_step(&mut self) -> Duration {
+        self.diagnostics.write().await.last_scan = Some(chrono::Utc::now());
+
+        // Pushed presence is recorded by the push handler, this keeps its end current
+        if let Some(history) = self.history.clone() {
+            if self.last_history_heartbeat.elapsed() >= Duration::from_secs(history::HEARTBEAT_INTERVAL_SECS) {
+                self.last_history_heartbeat = Instant::now();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = history.heartbeat(chrono::Utc::now()) {
+                        eprintln!("Failed to record history heartbeat: {}", e);
+                    }
+                });
+            }
+        }
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.ping();
+        }
+        Duration::from_secs(SLOW_UPDATE_INTERVAL_SECS)
+    }
+}
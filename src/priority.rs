@@ -0,0 +1,210 @@
+use futures_util::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::{env_parse, env_var};
+use crate::process_finder::RunningApp;
+
+/// How long a focus query or priority script may take before tiers decide alone
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Picks the app to show, by moving it to the front of the running apps
+pub trait PriorityResolver: Send + Sync {
+    /// `running_apps` come ranked by tier and start time, `current` is the app shown right now
+    fn resolve<'a>(&'a self, running_apps: Vec<RunningApp>, current: Option<&'a str>) -> BoxFuture<'a, Vec<RunningApp>>;
+}
+
+/// Which resolver `REPRESENCE_PRIORITY` selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolverKind {
+    Tier,
+    Focus,
+    Script,
+}
+
+impl std::str::FromStr for ResolverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tier" => Ok(Self::Tier),
+            "focus" => Ok(Self::Focus),
+            "script" => Ok(Self::Script),
+            _ => Err(format!("unknown resolver '{}', expected tier, focus or script", s)),
+        }
+    }
+}
+
+/// `REPRESENCE_PRIORITY=tier|focus|script`, tiers by default.
+/// The script resolver runs `REPRESENCE_PRIORITY_SCRIPT` and falls back to tiers without one.
+pub fn from_env(proc_path: &Path) -> Box<dyn PriorityResolver> {
+    match env_parse("REPRESENCE_PRIORITY", ResolverKind::Tier) {
+        ResolverKind::Tier => Box::new(TierResolver),
+        ResolverKind::Focus => Box::new(FocusResolver::new(proc_path)),
+        ResolverKind::Script => match env_var("REPRESENCE_PRIORITY_SCRIPT") {
+            Some(script) => Box::new(ScriptResolver::new(script)),
+            None => {
+                eprintln!("REPRESENCE_PRIORITY=script needs REPRESENCE_PRIORITY_SCRIPT, ranking by tier");
+                Box::new(TierResolver)
+            }
+        },
+    }
+}
+
+/// Lowest tier wins, and the app already shown keeps its place against newer apps of its tier
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TierResolver;
+
+impl TierResolver {
+    fn rank(mut running_apps: Vec<RunningApp>, current: Option<&str>) -> Vec<RunningApp> {
+        let position = current.and_then(|current| running_apps.iter().position(|app| app.name == current));
+        if let (Some(position), Some(top)) = (position, running_apps.first()) {
+            if running_apps[position].tier == top.tier {
+                let app = running_apps.remove(position);
+                running_apps.insert(0, app);
+            }
+        }
+        running_apps
+    }
+}
+
+impl PriorityResolver for TierResolver {
+    fn resolve<'a>(&'a self, running_apps: Vec<RunningApp>, current: Option<&'a str>) -> BoxFuture<'a, Vec<RunningApp>> {
+        Box::pin(async move { Self::rank(running_apps, current) })
+    }
+}
+
+/// The app owning the focused window wins, asked from Hyprland, Sway or X11 (`xdotool`). Tiers decide otherwise.
+#[derive(Debug, Clone)]
+pub struct FocusResolver {
+    proc_path: PathBuf,
+}
+
+impl FocusResolver {
+    pub fn new(proc_path: &Path) -> Self {
+        Self { proc_path: proc_path.to_path_buf() }
+    }
+
+    /// Executable name of the process owning the focused window
+    async fn focused_app(&self) -> Option<String> {
+        let pid = focused_pid().await?;
+        let exe = tokio::fs::read_link(self.proc_path.join(pid.to_string()).join("exe")).await.ok()?;
+        Some(exe.file_name()?.to_str()?.to_string())
+    }
+}
+
+impl PriorityResolver for FocusResolver {
+    fn resolve<'a>(&'a self, running_apps: Vec<RunningApp>, current: Option<&'a str>) -> BoxFuture<'a, Vec<RunningApp>> {
+        Box::pin(async move {
+            let mut running_apps = TierResolver::rank(running_apps, current);
+            if let Some(focused) = self.focused_app().await {
+                if let Some(position) = running_apps.iter().position(|app| app.name == focused) {
+                    let app = running_apps.remove(position);
+                    running_apps.insert(0, app);
+                }
+            }
+            running_apps
+        })
+    }
+}
+
+/// PID behind the focused window, from whichever compositor or X server answers
+async fn focused_pid() -> Option<u32> {
+    if let Some(output) = run(Command::new("hyprctl").args(["activewindow", "-j"])).await {
+        let window: serde_json::Value = serde_json::from_slice(&output).ok()?;
+        return window["pid"].as_u64().map(|pid| pid as u32);
+    }
+    if let Some(output) = run(Command::new("swaymsg").args(["-t", "get_tree"])).await {
+        let tree: serde_json::Value = serde_json::from_slice(&output).ok()?;
+        return focused_sway_pid(&tree);
+    }
+    let output = run(Command::new("xdotool").args(["getactivewindow", "getwindowpid"])).await?;
+    String::from_utf8_lossy(&output).trim().parse().ok()
+}
+
+fn focused_sway_pid(node: &serde_json::Value) -> Option<u32> {
+    if node["focused"].as_bool() == Some(true) {
+        return node["pid"].as_u64().map(|pid| pid as u32);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .find_map(focused_sway_pid)
+}
+
+/// Stdout of a command that succeeded within `RESOLVER_TIMEOUT`
+async fn run(command: &mut Command) -> Option<Vec<u8>> {
+    let output = tokio::time::timeout(RESOLVER_TIMEOUT, command.stderr(Stdio::null()).output()).await.ok()?.ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+/// Lets an executable order the apps: it gets `name tier instances` lines on stdin and prints app names,
+/// most important first. Apps it leaves out follow in tier order, and tiers decide alone when it fails.
+#[derive(Debug, Clone)]
+pub struct ScriptResolver {
+    script: PathBuf,
+}
+
+impl ScriptResolver {
+    pub fn new(script: impl Into<PathBuf>) -> Self {
+        Self { script: script.into() }
+    }
+
+    async fn order(&self, running_apps: &[RunningApp]) -> Option<Vec<String>> {
+        let mut child = Command::new(&self.script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| eprintln!("Failed to run priority script {}: {}", self.script.display(), e))
+            .ok()?;
+
+        let input: String = running_apps.iter().map(|app| format!("{} {} {}\n", app.name, app.tier, app.instances)).collect();
+        let mut stdin = child.stdin.take()?;
+        let output = tokio::time::timeout(RESOLVER_TIMEOUT, async move {
+            stdin.write_all(input.as_bytes()).await?;
+            drop(stdin);
+            child.wait_with_output().await
+        })
+        .await;
+
+        match output {
+            Ok(Ok(output)) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).lines().map(|line| line.trim().to_string()).collect())
+            }
+            Ok(Ok(output)) => {
+                eprintln!("Priority script {} failed with {}", self.script.display(), output.status);
+                None
+            }
+            Ok(Err(e)) => {
+                eprintln!("Priority script {} failed: {}", self.script.display(), e);
+                None
+            }
+            Err(_) => {
+                eprintln!("Priority script {} took longer than {}s", self.script.display(), RESOLVER_TIMEOUT.as_secs());
+                None
+            }
+        }
+    }
+}
+
+impl PriorityResolver for ScriptResolver {
+    fn resolve<'a>(&'a self, running_apps: Vec<RunningApp>, current: Option<&'a str>) -> BoxFuture<'a, Vec<RunningApp>> {
+        Box::pin(async move {
+            let mut running_apps = TierResolver::rank(running_apps, current);
+            if running_apps.is_empty() {
+                return running_apps;
+            }
+            let Some(order) = self.order(&running_apps).await else {
+                return running_apps;
+            };
+            // Stable, so apps the script didn't name keep their tier order behind the named ones
+            running_apps.sort_by_key(|app| order.iter().position(|name| *name == app.name).unwrap_or(usize::MAX));
+            running_apps
+        })
+    }
+}
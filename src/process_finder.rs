@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use procfs::process::Process;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::blocklist::Blocklist;
+use crate::config;
+use crate::electron;
+use crate::error::{RepresenceError, Result};
+use crate::remote::{self, SSH_CLIENT};
+
+const PROCESS_CACHE_TTL_SECS: u64 = 1; // Reduced cache TTL
+const DEFAULT_PROC_PATH: &str = "/proc";
+
+/// `PF_KTHREAD` in the flags of `/proc/<pid>/stat`
+const PF_KTHREAD: u32 = 0x0020_0000;
+
+/// New processes are resolved again for this long, a wrapper may still exec into the actual app
+const PID_SETTLE_TIME: Duration = Duration::from_secs(5);
+
+/// `USER_HZ`, the unit of process start times in `/proc/<pid>/stat`, 100 on every Linux architecture
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// Init systems expected as PID 1 when the host's processes are visible
+const HOST_INIT_NAMES: [&str; 8] = ["systemd", "init", "openrc-init", "runit", "runit-init", "s6-svscan", "dinit", "busybox"];
+
+#[derive(Debug, Clone)]
+pub struct TieredApp {
+    pub name: String,
+    pub tier: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningApp {
+    pub name: String,
+    pub tier: u32,
+    /// Start of the app's oldest process in clock ticks since boot, breaks ties within a tier
+    pub started: u64,
+    /// Host an SSH session is connected to
+    pub remote_host: Option<String>,
+    /// Running processes with this executable name
+    pub instances: u32,
+    /// Shown instead of the executable name, from the aliases
+    pub display_name: Option<String>,
+    /// Kind of activity from the aliases, overrides the built-in one
+    pub kind: Option<&'static str>,
+}
+
+impl RunningApp {
+    /// The name to put in texts: the alias, the executable name otherwise
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// An app in the payload with how many of its processes are running, e.g. 3 for three Chrome windows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct AppInstances {
+    pub name: String,
+    pub instances: u32,
+}
+
+impl From<&RunningApp> for AppInstances {
+    fn from(app: &RunningApp) -> Self {
+        Self { name: app.display_name().to_string(), instances: app.instances }
+    }
+}
+
+/// A running process as reported by a `ProcessSource`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// Executable name
+    pub name: String,
+    /// Start time in clock ticks since boot, 0 when unknown
+    pub started: u64,
+    /// Command line, only read for the SSH client
+    pub args: Vec<String>,
+}
+
+impl ProcessInfo {
+    /// A process without a PID yet, fixture sources number them in order
+    pub fn new(name: impl Into<String>, started: u64) -> Self {
+        Self { pid: 0, name: name.into(), started, args: Vec::new() }
+    }
+
+    pub fn with_pid(mut self, pid: u32) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    pub fn with_args<I: IntoIterator<Item = impl Into<String>>>(mut self, args: I) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// What one PID matched on the app list, valid for as long as the PID keeps its start time and executable
+#[derive(Debug)]
+struct PidMatch {
+    name: String,
+    started: u64,
+    /// `None` for processes that aren't on the app list
+    app: Option<RunningApp>,
+}
+
+impl PidMatch {
+    fn describes(&self, process: &ProcessInfo) -> bool {
+        self.started == process.started && self.name == process.name
+    }
+}
+
+/// Per-PID app matches, so a scan only matches processes it hasn't seen before
+#[derive(Debug)]
+pub struct ProcessCache {
+    matches: HashMap<u32, PidMatch>,
+    /// Apps derived from `matches` on the last scan, highest priority first
+    apps: Vec<RunningApp>,
+    last_updated: SystemTime,
+    ttl: Duration,
+}
+
+impl ProcessCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            matches: HashMap::new(),
+            apps: Vec::new(),
+            last_updated: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.last_updated.elapsed().unwrap_or(Duration::MAX) >= self.ttl
+    }
+
+    /// Match new and reused PIDs, forget exited ones and rebuild the app list.
+    /// Returns whether the app list differs from the last scan.
+    fn update(&mut self, processes: &[ProcessInfo], apps_to_check: &[TieredApp], blocklist: &Blocklist) -> bool {
+        let mut matches = HashMap::with_capacity(processes.len());
+        for process in processes {
+            let entry = match self.matches.remove(&process.pid) {
+                Some(entry) if entry.describes(process) => entry,
+                // New, or the PID was reused or exec'd into something else
+                _ => PidMatch { name: process.name.clone(), started: process.started, app: match_process(process, apps_to_check) },
+            };
+            matches.insert(process.pid, entry);
+        }
+        self.matches = matches;
+        self.last_updated = SystemTime::now();
+
+        // Blocked apps are filtered here rather than cached, time windows change what's blocked
+        // In scan order, which settles ties within a tier the same way every time
+        let apps = merge_apps(
+            processes
+                .iter()
+                .filter_map(|process| self.matches.get(&process.pid)?.app.as_ref())
+                .filter(|app| !blocklist.blocks(&app.name)),
+        );
+        let changed = apps != self.apps;
+        self.apps = apps;
+        changed
+    }
+
+    /// Apps from the last scan without what's blocked now
+    fn cached_apps(&self, blocklist: &Blocklist) -> Vec<RunningApp> {
+        self.apps.iter().filter(|app| !blocklist.blocks(&app.name)).cloned().collect()
+    }
+}
+
+/// Where running processes come from, `/proc` in production and fixtures in tests
+pub trait ProcessSource: Send + Sync {
+    /// All running processes
+    fn processes(&self) -> impl Future<Output = Result<Vec<ProcessInfo>>> + Send;
+
+    /// How long a scan stays fresh before the source is asked again
+    fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(PROCESS_CACHE_TTL_SECS)
+    }
+
+    /// How long ago a process with this start time started, `None` when the source can't tell
+    fn age(&self, _started: u64) -> Option<Duration> {
+        None
+    }
+}
+
+/// Reads `<proc>/<pid>/exe` for every new process
+#[derive(Debug, Clone)]
+pub struct ProcSource {
+    /// Where procfs is mounted, the host's `/proc` is usually bind-mounted to `/host/proc` in containers
+    proc_path: PathBuf,
+    /// Refuse to scan from a container that can't see the host's processes
+    check_isolation: bool,
+    /// What each PID resolved to, so long-running processes cost nothing but their directory entry
+    known: Arc<Mutex<HashMap<i32, KnownPid>>>,
+}
+
+#[derive(Debug)]
+struct KnownPid {
+    /// `None` for kernel threads and processes whose executable can't be read
+    process: Option<ProcessInfo>,
+    first_seen: Instant,
+}
+
+impl Default for ProcSource {
+    fn default() -> Self {
+        Self { proc_path: PathBuf::from(DEFAULT_PROC_PATH), check_isolation: true, known: Arc::default() }
+    }
+}
+
+impl ProcSource {
+    /// `REPRESENCE_PROC_PATH` points at another procfs mount,
+    /// `REPRESENCE_SKIP_PROC_CHECK=true` scans even when the container check says the host is hidden
+    pub fn from_env() -> Self {
+        Self {
+            proc_path: config::env_var("REPRESENCE_PROC_PATH").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_PROC_PATH)),
+            check_isolation: !config::env_parse("REPRESENCE_SKIP_PROC_CHECK", false),
+            known: Arc::default(),
+        }
+    }
+
+    /// Scan the procfs mounted at `proc_path`
+    pub fn new(proc_path: impl Into<PathBuf>) -> Self {
+        Self { proc_path: proc_path.into(), ..Self::default() }
+    }
+
+    pub fn proc_path(&self) -> &Path {
+        &self.proc_path
+    }
+}
+
+impl ProcessSource for ProcSource {
+    async fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        if self.check_isolation {
+            if let Some(init) = isolated_container_init(&self.proc_path).await {
+                return Err(RepresenceError::ProcUnavailable(format!(
+                    "running in a container without the host PID namespace (pid 1 in {} is {}), so no host processes are visible",
+                    self.proc_path.display(),
+                    init
+                )));
+            }
+        }
+
+        // procfs reads are blocking, thousands of them must not stall the runtime
+        let source = self.clone();
+        let (processes, process_count) = tokio::task::spawn_blocking(move || source.scan())
+            .await
+            .map_err(std::io::Error::other)
+            .and_then(|scan| scan)
+            .map_err(|source| RepresenceError::Scan { path: self.proc_path.display().to_string(), source })?;
+
+        // Executables of other users' processes can't be read, not even our own would mean /proc is useless
+        if processes.is_empty() {
+            return Err(RepresenceError::ProcUnavailable(format!(
+                "none of the {} processes in {} have a readable executable",
+                process_count,
+                self.proc_path.display()
+            )));
+        }
+
+        Ok(processes)
+    }
+
+    fn age(&self, started: u64) -> Option<Duration> {
+        if started == 0 {
+            return None;
+        }
+        let uptime = std::fs::read_to_string(self.proc_path.join("uptime")).ok()?;
+        let since_boot: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+        Some(Duration::from_secs_f64(since_boot).saturating_sub(Duration::from_millis(started * 1000 / CLOCK_TICKS_PER_SEC)))
+    }
+}
+
+impl ProcSource {
+    /// List the PIDs and only resolve the ones not seen before, or still new enough to exec into something else.
+    /// Returns the processes with a readable executable and how many PIDs there are.
+    fn scan(&self) -> std::io::Result<(Vec<ProcessInfo>, usize)> {
+        let pids = std::fs::read_dir(&self.proc_path)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok());
+
+        let mut known = self.known.lock().unwrap_or_else(|e| e.into_inner());
+        let mut current = HashMap::with_capacity(known.len());
+        for pid in pids {
+            let entry = match known.remove(&pid) {
+                Some(entry) if entry.first_seen.elapsed() >= PID_SETTLE_TIME => entry,
+                previous => KnownPid {
+                    process: self.resolve(pid),
+                    first_seen: previous.map_or_else(Instant::now, |previous| previous.first_seen),
+                },
+            };
+            current.insert(pid, entry);
+        }
+        // Exited processes drop out here
+        *known = current;
+
+        let mut processes: Vec<ProcessInfo> = known.values().filter_map(|entry| entry.process.clone()).collect();
+        processes.sort_unstable_by_key(|process| process.pid);
+        Ok((processes, known.len()))
+    }
+
+    /// Executable name and start time, `None` for kernel threads and executables we can't read
+    fn resolve(&self, pid: i32) -> Option<ProcessInfo> {
+        let process = Process::new_with_root(self.proc_path.join(pid.to_string())).ok()?;
+        let stat = process.stat().ok();
+        if stat.as_ref().is_some_and(|stat| stat.flags & PF_KTHREAD != 0) {
+            return None;
+        }
+
+        let name = process.exe().ok()?.file_name()?.to_str()?.to_string();
+        let (name, args) = match name.as_str() {
+            SSH_CLIENT => (name, process.cmdline().unwrap_or_default()),
+            // Apps on a shared Electron runtime all run as `electron`, they go by the app's name instead
+            runtime if electron::is_runtime(runtime) => {
+                (process.cmdline().ok().and_then(|args| electron::app_name(&args)).unwrap_or(name), Vec::new())
+            }
+            _ => (name, Vec::new()),
+        };
+        Some(ProcessInfo { pid: pid as u32, name, started: stat.map_or(0, |stat| stat.starttime), args })
+    }
+}
+
+/// Name of PID 1 when running in a container whose PID namespace hides the host's processes
+async fn isolated_container_init(proc_path: &Path) -> Option<String> {
+    let in_container = Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || std::env::var_os("container").is_some();
+    if !in_container {
+        return None;
+    }
+
+    // With the host PID namespace (`--pid=host`) or the host's procfs mounted, PID 1 is the host's init system
+    let init = fs::read_to_string(proc_path.join("1/comm")).await.ok()?.trim().to_string();
+    (!HOST_INIT_NAMES.contains(&init.as_str())).then_some(init)
+}
+
+/// Fixed process list that can be swapped between scans, for tests and simulations
+#[derive(Debug, Clone, Default)]
+pub struct FixtureProcessSource {
+    processes: Arc<Mutex<Vec<ProcessInfo>>>,
+}
+
+impl FixtureProcessSource {
+    pub fn new<I: IntoIterator<Item = impl Into<String>>>(names: I) -> Self {
+        let source = Self::default();
+        source.set(names);
+        source
+    }
+
+    /// Replace the running processes seen by the next scan, all with an unknown start time
+    pub fn set<I: IntoIterator<Item = impl Into<String>>>(&self, names: I) {
+        self.set_processes(names.into_iter().map(|name| ProcessInfo::new(name, 0)));
+    }
+
+    /// Processes without a PID get their position in the list, counting from 1
+    pub fn set_processes<I: IntoIterator<Item = ProcessInfo>>(&self, processes: I) {
+        let mut current = self.processes.lock().unwrap_or_else(|e| e.into_inner());
+        *current = processes
+            .into_iter()
+            .zip(1..)
+            .map(|(process, position)| match process.pid {
+                0 => process.with_pid(position),
+                _ => process,
+            })
+            .collect();
+    }
+}
+
+impl ProcessSource for FixtureProcessSource {
+    async fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        Ok(self.processes.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    fn cache_ttl(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// The app a single process belongs to, `None` when it isn't on the list
+fn match_process(process: &ProcessInfo, apps_to_check: &[TieredApp]) -> Option<RunningApp> {
+    let check_app = apps_to_check.iter().find(|check_app| process.name.starts_with(&check_app.name))?;
+
+    // Only interactive client sessions count, not sshd, ssh-agent or `git push`
+    let remote_host = match check_app.name == SSH_CLIENT {
+        true => Some((process.name == SSH_CLIENT).then(|| remote::ssh_host(&process.args)).flatten()?),
+        false => None,
+    };
+    Some(RunningApp { name: process.name.clone(), tier: check_app.tier, started: process.started, remote_host, instances: 1, display_name: None, kind: None })
+}
+
+/// One entry per executable name counting its processes, highest priority first
+fn merge_apps<'a>(matches: impl Iterator<Item = &'a RunningApp>) -> Vec<RunningApp> {
+    let mut running_apps: Vec<RunningApp> = Vec::new();
+    for matched in matches {
+        // Helper processes start after the main one, so the oldest process dates the app
+        match running_apps.iter_mut().find(|app| app.name == matched.name) {
+            Some(app) if matched.started < app.started => *app = RunningApp { instances: app.instances + 1, ..matched.clone() },
+            Some(app) => app.instances += 1,
+            None => running_apps.push(matched.clone()),
+        }
+    }
+
+    rank(&mut running_apps);
+    running_apps
+}
+
+/// Lowest tier first, the most recently started app wins within a tier
+fn rank(running_apps: &mut [RunningApp]) {
+    running_apps.sort_by_key(|app| (app.tier, std::cmp::Reverse(app.started)));
+}
+
+/// Optimized function to get running applications with resource limits and caching
+pub async fn get_running_apps_optimized(
+    source: &impl ProcessSource,
+    apps_to_check: &[TieredApp],
+    blocklist: &Blocklist,
+    cache: &mut ProcessCache
+) -> Result<(Vec<RunningApp>, bool)> {
+    // Return cached results if still valid, time windows may have changed what's blocked since
+    if !cache.is_expired() {
+        return Ok((cache.cached_apps(blocklist), false)); // No change, using cache
+    }
+
+    let processes = source.processes().await?;
+    let has_changed = cache.update(&processes, apps_to_check, blocklist);
+
+    Ok((cache.apps.clone(), has_changed))
+}
@@ -0,0 +1,177 @@
+use crate::OutputData;
+
+pub const START_MARKER: &str = "<!-- represence:start -->";
+pub const END_MARKER: &str = "<!-- represence:end -->";
+
+/// The line written into the README
+pub fn status_line(data: &OutputData) -> String {
+    match &data.emoji {
+        Some(emoji) => format!("{} {}", emoji, data.text),
+        None => data.text.clone(),
+    }
+}
+
+/// Put `line` between the markers, `None` when the README doesn't have them
+pub fn replace_status(readme: &str, line: &str) -> Option<String> {
+    let start = readme.find(START_MARKER)? + START_MARKER.len();
+    let end = start + readme[start..].find(END_MARKER)?;
+    Some(format!("{}\n{}\n{}", &readme[..start], line, &readme[end..]))
+}
+
+#[cfg(feature = "profile-readme")]
+pub mod client {
+    use base64::Engine;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    use super::{replace_status, status_line, END_MARKER, START_MARKER};
+    use crate::config::{env_parse, env_var};
+    use crate::debounce::Debounce;
+    use crate::trust::Trust;
+    use crate::web_server::{self, Broadcaster, SharedData};
+
+    const REQUEST_TIMEOUT_SECS: u64 = 10;
+    /// How long a text must stay before it's worth a commit
+    const QUIET: Duration = Duration::from_secs(60);
+
+    #[derive(Debug, Clone)]
+    pub struct ProfileReadmeConfig {
+        /// `owner/repo`, for a profile README the repository named like the account
+        pub repo: String,
+        /// Token allowed to write the repository's contents
+        pub token: String,
+        pub path: String,
+        /// Defaults to the repository's default branch
+        pub branch: Option<String>,
+        /// Also commit the status badge to this path
+        pub svg_path: Option<String>,
+        /// Least time between two commits
+        pub interval: Duration,
+    }
+
+    impl ProfileReadmeConfig {
+        /// `REPRESENCE_PROFILE_README_REPO` and `REPRESENCE_PROFILE_README_TOKEN`, `None` unless both are set
+        pub fn from_env() -> Option<Self> {
+            Some(Self {
+                repo: env_var("REPRESENCE_PROFILE_README_REPO")?,
+                token: env_var("REPRESENCE_PROFILE_README_TOKEN")?,
+                path: env_var("REPRESENCE_PROFILE_README_PATH").unwrap_or_else(|| "README.md".to_string()),
+                branch: env_var("REPRESENCE_PROFILE_README_BRANCH"),
+                svg_path: env_var("REPRESENCE_PROFILE_README_SVG"),
+                interval: Duration::from_secs(env_parse("REPRESENCE_PROFILE_README_INTERVAL_MINS", 30u64).max(1) * 60),
+            })
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Contents {
+        sha: String,
+        content: String,
+    }
+
+    /// Commit the public presence into the README, and the badge next to it, whenever it settles on a new one
+    pub fn spawn(config: ProfileReadmeConfig, shared_data: SharedData, broadcaster: &Broadcaster) {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Profile README updates disabled, failed to create HTTP client: {}", e);
+                return;
+            }
+        };
+
+        let mut texts = Debounce::new(shared_data.clone(), broadcaster, QUIET, config.interval);
+        tokio::spawn(async move {
+            while texts.next().await.is_some() {
+                let data = Trust::Public.view(shared_data.load_full());
+                if let Err(e) = update_readme(&client, &config, &status_line(&data)).await {
+                    eprintln!("Failed to update {} in {}: {}", config.path, config.repo, e);
+                }
+                if let Some(svg_path) = &config.svg_path {
+                    let result = commit(&client, &config, svg_path, &web_server::badge(&data), &data.text).await;
+                    if let Err(e) = result {
+                        eprintln!("Failed to update {} in {}: {}", svg_path, config.repo, e);
+                    }
+                }
+            }
+        });
+    }
+
+    async fn update_readme(client: &reqwest::Client, config: &ProfileReadmeConfig, line: &str) -> Result<(), String> {
+        let current = fetch(client, config, &config.path).await.map_err(|e| e.to_string())?;
+        let readme = match &current {
+            Some(contents) => decode(&contents.content)?,
+            None => return Err("the file doesn't exist".to_string()),
+        };
+        let updated = replace_status(&readme, line)
+            .ok_or_else(|| format!("add {}{} where the status should go", START_MARKER, END_MARKER))?;
+        if updated == readme {
+            return Ok(());
+        }
+        put(client, config, &config.path, &updated, current.map(|contents| contents.sha), line).await.map_err(|e| e.to_string())
+    }
+
+    /// Create or replace a file unless it already has `content`
+    async fn commit(client: &reqwest::Client, config: &ProfileReadmeConfig, path: &str, content: &str, text: &str) -> Result<(), String> {
+        let current = fetch(client, config, path).await.map_err(|e| e.to_string())?;
+        if let Some(contents) = &current {
+            if decode(&contents.content)? == content {
+                return Ok(());
+            }
+        }
+        put(client, config, path, content, current.map(|contents| contents.sha), text).await.map_err(|e| e.to_string())
+    }
+
+    fn decode(content: &str) -> Result<String, String> {
+        // GitHub wraps the base64 at 60 characters
+        let content: String = content.split_whitespace().collect();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(content).map_err(|e| e.to_string())?;
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }
+
+    fn contents_url(config: &ProfileReadmeConfig, path: &str) -> String {
+        format!("https://api.github.com/repos/{}/contents/{}", config.repo, path)
+    }
+
+    /// The file and its blob SHA, `None` when it doesn't exist yet
+    async fn fetch(client: &reqwest::Client, config: &ProfileReadmeConfig, path: &str) -> reqwest::Result<Option<Contents>> {
+        let mut request = client
+            .get(contents_url(config, path))
+            .bearer_auth(&config.token)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+        if let Some(branch) = &config.branch {
+            request = request.query(&[("ref", branch)]);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    async fn put(client: &reqwest::Client, config: &ProfileReadmeConfig, path: &str, content: &str, sha: Option<String>, text: &str) -> reqwest::Result<()> {
+        let mut body = serde_json::json!({
+            "message": format!("Update status: {}", text),
+            "content": base64::engine::general_purpose::STANDARD.encode(content),
+        });
+        // GitHub rejects nulls, only send what's known
+        if let Some(sha) = sha {
+            body["sha"] = sha.into();
+        }
+        if let Some(branch) = &config.branch {
+            body["branch"] = branch.clone().into();
+        }
+        client
+            .put(contents_url(config, path))
+            .bearer_auth(&config.token)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+use crate::config::{self, env_json};
+use crate::control::{Control, ControlSender};
+use crate::web_server::{self, AppState};
+
+/// Profile name → the variables it sets
+type ProfileVars = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Default)]
+struct Inner {
+    profiles: ProfileVars,
+    /// `.env` as last reloaded, empty until then since it's in the process environment from startup
+    dotenv: BTreeMap<String, String>,
+    active: Option<String>,
+}
+
+impl Inner {
+    /// Put `.env` and the active profile over the process environment, for [`config::env_var`] to read
+    fn apply(&self) {
+        let mut overlay = self.dotenv.clone();
+        if let Some(vars) = self.active.as_ref().and_then(|name| self.profiles.get(name)) {
+            overlay.extend(vars.clone());
+        }
+        config::set_overlay(overlay);
+    }
+}
+
+/// Named sets of variables from `REPRESENCE_PROFILES_FILE`, e.g. a `work` profile with its own blocklist
+/// and phrases, applied over the environment and `.env` without changing the process environment
+#[derive(Clone, Default)]
+pub struct Profiles {
+    inner: Arc<Mutex<Inner>>,
+    /// Where to ask the update loop to switch, for the API
+    controls: Option<ControlSender>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProfileStatus {
+    /// `null` without a profile
+    pub active: Option<String>,
+    pub profiles: Vec<String>,
+}
+
+impl Profiles {
+    /// Profiles from the JSON file at `REPRESENCE_PROFILES_FILE` or inline in `REPRESENCE_PROFILES_JSON`, none otherwise
+    pub fn from_env() -> Self {
+        let profiles = match env_json("REPRESENCE_PROFILES", Self::parse) {
+            Some((from, loaded)) => match loaded {
+                Ok(profiles) => profiles,
+                Err(e) => {
+                    eprintln!("Ignoring profiles, failed to load {}: {}", from, e);
+                    ProfileVars::new()
+                }
+            },
+            None => ProfileVars::new(),
+        };
+        Self::new(profiles)
+    }
+
+    pub fn load(path: &Path) -> Result<ProfileVars, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// `{"work": {"REPRESENCE_BLOCKLIST": "steam"}}`, only `REPRESENCE_*` variables
+    pub fn parse(source: &str) -> serde_json::Result<ProfileVars> {
+        let profiles: ProfileVars = serde_json::from_str(source)?;
+        for (name, vars) in &profiles {
+            if let Some(var) = vars.keys().find(|var| !var.starts_with("REPRESENCE_") || var.as_str() == "REPRESENCE_PROFILE") {
+                return Err(serde::de::Error::custom(format!("profile '{}' can't set {}", name, var)));
+            }
+        }
+        Ok(profiles)
+    }
+
+    pub fn new(profiles: ProfileVars) -> Self {
+        Self { inner: Arc::new(Mutex::new(Inner { profiles, ..Inner::default() })), controls: None }
+    }
+
+    /// Let the API switch profiles through the update loop
+    pub fn with_controls(mut self, controls: ControlSender) -> Self {
+        self.controls = Some(controls);
+        self
+    }
+
+    pub fn status(&self) -> ProfileStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        ProfileStatus { active: inner.active.clone(), profiles: inner.profiles.keys().cloned().collect() }
+    }
+
+    /// `Err` with the profiles to pick from when there is no profile `name`
+    pub fn find(&self, name: &str) -> Result<(), String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match inner.profiles.contains_key(name) {
+            true => Ok(()),
+            false => Err(unknown(name, &inner.profiles)),
+        }
+    }
+
+    /// Whether the active profile sets `var`
+    pub fn sets(&self, var: &str) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.active.as_ref().and_then(|name| inner.profiles.get(name)).is_some_and(|vars| vars.contains_key(var))
+    }
+
+    /// Use the variables of profile `name`, or go back to the environment without a profile.
+    /// Only settings that are re-read take effect, the reload after a switch takes care of that.
+    pub fn activate(&self, name: Option<&str>) -> Result<(), String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(name) = name {
+            inner.profiles.get(name).ok_or_else(|| unknown(name, &inner.profiles))?;
+        }
+        inner.active = name.map(str::to_string);
+        inner.apply();
+        Ok(())
+    }
+
+    /// Re-read `.env` and the profiles file, keeping the active profile on top when it still exists
+    pub fn reload(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.dotenv = dotenvy::dotenv_iter().map(|vars| vars.flatten().collect()).unwrap_or_default();
+        // Read the profiles file without the old profile, which may point at another one
+        let active = inner.active.take();
+        inner.apply();
+
+        inner.profiles = std::mem::take(&mut Self::from_env().inner.lock().unwrap_or_else(|e| e.into_inner()).profiles);
+        match active {
+            Some(name) if !inner.profiles.contains_key(&name) => {
+                eprintln!("Dropping profile after reload: {}", unknown(&name, &inner.profiles));
+            }
+            active => inner.active = active,
+        }
+        inner.apply();
+    }
+}
+
+fn unknown(name: &str, profiles: &ProfileVars) -> String {
+    let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+    match known.is_empty() {
+        true => format!("unknown profile '{}', REPRESENCE_PROFILES_FILE defines none", name),
+        false => format!("unknown profile '{}', expected one of {}", name, known.join(", ")),
+    }
+}
+
+/// The active profile and the ones to pick from, requires the admin token
+#[utoipa::path(
+    get,
+    path = "/api/admin/profile",
+    tag = "diagnostics",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Profiles", body = ProfileStatus),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+    )
+)]
+pub async fn get_profile(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    Json(state.profiles.status()).into_response()
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProfileChange {
+    /// `null` to go back to the configuration without a profile
+    pub profile: Option<String>,
+}
+
+/// Switch to another profile until the next restart, e.g. `{"profile": "work"}`, requires the admin token
+#[utoipa::path(
+    put,
+    path = "/api/admin/profile",
+    tag = "diagnostics",
+    security(("bearer" = [])),
+    request_body = ProfileChange,
+    responses(
+        (status = 202, description = "Switching with the next scan"),
+        (status = 400, description = "Unknown profile"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+        (status = 503, description = "Not detecting, e.g. while simulating"),
+    )
+)]
+pub async fn put_profile(State(state): State<AppState>, headers: HeaderMap, Json(change): Json<ProfileChange>) -> Response {
+    if let Err(status) = web_server::check_admin(&headers) {
+        return status.into_response();
+    }
+    if let Some(Err(e)) = change.profile.as_deref().map(|name| state.profiles.find(name)) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response();
+    }
+
+    let sent = match &state.profiles.controls {
+        Some(controls) => controls.send(Control::SwitchProfile(change.profile)).await.is_ok(),
+        None => false,
+    };
+    match sent {
+        true => StatusCode::ACCEPTED.into_response(),
+        false => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
@@ -0,0 +1,123 @@
+use axum::extract::{Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::config::env_list;
+use crate::web_server;
+
+/// Network in CIDR notation like `10.0.0.0/8`, a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let network: IpAddr = address.trim().parse().map_err(|_| format!("invalid address in '{}'", s))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix.trim() {
+            "" => max,
+            prefix => prefix.parse().ok().filter(|prefix| *prefix <= max).ok_or_else(|| format!("invalid prefix in '{}'", s))?,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let same_prefix = |a: u128, b: u128, bits: u32| self.prefix == 0 || (a ^ b) >> (bits - u32::from(self.prefix)) == 0;
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => same_prefix(u32::from(network).into(), u32::from(ip).into(), 32),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => same_prefix(network.into(), ip.into(), 128),
+            _ => false,
+        }
+    }
+}
+
+/// The client address as resolved through trusted proxies, for rate limiting and the access log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Reverse proxies (nginx, Caddy, Cloudflare) whose `Forwarded` and `X-Forwarded-For` headers are believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// `REPRESENCE_TRUSTED_PROXIES`, comma-separated addresses and CIDRs, `None` when unset
+    pub fn from_env() -> Option<Self> {
+        let networks: Vec<Cidr> = env_list("REPRESENCE_TRUSTED_PROXIES")
+            .iter()
+            .filter_map(|entry| match entry.parse() {
+                Ok(cidr) => Some(cidr),
+                Err(e) => {
+                    eprintln!("Ignoring trusted proxy: {}", e);
+                    None
+                }
+            })
+            .collect();
+        (!networks.is_empty()).then(|| Self::new(networks))
+    }
+
+    pub fn new(networks: Vec<Cidr>) -> Self {
+        Self { networks }
+    }
+
+    /// Requests over a Unix socket can only come from a proxy on this machine
+    fn is_trusted(&self, ip: Option<IpAddr>) -> bool {
+        ip.is_none_or(|ip| self.networks.iter().any(|network| network.contains(ip)))
+    }
+
+    /// The first address from the right of the forwarding chain that isn't a trusted proxy,
+    /// so clients can't spoof their address by sending the headers themselves
+    pub fn resolve(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+        let chain = forwarded_for(headers);
+        chain.iter().rev().find(|ip| !self.is_trusted(Some(**ip))).or(chain.first()).copied().or(peer)
+    }
+}
+
+/// Addresses from `Forwarded` (RFC 7239), or `X-Forwarded-For` without it, client first
+pub fn forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    let values = |name| headers.get_all(name).iter().filter_map(|value| value.to_str().ok()).flat_map(|value| value.split(','));
+    let forwarded: Vec<IpAddr> = values("forwarded")
+        .filter_map(|element| {
+            let node = element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim().eq_ignore_ascii_case("for").then_some(value.trim().trim_matches('"'))
+            })?;
+            parse_node(node)
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+    values("x-forwarded-for").filter_map(|node| parse_node(node.trim())).collect()
+}
+
+/// `192.0.2.60`, `192.0.2.60:4711`, `2001:db8::17` or `[2001:db8::17]:4711`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.parse().ok().or_else(|| node.rsplit_once(':')?.0.parse().ok())
+}
+
+/// Resolve the client address once for everything behind this layer
+pub async fn middleware(State(proxies): State<Arc<TrustedProxies>>, mut request: Request, next: Next) -> Response {
+    if let Some(ip) = proxies.resolve(web_server::peer_ip(&request), request.headers()) {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
@@ -0,0 +1,176 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+use crate::config::env_list;
+use crate::hub;
+use crate::web_server::{self, AppState};
+use crate::OutputData;
+
+/// Executable of the SSH client, whose arguments are read for the host
+pub const SSH_CLIENT: &str = "ssh";
+
+/// Presence pushed by a remote agent is ignored once it is this old
+pub const REMOTE_STALE_AFTER_SECS: u64 = 60;
+
+/// ssh options that take a value, from ssh(1)
+const OPTIONS_WITH_VALUE: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Shown for hosts that aren't listed in `REPRESENCE_SSH_HOSTS`
+const REDACTED_HOST: &str = "a remote server";
+
+/// Presence last pushed by the represence instance on the server we're connected to
+#[derive(Debug, Clone)]
+pub struct RemotePresence {
+    pub text: String,
+    pub received: Instant,
+}
+
+pub type SharedRemote = Arc<RwLock<Option<RemotePresence>>>;
+
+/// Which SSH hosts may be named in the presence, all others are redacted
+#[derive(Debug, Clone, Default)]
+pub struct SshHosts {
+    /// Host to the label shown for it
+    labels: HashMap<String, String>,
+}
+
+impl SshHosts {
+    /// `REPRESENCE_SSH_HOSTS` entries are `host` or `host:label`, e.g. `pi.local,prod.example.com:the prod box`
+    pub fn from_env() -> Self {
+        Self::parse(&env_list("REPRESENCE_SSH_HOSTS"))
+    }
+
+    pub fn parse(entries: &[String]) -> Self {
+        let labels = entries
+            .iter()
+            .map(|entry| match entry.split_once(':') {
+                Some((host, label)) => (host.trim().to_string(), label.trim().to_string()),
+                None => (entry.clone(), entry.clone()),
+            })
+            .collect();
+        Self { labels }
+    }
+
+    /// How the host appears in the presence
+    pub fn label(&self, host: &str) -> &str {
+        self.labels.get(host).map(String::as_str).unwrap_or(REDACTED_HOST)
+    }
+
+    /// Text for an SSH session, carrying what the remote agent pushed while it is fresh
+    pub fn text(&self, host: &str, remote: Option<&RemotePresence>, now: Instant) -> String {
+        let fresh = remote.filter(|remote| now.duration_since(remote.received) < Duration::from_secs(REMOTE_STALE_AFTER_SECS));
+        match fresh {
+            Some(remote) => format!("{} on {}", remote.text, self.label(host)),
+            None => format!("working on {}", self.label(host)),
+        }
+    }
+}
+
+/// Host of an interactive ssh session, `None` for port forwards and one-off commands like `git push`
+pub fn ssh_host(args: &[String]) -> Option<String> {
+    let mut args = args.iter().skip(1);
+    let mut forced_tty = false;
+
+    let destination = loop {
+        let arg = args.next()?;
+        let Some(flags) = arg.strip_prefix('-') else {
+            break arg;
+        };
+        for (index, flag) in flags.char_indices() {
+            match flag {
+                // Forwarding only or used as a proxy, nobody is typing into it
+                'N' | 'W' => return None,
+                't' => forced_tty = true,
+                flag if OPTIONS_WITH_VALUE.contains(flag) => {
+                    if index + 1 == flags.len() {
+                        args.next()?;
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+    };
+
+    // A remote command without -t runs non-interactively
+    if args.next().is_some() && !forced_tty {
+        return None;
+    }
+
+    let host = destination.strip_prefix("ssh://").unwrap_or(destination);
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next().unwrap_or(host);
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/api/remote", post(push_remote_presence))
+}
+
+/// A plain presence object, or the envelope sent by represence webhooks
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RemotePush {
+    Webhook { data: OutputData },
+    Presence(OutputData),
+}
+
+/// Accept the presence of the represence instance on the server we're connected to over SSH
+#[utoipa::path(
+    post,
+    path = "/api/remote",
+    tag = "presence",
+    request_body = OutputData,
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Remote presence accepted"),
+        (status = 400, description = "Body is neither a presence nor a webhook payload"),
+        (status = 401, description = "Missing or invalid admin token or webhook signature"),
+        (status = 404, description = "No admin token or remote secret configured"),
+        (status = 413, description = "Status text too long"),
+    )
+)]
+pub async fn push_remote_presence(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    if let Err(status) = authorize(&headers, &body) {
+        return status;
+    }
+
+    let data = match serde_json::from_slice(&body) {
+        Ok(RemotePush::Webhook { data } | RemotePush::Presence(data)) => data,
+        Err(_) => return StatusCode::BAD_REQUEST,
+    };
+    if data.text.len() > hub::MAX_TEXT_LEN {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+
+    *state.remote.write().await = Some(RemotePresence { text: data.text, received: Instant::now() });
+    StatusCode::NO_CONTENT
+}
+
+/// The admin token, or a webhook signature made with `REPRESENCE_REMOTE_SECRET`
+fn authorize(headers: &HeaderMap, body: &[u8]) -> Result<(), StatusCode> {
+    #[cfg(feature = "webhooks")]
+    if let Some(secret) = crate::config::env_var("REPRESENCE_REMOTE_SECRET") {
+        let expected = format!("sha256={}", crate::webhook::sign(&secret, body));
+        let signed = headers
+            .get("X-Represence-Signature")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|signature| web_server::constant_time_eq(signature, &expected));
+        if signed {
+            return Ok(());
+        }
+    }
+    #[cfg(not(feature = "webhooks"))]
+    let _ = body;
+
+    web_server::check_admin(headers)
+}
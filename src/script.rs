@@ -0,0 +1,152 @@
+use chrono::{Datelike, Timelike};
+use serde::Serialize;
+
+use crate::process_finder::RunningApp;
+use crate::vscode_client::FileInfo;
+
+/// Everything detected on a scan, handed to a text script as `ctx`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScriptContext {
+    /// What represence would show without the script
+    pub text: String,
+    /// Detected apps, the one shown first
+    pub apps: Vec<ScriptApp>,
+    /// File open in VS Code
+    pub file: Option<FileInfo>,
+    /// Command running in the terminal shown, e.g. `cargo test`
+    pub terminal_command: Option<String>,
+    /// SSH session text, e.g. `ssh'd into prod`
+    pub remote: Option<String>,
+    /// Track text of the app shown
+    pub playing: Option<String>,
+    /// Busy calendar event
+    pub meeting: Option<String>,
+    /// Live stream text
+    pub live: Option<String>,
+    /// Recent GitHub activity while idle
+    pub idle_activity: Option<String>,
+    /// Local hour, 0 to 23
+    pub hour: u32,
+    /// Local weekday, e.g. `Mon`
+    pub weekday: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptApp {
+    pub name: String,
+    pub tier: u32,
+    pub instances: u32,
+}
+
+impl From<&RunningApp> for ScriptApp {
+    fn from(app: &RunningApp) -> Self {
+        Self { name: app.name.clone(), tier: app.tier, instances: app.instances }
+    }
+}
+
+impl ScriptContext {
+    /// Context stamped with the current local time
+    pub fn now() -> Self {
+        let now = chrono::Local::now();
+        Self { hour: now.hour(), weekday: now.weekday().to_string(), ..Self::default() }
+    }
+}
+
+/// Replaces the generated text, for rules the composer and phrase pools can't express
+pub trait TextScript: Send + Sync {
+    /// Text to show instead of `context.text`, `None` keeps it
+    fn text(&self, context: &ScriptContext) -> Option<String>;
+}
+
+/// Text scripts written in Rhai
+#[cfg(feature = "scripting")]
+pub mod rhai {
+    use rhai::{Dynamic, Engine, Scope, AST};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::{ScriptContext, TextScript};
+    use crate::config::{env_parse, env_var};
+
+    /// Bounds keeping a runaway script from eating memory
+    const MAX_STRING_SIZE: usize = 4096;
+    const MAX_COLLECTION_SIZE: usize = 1024;
+    const MAX_CALL_LEVELS: usize = 32;
+
+    /// A compiled script evaluated once per scan. The last expression is the text,
+    /// `()` or an empty string keeps what represence would show.
+    pub struct RhaiScript {
+        engine: Engine,
+        ast: AST,
+        /// When the running evaluation gets aborted
+        deadline: Arc<Mutex<Instant>>,
+        timeout: Duration,
+        /// Errors are logged once until the script works again
+        failing: AtomicBool,
+    }
+
+    impl RhaiScript {
+        /// The script in `REPRESENCE_TEXT_SCRIPT`, aborted after `REPRESENCE_TEXT_SCRIPT_TIMEOUT_MS` (default 50)
+        pub fn from_env() -> Option<Self> {
+            let path = env_var("REPRESENCE_TEXT_SCRIPT")?;
+            let timeout = Duration::from_millis(env_parse("REPRESENCE_TEXT_SCRIPT_TIMEOUT_MS", 50u64));
+            match std::fs::read_to_string(Path::new(&path)).map_err(|e| e.to_string()).and_then(|source| Self::compile(&source, timeout)) {
+                Ok(script) => Some(script),
+                Err(e) => {
+                    eprintln!("Ignoring text script, failed to load {}: {}", path, e);
+                    None
+                }
+            }
+        }
+
+        pub fn compile(source: &str, timeout: Duration) -> Result<Self, String> {
+            let deadline = Arc::new(Mutex::new(Instant::now()));
+            let mut engine = Engine::new();
+            engine
+                .set_max_string_size(MAX_STRING_SIZE)
+                .set_max_array_size(MAX_COLLECTION_SIZE)
+                .set_max_map_size(MAX_COLLECTION_SIZE)
+                .set_max_call_levels(MAX_CALL_LEVELS);
+            let progress_deadline = deadline.clone();
+            engine.on_progress(move |_| {
+                let expired = Instant::now() >= *progress_deadline.lock().unwrap_or_else(|e| e.into_inner());
+                expired.then_some(Dynamic::UNIT)
+            });
+
+            let ast = engine.compile(source).map_err(|e| e.to_string())?;
+            Ok(Self { engine, ast, deadline, timeout, failing: AtomicBool::new(false) })
+        }
+    }
+
+    impl TextScript for RhaiScript {
+        fn text(&self, context: &ScriptContext) -> Option<String> {
+            let ctx = match rhai::serde::to_dynamic(context) {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    eprintln!("Failed to hand the detection context to the text script: {}", e);
+                    return None;
+                }
+            };
+            let mut scope = Scope::new();
+            scope.push_constant("ctx", ctx);
+
+            *self.deadline.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now() + self.timeout;
+            match self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast) {
+                Ok(text) => {
+                    if self.failing.swap(false, Ordering::Relaxed) {
+                        println!("Text script works again");
+                    }
+                    text.into_string().ok().filter(|text| !text.trim().is_empty())
+                }
+                Err(e) => {
+                    if !self.failing.swap(true, Ordering::Relaxed) {
+                        eprintln!("Text script failed, showing the generated text: {}", e);
+                    }
+                    None
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use utoipa::IntoParams;
+
+use crate::config::env_parse;
+use crate::history::{self, PresenceStore, Session, Span, StoreError};
+use crate::web_server::{self, AppState};
+
+/// Range returned when the request doesn't give a start
+const DEFAULT_RANGE_DAYS: i64 = 7;
+
+/// Longest break that doesn't end a session, from `REPRESENCE_SESSION_GAP_MINS` (15 by default)
+pub fn gap_from_env() -> Duration {
+    Duration::minutes(env_parse("REPRESENCE_SESSION_GAP_MINS", 15i64).max(1))
+}
+
+/// Group spans into sessions, breaks without an app in front longer than `gap` end one.
+/// Sessions that may still grow at `now` are marked ongoing.
+pub fn segment(spans: &[Span], gap: Duration, now: i64) -> Vec<Session> {
+    let gap = gap.num_milliseconds();
+    // First and last millisecond and time per app of the session being built
+    let mut sessions: Vec<(i64, i64, BTreeMap<String, i64>)> = Vec::new();
+
+    for span in spans.iter().filter(|span| span.end > span.start) {
+        let Some(app) = &span.app else {
+            continue;
+        };
+        match sessions.last_mut() {
+            Some((_, end, apps)) if span.start - *end <= gap => {
+                *end = (*end).max(span.end);
+                *apps.entry(app.clone()).or_default() += span.end - span.start;
+            }
+            _ => sessions.push((span.start, span.end, BTreeMap::from([(app.clone(), span.end - span.start)]))),
+        }
+    }
+
+    sessions
+        .into_iter()
+        .map(|(start, end, apps)| {
+            let dominant_app = apps
+                .iter()
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(app, _)| app.clone());
+            Session {
+                start: history::millis_to_datetime(start),
+                end: history::millis_to_datetime(end),
+                duration_secs: (end - start) / 1000,
+                active_secs: apps.values().sum::<i64>() / 1000,
+                dominant_app,
+                apps: apps.into_iter().map(|(app, millis)| (app, millis / 1000)).collect(),
+                ongoing: end + gap > now,
+            }
+        })
+        .collect()
+}
+
+/// Sessions since the last stored one, from the raw events, blocking
+fn unstored(history: &dyn PresenceStore, gap: Duration, now: i64) -> Result<Vec<Session>, StoreError> {
+    let from = match history.latest_session_end()? {
+        Some(end) => end,
+        None => history.first_event()?.unwrap_or(now),
+    };
+    Ok(segment(&history.spans(from, now)?, gap, now))
+}
+
+/// Store the sessions that are over, blocking. Runs before raw events are pruned.
+pub fn store_closed(history: &dyn PresenceStore, gap: Duration) -> Result<usize, StoreError> {
+    let closed: Vec<Session> = unstored(history, gap, Utc::now().timestamp_millis())?
+        .into_iter()
+        .filter(|session| !session.ongoing)
+        .collect();
+    for session in &closed {
+        history.store_session(session)?;
+    }
+    Ok(closed.len())
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/api/history/sessions", get(get_sessions))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SessionParams {
+    /// Start of the range (RFC 3339, `YYYY-MM-DD` or unix seconds), defaults to a week ago
+    from: Option<String>,
+    /// End of the range, exclusive, defaults to now
+    to: Option<String>,
+}
+
+/// Stored sessions overlapping the range, and the ones since, blocking
+fn load(history: &dyn PresenceStore, from: i64, to: i64, gap: Duration) -> Result<Vec<Session>, StoreError> {
+    let mut sessions = history.sessions(from, to)?;
+    let recent = unstored(history, gap, Utc::now().timestamp_millis())?;
+    sessions.extend(recent.into_iter().filter(|session| session.start.timestamp_millis() < to && session.end.timestamp_millis() > from));
+    Ok(sessions)
+}
+
+/// Presence history grouped into sessions of continuous activity
+#[utoipa::path(
+    get,
+    path = "/api/history/sessions",
+    tag = "history",
+    params(SessionParams),
+    responses(
+        (status = 200, description = "Sessions overlapping the range, oldest first", body = [Session]),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_sessions(State(state): State<AppState>, Query(params): Query<SessionParams>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "history is disabled" }))).into_response();
+    };
+
+    let now = Utc::now();
+    let from = match params.from.as_deref().map(history::parse_time) {
+        Some(Some(from)) => from,
+        Some(None) => return web_server::bad_request("invalid 'from'"),
+        None => now - Duration::days(DEFAULT_RANGE_DAYS),
+    };
+    let to = match params.to.as_deref().map(history::parse_time) {
+        Some(Some(to)) => to,
+        Some(None) => return web_server::bad_request("invalid 'to'"),
+        None => now,
+    };
+
+    let gap = gap_from_env();
+    match tokio::task::spawn_blocking(move || load(history.as_ref(), from.timestamp_millis(), to.timestamp_millis(), gap)).await {
+        Ok(Ok(sessions)) => Json(sessions).into_response(),
+        Ok(Err(e)) => {
+            eprintln!("Session query failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
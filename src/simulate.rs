@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::blocklist::Blocklist;
+use crate::compose::Composer;
+use crate::device::DeviceInfo;
+use crate::idle_text::IdleRules;
+use crate::diagnostics::SharedDiagnostics;
+use crate::phrases::Phrases;
+use crate::presence::Pipeline;
+use crate::process_finder::FixtureProcessSource;
+use crate::uptime::MinUptime;
+use crate::vscode_client::{FileInfo, FixtureEditorSource};
+use crate::web_server::{Broadcaster, SharedData};
+
+/// A scripted sequence of process lists and editor state
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    /// Time acceleration, a 60 second step lasts 6 seconds at speed 10
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// Start over after the last step
+    #[serde(default, rename = "loop")]
+    pub repeat: bool,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioStep {
+    /// Scenario seconds this state lasts
+    pub duration: f64,
+    /// Executable names of the running processes
+    #[serde(default)]
+    pub processes: Vec<String>,
+    /// File open in VS Code, only used while a `code` process is running
+    pub editor: Option<ScenarioEditor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioEditor {
+    pub file: String,
+    #[serde(default)]
+    pub language: String,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let scenario: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if scenario.steps.is_empty() {
+            return Err("scenario has no steps".into());
+        }
+        if scenario.speed <= 0.0 || !scenario.speed.is_finite() {
+            return Err("scenario speed must be positive".into());
+        }
+        Ok(scenario)
+    }
+}
+
+/// Feed the scenario through the real pipeline instead of scanning `/proc`
+pub async fn run(
+    scenario: Scenario,
+    shared_data: SharedData,
+    broadcaster: Broadcaster,
+    diagnostics: SharedDiagnostics,
+    editor: watch::Sender<Option<FileInfo>>,
+    device: Option<DeviceInfo>,
+) {
+    let processes = FixtureProcessSource::default();
+    let editor_source = FixtureEditorSource::default();
+
+    // Simulated transitions never end up in the history database
+    let mut pipeline = Pipeline::new(
+        processes.clone(),
+        Some(editor_source.clone()),
+        shared_data,
+        broadcaster,
+        diagnostics,
+        None,
+        editor,
+    )
+    .with_blocklist(Blocklist::from_env())
+    .with_min_uptime(MinUptime::from_env())
+    .with_device(device)
+    .with_phrases(Phrases::from_env())
+    .with_composer(Composer::from_env())
+    .with_idle_rules(IdleRules::from_env());
+
+    loop {
+        for (index, step) in scenario.steps.iter().enumerate() {
+            processes.set(step.processes.iter().cloned());
+            editor_source.set(
+                step.editor
+                    .as_ref()
+                    .map(|editor| FileInfo::for_path(&editor.file, &editor.language)),
+            );
+            pipeline.step().await;
+
+            println!(
+                "Simulation step {}/{}: {}",
+                index + 1,
+                scenario.steps.len(),
+                if step.processes.is_empty() { "no processes".to_string() } else { step.processes.join(", ") }
+            );
+            tokio::time::sleep(Duration::from_secs_f64(step.duration.max(0.0) / scenario.speed)).await;
+        }
+
+        if !scenario.repeat {
+            println!("Simulation finished, last state stays published");
+            break;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use tokio::sync::watch;
+
+/// Published right before the machine suspends
+pub const ASLEEP_TEXT: &str = "asleep";
+
+/// `true` from right before a suspend until the machine resumes
+pub type SleepState = watch::Receiver<bool>;
+
+/// Follows logind's `PrepareForSleep` signal on the system bus
+#[cfg(feature = "logind")]
+pub mod logind {
+    use futures_util::StreamExt;
+    use std::time::Duration;
+    use tokio::sync::watch;
+    use zbus::zvariant::OwnedFd;
+    use zbus::{Connection, Proxy};
+
+    use crate::config::env_parse;
+
+    /// How long suspend is held off for the asleep state to reach webhooks and MQTT
+    const SUSPEND_GRACE: Duration = Duration::from_secs(1);
+
+    /// Watch suspend and resume unless `REPRESENCE_SUSPEND_AWARE=false`, `None` when turned off
+    pub fn spawn() -> Option<watch::Receiver<bool>> {
+        if !env_parse("REPRESENCE_SUSPEND_AWARE", true) {
+            return None;
+        }
+        let (asleep, receiver) = watch::channel(false);
+        tokio::spawn(async move {
+            if let Err(e) = watch_sleep(asleep).await {
+                eprintln!("Not following suspend and resume, logind is unavailable: {}", e);
+            }
+        });
+        Some(receiver)
+    }
+
+    async fn watch_sleep(asleep: watch::Sender<bool>) -> zbus::Result<()> {
+        let connection = Connection::system().await?;
+        let manager = Proxy::new(
+            &connection,
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+        )
+        .await?;
+        let mut signals = manager.receive_signal("PrepareForSleep").await?;
+
+        // A delay lock gives us a moment to publish before the machine actually sleeps
+        let mut inhibitor = inhibit(&manager).await;
+        while let Some(signal) = signals.next().await {
+            let Ok(suspending) = signal.body().deserialize::<bool>() else {
+                continue;
+            };
+            asleep.send_replace(suspending);
+            if suspending {
+                println!("Suspending, publishing '{}'", super::ASLEEP_TEXT);
+                tokio::time::sleep(SUSPEND_GRACE).await;
+                inhibitor = None;
+            } else {
+                println!("Resumed, scanning again");
+                inhibitor = inhibit(&manager).await;
+            }
+        }
+        drop(inhibitor);
+        Ok(())
+    }
+
+    async fn inhibit(manager: &Proxy<'_>) -> Option<OwnedFd> {
+        match manager.call("Inhibit", &("sleep", "represence", "Publishing the asleep status", "delay")).await {
+            Ok(fd) => Some(fd),
+            Err(e) => {
+                eprintln!("Failed to take a sleep delay lock, the asleep status may not go out: {}", e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::config::{env_parse, env_var};
+use crate::diagnostics::SharedDiagnostics;
+use crate::now_playing::{self, NowPlaying, Track};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const CURRENTLY_PLAYING_URL: &str = "https://api.spotify.com/v1/me/player/currently-playing?additional_types=episode";
+
+/// Matches both the desktop client and spotifyd
+const APP: &str = "spotify";
+
+#[derive(Debug, Clone)]
+pub struct SpotifyConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    pub poll_interval: Duration,
+}
+
+impl SpotifyConfig {
+    /// `None` unless `REPRESENCE_SPOTIFY_CLIENT_ID`, `REPRESENCE_SPOTIFY_CLIENT_SECRET` and
+    /// `REPRESENCE_SPOTIFY_REFRESH_TOKEN` are all set
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            client_id: env_var("REPRESENCE_SPOTIFY_CLIENT_ID")?,
+            client_secret: env_var("REPRESENCE_SPOTIFY_CLIENT_SECRET")?,
+            refresh_token: env_var("REPRESENCE_SPOTIFY_REFRESH_TOKEN")?,
+            poll_interval: Duration::from_secs(env_parse("REPRESENCE_SPOTIFY_POLL_SECS", 10).max(1)),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentlyPlaying {
+    is_playing: bool,
+    item: Option<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    name: String,
+    /// Tracks only
+    #[serde(default)]
+    artists: Vec<Named>,
+    album: Option<Images>,
+    /// Podcast episodes only
+    show: Option<Show>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Show {
+    name: String,
+    #[serde(default)]
+    images: Vec<Image>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Images {
+    #[serde(default)]
+    images: Vec<Image>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    url: String,
+}
+
+impl Item {
+    fn into_track(self) -> Track {
+        let (artist, images) = match self.show {
+            Some(show) => (Some(show.name), show.images),
+            None => (
+                (!self.artists.is_empty()).then(|| self.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join(", ")),
+                self.album.map(|album| album.images).unwrap_or_default(),
+            ),
+        };
+        Track {
+            title: self.name,
+            artist,
+            // Spotify lists the largest image first
+            album_art: images.into_iter().next().map(|image| image.url),
+            video: false,
+        }
+    }
+}
+
+struct Client {
+    http: reqwest::Client,
+    config: SpotifyConfig,
+    access_token: Option<(String, Instant)>,
+}
+
+impl Client {
+    /// A valid access token, refreshed shortly before it expires
+    async fn access_token(&mut self) -> Result<String, reqwest::Error> {
+        if let Some((token, expires)) = &self.access_token {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let response: TokenResponse = self.http
+            .post(TOKEN_URL)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[("grant_type", "refresh_token"), ("refresh_token", self.config.refresh_token.as_str())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let expires = Instant::now() + Duration::from_secs(response.expires_in.saturating_sub(60));
+        self.access_token = Some((response.access_token.clone(), expires));
+        Ok(response.access_token)
+    }
+
+    /// The playing track, `None` when paused or nothing is playing
+    async fn currently_playing(&mut self) -> Result<Option<Track>, reqwest::Error> {
+        let token = self.access_token().await?;
+        let response = self.http.get(CURRENTLY_PLAYING_URL).bearer_auth(token).send().await?.error_for_status()?;
+
+        // 204 when no device is active
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let playing: CurrentlyPlaying = response.json().await?;
+        Ok(playing.item.filter(|_| playing.is_playing).map(Item::into_track))
+    }
+}
+
+/// Poll the Spotify Web API for the current track while spotify or spotifyd is running
+pub fn spawn(config: SpotifyConfig, diagnostics: SharedDiagnostics, now_playing: NowPlaying) {
+    let http = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(http) => http,
+        Err(e) => {
+            eprintln!("Spotify disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let poll_interval = config.poll_interval;
+    let mut client = Client { http, config, access_token: None };
+
+    tokio::spawn(async move {
+        let mut failing = false;
+        loop {
+            let track = match now_playing::is_running(&diagnostics, APP).await {
+                true => match client.currently_playing().await {
+                    Ok(track) => {
+                        if failing {
+                            println!("Spotify API reachable again");
+                            failing = false;
+                        }
+                        track
+                    }
+                    Err(e) => {
+                        if !failing {
+                            eprintln!("Spotify API request failed: {}", e);
+                            failing = true;
+                        }
+                        None
+                    }
+                },
+                false => None,
+            };
+            now_playing.set(APP, "Spotify", track).await;
+            tokio::time::sleep(poll_interval).await;
+        }
+    });
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::env_parse;
+use crate::diagnostics::SharedDiagnostics;
+use crate::sleep::ASLEEP_TEXT;
+use crate::web_server::{Broadcaster, SharedData};
+use crate::OutputData;
+
+/// Served once the update loop has been stuck for the offline threshold
+const OFFLINE_TEXT: &str = "offline";
+
+/// How often the update loop's progress is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Marks the presence stale, and eventually offline, when the update loop stops scanning
+#[derive(Debug, Clone)]
+pub struct StaleGuard {
+    stale_after: Duration,
+    offline_after: Duration,
+    /// Last presence published by the update loop itself, restored when it recovers
+    fresh: Option<OutputData>,
+}
+
+impl StaleGuard {
+    /// `REPRESENCE_STALE_AFTER_SECS` (0 turns the guard off) and `REPRESENCE_OFFLINE_AFTER_SECS`
+    pub fn from_env() -> Option<Self> {
+        let stale_after = env_parse("REPRESENCE_STALE_AFTER_SECS", 30u64);
+        let offline_after = env_parse("REPRESENCE_OFFLINE_AFTER_SECS", 300u64);
+        (stale_after > 0).then(|| Self::new(Duration::from_secs(stale_after), Duration::from_secs(offline_after.max(stale_after))))
+    }
+
+    pub fn new(stale_after: Duration, offline_after: Duration) -> Self {
+        Self { stale_after, offline_after, fresh: None }
+    }
+
+    /// What to publish instead of `current` when the last scan was `age` ago, `None` to leave it
+    pub fn update(&mut self, current: &OutputData, age: Duration) -> Option<OutputData> {
+        if age < self.stale_after {
+            // The loop recovered without anything new to publish
+            return self.fresh.take().filter(|_| current.stale);
+        }
+        // Nothing scans while the machine sleeps
+        if current.text == ASLEEP_TEXT {
+            return None;
+        }
+        if !current.stale {
+            self.fresh = Some(current.clone());
+        }
+
+        let fresh = self.fresh.as_ref()?;
+        let output = if age >= self.offline_after {
+            OutputData { device: fresh.device.clone(), stale: true, ..OutputData::new(OFFLINE_TEXT) }
+        } else {
+            OutputData { stale: true, ..fresh.clone() }
+        };
+        (output != *current).then_some(output)
+    }
+}
+
+/// Check the update loop's last scan periodically and publish what the guard decides
+pub fn spawn(mut guard: StaleGuard, shared_data: SharedData, broadcaster: Broadcaster, diagnostics: SharedDiagnostics) {
+    let started = chrono::Utc::now();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (last_scan, energy_saving) = {
+                let diagnostics = diagnostics.read().await;
+                let last_scan = diagnostics.last_scan.unwrap_or(started).max(diagnostics.last_wake.unwrap_or(started));
+                (last_scan, diagnostics.energy_saving)
+            };
+            // Scans are held back on purpose while nobody is connected
+            let age = match energy_saving {
+                true => Duration::ZERO,
+                false => (chrono::Utc::now() - last_scan).to_std().unwrap_or_default(),
+            };
+
+            let current = shared_data.load_full();
+            let Some(output) = guard.update(&current, age) else {
+                continue;
+            };
+            // Leave it if the update loop published something in the meantime
+            let output = Arc::new(output);
+            if !Arc::ptr_eq(&shared_data.compare_and_swap(&current, output.clone()), &current) {
+                continue;
+            }
+            match (output.stale, output.text == OFFLINE_TEXT) {
+                (false, _) => println!("Update loop recovered, presence is current again"),
+                (true, true) => eprintln!("No scan for {}s, publishing '{}'", age.as_secs(), OFFLINE_TEXT),
+                (true, false) => eprintln!("No scan for {}s, marking the presence stale", age.as_secs()),
+            }
+            let _ = broadcaster.send(output);
+        }
+    });
+}
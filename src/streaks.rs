@@ -0,0 +1,191 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{Days, Local, NaiveDate};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+use crate::config::env_parse;
+use crate::history::{DailyTotals, PresenceStore, SharedHistory, StoreError};
+use crate::summary;
+use crate::web_server::AppState;
+
+/// Today's coding time grows all day, so the streak shown in the text is refreshed more often than summaries are rolled up
+const REFRESH_INTERVAL_SECS: u64 = 900;
+
+/// Streak lengths in days worth a milestone
+const STREAK_MILESTONES: [u32; 5] = [7, 30, 100, 365, 1000];
+/// Total coding hours worth a milestone
+const HOUR_MILESTONES: [i64; 5] = [100, 500, 1000, 5000, 10000];
+
+/// Days in a row of the current coding streak, 0 without one
+pub type SharedStreak = Arc<RwLock<u32>>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct StreakConfig {
+    /// Coding time a day needs to count
+    pub min_minutes: i64,
+    /// Mention the streak in the text while coding
+    pub in_text: bool,
+}
+
+impl StreakConfig {
+    /// `REPRESENCE_STREAK_MIN_MINS` (30 by default) and `REPRESENCE_STREAK_TEXT`
+    pub fn from_env() -> Self {
+        Self {
+            min_minutes: env_parse("REPRESENCE_STREAK_MIN_MINS", 30i64).max(1),
+            in_text: env_parse("REPRESENCE_STREAK_TEXT", false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Milestone {
+    /// e.g. `30-day coding streak` or `500 hours of coding`
+    pub name: String,
+    pub reached: NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Streaks {
+    /// Coding time a day needs to count
+    pub min_minutes: i64,
+    /// Days in a row up to today, or up to yesterday while today hasn't reached the minimum yet
+    pub current: u32,
+    pub current_start: Option<NaiveDate>,
+    pub longest: u32,
+    pub longest_start: Option<NaiveDate>,
+    /// Days that reached the minimum
+    pub coding_days: u32,
+    pub coding_hours: i64,
+    /// Oldest first
+    pub milestones: Vec<Milestone>,
+}
+
+/// Streaks and milestones from daily totals, oldest first
+pub fn compute(days: &[DailyTotals], today: NaiveDate, min_minutes: i64) -> Streaks {
+    let mut streaks = Streaks {
+        min_minutes,
+        current: 0,
+        current_start: None,
+        longest: 0,
+        longest_start: None,
+        coding_days: 0,
+        coding_hours: 0,
+        milestones: Vec::new(),
+    };
+    // The run being walked: first day, last day and length
+    let mut run: Option<(NaiveDate, NaiveDate, u32)> = None;
+    let mut coding_secs = 0;
+
+    for day in days {
+        let hours_before = coding_secs / 3600;
+        coding_secs += day.coding_secs;
+        for hours in HOUR_MILESTONES {
+            if hours_before < hours && coding_secs / 3600 >= hours {
+                streaks.milestones.push(Milestone { name: format!("{} hours of coding", hours), reached: day.day });
+            }
+        }
+
+        if day.coding_secs < min_minutes * 60 {
+            continue;
+        }
+        streaks.coding_days += 1;
+        let (start, length) = match run {
+            Some((start, last, length)) if last + Days::new(1) == day.day => (start, length + 1),
+            _ => (day.day, 1),
+        };
+        run = Some((start, day.day, length));
+        // Only the first streak to get there reaches a milestone
+        if length > streaks.longest {
+            if STREAK_MILESTONES.contains(&length) {
+                streaks.milestones.push(Milestone { name: format!("{}-day coding streak", length), reached: day.day });
+            }
+            streaks.longest = length;
+            streaks.longest_start = Some(start);
+        }
+    }
+
+    streaks.coding_hours = coding_secs / 3600;
+    if let Some((start, last, length)) = run {
+        if last == today || last + Days::new(1) == today {
+            streaks.current = length;
+            streaks.current_start = Some(start);
+        }
+    }
+    streaks
+}
+
+/// `day 42 of coding streak`, from the second day on
+pub fn text(current: u32) -> Option<String> {
+    (current >= 2).then(|| format!("day {} of coding streak", current))
+}
+
+/// Every stored summary plus today, blocking
+fn load(history: &dyn PresenceStore, today: NaiveDate) -> Result<Vec<DailyTotals>, StoreError> {
+    let mut days = history.summaries(NaiveDate::default(), today)?;
+    days.push(summary::compute_day(history, today)?);
+    Ok(days)
+}
+
+/// Keep the current streak up to date for the text
+pub fn spawn(history: SharedHistory, config: StreakConfig, streak: SharedStreak) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(REFRESH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let history = history.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let today = Local::now().date_naive();
+                load(history.as_ref(), today).map(|days| compute(&days, today, config.min_minutes))
+            })
+            .await;
+            match result {
+                Ok(Ok(streaks)) => *streak.write().await = streaks.current,
+                Ok(Err(e)) => eprintln!("Failed to compute the coding streak: {}", e),
+                Err(e) => eprintln!("Coding streak task panicked: {}", e),
+            }
+        }
+    });
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/api/streaks", get(get_streaks))
+}
+
+/// Current and longest coding streak and the milestones reached
+#[utoipa::path(
+    get,
+    path = "/api/streaks",
+    tag = "history",
+    responses(
+        (status = 200, description = "Coding streaks", body = Streaks),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_streaks(State(state): State<AppState>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "history is disabled" }))).into_response();
+    };
+
+    let min_minutes = StreakConfig::from_env().min_minutes;
+    let result = tokio::task::spawn_blocking(move || {
+        let today = Local::now().date_naive();
+        load(history.as_ref(), today).map(|days| compute(&days, today, min_minutes))
+    })
+    .await;
+    match result {
+        Ok(Ok(streaks)) => Json(streaks).into_response(),
+        Ok(Err(e)) => {
+            eprintln!("Streak query failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::OutputData;
+
+/// Current presence, sent on connect and after every subscribe message
+pub const SNAPSHOT: &str = "snapshot";
+/// Presence changed
+pub const PRESENCE_UPDATE: &str = "presence_update";
+
+const EVENTS: [&str; 2] = [SNAPSHOT, PRESENCE_UPDATE];
+
+/// Patch mode sends a full snapshot after this many patches
+const PATCHES_PER_SNAPSHOT: u32 = 30;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamMode {
+    /// Every message is the complete (filtered) object
+    #[default]
+    Full,
+    /// RFC 6902 JSON Patch deltas with periodic snapshots
+    Patch,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct SubscriptionParams {
+    /// Comma separated top-level fields to send, all by default
+    fields: Option<String>,
+    /// Comma separated events to receive (`snapshot`, `presence_update`), all by default
+    events: Option<String>,
+    /// `patch` to receive JSON Patch deltas instead of full objects
+    mode: Option<StreamMode>,
+}
+
+/// Messages a WebSocket client can send
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Replace the subscription, omitted lists mean everything
+    Subscribe {
+        fields: Option<Vec<String>>,
+        events: Option<Vec<String>>,
+        #[serde(default)]
+        mode: StreamMode,
+    },
+    /// Ask for a full snapshot, e.g. after a patch failed to apply
+    Resync,
+}
+
+/// What a streaming client wants to receive
+#[derive(Debug, Clone, Default)]
+pub struct Subscription {
+    fields: Option<Vec<String>>,
+    events: Option<Vec<String>>,
+    mode: StreamMode,
+}
+
+impl Subscription {
+    pub fn from_params(params: &SubscriptionParams) -> Result<Self, String> {
+        Self::new(
+            params.fields.as_deref().map(split_list),
+            params.events.as_deref().map(split_list),
+            params.mode.unwrap_or_default(),
+        )
+    }
+
+    fn new(fields: Option<Vec<String>>, events: Option<Vec<String>>, mode: StreamMode) -> Result<Self, String> {
+        if let Some(unknown) = events.iter().flatten().find(|event| !EVENTS.contains(&event.as_str())) {
+            return Err(format!("unknown event '{}'", unknown));
+        }
+        Ok(Self { fields, events, mode })
+    }
+
+    /// Apply a client message, leaving the subscription unchanged if it is invalid.
+    /// Returns whether the client should be sent the current state now.
+    pub fn handle_message(&mut self, text: &str) -> Result<bool, String> {
+        self.apply(serde_json::from_str(text).map_err(|e| format!("invalid message: {}", e))?)
+    }
+
+    /// Like `handle_message` for clients speaking MessagePack
+    pub fn handle_msgpack(&mut self, bytes: &[u8]) -> Result<bool, String> {
+        self.apply(rmp_serde::from_slice(bytes).map_err(|e| format!("invalid message: {}", e))?)
+    }
+
+    fn apply(&mut self, message: ClientMessage) -> Result<bool, String> {
+        match message {
+            ClientMessage::Subscribe { fields, events, mode } => {
+                *self = Self::new(fields, events, mode)?;
+                Ok(self.wants(SNAPSHOT))
+            }
+            ClientMessage::Resync => Ok(true),
+        }
+    }
+
+    pub fn mode(&self) -> StreamMode {
+        self.mode
+    }
+
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.as_ref().is_none_or(|events| events.iter().any(|e| e == event))
+    }
+
+    /// The data reduced to the subscribed fields
+    pub fn project(&self, data: &OutputData) -> Value {
+        let value = serde_json::to_value(data).unwrap_or(Value::Null);
+        match (&self.fields, value) {
+            (Some(fields), Value::Object(mut object)) => {
+                object.retain(|key, _| fields.contains(key));
+                Value::Object(object)
+            }
+            (_, value) => value,
+        }
+    }
+}
+
+/// What a client was last sent, to skip repeats and compute patches against
+#[derive(Debug, Default)]
+pub struct Delivery {
+    last_sent: Option<Value>,
+    seq: u64,
+    patches_since_snapshot: u32,
+}
+
+impl Delivery {
+    /// Make the next message complete, e.g. after the subscription changed
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+    }
+
+    /// Message carrying `projected`, `None` when the client already has exactly that.
+    /// WebSocket delivery is ordered, so every message sent counts as acknowledged.
+    pub fn message(&mut self, mode: StreamMode, projected: Value) -> Option<Value> {
+        if self.last_sent.as_ref() == Some(&projected) {
+            return None;
+        }
+
+        let message = match (mode, &self.last_sent) {
+            (StreamMode::Full, _) => projected.clone(),
+            (StreamMode::Patch, Some(last)) if self.patches_since_snapshot < PATCHES_PER_SNAPSHOT => {
+                self.seq += 1;
+                self.patches_since_snapshot += 1;
+                json!({ "type": "patch", "seq": self.seq, "base": self.seq - 1, "ops": json_patch::diff(last, &projected) })
+            }
+            (StreamMode::Patch, _) => {
+                self.seq += 1;
+                self.patches_since_snapshot = 0;
+                json!({ "type": "snapshot", "seq": self.seq, "data": projected })
+            }
+        };
+
+        self.last_sent = Some(projected);
+        Some(message)
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
@@ -0,0 +1,445 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::compose;
+use crate::config::env_parse;
+use crate::history::{DailyTotals, PresenceStore, SharedHistory, StoreError};
+use crate::sessions;
+use crate::web_server::{self, AppState};
+
+/// How often completed days are rolled up
+const SUMMARIZE_INTERVAL_SECS: u64 = 3600;
+
+/// Apps listed per summary, the stored totals keep all of them
+const TOP_APPS: usize = 5;
+
+pub const DEFAULT_DAILY_RANGE_DAYS: u64 = 30;
+pub const DEFAULT_WEEKLY_RANGE_WEEKS: u64 = 12;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct AppTime {
+    pub app: String,
+    pub secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    /// Seconds with any detected app in front
+    pub active_secs: i64,
+    /// Seconds spent in VS Code
+    pub coding_secs: i64,
+    /// Language edited the longest, from the VS Code extension
+    pub top_language: Option<String>,
+    pub top_apps: Vec<AppTime>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklySummary {
+    /// ISO week, e.g. `2025-W03`
+    pub week: String,
+    /// Monday of the week
+    pub start: NaiveDate,
+    pub active_secs: i64,
+    pub coding_secs: i64,
+    pub top_language: Option<String>,
+    pub top_apps: Vec<AppTime>,
+}
+
+/// One square of the heatmap
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HeatmapDay {
+    pub date: NaiveDate,
+    /// Minutes with any detected app in front
+    pub minutes: i64,
+    /// Minutes per kind of activity (`editor`, `browser`, `media`, ...), `other` for apps without one
+    pub kinds: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Heatmap {
+    pub year: i32,
+    /// The busiest day, to scale the colors
+    pub max_minutes: i64,
+    /// Every day of the year up to today, oldest first
+    pub days: Vec<HeatmapDay>,
+}
+
+impl DailySummary {
+    fn from_totals(totals: &DailyTotals) -> Self {
+        Self {
+            date: totals.day,
+            active_secs: totals.active_secs,
+            coding_secs: totals.coding_secs,
+            top_language: top_language(&totals.languages),
+            top_apps: top_apps(&totals.apps),
+        }
+    }
+}
+
+fn top_language(languages: &HashMap<String, i64>) -> Option<String> {
+    languages
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(language, _)| language.clone())
+}
+
+fn top_apps(apps: &HashMap<String, i64>) -> Vec<AppTime> {
+    let mut apps: Vec<AppTime> = apps
+        .iter()
+        .map(|(app, secs)| AppTime { app: app.clone(), secs: *secs })
+        .collect();
+    apps.sort_by(|a, b| b.secs.cmp(&a.secs).then_with(|| a.app.cmp(&b.app)));
+    apps.truncate(TOP_APPS);
+    apps
+}
+
+/// Every day of `year` until `last_day`, with the totals of the days that have some
+pub fn heatmap(year: i32, totals: &[DailyTotals], last_day: NaiveDate) -> Heatmap {
+    let by_day: HashMap<NaiveDate, &DailyTotals> = totals.iter().map(|totals| (totals.day, totals)).collect();
+    let mut days = Vec::new();
+    let mut day = NaiveDate::from_ymd_opt(year, 1, 1);
+    while let Some(date) = day.filter(|date| date.year() == year && *date <= last_day) {
+        let (minutes, kinds) = match by_day.get(&date) {
+            Some(totals) => {
+                let mut kinds: BTreeMap<String, i64> = BTreeMap::new();
+                for (app, secs) in &totals.apps {
+                    *kinds.entry(compose::category(app).unwrap_or("other").to_string()).or_default() += secs;
+                }
+                kinds.values_mut().for_each(|secs| *secs /= 60);
+                kinds.retain(|_, minutes| *minutes > 0);
+                (totals.active_secs / 60, kinds)
+            }
+            None => (0, BTreeMap::new()),
+        };
+        days.push(HeatmapDay { date, minutes, kinds });
+        day = date.succ_opt();
+    }
+    let max_minutes = days.iter().map(|day| day.minutes).max().unwrap_or_default();
+    Heatmap { year, max_minutes, days }
+}
+
+/// Local midnight at the start of `day` in unix millis
+fn day_start(day: NaiveDate) -> i64 {
+    Local
+        .from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|start| start.timestamp_millis())
+        .unwrap_or_default()
+}
+
+fn local_day(millis: i64) -> NaiveDate {
+    Local
+        .timestamp_millis_opt(millis)
+        .single()
+        .map(|datetime| datetime.date_naive())
+        .unwrap_or_default()
+}
+
+/// Aggregate the raw events of one local day, blocking
+pub fn compute_day(history: &dyn PresenceStore, day: NaiveDate) -> Result<DailyTotals, StoreError> {
+    let start = day_start(day);
+    let end = day_start(day + Days::new(1));
+
+    let mut totals = DailyTotals { day, ..Default::default() };
+    let mut active_millis = 0;
+    let mut coding_millis = 0;
+    let mut apps: HashMap<String, i64> = HashMap::new();
+    let mut languages: HashMap<String, i64> = HashMap::new();
+
+    for span in history.spans(start, end)? {
+        let millis = span.end - span.start;
+        let Some(app) = span.app else {
+            continue;
+        };
+
+        active_millis += millis;
+        if app.starts_with("code") {
+            coding_millis += millis;
+            if let Some(language) = span.language {
+                *languages.entry(language).or_default() += millis;
+            }
+        }
+        *apps.entry(app).or_default() += millis;
+    }
+
+    totals.active_secs = active_millis / 1000;
+    totals.coding_secs = coding_millis / 1000;
+    totals.apps = apps.into_iter().map(|(app, millis)| (app, millis / 1000)).collect();
+    totals.languages = languages
+        .into_iter()
+        .map(|(language, millis)| (language, millis / 1000))
+        .collect();
+    Ok(totals)
+}
+
+/// Summarize every completed day that has no summary yet and store finished sessions, then prune old raw events, blocking
+fn summarize_pending(history: &dyn PresenceStore, retention_days: u64, session_gap: chrono::Duration) -> Result<(), StoreError> {
+    let today = Local::now().date_naive();
+
+    let mut day = match history.latest_summary_day()? {
+        Some(latest) => latest + Days::new(1),
+        None => match history.first_event()? {
+            Some(first) => local_day(first),
+            None => return Ok(()),
+        },
+    };
+
+    while day < today {
+        history.store_summary(&compute_day(history, day)?)?;
+        day = day + Days::new(1);
+    }
+
+    sessions::store_closed(history, session_gap)?;
+
+    // Only days that are already summarized may lose their raw events
+    if retention_days > 0 {
+        let cutoff = today - Days::new(retention_days);
+        let pruned = history.prune(day_start(cutoff.min(day)))?;
+        if pruned > 0 {
+            println!("Pruned {} history event(s) older than {}", pruned, cutoff);
+        }
+    }
+
+    Ok(())
+}
+
+/// Roll raw history into daily summaries and sessions once an hour.
+/// `REPRESENCE_HISTORY_RETENTION_DAYS` (0 keeps everything) limits how long raw events are kept.
+pub fn spawn(history: SharedHistory) {
+    let retention_days: u64 = env_parse("REPRESENCE_HISTORY_RETENTION_DAYS", 0);
+    let session_gap = sessions::gap_from_env();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SUMMARIZE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let history = history.clone();
+            match tokio::task::spawn_blocking(move || summarize_pending(history.as_ref(), retention_days, session_gap)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Failed to summarize history: {}", e),
+                Err(e) => eprintln!("History summarizer panicked: {}", e),
+            }
+        }
+    });
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/summary/daily", get(get_daily_summary))
+        .route("/api/summary/weekly", get(get_weekly_summary))
+        .route("/api/heatmap", get(get_heatmap))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SummaryParams {
+    /// First day (`YYYY-MM-DD`, local time), inclusive
+    from: Option<String>,
+    /// Last day, inclusive, defaults to today
+    to: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HeatmapParams {
+    /// Calendar year, defaults to the current one
+    year: Option<i32>,
+}
+
+/// Stored summaries for `[from, to]`, with today computed live when it is in range
+async fn load_range(history: SharedHistory, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyTotals>, StatusCode> {
+    let result = tokio::task::spawn_blocking(move || {
+        let mut days = history.summaries(from, to)?;
+        let today = Local::now().date_naive();
+        if from <= today && today <= to {
+            days.push(compute_day(history.as_ref(), today)?);
+        }
+        Ok::<_, StoreError>(days)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(days)) => Ok(days),
+        Ok(Err(e)) => {
+            eprintln!("Summary query failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Resolve the requested range, `default_days` back from `to` when `from` is missing
+fn parse_range(params: &SummaryParams, default_days: u64) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |value: &str, name: &str| {
+        value
+            .parse::<NaiveDate>()
+            .map_err(|_| format!("invalid '{}'", name))
+    };
+
+    let to = params.to.as_deref().map(|to| parse(to, "to")).transpose()?;
+    let from = params.from.as_deref().map(|from| parse(from, "from")).transpose()?;
+    range(from, to, default_days)
+}
+
+/// `[from, to]`, up to today and `default_days` long unless given
+pub fn range(from: Option<NaiveDate>, to: Option<NaiveDate>, default_days: u64) -> Result<(NaiveDate, NaiveDate), String> {
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or_else(|| to - Days::new(default_days - 1));
+
+    if from > to {
+        return Err("'from' is after 'to'".to_string());
+    }
+    Ok((from, to))
+}
+
+fn history_disabled() -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "history is disabled" }))).into_response()
+}
+
+/// Per-day totals of active and coding time with top language and apps
+#[utoipa::path(
+    get,
+    path = "/api/summary/daily",
+    tag = "history",
+    params(SummaryParams),
+    responses(
+        (status = 200, description = "Daily summaries, oldest first", body = [DailySummary]),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_daily_summary(State(state): State<AppState>, Query(params): Query<SummaryParams>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return history_disabled();
+    };
+
+    let (from, to) = match parse_range(&params, DEFAULT_DAILY_RANGE_DAYS) {
+        Ok(range) => range,
+        Err(message) => return web_server::bad_request(&message),
+    };
+
+    match daily(history, from, to).await {
+        Ok(days) => Json(days).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Daily summaries rolled up into ISO weeks
+#[utoipa::path(
+    get,
+    path = "/api/summary/weekly",
+    tag = "history",
+    params(SummaryParams),
+    responses(
+        (status = 200, description = "Weekly summaries, oldest first", body = [WeeklySummary]),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_weekly_summary(State(state): State<AppState>, Query(params): Query<SummaryParams>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return history_disabled();
+    };
+
+    let (from, to) = match parse_range(&params, DEFAULT_WEEKLY_RANGE_WEEKS * 7) {
+        Ok(range) => range,
+        Err(message) => return web_server::bad_request(&message),
+    };
+    match weekly(history, from, to).await {
+        Ok(weeks) => Json(weeks).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Active minutes per day of a year, split by kind of activity, for a contribution graph
+#[utoipa::path(
+    get,
+    path = "/api/heatmap",
+    tag = "history",
+    params(HeatmapParams),
+    responses(
+        (status = 200, description = "Every day of the year up to today", body = Heatmap),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_heatmap(State(state): State<AppState>, Query(params): Query<HeatmapParams>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return history_disabled();
+    };
+
+    let today = Local::now().date_naive();
+    let year = params.year.unwrap_or_else(|| today.year());
+    let (first, last) = match (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year, 12, 31)) {
+        (Some(first), Some(last)) => (first, last.min(today)),
+        _ => return web_server::bad_request("invalid 'year'"),
+    };
+    if first > last {
+        return Json(heatmap(year, &[], last)).into_response();
+    }
+
+    match load_range(history, first, last).await {
+        Ok(days) => Json(heatmap(year, &days, last)).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Summaries of the days in `[from, to]`, oldest first
+pub async fn daily(history: SharedHistory, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailySummary>, StatusCode> {
+    let days = load_range(history, from, to).await?;
+    Ok(days.iter().map(DailySummary::from_totals).collect())
+}
+
+/// Summaries of the ISO weeks overlapping `[from, to]`, oldest first
+pub async fn weekly(history: SharedHistory, from: NaiveDate, to: NaiveDate) -> Result<Vec<WeeklySummary>, StatusCode> {
+    // Whole weeks only, so the first one isn't cut short
+    let from = from.week(chrono::Weekday::Mon).first_day();
+    let days = load_range(history, from, to).await?;
+
+    let mut weeks: Vec<(NaiveDate, DailyTotals)> = Vec::new();
+    for day in days {
+        let start = day.day.week(chrono::Weekday::Mon).first_day();
+        if weeks.last().is_none_or(|(week_start, _)| *week_start != start) {
+            weeks.push((start, DailyTotals { day: start, ..Default::default() }));
+        }
+        let Some((_, week)) = weeks.last_mut() else {
+            continue;
+        };
+        week.active_secs += day.active_secs;
+        week.coding_secs += day.coding_secs;
+        for (app, secs) in day.apps {
+            *week.apps.entry(app).or_default() += secs;
+        }
+        for (language, secs) in day.languages {
+            *week.languages.entry(language).or_default() += secs;
+        }
+    }
+
+    Ok(weeks
+        .into_iter()
+        .map(|(start, totals)| {
+            let iso = start.iso_week();
+            WeeklySummary {
+                week: format!("{}-W{:02}", iso.year(), iso.week()),
+                start,
+                active_secs: totals.active_secs,
+                coding_secs: totals.coding_secs,
+                top_language: top_language(&totals.languages),
+                top_apps: top_apps(&totals.apps),
+            }
+        })
+        .collect())
+}
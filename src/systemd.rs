@@ -0,0 +1,144 @@
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener};
+use std::time::Duration;
+
+use crate::listener::Listener;
+
+/// First file descriptor passed by systemd socket activation (SD_LISTEN_FDS_START)
+const LISTEN_FDS_START: i32 = 3;
+
+/// Send a state string (e.g. "READY=1") to the service manager, if we were started as Type=notify
+pub fn notify(state: &str) -> bool {
+    match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => notify_socket(&path.to_string_lossy(), state),
+        None => false,
+    }
+}
+
+/// Send a state string to the notification socket at `path`
+fn notify_socket(path: &str, state: &str) -> bool {
+    let addr = match path.strip_prefix('@') {
+        // Leading '@' means a socket in the abstract namespace
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path),
+    };
+
+    let Ok(addr) = addr else {
+        return false;
+    };
+
+    UnixDatagram::unbound()
+        .and_then(|socket| socket.send_to_addr(state.as_bytes(), &addr))
+        .is_ok()
+}
+
+pub fn notify_ready() -> bool {
+    notify("READY=1")
+}
+
+/// The service manager's watchdog, expecting a ping at least every `interval`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchdog {
+    pub interval: Duration,
+    /// Notification socket the pings go to
+    pub socket: String,
+}
+
+impl Watchdog {
+    /// `None` when WatchdogSec is not configured or the watchdog is meant for another process.
+    /// The interval is half the configured timeout, as recommended by sd_watchdog_enabled(3).
+    pub fn from_env() -> Option<Self> {
+        if let Ok(pid) = env::var("WATCHDOG_PID") {
+            if pid.parse::<u32>().ok()? != std::process::id() {
+                return None;
+            }
+        }
+
+        let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+        if usec == 0 {
+            return None;
+        }
+
+        Some(Self { interval: Duration::from_micros(usec / 2), socket: env::var("NOTIFY_SOCKET").ok()? })
+    }
+
+    pub fn ping(&self) -> bool {
+        notify_socket(&self.socket, "WATCHDOG=1")
+    }
+}
+
+/// Take over the listening sockets passed via systemd socket activation, if any. The LISTEN_* variables
+/// are cleared once they turn out to be meant for us, so child processes don't inherit them.
+/// TCP and Unix stream sockets (`ListenStream=/path`) are served, anything else is an error.
+pub fn take_listeners() -> io::Result<Vec<Listener>> {
+    let pid = env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok());
+    let fds = env::var("LISTEN_FDS").ok().and_then(|fds| fds.parse::<i32>().ok());
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return Ok(Vec::new());
+    };
+    let Some(listeners) = take_listeners_from(pid, fds) else {
+        return Ok(Vec::new());
+    };
+
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    listeners
+}
+
+/// Take over the `fds` sockets systemd passed to `pid`, `None` when that is another process,
+/// e.g. the one that started us, so the descriptors aren't ours to take
+pub fn take_listeners_from(pid: u32, fds: i32) -> Option<io::Result<Vec<Listener>>> {
+    if pid != std::process::id() {
+        return None;
+    }
+
+    let listeners = (LISTEN_FDS_START..LISTEN_FDS_START + fds)
+        .map(|fd| {
+            // SAFETY: systemd guarantees fds 3..3+LISTEN_FDS are open sockets owned by this process,
+            // and the family check picks the matching wrapper
+            let listener = match stream_family(fd)? {
+                libc::AF_INET | libc::AF_INET6 => Listener::Tcp(unsafe { TcpListener::from_raw_fd(fd) }),
+                libc::AF_UNIX => Listener::Unix(unsafe { UnixListener::from_raw_fd(fd) }),
+                family => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("socket-activated fd {} has address family {}, expected a TCP or Unix socket", fd, family),
+                    ))
+                }
+            };
+            match &listener {
+                Listener::Tcp(listener) => listener.set_nonblocking(true)?,
+                Listener::Unix(listener) => listener.set_nonblocking(true)?,
+            }
+            Ok(listener)
+        })
+        .collect();
+    Some(listeners)
+}
+
+/// Address family of the stream socket `fd`, an error for datagram sockets (`ListenDatagram=`) and other files
+fn stream_family(fd: RawFd) -> io::Result<i32> {
+    let mut kind: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `kind` and `len` describe a buffer of the size SO_TYPE writes
+    if unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_TYPE, (&mut kind as *mut libc::c_int).cast(), &mut len) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if kind != libc::SOCK_STREAM {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("socket-activated fd {} is not a stream socket", fd)));
+    }
+
+    // SAFETY: all zeroes is a valid sockaddr_storage, which fits any address family
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    // SAFETY: `addr` and `len` describe a buffer large enough for any address
+    if unsafe { libc::getsockname(fd, (&mut addr as *mut libc::sockaddr_storage).cast(), &mut len) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(addr.ss_family as i32)
+}
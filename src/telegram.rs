@@ -0,0 +1,220 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::control::{Control, ControlSender};
+use crate::web_server::SharedData;
+
+/// How long one `getUpdates` call waits for messages
+const POLL_TIMEOUT_SECS: u64 = 50;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Commands sent this long before the bot got them are dropped, they were meant for another moment
+const MAX_COMMAND_AGE_SECS: i64 = 300;
+
+const USAGE: &str = "/status, /override <text> <duration> (e.g. 45m or 1h30m), /override off, /privacy on|off, /profile <name>|off";
+
+#[derive(Debug, Clone)]
+pub struct TelegramConfig {
+    pub token: String,
+    /// Only messages from this account are answered
+    pub user_id: i64,
+}
+
+impl TelegramConfig {
+    /// `REPRESENCE_TELEGRAM_TOKEN` and `REPRESENCE_TELEGRAM_USER_ID`, `None` unless both are set
+    pub fn from_env() -> Option<Self> {
+        let token = env_var("REPRESENCE_TELEGRAM_TOKEN")?;
+        match env_parse("REPRESENCE_TELEGRAM_USER_ID", 0i64) {
+            0 => {
+                eprintln!("Telegram bot disabled, REPRESENCE_TELEGRAM_USER_ID must be your numeric account ID");
+                None
+            }
+            user_id => Some(Self { token, user_id }),
+        }
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("https://api.telegram.org/bot{}/{}", self.token, method)
+    }
+}
+
+/// What a message asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotCommand {
+    Status,
+    Control(Control),
+}
+
+/// Parse a message, the error is the reply explaining what went wrong
+pub fn parse_command(text: &str) -> Result<BotCommand, String> {
+    let mut words = text.split_whitespace();
+    // Commands in groups come as `/status@my_bot`
+    let command = words.next().unwrap_or_default().split('@').next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+    match (command, args.as_slice()) {
+        ("/status", []) => Ok(BotCommand::Status),
+        ("/privacy", ["on"]) => Ok(BotCommand::Control(Control::SetPrivacy(true))),
+        ("/privacy", ["off"]) => Ok(BotCommand::Control(Control::SetPrivacy(false))),
+        ("/override", ["off"]) => Ok(BotCommand::Control(Control::EndOverride)),
+        ("/profile", ["off"]) => Ok(BotCommand::Control(Control::SwitchProfile(None))),
+        ("/profile", [name]) => Ok(BotCommand::Control(Control::SwitchProfile(Some(name.to_string())))),
+        ("/override", [text @ .., duration]) if !text.is_empty() => match parse_duration(duration) {
+            Some(duration) => Ok(BotCommand::Control(Control::Override { text: text.join(" "), duration })),
+            None => Err(format!("'{}' isn't a duration, try 45m or 1h30m", duration)),
+        },
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// `90s`, `45m`, `2h`, `1h30m` and the like
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            unit => {
+                let amount: u64 = number.parse().ok()?;
+                number.clear();
+                seconds += amount * match unit {
+                    'd' => 86400,
+                    'h' => 3600,
+                    'm' => 60,
+                    's' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    // A unit is required, and the duration must be something
+    (number.is_empty() && seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+#[derive(Debug, Deserialize)]
+struct Updates {
+    result: Vec<Update>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    from: Option<User>,
+    chat: Chat,
+    /// Unix time
+    date: i64,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Answer commands from the configured account, long polling the Bot API
+pub fn spawn(config: TelegramConfig, shared_data: SharedData, controls: ControlSender) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Telegram bot disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut offset = 0;
+        loop {
+            let updates = match poll(&client, &config, offset).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    // Without the URL, it holds the bot token
+                    eprintln!("Telegram polling failed: {}", e.without_url());
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            for update in updates {
+                offset = offset.max(update.update_id + 1);
+                let message = match update.message {
+                    Some(message) => message,
+                    None => continue,
+                };
+                if message.from.as_ref().map(|user| user.id) != Some(config.user_id) {
+                    continue;
+                }
+                if chrono::Utc::now().timestamp() - message.date > MAX_COMMAND_AGE_SECS {
+                    continue;
+                }
+                let reply = match parse_command(message.text.as_deref().unwrap_or_default()) {
+                    Ok(BotCommand::Status) => status(&shared_data),
+                    Ok(BotCommand::Control(control)) => {
+                        let reply = describe(&control);
+                        match controls.send(control).await {
+                            Ok(()) => reply,
+                            Err(_) => "The update loop isn't running".to_string(),
+                        }
+                    }
+                    Err(reply) => reply,
+                };
+                if let Err(e) = send_message(&client, &config, message.chat.id, &reply).await {
+                    eprintln!("Failed to answer on Telegram: {}", e.without_url());
+                }
+            }
+        }
+    });
+}
+
+fn status(shared_data: &SharedData) -> String {
+    let data = shared_data.load();
+    let apps: Vec<&str> = data.apps.iter().map(|app| app.name.as_str()).collect();
+    match apps.is_empty() {
+        true => data.text.clone(),
+        false => format!("{}\nRunning: {}", data.text, apps.join(", ")),
+    }
+}
+
+fn describe(control: &Control) -> String {
+    match control {
+        Control::SetPrivacy(true) => "Privacy mode on".to_string(),
+        Control::SetPrivacy(false) => "Privacy mode off".to_string(),
+        Control::Override { text, duration } => format!("Showing '{}' for {} minute(s)", text, duration.as_secs().div_ceil(60)),
+        Control::EndOverride => "Back to the detected presence".to_string(),
+        Control::SwitchProfile(Some(name)) => format!("Switching to profile {}", name),
+        Control::SwitchProfile(None) => "Switching back to no profile".to_string(),
+        _ => "Done".to_string(),
+    }
+}
+
+async fn poll(client: &reqwest::Client, config: &TelegramConfig, offset: i64) -> reqwest::Result<Vec<Update>> {
+    let response = client
+        .get(config.method_url("getUpdates"))
+        .query(&[("offset", offset.to_string()), ("timeout", POLL_TIMEOUT_SECS.to_string()), ("allowed_updates", r#"["message"]"#.to_string())])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json::<Updates>().await?.result)
+}
+
+async fn send_message(client: &reqwest::Client, config: &TelegramConfig, chat_id: i64, text: &str) -> reqwest::Result<()> {
+    client
+        .post(config.method_url("sendMessage"))
+        .json(&serde_json::json!({ "chat_id": chat_id, "text": text }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use opentelemetry::context::{Context, FutureExt};
+use opentelemetry::global::{self, BoxedSpan};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::trace::{TraceContextExt, Tracer};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::config::env_var;
+use crate::device;
+
+const SCOPE: &str = "represence";
+
+/// Parts of the pipeline worth timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// One `Pipeline::step`, with the stages below as children
+    ScanCycle,
+    /// Asking the VS Code extension for the open file
+    VsCode,
+    /// Handing a new presence to every subscriber
+    Broadcast,
+}
+
+impl Stage {
+    pub fn span_name(self) -> &'static str {
+        match self {
+            Self::ScanCycle => "represence.scan_cycle",
+            Self::VsCode => "represence.vscode",
+            Self::Broadcast => "represence.broadcast",
+        }
+    }
+
+    fn histogram(self) -> &'static Histogram<f64> {
+        static HISTOGRAMS: OnceLock<[Histogram<f64>; 3]> = OnceLock::new();
+        let histograms = HISTOGRAMS.get_or_init(|| {
+            let meter = global::meter(SCOPE);
+            [Self::ScanCycle, Self::VsCode, Self::Broadcast].map(|stage| {
+                meter.f64_histogram(format!("{}.duration", stage.span_name())).with_unit("s").build()
+            })
+        });
+        &histograms[self as usize]
+    }
+}
+
+/// A running span that also records its duration in the stage's histogram
+pub struct Measurement {
+    stage: Stage,
+    context: Context,
+    start: Instant,
+}
+
+impl Measurement {
+    /// Started as a child of the current span, if any
+    pub fn start(stage: Stage) -> Self {
+        let span: BoxedSpan = global::tracer(SCOPE).start(stage.span_name());
+        Self { stage, context: Context::current_with_span(span), start: Instant::now() }
+    }
+
+    /// Only on the span, for high-cardinality values like the cycle number
+    pub fn set_span_attribute(&self, attribute: KeyValue) {
+        self.context.span().set_attribute(attribute);
+    }
+
+    /// End the span, `attributes` go on both the span and the duration
+    pub fn finish(self, attributes: &[KeyValue]) {
+        self.stage.histogram().record(self.start.elapsed().as_secs_f64(), attributes);
+        let span = self.context.span();
+        span.set_attributes(attributes.iter().cloned());
+        span.end();
+    }
+}
+
+/// Run `future` inside a `stage` span, so spans started while it runs become children
+pub async fn measure<F: Future>(stage: Stage, span_attributes: Vec<KeyValue>, future: F) -> F::Output {
+    let measurement = Measurement::start(stage);
+    for attribute in span_attributes {
+        measurement.set_span_attribute(attribute);
+    }
+    let output = future.with_context(measurement.context.clone()).await;
+    measurement.finish(&[]);
+    output
+}
+
+/// OTLP export of spans and metrics over HTTP
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// Collector base URL, e.g. `http://localhost:4318`
+    pub endpoint: String,
+}
+
+impl TelemetryConfig {
+    /// `REPRESENCE_OTLP_ENDPOINT`, `None` when unset
+    pub fn from_env() -> Option<Self> {
+        env_var("REPRESENCE_OTLP_ENDPOINT").map(|endpoint| Self { endpoint: endpoint.trim_end_matches('/').to_string() })
+    }
+
+    /// Install the global tracer and meter providers, flushed when the guard is dropped
+    pub fn init(self) -> Option<Telemetry> {
+        let spans = SpanExporter::builder().with_http().with_endpoint(format!("{}/v1/traces", self.endpoint)).build();
+        let metrics = MetricExporter::builder().with_http().with_endpoint(format!("{}/v1/metrics", self.endpoint)).build();
+        let (spans, metrics) = match (spans, metrics) {
+            (Ok(spans), Ok(metrics)) => (spans, metrics),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("OpenTelemetry export disabled: {}", e);
+                return None;
+            }
+        };
+        let resource = Resource::builder()
+            .with_service_name("represence")
+            .with_attributes([
+                KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+                KeyValue::new("host.name", device::hostname()),
+            ])
+            .build();
+        let tracer_provider = SdkTracerProvider::builder().with_batch_exporter(spans).with_resource(resource.clone()).build();
+        let meter_provider = SdkMeterProvider::builder().with_periodic_exporter(metrics).with_resource(resource).build();
+        global::set_tracer_provider(tracer_provider.clone());
+        global::set_meter_provider(meter_provider.clone());
+        println!("Exporting OpenTelemetry traces and metrics to {}", self.endpoint);
+        Some(Telemetry { tracer_provider, meter_provider })
+    }
+}
+
+/// Keeps the exporters alive, shutting them down flushes what's left
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            eprintln!("Failed to flush metrics: {}", e);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::env_parse;
+
+/// Shells at the bottom of the tree mean the terminal sits at a prompt
+const SHELLS: [&str; 11] = ["bash", "zsh", "fish", "sh", "dash", "ksh", "tcsh", "nu", "elvish", "xonsh", "tmux"];
+
+/// Longest command text shown, longer ones are cut at a word
+const MAX_COMMAND_LEN: usize = 40;
+
+#[derive(Debug, Clone)]
+struct Process {
+    pid: u32,
+    ppid: u32,
+    comm: String,
+    started: u64,
+}
+
+/// Finds what runs inside a terminal emulator, through tmux or the terminal's process tree
+#[derive(Debug, Clone)]
+pub struct TerminalInspector {
+    proc_path: PathBuf,
+}
+
+impl TerminalInspector {
+    pub fn new(proc_path: &Path) -> Self {
+        Self { proc_path: proc_path.to_path_buf() }
+    }
+
+    /// Enabled unless `REPRESENCE_TERMINAL_COMMANDS=false`
+    pub fn from_env(proc_path: &Path) -> Option<Self> {
+        env_parse("REPRESENCE_TERMINAL_COMMANDS", true).then(|| Self::new(proc_path))
+    }
+
+    /// Program and subcommand running in the newest `terminal` window, e.g. `cargo test`.
+    /// Only plain words are shown so paths and arguments never leak.
+    pub async fn foreground_command(&self, terminal: &str) -> Option<String> {
+        let proc_path = self.proc_path.clone();
+        let processes = tokio::task::spawn_blocking(move || read_processes(&proc_path)).await.ok()?;
+
+        let mut children: HashMap<u32, Vec<&Process>> = HashMap::new();
+        for process in &processes {
+            children.entry(process.ppid).or_default().push(process);
+        }
+
+        let root = processes
+            .iter()
+            .filter(|process| process.comm.starts_with(terminal))
+            .max_by_key(|process| process.started)?;
+
+        // Inside tmux the terminal only runs the client, the command lives under the active pane
+        let runs_tmux = newest_descendant(root.pid, &children, &|process| process.comm.starts_with("tmux")).is_some();
+        let root_pid = match runs_tmux {
+            true => active_tmux_pane().await.unwrap_or(root.pid),
+            false => root.pid,
+        };
+
+        let leaf = newest_descendant(root_pid, &children, &|_| true)?;
+        if SHELLS.contains(&leaf.comm.as_str()) {
+            return None;
+        }
+
+        let cmdline = std::fs::read(self.proc_path.join(leaf.pid.to_string()).join("cmdline")).ok()?;
+        Some(describe_command(&cmdline).unwrap_or_else(|| leaf.comm.clone()))
+    }
+}
+
+fn read_processes(proc_path: &Path) -> Vec<Process> {
+    let Ok(entries) = std::fs::read_dir(proc_path) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| {
+            let pid: u32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(proc_path.join(pid.to_string()).join("stat")).ok()?;
+            let (head, rest) = stat.rsplit_once(')')?;
+            let comm = head.split_once('(')?.1.to_string();
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            Some(Process {
+                pid,
+                ppid: fields.get(1)?.parse().ok()?,
+                comm,
+                started: fields.get(19)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Follow the most recently started child down the tree, the last process matching `filter`
+fn newest_descendant<'a>(
+    pid: u32,
+    children: &HashMap<u32, Vec<&'a Process>>,
+    filter: &dyn Fn(&Process) -> bool,
+) -> Option<&'a Process> {
+    let mut found = None;
+    let mut current = pid;
+    while let Some(child) = children.get(&current).and_then(|kids| kids.iter().max_by_key(|process| process.started)) {
+        if filter(child) {
+            found = Some(*child);
+        }
+        current = child.pid;
+    }
+    found
+}
+
+/// PID of the shell in the active pane of an attached tmux session
+async fn active_tmux_pane() -> Option<u32> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(1),
+        Command::new("tmux")
+            .args(["list-panes", "-a", "-F", "#{session_attached} #{window_active} #{pane_active} #{pane_pid}"])
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("1 1 1 ")?.trim().parse().ok())
+}
+
+/// Program name plus its first argument when that is a plain word, e.g. `cargo test` but just `nvim` for `nvim secret.txt`
+fn describe_command(cmdline: &[u8]) -> Option<String> {
+    let mut args = cmdline.split(|byte| *byte == 0).filter(|arg| !arg.is_empty()).map(String::from_utf8_lossy);
+    let program = args.next()?.rsplit('/').next()?.to_string();
+
+    let subcommand = args
+        .next()
+        .filter(|arg| arg.chars().all(|c| c.is_ascii_lowercase() || c == '-') && !arg.starts_with('-'));
+
+    let mut command = match subcommand {
+        Some(subcommand) => format!("{} {}", program, subcommand),
+        None => program,
+    };
+    command.truncate(MAX_COMMAND_LEN);
+    Some(command)
+}
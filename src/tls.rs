@@ -0,0 +1,105 @@
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use futures_util::StreamExt;
+use rustls_acme::caches::DirCache;
+use rustls_acme::AcmeConfig;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+
+use crate::config::{env_list, env_parse, env_var};
+
+#[derive(Debug, Clone)]
+pub enum TlsConfig {
+    /// Certificate and private key in PEM files
+    Files { cert: PathBuf, key: PathBuf },
+    /// Certificates obtained and renewed automatically from Let's Encrypt (TLS-ALPN-01)
+    Acme {
+        domains: Vec<String>,
+        contact: Vec<String>,
+        cache_dir: PathBuf,
+        production: bool,
+    },
+}
+
+impl TlsConfig {
+    /// Explicit cert/key files take precedence over ACME, `None` keeps plain HTTP
+    pub fn from_env() -> Option<Self> {
+        if let (Some(cert), Some(key)) = (env_var("REPRESENCE_TLS_CERT"), env_var("REPRESENCE_TLS_KEY")) {
+            return Some(Self::Files { cert: cert.into(), key: key.into() });
+        }
+
+        let domains = env_list("REPRESENCE_ACME_DOMAINS");
+        if domains.is_empty() {
+            return None;
+        }
+
+        Some(Self::Acme {
+            domains,
+            contact: env_list("REPRESENCE_ACME_EMAIL")
+                .into_iter()
+                .map(|email| format!("mailto:{}", email))
+                .collect(),
+            cache_dir: env_var("REPRESENCE_ACME_CACHE")
+                .unwrap_or_else(|| "acme-cache".to_string())
+                .into(),
+            production: !env_parse("REPRESENCE_ACME_STAGING", false),
+        })
+    }
+}
+
+/// Serve the app over HTTPS on already bound listeners
+pub async fn serve(config: TlsConfig, listeners: Vec<TcpListener>, app: Router) -> io::Result<()> {
+    let mut servers = JoinSet::new();
+
+    // axum-server and rustls-acme are built without a bundled provider, use ring process-wide
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    match config {
+        TlsConfig::Files { cert, key } => {
+            let rustls_config = RustlsConfig::from_pem_file(&cert, &key).await?;
+            println!("TLS enabled with certificate {}", cert.display());
+            for listener in listeners {
+                let server = axum_server::from_tcp_rustls(listener, rustls_config.clone())?;
+                let app = app.clone();
+                servers.spawn(async move {
+                    server.serve(app.into_make_service_with_connect_info::<SocketAddr>()).await
+                });
+            }
+        }
+        TlsConfig::Acme { domains, contact, cache_dir, production } => {
+            println!("TLS enabled via ACME for {}", domains.join(", "));
+            let mut state = AcmeConfig::new(domains)
+                .contact(contact)
+                .cache(DirCache::new(cache_dir))
+                .directory_lets_encrypt(production)
+                .state();
+            let acceptor = state.axum_acceptor(state.default_rustls_config());
+
+            // Drives certificate ordering and renewal
+            tokio::spawn(async move {
+                while let Some(event) = state.next().await {
+                    match event {
+                        Ok(event) => println!("ACME: {:?}", event),
+                        Err(e) => eprintln!("ACME error: {:?}", e),
+                    }
+                }
+            });
+
+            for listener in listeners {
+                let server = axum_server::from_tcp(listener)?.acceptor(acceptor.clone());
+                let app = app.clone();
+                servers.spawn(async move {
+                    server.serve(app.into_make_service_with_connect_info::<SocketAddr>()).await
+                });
+            }
+        }
+    }
+
+    while let Some(result) = servers.join_next().await {
+        result.map_err(io::Error::other)??;
+    }
+
+    Ok(())
+}
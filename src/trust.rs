@@ -0,0 +1,153 @@
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::{Stream, StreamExt};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use crate::config::{env_list, env_var};
+use crate::control;
+use crate::web_server::{self, bearer_token, constant_time_eq, AppState, Broadcaster, SharedData};
+use crate::OutputData;
+
+/// How much of the presence a visitor gets to see, set on every request by `middleware`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    /// The coarse status, without file names, commands, tracks or the device
+    Public,
+    /// Everything, for requests carrying a friend token
+    Friend,
+}
+
+impl Trust {
+    /// `data` as this visitor may see it
+    pub fn view(self, data: Arc<OutputData>) -> Arc<OutputData> {
+        if self == Self::Friend {
+            return match data.coarse_text {
+                Some(_) => Arc::new(OutputData { coarse_text: None, ..(*data).clone() }),
+                None => data,
+            };
+        }
+        let detailed = data.coarse_text.is_some()
+            || data.device.is_some()
+            || data.album_art.is_some()
+            || data.link.is_some()
+            || !data.apps.is_empty();
+        if !detailed {
+            return data;
+        }
+        // Detected presences carry their coarse text, one without it (e.g. from an older hub agent) may name anything
+        let text = match &data.coarse_text {
+            Some(coarse_text) => coarse_text.clone(),
+            None if data.apps.is_empty() => data.text.clone(),
+            None => control::privacy_text(),
+        };
+        let message = data.message.clone().filter(|_| text == data.text);
+        Arc::new(OutputData {
+            text,
+            message,
+            coarse_text: None,
+            device: None,
+            album_art: None,
+            link: None,
+            apps: Vec::new(),
+            ..(*data).clone()
+        })
+    }
+}
+
+/// The presence as one visitor may see it. Every route serving the presence reads it through this rather than
+/// the shared data, so the filtering for [`Trust`] happens here and nowhere else.
+#[derive(Clone)]
+pub struct Visible {
+    trust: Trust,
+    shared_data: SharedData,
+    broadcaster: Broadcaster,
+}
+
+impl Visible {
+    pub fn new(trust: Trust, shared_data: &SharedData, broadcaster: &Broadcaster) -> Self {
+        Self { trust, shared_data: shared_data.clone(), broadcaster: broadcaster.clone() }
+    }
+
+    pub fn trust(&self) -> Trust {
+        self.trust
+    }
+
+    pub fn current(&self) -> Arc<OutputData> {
+        self.trust.view(self.shared_data.load_full())
+    }
+
+    /// The current presence, then every change
+    pub fn updates(&self) -> impl Stream<Item = Arc<OutputData>> + Send + 'static {
+        let trust = self.trust;
+        web_server::presence_updates(&self.shared_data, &self.broadcaster).map(move |data| trust.view(data))
+    }
+}
+
+/// The local presence at the trust level `middleware` set, public if it didn't run
+impl FromRequestParts<AppState> for Visible {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let trust = parts.extensions.get::<Trust>().copied().unwrap_or(Trust::Public);
+        Ok(Self::new(trust, &state.shared_data, &state.broadcaster))
+    }
+}
+
+/// Tokens that unlock the detailed presence, everyone sees it while there are none
+#[derive(Debug, Clone, Default)]
+pub struct TrustConfig {
+    friend_tokens: Vec<String>,
+}
+
+impl TrustConfig {
+    /// `REPRESENCE_FRIEND_TOKENS`, comma-separated, the admin token counts as one too
+    pub fn from_env() -> Self {
+        let mut friend_tokens = env_list("REPRESENCE_FRIEND_TOKENS");
+        if !friend_tokens.is_empty() {
+            friend_tokens.extend(env_var("REPRESENCE_ADMIN_TOKEN"));
+        }
+        Self::new(friend_tokens)
+    }
+
+    pub fn new(friend_tokens: Vec<String>) -> Self {
+        Self { friend_tokens }
+    }
+
+    /// From `Authorization: Bearer <token>`, or `?token=` for widgets and WebSockets that can't set headers
+    pub fn level(&self, headers: &HeaderMap, query: Option<&str>) -> Trust {
+        if self.friend_tokens.is_empty() {
+            return Trust::Friend;
+        }
+        let query_token = query.and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes()).find_map(|(key, value)| (key == "token").then(|| value.into_owned()))
+        });
+        let friend = bearer_token(headers)
+            .map(str::to_string)
+            .or(query_token)
+            .is_some_and(|token| self.friend_tokens.iter().any(|friend| constant_time_eq(&token, friend)));
+        match friend {
+            true => Trust::Friend,
+            false => Trust::Public,
+        }
+    }
+}
+
+/// Tag the request with the visitor's trust level for [`Visible`] to filter by
+pub async fn middleware(State(config): State<Arc<TrustConfig>>, mut request: Request, next: Next) -> Response {
+    let trust = config.level(request.headers(), request.uri().query());
+    request.extensions_mut().insert(trust);
+    next.run(request).await
+}
+
+/// Turn away visitors without a friend token from routes that can't be filtered, like the recorded history
+pub async fn friends_only(request: Request, next: Next) -> Response {
+    match request.extensions().get::<Trust>() {
+        Some(Trust::Friend) => next.run(request).await,
+        _ => (StatusCode::UNAUTHORIZED, axum::Json(serde_json::json!({ "error": "requires a friend token" }))).into_response(),
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use crate::config::{env_list, env_parse};
+
+/// How long apps must have been running before they are reported
+#[derive(Debug, Clone, Default)]
+pub struct MinUptime {
+    default: Duration,
+    /// App name prefix → minimum uptime, overrides `default`
+    per_app: Vec<(String, Duration)>,
+}
+
+impl MinUptime {
+    /// `REPRESENCE_MIN_UPTIME_SECS` for every app, `REPRESENCE_MIN_UPTIME` as `name:secs` pairs per app, e.g. `chrome:30,steam:10`
+    pub fn from_env() -> Self {
+        let per_app = env_list("REPRESENCE_MIN_UPTIME")
+            .into_iter()
+            .filter_map(|entry| {
+                let parsed = entry
+                    .split_once(':')
+                    .and_then(|(name, secs)| Some((name.trim().to_string(), Duration::from_secs(secs.trim().parse().ok()?))));
+                if parsed.is_none() {
+                    eprintln!("Ignoring minimum uptime '{}', expected name:seconds", entry);
+                }
+                parsed
+            })
+            .collect();
+
+        Self::new(Duration::from_secs(env_parse("REPRESENCE_MIN_UPTIME_SECS", 0)), per_app)
+    }
+
+    pub fn new(default: Duration, per_app: Vec<(String, Duration)>) -> Self {
+        Self { default, per_app }
+    }
+
+    /// Whether every app is reported right away
+    pub fn is_zero(&self) -> bool {
+        self.default.is_zero() && self.per_app.iter().all(|(_, uptime)| uptime.is_zero())
+    }
+
+    /// Minimum uptime of an app, the longest matching prefix wins
+    pub fn for_app(&self, name: &str) -> Duration {
+        self.per_app
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, uptime)| *uptime)
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config_file::{ConfigFile, Problem};
+
+/// What a setting holds. A value that doesn't parse falls back to the default without a word at startup,
+/// so `represence check-config` is where it's caught.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Anything, names, URLs, tokens and lists
+    Text,
+    /// `true` or `false`
+    Bool,
+    /// A whole number, not negative
+    Count,
+    /// A whole number, may be negative
+    Integer,
+    /// A number with or without a fraction
+    Number,
+    /// A whole number of the unit, `3s` or `1.5` don't parse
+    Duration(&'static str),
+    Port,
+    /// One of these words
+    Choice(&'static [&'static str]),
+}
+
+impl Kind {
+    /// Why `value` isn't one of these, `None` when it is
+    pub fn check(self, value: &str) -> Option<String> {
+        let value = value.trim();
+        let (valid, expected) = match self {
+            Self::Text => return None,
+            Self::Bool => (value.parse::<bool>().is_ok(), "true or false".to_string()),
+            Self::Count => (value.parse::<u64>().is_ok(), "a whole number".to_string()),
+            Self::Integer => (value.parse::<i64>().is_ok(), "a whole number".to_string()),
+            Self::Number => (value.parse::<f64>().is_ok_and(f64::is_finite), "a number".to_string()),
+            Self::Duration(unit) => (value.parse::<u64>().is_ok(), format!("a whole number of {}", unit)),
+            Self::Port => (value.parse::<u16>().is_ok(), "a port from 0 to 65535".to_string()),
+            Self::Choice(choices) => (choices.contains(&value), format!("one of {}", choices.join(", "))),
+        };
+        (!valid).then(|| format!("'{}' isn't {}, the default is used instead", value, expected))
+    }
+}
+
+const SECONDS: Kind = Kind::Duration("seconds");
+const MINUTES: Kind = Kind::Duration("minutes");
+
+/// Every variable represence reads besides the configuration files' `_FILE` and `_JSON`, see [`ConfigFile`]
+pub const VARIABLES: &[(&str, Kind)] = &[
+    ("REPRESENCE_ACCESS_LOG", Kind::Text),
+    ("REPRESENCE_ACME_CACHE", Kind::Text),
+    ("REPRESENCE_ACME_DOMAINS", Kind::Text),
+    ("REPRESENCE_ACME_EMAIL", Kind::Text),
+    ("REPRESENCE_ACME_STAGING", Kind::Bool),
+    ("REPRESENCE_ADMIN_TOKEN", Kind::Text),
+    ("REPRESENCE_AUDIT_LOG", Kind::Text),
+    ("REPRESENCE_BIND", Kind::Text),
+    ("REPRESENCE_BLOCKLIST", Kind::Text),
+    ("REPRESENCE_CACHE_MAX_AGE_SECS", SECONDS),
+    ("REPRESENCE_CALENDAR_MESSAGE", Kind::Text),
+    ("REPRESENCE_CALENDAR_REFRESH_SECS", SECONDS),
+    ("REPRESENCE_CALENDAR_URLS", Kind::Text),
+    ("REPRESENCE_COMPRESSION", Kind::Bool),
+    ("REPRESENCE_DBUS", Kind::Bool),
+    ("REPRESENCE_DEVICE_INFO", Kind::Bool),
+    ("REPRESENCE_DEVICE_NAME", Kind::Text),
+    ("REPRESENCE_DISABLED_MODULES", Kind::Text),
+    ("REPRESENCE_ENERGY_SAVING_INTERVAL_SECS", SECONDS),
+    ("REPRESENCE_ENERGY_SAVING_MINS", MINUTES),
+    ("REPRESENCE_FILM_LOG_REFRESH_SECS", SECONDS),
+    ("REPRESENCE_FRIEND_TOKENS", Kind::Text),
+    ("REPRESENCE_GITHUB_POLL_SECS", SECONDS),
+    ("REPRESENCE_GITHUB_RECENT_MINS", MINUTES),
+    ("REPRESENCE_GITHUB_TOKEN", Kind::Text),
+    ("REPRESENCE_GITHUB_USER", Kind::Text),
+    ("REPRESENCE_GRPC_PORT", Kind::Port),
+    ("REPRESENCE_HISTORY", Kind::Bool),
+    ("REPRESENCE_HISTORY_PATH", Kind::Text),
+    ("REPRESENCE_HISTORY_RETENTION_DAYS", Kind::Duration("days")),
+    ("REPRESENCE_HISTORY_URL", Kind::Text),
+    ("REPRESENCE_HUB_TOKEN", Kind::Text),
+    ("REPRESENCE_HUB_URL", Kind::Text),
+    ("REPRESENCE_HUB_USER", Kind::Text),
+    ("REPRESENCE_HUB_USERS", Kind::Text),
+    ("REPRESENCE_INFLUX_INTERVAL_SECS", SECONDS),
+    ("REPRESENCE_INFLUX_MEASUREMENT", Kind::Text),
+    ("REPRESENCE_INFLUX_TOKEN", Kind::Text),
+    ("REPRESENCE_INFLUX_URL", Kind::Text),
+    ("REPRESENCE_JELLYFIN_API_KEY", Kind::Text),
+    ("REPRESENCE_JELLYFIN_URL", Kind::Text),
+    ("REPRESENCE_JELLYFIN_USER", Kind::Text),
+    ("REPRESENCE_LANYARD", Kind::Bool),
+    ("REPRESENCE_LANYARD_USER_ID", Kind::Text),
+    ("REPRESENCE_LASTFM_API_KEY", Kind::Text),
+    ("REPRESENCE_LASTFM_POLL_SECS", SECONDS),
+    ("REPRESENCE_LASTFM_USER", Kind::Text),
+    ("REPRESENCE_LETTERBOXD_USER", Kind::Text),
+    ("REPRESENCE_LOG_FILE", Kind::Text),
+    ("REPRESENCE_LOG_KEEP", Kind::Count),
+    ("REPRESENCE_LOG_MAX_BYTES", Kind::Count),
+    ("REPRESENCE_MASTODON_DEBOUNCE_SECS", SECONDS),
+    ("REPRESENCE_MASTODON_FIELD", Kind::Text),
+    ("REPRESENCE_MASTODON_MIN_INTERVAL_SECS", SECONDS),
+    ("REPRESENCE_MASTODON_TOKEN", Kind::Text),
+    ("REPRESENCE_MASTODON_URL", Kind::Text),
+    ("REPRESENCE_MATRIX_DEBOUNCE_SECS", SECONDS),
+    ("REPRESENCE_MATRIX_HOMESERVER", Kind::Text),
+    ("REPRESENCE_MATRIX_MIN_INTERVAL_SECS", SECONDS),
+    ("REPRESENCE_MATRIX_TOKEN", Kind::Text),
+    ("REPRESENCE_MATRIX_USER", Kind::Text),
+    ("REPRESENCE_MEDIA_SERVER_POLL_SECS", SECONDS),
+    ("REPRESENCE_MIN_UPTIME", Kind::Text),
+    ("REPRESENCE_MIN_UPTIME_SECS", SECONDS),
+    ("REPRESENCE_MPD", Kind::Bool),
+    ("REPRESENCE_MPD_ADDRESS", Kind::Text),
+    ("REPRESENCE_MPD_PASSWORD", Kind::Text),
+    ("REPRESENCE_MQTT_CLIENT_ID", Kind::Text),
+    ("REPRESENCE_MQTT_DISCOVERY", Kind::Bool),
+    ("REPRESENCE_MQTT_DISCOVERY_PREFIX", Kind::Text),
+    ("REPRESENCE_MQTT_HOST", Kind::Text),
+    ("REPRESENCE_MQTT_PASSWORD", Kind::Text),
+    ("REPRESENCE_MQTT_PORT", Kind::Port),
+    ("REPRESENCE_MQTT_QOS", Kind::Choice(&["0", "1", "2"])),
+    ("REPRESENCE_MQTT_RETAIN", Kind::Bool),
+    ("REPRESENCE_MQTT_TOPIC", Kind::Text),
+    ("REPRESENCE_MQTT_USERNAME", Kind::Text),
+    ("REPRESENCE_NATS_STREAM", Kind::Text),
+    ("REPRESENCE_NATS_SUBJECT", Kind::Text),
+    ("REPRESENCE_NATS_TOKEN", Kind::Text),
+    ("REPRESENCE_NATS_URL", Kind::Text),
+    ("REPRESENCE_NOTIFY", Kind::Text),
+    ("REPRESENCE_NTFY_TOKEN", Kind::Text),
+    ("REPRESENCE_NTFY_URL", Kind::Text),
+    ("REPRESENCE_OFFLINE_AFTER_SECS", SECONDS),
+    ("REPRESENCE_ON_DEMAND_MAX_AGE_SECS", SECONDS),
+    ("REPRESENCE_OTLP_ENDPOINT", Kind::Text),
+    ("REPRESENCE_PID_FILE", Kind::Text),
+    ("REPRESENCE_PLEX_TOKEN", Kind::Text),
+    ("REPRESENCE_PLEX_URL", Kind::Text),
+    ("REPRESENCE_PLEX_USER", Kind::Text),
+    ("REPRESENCE_PORT", Kind::Port),
+    ("REPRESENCE_PRIORITY", Kind::Choice(&["tier", "focus", "script"])),
+    ("REPRESENCE_PRIORITY_SCRIPT", Kind::Text),
+    ("REPRESENCE_PRIVACY", Kind::Bool),
+    ("REPRESENCE_PRIVACY_TEXT", Kind::Text),
+    ("REPRESENCE_PROC_PATH", Kind::Text),
+    ("REPRESENCE_PROFILE", Kind::Text),
+    ("REPRESENCE_PROFILE_README_BRANCH", Kind::Text),
+    ("REPRESENCE_PROFILE_README_INTERVAL_MINS", MINUTES),
+    ("REPRESENCE_PROFILE_README_PATH", Kind::Text),
+    ("REPRESENCE_PROFILE_README_REPO", Kind::Text),
+    ("REPRESENCE_PROFILE_README_SVG", Kind::Text),
+    ("REPRESENCE_PROFILE_README_TOKEN", Kind::Text),
+    ("REPRESENCE_PUSHOVER_TOKEN", Kind::Text),
+    ("REPRESENCE_PUSHOVER_USER", Kind::Text),
+    ("REPRESENCE_RATE_LIMIT_BURST", Kind::Count),
+    ("REPRESENCE_RATE_LIMIT_PER_SECOND", Kind::Number),
+    ("REPRESENCE_REDIS_CHANNEL", Kind::Text),
+    ("REPRESENCE_REDIS_KEY", Kind::Text),
+    ("REPRESENCE_REDIS_TTL", SECONDS),
+    ("REPRESENCE_REDIS_URL", Kind::Text),
+    ("REPRESENCE_REMOTE_SECRET", Kind::Text),
+    ("REPRESENCE_SENTRY_DSN", Kind::Text),
+    ("REPRESENCE_SENTRY_ENVIRONMENT", Kind::Text),
+    ("REPRESENCE_SESSION_GAP_MINS", MINUTES),
+    ("REPRESENCE_SETTLE_MS", Kind::Duration("milliseconds")),
+    ("REPRESENCE_SHIELDS_LABEL", Kind::Text),
+    ("REPRESENCE_SINGLE_INSTANCE", Kind::Bool),
+    ("REPRESENCE_SKIP_PROC_CHECK", Kind::Bool),
+    ("REPRESENCE_SPOTIFY_CLIENT_ID", Kind::Text),
+    ("REPRESENCE_SPOTIFY_CLIENT_SECRET", Kind::Text),
+    ("REPRESENCE_SPOTIFY_POLL_SECS", SECONDS),
+    ("REPRESENCE_SPOTIFY_REFRESH_TOKEN", Kind::Text),
+    ("REPRESENCE_SSH_HOSTS", Kind::Text),
+    ("REPRESENCE_STALE_AFTER_SECS", SECONDS),
+    ("REPRESENCE_STREAK_MIN_MINS", MINUTES),
+    ("REPRESENCE_STREAK_TEXT", Kind::Bool),
+    ("REPRESENCE_SUSPEND_AWARE", Kind::Bool),
+    ("REPRESENCE_TELEGRAM_TOKEN", Kind::Text),
+    ("REPRESENCE_TELEGRAM_USER_ID", Kind::Integer),
+    ("REPRESENCE_TERMINAL_COMMANDS", Kind::Bool),
+    ("REPRESENCE_TEXT_SCRIPT", Kind::Text),
+    ("REPRESENCE_TEXT_SCRIPT_TIMEOUT_MS", Kind::Duration("milliseconds")),
+    ("REPRESENCE_TLS_CERT", Kind::Text),
+    ("REPRESENCE_TLS_KEY", Kind::Text),
+    ("REPRESENCE_TRAKT_CLIENT_ID", Kind::Text),
+    ("REPRESENCE_TRAKT_USER", Kind::Text),
+    ("REPRESENCE_TRUSTED_PROXIES", Kind::Text),
+    ("REPRESENCE_TWITCH_CHANNEL", Kind::Text),
+    ("REPRESENCE_TWITCH_CLIENT_ID", Kind::Text),
+    ("REPRESENCE_TWITCH_CLIENT_SECRET", Kind::Text),
+    ("REPRESENCE_TWITCH_POLL_SECS", SECONDS),
+    ("REPRESENCE_VLC_PASSWORD", Kind::Text),
+    ("REPRESENCE_VLC_POLL_SECS", SECONDS),
+    ("REPRESENCE_VLC_URL", Kind::Text),
+    ("REPRESENCE_WAKATIME_API_KEY", Kind::Text),
+    ("REPRESENCE_WAKATIME_API_URL", Kind::Text),
+    ("REPRESENCE_WEBHOOK_MAX_RETRIES", Kind::Count),
+    ("REPRESENCE_WEBHOOK_SECRET", Kind::Text),
+    ("REPRESENCE_WEBHOOK_URLS", Kind::Text),
+];
+
+/// What `name` holds, `None` for a variable represence doesn't read
+pub fn kind(name: &str) -> Option<Kind> {
+    let file = ConfigFile::ALL.iter().any(|file| file.var() == name || file.inline_var() == name);
+    match file {
+        true => Some(Kind::Text),
+        false => VARIABLES.iter().find(|(known, _)| *known == name).map(|(_, kind)| *kind),
+    }
+}
+
+/// Values that don't parse as their kind, as errors, and `REPRESENCE_*` names nothing reads, as warnings
+pub fn check(vars: impl IntoIterator<Item = (String, String)>) -> Vec<Problem> {
+    let mut vars: Vec<(String, String)> = vars.into_iter().filter(|(name, _)| name.starts_with("REPRESENCE_")).collect();
+    vars.sort();
+    vars.iter()
+        .filter_map(|(name, value)| match kind(name) {
+            // Empty counts as unset everywhere
+            Some(_) if value.trim().is_empty() => None,
+            Some(kind) => kind.check(value).map(|message| Problem::error(name.as_str(), message)),
+            None => Some(Problem::warning(name.as_str(), "isn't a setting represence reads, misspelled?")),
+        })
+        .collect()
+}
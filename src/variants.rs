@@ -0,0 +1,98 @@
+use chrono::{DateTime, Datelike, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{self, env_json};
+
+/// Texts for an app on workdays and on weekends, either may be left out to keep the usual text
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DayVariant {
+    #[serde(default)]
+    pub weekday: Option<String>,
+    #[serde(default)]
+    pub weekend: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DayVariantsFile {
+    /// IANA name like `Europe/Istanbul`, the system timezone otherwise
+    #[serde(default, deserialize_with = "config::deserialize_timezone")]
+    timezone: Option<Tz>,
+    /// Days counted as the weekend, Saturday and Sunday by default
+    weekend: Option<Vec<Weekday>>,
+    apps: HashMap<String, DayVariant>,
+}
+
+/// Per-app texts that depend on whether it's a workday, e.g. Chrome is the work account only on workdays
+#[derive(Debug, Clone)]
+pub struct DayVariants {
+    /// App name prefix → variant, the longest matching prefix wins
+    apps: HashMap<String, DayVariant>,
+    weekend: Vec<Weekday>,
+    timezone: Option<Tz>,
+}
+
+impl Default for DayVariants {
+    fn default() -> Self {
+        Self::new(HashMap::new(), vec![Weekday::Sat, Weekday::Sun], None)
+    }
+}
+
+impl DayVariants {
+    /// Variants from the JSON file in `REPRESENCE_DAY_VARIANTS_FILE` or inline in `REPRESENCE_DAY_VARIANTS_JSON`, none otherwise
+    pub fn from_env() -> Self {
+        let Some((from, loaded)) = env_json("REPRESENCE_DAY_VARIANTS", Self::parse) else {
+            return Self::default();
+        };
+
+        match loaded {
+            Ok(variants) => {
+                println!("Loaded weekday and weekend texts for {} app(s) from {}", variants.apps.len(), from);
+                variants
+            }
+            Err(e) => {
+                eprintln!("Ignoring day variants, failed to load {}: {}", from, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn parse(source: &str) -> serde_json::Result<Self> {
+        let file: DayVariantsFile = serde_json::from_str(source)?;
+        Ok(Self::new(file.apps, file.weekend.unwrap_or_else(|| vec![Weekday::Sat, Weekday::Sun]), file.timezone))
+    }
+
+    pub fn new(apps: HashMap<String, DayVariant>, weekend: Vec<Weekday>, timezone: Option<Tz>) -> Self {
+        Self { apps, weekend, timezone }
+    }
+
+    /// Text for `app_name` right now, `None` to keep the usual one
+    pub fn text(&self, app_name: &str) -> Option<&str> {
+        self.text_at(app_name, Utc::now())
+    }
+
+    pub fn text_at(&self, app_name: &str, now: DateTime<Utc>) -> Option<&str> {
+        let (_, variant) = self
+            .apps
+            .iter()
+            .filter(|(key, _)| app_name.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())?;
+
+        let weekday = match self.timezone {
+            Some(timezone) => timezone.from_utc_datetime(&now.naive_utc()).weekday(),
+            None => now.with_timezone(&chrono::Local).weekday(),
+        };
+        match self.weekend.contains(&weekday) {
+            true => variant.weekend.as_deref(),
+            false => variant.weekday.as_deref(),
+        }
+    }
+}
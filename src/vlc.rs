@@ -0,0 +1,110 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{env_parse, env_var};
+use crate::diagnostics::SharedDiagnostics;
+use crate::now_playing::{self, NowPlaying, Track};
+
+const REQUEST_TIMEOUT_SECS: u64 = 3;
+const DEFAULT_URL: &str = "http://localhost:8080";
+const APP: &str = "vlc";
+
+#[derive(Debug, Clone)]
+pub struct VlcConfig {
+    pub url: String,
+    pub password: String,
+    pub poll_interval: Duration,
+}
+
+impl VlcConfig {
+    /// `None` unless `REPRESENCE_VLC_PASSWORD` is set, VLC's web interface refuses requests without one.
+    /// `REPRESENCE_VLC_URL` overrides where the interface listens.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            password: env_var("REPRESENCE_VLC_PASSWORD")?,
+            url: env_var("REPRESENCE_VLC_URL")
+                .unwrap_or_else(|| DEFAULT_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            poll_interval: Duration::from_secs(env_parse("REPRESENCE_VLC_POLL_SECS", 5).max(1)),
+        })
+    }
+}
+
+/// The parts of `/requests/status.json` that name what's playing
+#[derive(Debug, Deserialize)]
+pub struct Status {
+    state: String,
+    information: Option<Information>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Information {
+    category: HashMap<String, serde_json::Value>,
+}
+
+impl Status {
+    /// Title of the media, `None` unless it's playing. Files with an artist tag are music.
+    pub fn track(&self) -> Option<Track> {
+        if self.state != "playing" {
+            return None;
+        }
+        let meta = self.information.as_ref()?.category.get("meta")?;
+        let get = |key: &str| meta.get(key).and_then(|value| value.as_str()).filter(|value| !value.is_empty());
+
+        let title = match (get("showName"), get("episodeNumber")) {
+            (Some(show), Some(episode)) => format!("{} episode {}", show, episode),
+            _ => get("title")
+                .or_else(|| get("filename").map(|file| Path::new(file).file_stem().and_then(|stem| stem.to_str()).unwrap_or(file)))?
+                .to_string(),
+        };
+        Some(match get("artist") {
+            Some(artist) => Track::new(title, Some(artist.to_string())),
+            None => Track::video(title),
+        })
+    }
+}
+
+/// Ask VLC's HTTP interface what it plays while vlc is running
+pub fn spawn(config: VlcConfig, diagnostics: SharedDiagnostics, now_playing: NowPlaying) {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("VLC integration disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+    let endpoint = format!("{}/requests/status.json", config.url);
+
+    tokio::spawn(async move {
+        let mut failing = false;
+        loop {
+            let track = match now_playing::is_running(&diagnostics, APP).await {
+                true => {
+                    let status = async {
+                        client.get(&endpoint).basic_auth("", Some(&config.password)).send().await?.error_for_status()?.json::<Status>().await
+                    };
+                    match status.await {
+                        Ok(status) => {
+                            failing = false;
+                            status.track()
+                        }
+                        Err(e) => {
+                            // VLC runs without the web interface unless it's enabled
+                            if !failing {
+                                eprintln!("VLC web interface at {} unreachable: {}", config.url, e);
+                                failing = true;
+                            }
+                            None
+                        }
+                    }
+                }
+                false => None,
+            };
+            now_playing.set(APP, "VLC", track).await;
+            tokio::time::sleep(config.poll_interval).await;
+        }
+    });
+}
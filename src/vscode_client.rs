@@ -1,9 +1,16 @@
 use serde::{Deserialize, Serialize};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use futures_util::StreamExt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::error::{RepresenceError, Result};
+
+const DEFAULT_PORT: u16 = 3847;
+const CHECK_INTERVAL_SECS: u64 = 2; // Much faster VSCode checks
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FileInfo {
     #[serde(rename = "fileName")]
     pub file_name: String,
@@ -20,28 +27,103 @@ pub struct FileInfo {
 }
 
 /// Connect to VS Code once and get the current file info (optimized)
-pub async fn connect_to_vscode_once(port: u16) -> Result<FileInfo, Box<dyn std::error::Error>> {
+pub async fn connect_to_vscode_once(port: u16) -> Result<FileInfo> {
     let url = format!("ws://localhost:{}", port);
 
     // Set connection timeout - pass the string directly instead of parsing to URL
     let connect_future = connect_async(&url);
-    let (ws_stream, _) = tokio::time::timeout(Duration::from_secs(3), connect_future).await??;
+    let (ws_stream, _) = tokio::time::timeout(Duration::from_secs(3), connect_future)
+        .await
+        .map_err(|_| RepresenceError::Timeout("the VS Code connection"))?
+        .map_err(|source| RepresenceError::Connection { target: url.clone(), source })?;
     
     let (_, mut receiver) = ws_stream.split();
 
     // Wait for the first message with timeout
     let message_future = receiver.next();
-    if let Some(msg) = tokio::time::timeout(Duration::from_secs(2), message_future).await? {
-        match msg? {
+    if let Some(msg) = tokio::time::timeout(Duration::from_secs(2), message_future)
+        .await
+        .map_err(|_| RepresenceError::Timeout("VS Code file info"))?
+    {
+        match msg.map_err(|source| RepresenceError::Connection { target: url, source })? {
             Message::Text(text) => {
-                let file_info = serde_json::from_str::<FileInfo>(&text)?;
+                let file_info = serde_json::from_str::<FileInfo>(&text)
+                    .map_err(|source| RepresenceError::Parse { what: "VS Code file info", source })?;
                 return Ok(file_info);
             }
-            _ => return Err("Unexpected message type".into()),
+            _ => return Err(RepresenceError::UnexpectedMessage("VS Code")),
         }
     }
 
-    Err("No file info received within timeout".into())
+    Err(RepresenceError::Closed("VS Code"))
+}
+
+/// Where the file open in the editor comes from, the VS Code extension in production
+pub trait EditorSource: Send + Sync {
+    fn file_info(&self) -> impl Future<Output = Result<FileInfo>> + Send;
+
+    /// Minimum time between successful queries
+    fn check_interval(&self) -> Duration {
+        Duration::from_secs(CHECK_INTERVAL_SECS)
+    }
+}
+
+/// The represence VS Code extension's WebSocket
+#[derive(Debug, Clone, Copy)]
+pub struct VsCodeSource {
+    pub port: u16,
+}
+
+impl Default for VsCodeSource {
+    fn default() -> Self {
+        Self { port: DEFAULT_PORT }
+    }
+}
+
+impl EditorSource for VsCodeSource {
+    async fn file_info(&self) -> Result<FileInfo> {
+        connect_to_vscode_once(self.port).await
+    }
 }
 
- 
\ No newline at end of file
+/// Scripted editor state for tests and simulations, `None` behaves like an unreachable extension
+#[derive(Debug, Clone, Default)]
+pub struct FixtureEditorSource {
+    file: Arc<Mutex<Option<FileInfo>>>,
+}
+
+impl FixtureEditorSource {
+    pub fn set(&self, file: Option<FileInfo>) {
+        *self.file.lock().unwrap_or_else(|e| e.into_inner()) = file;
+    }
+}
+
+impl EditorSource for FixtureEditorSource {
+    async fn file_info(&self) -> Result<FileInfo> {
+        self.file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or(RepresenceError::Closed("fixture editor"))
+    }
+
+    fn check_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+impl FileInfo {
+    /// File info as the extension would report it for `path`
+    pub fn for_path(path: &str, language_id: &str) -> Self {
+        let file_name = path.rsplit('/').next().unwrap_or(path).to_string();
+        Self {
+            extension: file_name.rsplit_once('.').map(|(_, ext)| ext.to_string()).unwrap_or_default(),
+            file_name,
+            full_path: path.to_string(),
+            language_id: language_id.to_string(),
+            line_count: 0,
+            word_count: 0,
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use base64::Engine;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::config::env_var;
+use crate::vscode_client::FileInfo;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// WakaTime plugins resend a heartbeat for the same file at most this often
+const HEARTBEAT_INTERVAL_SECS: u64 = 120;
+
+/// How often queued heartbeats are flushed
+const FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// The bulk endpoint accepts at most this many heartbeats per request
+const BULK_LIMIT: usize = 25;
+
+/// Heartbeats kept while the API is unreachable, oldest are dropped first
+const MAX_QUEUED: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct WakaTimeConfig {
+    pub api_url: String,
+    pub api_key: String,
+}
+
+impl WakaTimeConfig {
+    /// `None` unless `REPRESENCE_WAKATIME_API_KEY` is set.
+    /// `REPRESENCE_WAKATIME_API_URL` points at a Wakapi instance instead of wakatime.com.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            api_key: env_var("REPRESENCE_WAKATIME_API_KEY")?,
+            api_url: env_var("REPRESENCE_WAKATIME_API_URL")
+                .unwrap_or_else(|| "https://api.wakatime.com/api/v1".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Heartbeat {
+    entity: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    category: &'static str,
+    time: f64,
+    project: Option<String>,
+    language: Option<String>,
+    lines: u32,
+    is_write: bool,
+}
+
+impl Heartbeat {
+    fn new(file: &FileInfo) -> Self {
+        Self {
+            entity: file.full_path.clone(),
+            kind: "file",
+            category: "coding",
+            time: chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
+            project: project_name(Path::new(&file.full_path)),
+            language: (!file.language_id.is_empty()).then(|| file.language_id.clone()),
+            lines: file.line_count,
+            is_write: false,
+        }
+    }
+}
+
+/// Name of the enclosing git repository, or the file's directory when there is none
+fn project_name(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    let root = parent
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(parent);
+    root.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Send WakaTime heartbeats for the file being edited in VS Code
+pub fn spawn(config: WakaTimeConfig, mut editor: watch::Receiver<Option<FileInfo>>) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION"), " represence-wakatime/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("WakaTime disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let endpoint = format!("{}/users/current/heartbeats.bulk", config.api_url);
+    let authorization = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(&config.api_key));
+
+    tokio::spawn(async move {
+        let mut queue: VecDeque<Heartbeat> = VecDeque::new();
+        let mut last_sent: Option<(String, Instant)> = None;
+        let mut flush = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+        let mut failing = false;
+
+        loop {
+            let tick = tokio::select! {
+                changed = editor.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    false
+                }
+                _ = flush.tick() => true,
+            };
+
+            // Same rules as the editor plugins: on file switch, or periodically while still editing
+            if let Some(file) = editor.borrow_and_update().clone() {
+                let due = match &last_sent {
+                    Some((entity, at)) => {
+                        *entity != file.full_path || at.elapsed() >= Duration::from_secs(HEARTBEAT_INTERVAL_SECS)
+                    }
+                    None => true,
+                };
+                if due {
+                    last_sent = Some((file.full_path.clone(), Instant::now()));
+                    queue.push_back(Heartbeat::new(&file));
+                    if queue.len() > MAX_QUEUED {
+                        queue.pop_front();
+                    }
+                }
+            }
+
+            // While the API is failing, only retry on the flush interval
+            if failing && !tick {
+                continue;
+            }
+
+            failing = false;
+            while !queue.is_empty() {
+                let batch: Vec<Heartbeat> = queue.iter().take(BULK_LIMIT).cloned().collect();
+                let result = client
+                    .post(&endpoint)
+                    .header(reqwest::header::AUTHORIZATION, &authorization)
+                    .json(&batch)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+
+                match result {
+                    Ok(_) => {
+                        queue.drain(..batch.len());
+                    }
+                    Err(e) => {
+                        // Keep the queue and try again on the next flush
+                        eprintln!("Failed to send WakaTime heartbeats: {}", e);
+                        failing = true;
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
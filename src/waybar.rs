@@ -0,0 +1,116 @@
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::cli::WaybarArgs;
+use crate::config::{env_parse, env_var};
+use crate::error::{RepresenceError, Result};
+use crate::OutputData;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A line for a waybar custom module with `"return-type": "json"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BarOutput {
+    pub text: String,
+    pub tooltip: String,
+    /// The app shown (or `idle`), `stale` when the presence may be out of date, `offline` without a daemon
+    pub class: Vec<String>,
+}
+
+impl From<&OutputData> for BarOutput {
+    fn from(data: &OutputData) -> Self {
+        let text = match &data.emoji {
+            Some(emoji) => format!("{} {}", emoji, data.text),
+            None => data.text.clone(),
+        };
+        let apps: Vec<String> = data
+            .apps
+            .iter()
+            .map(|app| match app.instances {
+                0 | 1 => app.name.clone(),
+                instances => format!("{} ({})", app.name, instances),
+            })
+            .collect();
+        let tooltip = match apps.is_empty() {
+            true => data.text.clone(),
+            false => format!("{}\nRunning: {}", data.text, apps.join(", ")),
+        };
+        let mut class = vec![data.apps.first().map_or_else(|| "idle".to_string(), |app| app.name.clone())];
+        if data.stale {
+            class.push("stale".to_string());
+        }
+        Self { text, tooltip, class }
+    }
+}
+
+impl BarOutput {
+    pub fn offline(reason: &str) -> Self {
+        Self { text: String::new(), tooltip: format!("represence is not reachable: {}", reason), class: vec!["offline".to_string()] }
+    }
+
+    /// JSON for waybar, just the text for polybar and other bars reading plain lines
+    pub fn render(&self, plain: bool) -> String {
+        match plain {
+            true => self.text.clone(),
+            false => serde_json::to_string(self).unwrap_or_default(),
+        }
+    }
+}
+
+/// Follow the running server's WebSocket and print a line per update, reconnecting until killed
+pub async fn run(args: WaybarArgs) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let url = args.url.unwrap_or_else(|| format!("ws://127.0.0.1:{}/ws/represence", env_parse("REPRESENCE_PORT", 3001u16)));
+    // The bar is ours, it may show what friends see
+    let token = args.token.or_else(|| env_var("REPRESENCE_ADMIN_TOKEN"));
+    let mut printer = Printer { plain: args.plain, last: None };
+    loop {
+        if let Err(e) = follow(&url, token.as_deref(), &mut printer).await {
+            printer.print(&BarOutput::offline(&e.to_string()));
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Prints lines that differ from the previous one
+struct Printer {
+    plain: bool,
+    last: Option<String>,
+}
+
+impl Printer {
+    fn print(&mut self, output: &BarOutput) {
+        let line = output.render(self.plain);
+        if self.last.as_ref() != Some(&line) {
+            println!("{}", line);
+            self.last = Some(line);
+        }
+    }
+}
+
+async fn follow(url: &str, token: Option<&str>, printer: &mut Printer) -> Result<()> {
+    let connection_error = |source| RepresenceError::Connection { target: url.to_string(), source };
+    let mut request = url.into_client_request().map_err(connection_error)?;
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| connection_error(e.into()))?;
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+    let (stream, _) = connect_async(request).await.map_err(connection_error)?;
+    let (_, mut receiver) = stream.split();
+
+    while let Some(message) = receiver.next().await {
+        match message.map_err(connection_error)? {
+            Message::Text(text) => {
+                let data: OutputData =
+                    serde_json::from_str(&text).map_err(|source| RepresenceError::Parse { what: "presence", source })?;
+                printer.print(&BarOutput::from(&data));
+            }
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    Err(RepresenceError::Closed("represence"))
+}
@@ -1,23 +1,132 @@
 use axum::{
-    extract::{WebSocketUpgrade, State},
-    response::{Json, Response},
+    extract::{ConnectInfo, Query, Request, WebSocketUpgrade, State},
+    middleware,
+    http::{header, HeaderMap, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, Html, IntoResponse, Json, Response},
     routing::get,
     Router,
 };
 use axum::extract::ws::{WebSocket, Message};
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::{RwLock, broadcast};
+use tokio::sync::broadcast;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{CorsLayer, AllowOrigin};
 use std::env;
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{stream, Stream, StreamExt};
+#[cfg(feature = "otel")]
+use opentelemetry::KeyValue;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
 
+use crate::access_log::{self, AccessLogFormat};
+use crate::audit::{self, AuditLog, SharedAuditLog};
+use crate::cache::{self, CachePolicy};
+use crate::config;
+use crate::config_file;
+use crate::consumers::{self, SharedConsumers};
+use crate::diagnostics::{DetectorStatus, SharedDiagnostics};
+use crate::effective_config;
+use crate::export;
+use crate::features;
+use crate::history::{self, HistoryPage, HistoryQuery, SharedHistory};
+use crate::hub::{self, Hub};
+use crate::i18n::{LangParams, Translations};
+use crate::lanyard;
+use crate::modules::{self, Modules};
+use crate::openapi;
+use crate::profiles::{self, Profiles};
+use crate::rate_limit::{self, RateLimiter};
+use crate::proxy::{self, ClientIp, TrustedProxies};
+use crate::remote::{self, SharedRemote};
+use crate::subscription::{self, Delivery, Subscription, SubscriptionParams};
+use crate::sessions;
+use crate::streaks;
+use crate::summary;
+#[cfg(feature = "otel")]
+use crate::telemetry::{Measurement, Stage};
+use crate::trust::{self, Trust, TrustConfig, Visible};
 use crate::OutputData;
 
-pub type SharedData = Arc<RwLock<OutputData>>;
-pub type Broadcaster = broadcast::Sender<OutputData>;
+/// Latest presence, swapped as a whole so readers never wait for the writer
+pub type SharedData = Arc<ArcSwap<OutputData>>;
+pub type Broadcaster = broadcast::Sender<Arc<OutputData>>;
 
-pub async fn create_server(shared_data: SharedData) -> (Router, Broadcaster) {
+pub fn shared_data(initial: OutputData) -> SharedData {
+    Arc::new(ArcSwap::from_pointee(initial))
+}
+
+/// Make `data` the current presence and send it to every subscriber
+pub fn publish(shared_data: &SharedData, broadcaster: &Broadcaster, data: impl Into<Arc<OutputData>>) {
+    let data = data.into();
+    shared_data.store(data.clone());
+    #[cfg(not(feature = "otel"))]
+    let _ = broadcaster.send(data);
+    #[cfg(feature = "otel")]
+    {
+        let fan_out = Measurement::start(Stage::Broadcast);
+        let receivers = broadcaster.send(data).unwrap_or(0);
+        fan_out.finish(&[KeyValue::new("represence.receivers", receivers as i64)]);
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub shared_data: SharedData,
+    pub broadcaster: Broadcaster,
+    pub diagnostics: SharedDiagnostics,
+    pub websocket_clients: Arc<AtomicUsize>,
+    pub hub: Arc<Hub>,
+    pub history: Option<SharedHistory>,
+    pub remote: SharedRemote,
+    pub consumers: SharedConsumers,
+    pub translations: Arc<Translations>,
+    pub audit: Option<SharedAuditLog>,
+    pub modules: Modules,
+    pub profiles: Profiles,
+}
+
+impl AppState {
+    /// Current presence as the visitor may see it, in the language the request asks for when there are translations for it
+    pub fn localized(&self, presence: &Visible, lang: &LangParams, headers: &HeaderMap) -> Arc<OutputData> {
+        let data = presence.current();
+        match self.translations.translate(&data, lang, headers) {
+            Some(translated) => Arc::new(translated),
+            None => data,
+        }
+    }
+}
+
+/// Tracks a connected WebSocket client for as long as it is alive
+pub struct ClientGuard(Arc<AtomicUsize>);
+
+impl ClientGuard {
+    pub fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn create_server(
+    shared_data: SharedData,
+    diagnostics: SharedDiagnostics,
+    history: Option<SharedHistory>,
+    remote: SharedRemote,
+    consumers: SharedConsumers,
+    modules: Modules,
+    profiles: Profiles,
+) -> (Router, Broadcaster) {
     // Create broadcast channel for WebSocket updates with reasonable buffer
     let (tx, _rx) = broadcast::channel(32);
     let broadcaster = tx.clone();
@@ -25,17 +134,130 @@ pub async fn create_server(shared_data: SharedData) -> (Router, Broadcaster) {
     // Configure CORS more specifically for security
     let cors = CorsLayer::new()
         .allow_origin(AllowOrigin::any()) // Consider restricting this in production
-        .allow_methods([axum::http::Method::GET])
-        .allow_headers([axum::http::header::CONTENT_TYPE]);
+        // GraphQL clients POST their queries
+        .allow_methods(match cfg!(feature = "graphql") {
+            true => vec![axum::http::Method::GET, axum::http::Method::POST],
+            false => vec![axum::http::Method::GET],
+        })
+        .allow_headers([axum::http::header::CONTENT_TYPE])
+        // The presence may be translated, caches must keep languages apart
+        .vary([header::ORIGIN, header::ACCESS_CONTROL_REQUEST_METHOD, header::ACCESS_CONTROL_REQUEST_HEADERS, header::ACCEPT_LANGUAGE]);
 
-    let app = Router::new()
-        .route("/", get(root))
-        .route("/api/represence", get(get_presence))
+    // Presence routes hit by embeds and scrapers, subject to rate limiting
+    let mut public = Router::new()
+        .route("/api/represence", get(get_presence).post(push_presence))
+        .route("/api/represence.txt", get(get_presence_text))
+        .route("/api/shields", get(get_shields))
+        .route("/api/badge.svg", get(get_badge))
         .route("/ws/represence", get(websocket_handler))
+        .route("/sse/represence", get(sse_handler));
+
+    // The recorded texts, apps and languages can't be made coarse afterwards, so the history and what's built from it is for friends only
+    let history_routes = Router::new()
+        .route("/api/history", get(get_history))
+        .merge(sessions::routes())
+        .merge(summary::routes())
+        .merge(streaks::routes())
+        .route_layer(middleware::from_fn(trust::friends_only));
+    public = public.merge(history_routes);
+
+    #[cfg(feature = "graphql")]
+    {
+        public = public.merge(crate::graphql::routes());
+    }
+
+    // Multi-user routes when hub users are configured
+    let hub = Hub::from_env();
+    if !hub.is_empty() {
+        println!("Hub mode: serving presence for {} user(s)", hub.len());
+        public = public.merge(hub::routes());
+    }
+
+    // Optional Lanyard-compatible routes for existing front-end widgets
+    if let Some(lanyard_config) = lanyard::LanyardConfig::from_env() {
+        public = public.merge(lanyard::routes(lanyard_config));
+    }
+
+    if let Some(limiter) = RateLimiter::from_env() {
+        public = public.route_layer(middleware::from_fn_with_state(Arc::new(limiter), rate_limit::middleware));
+    }
+
+    let router = Router::new()
+        .route("/", get(root))
         .route("/health", get(health_check))
-        .with_state((shared_data, tx))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/overlay", get(overlay))
+        .route("/widget.js", get(widget))
+        .route("/dashboard", get(dashboard))
+        .route("/api/dashboard", get(dashboard_data))
+        .route("/api/history/export", get(export::export_history))
+        .route("/api/audit", get(audit::get_audit))
+        .route("/api/admin/modules", get(modules::get_modules).patch(modules::patch_modules))
+        .route("/api/admin/config", get(effective_config::get_config))
+        .route("/api/admin/profile", get(profiles::get_profile).put(profiles::put_profile))
+        .route("/api/config-schema", get(config_file::get_config_schema))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/docs", get(openapi::docs));
+    #[cfg(feature = "history")]
+    let router = router.route("/api/admin/backup", get(crate::backup::get_backup));
+    let router = router.merge(remote::routes()).merge(public);
+
+    // Record what anonymous visitors get to see, to check for leaks later
+    let audit = match AuditLog::from_env() {
+        Some(Ok(audit)) => {
+            println!("Recording publicly served presence to {}", audit.path().display());
+            let audit = Arc::new(audit);
+            audit::spawn(audit.clone(), &tx);
+            Some(audit)
+        }
+        Some(Err(e)) => {
+            eprintln!("Audit log disabled, failed to open it: {}", e);
+            None
+        }
+        None => None,
+    };
+
+    let state = AppState {
+        shared_data,
+        broadcaster: tx,
+        diagnostics,
+        websocket_clients: consumers.websocket_clients.clone(),
+        hub: Arc::new(hub),
+        history,
+        remote,
+        consumers: consumers.clone(),
+        translations: Arc::new(Translations::from_env()),
+        audit,
+        modules,
+        profiles,
+    };
+
+    let app = router
+        .with_state(state)
+        .layer(middleware::from_fn_with_state(consumers, consumers::middleware))
+        .layer(middleware::from_fn_with_state(Arc::new(TrustConfig::from_env()), trust::middleware))
+        .layer(middleware::from_fn_with_state(Arc::new(CachePolicy::from_env()), cache::middleware))
         .layer(cors);
 
+    // Outermost, so rejected and preflight requests show up too
+    let app = match AccessLogFormat::from_env() {
+        Some(format) => app.layer(middleware::from_fn_with_state(Arc::new(format), access_log::middleware)),
+        None => app,
+    };
+
+    // Before anything that looks at the client address
+    let app = match TrustedProxies::from_env() {
+        Some(proxies) => app.layer(middleware::from_fn_with_state(Arc::new(proxies), proxy::middleware)),
+        None => app,
+    };
+
+    // gzip or brotli as the client prefers, event streams and images are left alone
+    let app = match config::env_parse("REPRESENCE_COMPRESSION", true) {
+        true => app.layer(CompressionLayer::new()),
+        false => app,
+    };
+
     (app, broadcaster)
 }
 
@@ -43,78 +265,601 @@ async fn root() -> &'static str {
     "Represence API Server - Use /api/represence to get current presence data"
 }
 
-async fn get_presence(
-    State((shared_data, _)): State<(SharedData, Broadcaster)>
-) -> Json<OutputData> {
-    let data = shared_data.read().await;
-    Json(data.clone())
+/// Self-contained page for OBS browser sources, styled through query parameters
+async fn overlay() -> Html<&'static str> {
+    Html(include_str!("../assets/overlay.html"))
+}
+
+/// Embeddable script that injects a live-updating status element into any page
+async fn widget(headers: HeaderMap) -> Response {
+    // The script only changes with a new release
+    let etag = concat!("\"", env!("CARGO_PKG_VERSION"), "\"");
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|value| value.as_bytes() == etag.as_bytes()) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "application/javascript; charset=utf-8"), (header::ETAG, etag)],
+        include_str!("../assets/widget.js"),
+    )
+        .into_response()
+}
+
+/// Built-in debugging UI, polls /api/dashboard
+async fn dashboard() -> Html<&'static str> {
+    Html(include_str!("../assets/dashboard.html"))
+}
+
+/// Detection diagnostics backing the dashboard
+#[utoipa::path(
+    get,
+    path = "/api/dashboard",
+    tag = "diagnostics",
+    responses((status = 200, description = "Current presence, detected apps, VS Code health and recent transitions, the apps and transitions only with a friend token", body = Object))
+)]
+pub async fn dashboard_data(State(state): State<AppState>, presence: Visible) -> Json<Value> {
+    let mut diagnostics = state.diagnostics.read().await.clone();
+    if presence.trust() == Trust::Public {
+        diagnostics.detected_apps.clear();
+        diagnostics.recent.clear();
+    }
+    let presence = presence.current();
+
+    Json(serde_json::json!({
+        "presence": *presence,
+        "detected_apps": diagnostics.detected_apps,
+        "last_scan": diagnostics.last_scan,
+        "vscode": diagnostics.vscode,
+        "websocket_clients": state.websocket_clients.load(Ordering::Relaxed),
+        "recent": diagnostics.recent,
+    }))
+}
+
+/// Current presence data
+#[utoipa::path(
+    get,
+    path = "/api/represence",
+    tag = "presence",
+    params(LangParams),
+    responses(
+        (status = 200, description = "Current presence", body = OutputData),
+        (status = 429, description = "Rate limited, see Retry-After"),
+    )
+)]
+pub async fn get_presence(
+    State(state): State<AppState>,
+    presence: Visible,
+    Query(lang): Query<LangParams>,
+    headers: HeaderMap,
+) -> Json<Arc<OutputData>> {
+    state.consumers.refresh(&state.diagnostics).await;
+    Json(state.localized(&presence, &lang, &headers))
+}
+
+/// Set the presence from outside while the process list is unavailable (push-only mode)
+#[utoipa::path(
+    post,
+    path = "/api/represence",
+    tag = "presence",
+    request_body = OutputData,
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Presence accepted"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "No admin token configured"),
+        (status = 409, description = "Presence comes from the local process scan"),
+        (status = 413, description = "Status text too long"),
+    )
+)]
+pub async fn push_presence(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(data): Json<OutputData>,
+) -> Response {
+    if let Err(status) = check_admin(&headers) {
+        return status.into_response();
+    }
+    if !state.diagnostics.read().await.push_only {
+        return (StatusCode::CONFLICT, Json(serde_json::json!({ "error": "presence comes from the local process scan" }))).into_response();
+    }
+    if data.text.len() > hub::MAX_TEXT_LEN {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    if **state.shared_data.load() != data {
+        state.diagnostics.write().await.record_transition(&data.text);
+        if let Some(history) = state.history.clone() {
+            let text = data.text.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = history.record(chrono::Utc::now(), &text, None, None) {
+                    eprintln!("Failed to record history: {}", e);
+                }
+            });
+        }
+        publish(&state.shared_data, &state.broadcaster, data);
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Just the status string, for terminal prompts and scripts
+#[utoipa::path(
+    get,
+    path = "/api/represence.txt",
+    tag = "presence",
+    params(LangParams),
+    responses((status = 200, description = "Status text followed by a newline", body = String, content_type = "text/plain"))
+)]
+pub async fn get_presence_text(
+    State(state): State<AppState>,
+    presence: Visible,
+    Query(lang): Query<LangParams>,
+    headers: HeaderMap,
+) -> String {
+    state.consumers.refresh(&state.diagnostics).await;
+    format!("{}\n", state.localized(&presence, &lang, &headers).text)
+}
+
+/// shields.io endpoint badge payload
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShieldsBadge {
+    pub schema_version: u8,
+    pub label: String,
+    pub message: String,
+    pub color: String,
+}
+
+/// shields.io endpoint badge, e.g. https://img.shields.io/endpoint?url=https://host/api/shields
+#[utoipa::path(
+    get,
+    path = "/api/shields",
+    tag = "presence",
+    responses((status = 200, description = "shields.io endpoint badge JSON", body = ShieldsBadge))
+)]
+pub async fn get_shields(presence: Visible) -> Json<ShieldsBadge> {
+    let data = presence.current();
+    // shields.io takes hex colors without the `#`
+    let color = match &data.color {
+        Some(color) => color.trim_start_matches('#').to_string(),
+        None if data.text == "idle" => "lightgrey".to_string(),
+        None => "brightgreen".to_string(),
+    };
+
+    Json(ShieldsBadge {
+        schema_version: 1,
+        label: shields_label(),
+        message: data.text.clone(),
+        color,
+    })
+}
+
+fn shields_label() -> String {
+    config::env_var("REPRESENCE_SHIELDS_LABEL").unwrap_or_else(|| "status".to_string())
+}
+
+/// Self-hosted SVG badge in the shields.io flat style, tinted with the activity's accent color
+#[utoipa::path(
+    get,
+    path = "/api/badge.svg",
+    tag = "presence",
+    responses((status = 200, description = "SVG badge of the current presence", content_type = "image/svg+xml"))
+)]
+pub async fn get_badge(presence: Visible) -> Response {
+    let data = presence.current();
+    ([(header::CONTENT_TYPE, "image/svg+xml")], badge(&data)).into_response()
+}
+
+/// The badge for `data`, grey while idle
+pub fn badge(data: &OutputData) -> String {
+    let color = match &data.color {
+        Some(color) => color.clone(),
+        None if data.text == "idle" => "#9f9f9f".to_string(),
+        None => "#4c1".to_string(),
+    };
+    badge_svg(&shields_label(), &data.text, &color)
+}
+
+/// Rough width of Verdana 11px text, close enough for short badge texts
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+pub fn badge_svg(label: &str, message: &str, color: &str) -> String {
+    let (label_width, message_width) = (text_width(label), text_width(message));
+    let width = label_width + message_width;
+    let (label, message, color) = (xml_escape(label), xml_escape(message), xml_escape(color));
+    format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"##,
+            r##"<title>{label}: {message}</title>"##,
+            r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##,
+            r##"<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>"##,
+            r##"<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>"##,
+            r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##,
+            r##"<text x="{label_x}" y="14">{label}</text><text x="{message_x}" y="14">{message}</text></g></svg>"##,
+        ),
+        width = width,
+        label_width = label_width,
+        message_width = message_width,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+        label = label,
+        message = message,
+        color = color,
+    )
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryParams {
+    /// Start of the range (RFC 3339, `YYYY-MM-DD` or unix seconds), inclusive
+    from: Option<String>,
+    /// End of the range, exclusive
+    to: Option<String>,
+    /// Only events produced by apps starting with this name
+    app: Option<String>,
+    /// Page size, at most 1000
+    limit: Option<u32>,
+    /// `next_cursor` from the previous page
+    cursor: Option<String>,
+}
+
+pub fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Presence transitions from the persisted history, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/history",
+    tag = "history",
+    params(HistoryParams),
+    responses(
+        (status = 200, description = "A page of presence events", body = HistoryPage),
+        (status = 400, description = "Invalid query parameter"),
+        (status = 401, description = "Friend tokens are configured and the request carries none"),
+        (status = 404, description = "History is disabled"),
+    )
+)]
+pub async fn get_history(State(state): State<AppState>, Query(params): Query<HistoryParams>) -> Response {
+    let Some(history) = state.history.clone() else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "history is disabled" }))).into_response();
+    };
+
+    let mut query = HistoryQuery {
+        app: params.app,
+        limit: params.limit.unwrap_or(history::DEFAULT_PAGE_SIZE),
+        ..Default::default()
+    };
+
+    if let Some(from) = params.from {
+        match history::parse_time(&from) {
+            Some(from) => query.from = Some(from),
+            None => return bad_request("invalid 'from'"),
+        }
+    }
+    if let Some(to) = params.to {
+        match history::parse_time(&to) {
+            Some(to) => query.to = Some(to),
+            None => return bad_request("invalid 'to'"),
+        }
+    }
+    if let Some(cursor) = params.cursor {
+        match history::Cursor::parse(&cursor) {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => return bad_request("invalid 'cursor'"),
+        }
+    }
+
+    match tokio::task::spawn_blocking(move || history.query(&query)).await {
+        Ok(Ok(page)) => Json(page).into_response(),
+        Ok(Err(e)) => {
+            eprintln!("History query failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
 }
 
-async fn websocket_handler(
+/// Live presence updates, one JSON OutputData message per change
+#[utoipa::path(
+    get,
+    path = "/ws/represence",
+    tag = "streaming",
+    params(SubscriptionParams),
+    responses(
+        (status = 101, description = "WebSocket upgrade, sends OutputData messages limited to the subscribed fields, or JSON Patch deltas with `mode=patch`, as JSON or MessagePack with the `represence.msgpack` subprotocol"),
+        (status = 400, description = "Unknown event"),
+    )
+)]
+pub async fn websocket_handler(
     ws: WebSocketUpgrade,
-    State((shared_data, broadcaster)): State<(SharedData, Broadcaster)>
+    Query(params): Query<SubscriptionParams>,
+    State(state): State<AppState>,
+    presence: Visible,
 ) -> Response {
-    ws.on_upgrade(move |socket| websocket_connection(socket, shared_data, broadcaster))
+    let subscription = match Subscription::from_params(&params) {
+        Ok(subscription) => subscription,
+        Err(e) => return bad_request(&e),
+    };
+    let ws = ws.protocols(WireFormat::PROTOCOLS);
+    let format = WireFormat::negotiated(&ws);
+    let guard = ClientGuard::new(&state.websocket_clients);
+    ws.on_upgrade(move |socket| websocket_connection(socket, presence, subscription, format, guard))
 }
 
-async fn websocket_connection(socket: WebSocket, shared_data: SharedData, broadcaster: Broadcaster) {
-    let (mut sender, mut receiver) = socket.split();
-    let mut rx = broadcaster.subscribe();
+/// Encoding of WebSocket messages, picked through the `Sec-WebSocket-Protocol` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Text frames with JSON, the default without a subprotocol
+    Json,
+    /// Binary frames with MessagePack maps, same keys as the JSON messages
+    MessagePack,
+}
 
-    // Send current data immediately upon connection
-    {
-        let current_data = shared_data.read().await;
-        if let Ok(json) = serde_json::to_string(&*current_data) {
-            if sender.send(Message::Text(json.into())).await.is_err() {
-                return;
-            }
+impl WireFormat {
+    pub const PROTOCOLS: [&'static str; 2] = ["represence.msgpack", "represence.json"];
+
+    pub fn negotiated(ws: &WebSocketUpgrade) -> Self {
+        match ws.selected_protocol().and_then(|protocol| protocol.to_str().ok()) {
+            Some("represence.msgpack") => Self::MessagePack,
+            _ => Self::Json,
         }
     }
 
-    // Handle incoming messages and broadcast updates
-    let send_task = tokio::spawn(async move {
-        while let Ok(data) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&data) {
-                if sender.send(Message::Text(json.into())).await.is_err() {
-                    break;
-                }
-            }
+    pub fn encode(self, value: &Value) -> Message {
+        match self {
+            Self::Json => Message::Text(value.to_string().into()),
+            Self::MessagePack => Message::Binary(rmp_serde::to_vec_named(value).unwrap_or_default().into()),
         }
-    });
+    }
+}
+
+pub async fn websocket_connection(
+    mut socket: WebSocket,
+    presence: Visible,
+    mut subscription: Subscription,
+    format: WireFormat,
+    _guard: ClientGuard,
+) {
+    let mut updates = Box::pin(presence.updates());
+    let mut delivery = Delivery::default();
 
-    // Simplified receive task - just handle close and ping/pong
-    let recv_task = tokio::spawn(async move {
-        while let Some(msg) = receiver.next().await {
-            match msg {
-                Ok(Message::Close(_)) => break,
-                Ok(Message::Ping(data)) => {
-                    // Respond to ping with pong (handled automatically by axum)
-                    let _ = data;
+    // Send current data immediately upon connection
+    let Some(current) = updates.next().await else {
+        return;
+    };
+    if subscription.wants(subscription::SNAPSHOT) && send_projected(&mut socket, format, &subscription, &current, &mut delivery).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            update = updates.next() => match update {
+                Some(data) => {
+                    if subscription.wants(subscription::PRESENCE_UPDATE)
+                        && send_projected(&mut socket, format, &subscription, &data, &mut delivery).await.is_err()
+                    {
+                        break;
+                    }
                 }
-                Ok(_) => {
-                    // Ignore other message types
+                None => break,
+            },
+            msg = socket.recv() => match msg {
+                // Subscribe messages replace the filters from the query string
+                Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))) => {
+                    let handled = match message {
+                        Message::Binary(bytes) => subscription.handle_msgpack(&bytes),
+                        message => subscription.handle_message(message.to_text().unwrap_or_default()),
+                    };
+                    let sent = match handled {
+                        Ok(send_current) => {
+                            delivery.reset();
+                            if send_current {
+                                send_projected(&mut socket, format, &subscription, &presence.current(), &mut delivery).await
+                            } else {
+                                Ok(())
+                            }
+                        }
+                        Err(e) => {
+                            let error = serde_json::json!({ "type": "error", "message": e });
+                            socket.send(format.encode(&error)).await
+                        }
+                    };
+                    if sent.is_err() {
+                        break;
+                    }
                 }
-                Err(_) => break,
+                // Pings are answered by axum
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Send the subscribed part of `data`, skipping it when the client already has exactly that
+async fn send_projected(
+    socket: &mut WebSocket,
+    format: WireFormat,
+    subscription: &Subscription,
+    data: &OutputData,
+    delivery: &mut Delivery,
+) -> Result<(), axum::Error> {
+    match delivery.message(subscription.mode(), subscription.project(data)) {
+        Some(message) => socket.send(format.encode(&message)).await,
+        None => Ok(()),
+    }
+}
+
+/// Live presence updates as Server-Sent Events, for clients that can't use WebSockets (e.g. behind strict proxies)
+#[utoipa::path(
+    get,
+    path = "/sse/represence",
+    tag = "streaming",
+    responses((status = 200, description = "Event stream of OutputData JSON", content_type = "text/event-stream"))
+)]
+pub async fn sse_handler(presence: Visible) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    sse_stream(&presence).await
+}
+
+pub async fn sse_stream(presence: &Visible) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = presence.updates().filter_map(|data| async move { Event::default().json_data(data).ok().map(Ok) });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// The current presence, then every broadcast update, skipping ahead when the stream lags behind
+pub fn presence_updates(shared_data: &SharedData, broadcaster: &Broadcaster) -> impl Stream<Item = Arc<OutputData>> + Send + 'static {
+    let rx = broadcaster.subscribe();
+    let current = shared_data.load_full();
+
+    let updates = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(data) => return Some((data, rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
             }
         }
     });
+    stream::once(async move { current }).chain(updates)
+}
+
+/// Address of the connected peer, requires serving with connect info
+pub fn peer_ip(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Address of the client, from the forwarding headers when the peer is a trusted proxy
+pub fn client_ip(request: &Request) -> Option<IpAddr> {
+    match request.extensions().get::<ClientIp>() {
+        Some(ClientIp(ip)) => Some(*ip),
+        None => peer_ip(request),
+    }
+}
+
+/// Extract the token from an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
 
-    // Wait for either task to finish
-    tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+/// Admin endpoints are only served when `REPRESENCE_ADMIN_TOKEN` is set
+pub fn check_admin(headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Some(admin_token) = config::env_var("REPRESENCE_ADMIN_TOKEN") else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    match bearer_token(headers) {
+        Some(token) if constant_time_eq(token, &admin_token) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
     }
 }
 
-async fn health_check() -> Json<Value> {
+/// Compare secrets without short-circuiting on the first differing byte
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Liveness probe, answers as long as the process serves requests
+#[utoipa::path(
+    get,
+    path = "/livez",
+    tag = "diagnostics",
+    responses((status = 200, description = "Process is alive", body = String))
+)]
+pub async fn livez() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe, fails until the first detection pass completed and the listeners are bound
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "diagnostics",
+    responses(
+        (status = 200, description = "Ready to serve presence", body = Object),
+        (status = 503, description = "Still starting, with the reasons", body = Object),
+    )
+)]
+pub async fn readyz(State(state): State<AppState>) -> Response {
+    let reasons = state.diagnostics.read().await.not_ready_reasons();
+    if reasons.is_empty() {
+        Json(serde_json::json!({ "status": "ready" })).into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "status": "starting", "reasons": reasons }))).into_response()
+    }
+}
+
+/// Health check with detector status and endpoint listing
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "diagnostics",
+    responses((status = 200, description = "Service status, `degraded` when a detector hasn't succeeded recently", body = Object))
+)]
+pub async fn health_check(State(state): State<AppState>) -> Json<Value> {
+    let now = chrono::Utc::now();
+    let (scan, vscode, update_loop_lag_ms, push_only, energy_saving) = {
+        let diagnostics = state.diagnostics.read().await;
+        (
+            diagnostics.scan_health(now),
+            diagnostics.vscode_health(now),
+            diagnostics.update_loop_lag_ms,
+            diagnostics.push_only,
+            diagnostics.energy_saving,
+        )
+    };
+    let degraded = [scan.status, vscode.status].contains(&DetectorStatus::Degraded);
+
     Json(serde_json::json!({
-        "status": "healthy",
-        "timestamp": chrono::Utc::now().timestamp(),
+        "status": if degraded { "degraded" } else { "healthy" },
+        "timestamp": now.timestamp(),
         "version": env!("CARGO_PKG_VERSION"),
+        "features": features::compiled(),
+        "detectors": {
+            "process_scan": scan,
+            "vscode": vscode,
+        },
+        "broadcast_subscribers": state.broadcaster.receiver_count(),
+        "websocket_clients": state.websocket_clients.load(Ordering::Relaxed),
+        "update_loop_lag_ms": update_loop_lag_ms,
+        "push_only": push_only,
+        "energy_saving": energy_saving,
         "endpoints": {
             "presence": "/api/represence",
+            "text": "/api/represence.txt",
+            "shields": "/api/shields",
+            "badge": "/api/badge.svg",
+            "history": "/api/history",
+            "summary": "/api/summary/daily",
+            "sessions": "/api/history/sessions",
+            "heatmap": "/api/heatmap",
+            "streaks": "/api/streaks",
+            "export": "/api/history/export",
+            "audit": "/api/audit",
             "websocket": "/ws/represence",
-            "health": "/health"
+            "sse": "/sse/represence",
+            "health": "/health",
+            "livez": "/livez",
+            "readyz": "/readyz",
+            "overlay": "/overlay",
+            "widget": "/widget.js",
+            "dashboard": "/dashboard",
+            "openapi": "/openapi.json",
+            "docs": "/docs"
         }
     }))
 } 
\ No newline at end of file
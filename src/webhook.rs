@@ -0,0 +1,144 @@
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::{env_list, env_parse, env_var};
+use crate::web_server::Broadcaster;
+use crate::OutputData;
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const INITIAL_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    pub secret: Option<String>,
+    pub max_retries: u32,
+}
+
+impl WebhookConfig {
+    /// Load webhook settings from the environment, `None` when no URLs are configured
+    pub fn from_env() -> Option<Self> {
+        let urls = env_list("REPRESENCE_WEBHOOK_URLS");
+        if urls.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            urls,
+            secret: env_var("REPRESENCE_WEBHOOK_SECRET"),
+            max_retries: env_parse("REPRESENCE_WEBHOOK_MAX_RETRIES", 5),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    event: &'static str,
+    timestamp: i64,
+    data: &'a OutputData,
+}
+
+/// Start one delivery worker per configured URL, each with its own broadcast subscription
+pub fn spawn(config: WebhookConfig, broadcaster: &Broadcaster) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .user_agent(concat!("represence/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Webhooks disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    for url in config.urls.iter().cloned() {
+        let rx = broadcaster.subscribe();
+        let client = client.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            run_worker(url, rx, client, config).await;
+        });
+    }
+}
+
+async fn run_worker(
+    url: String,
+    mut rx: tokio::sync::broadcast::Receiver<Arc<OutputData>>,
+    client: reqwest::Client,
+    config: WebhookConfig,
+) {
+    loop {
+        let data = match rx.recv().await {
+            Ok(data) => data,
+            // Only the latest presence matters, skipped updates are fine
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+
+        let payload = WebhookPayload {
+            event: "presence.updated",
+            timestamp: chrono::Utc::now().timestamp(),
+            data: &data,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(_) => continue,
+        };
+
+        deliver(&client, &url, body, &config).await;
+    }
+}
+
+/// POST a payload, retrying with exponential backoff on network errors, 429 and 5xx responses
+async fn deliver(client: &reqwest::Client, url: &str, body: Vec<u8>, config: &WebhookConfig) {
+    let signature = config.secret.as_deref().map(|secret| sign(secret, &body));
+    let mut backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+
+    for attempt in 0..=config.max_retries {
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header("X-Represence-Signature", format!("sha256={}", signature));
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                let status = response.status();
+                if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    eprintln!("Webhook {} rejected delivery with {}, not retrying", url, status);
+                    return;
+                }
+                eprintln!("Webhook {} returned {} (attempt {})", url, status, attempt + 1);
+            }
+            Err(e) => eprintln!("Webhook {} failed: {} (attempt {})", url, e, attempt + 1),
+        }
+
+        if attempt < config.max_retries {
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_secs(MAX_BACKOFF_SECS));
+        }
+    }
+
+    eprintln!("Webhook {} giving up after {} retries", url, config.max_retries);
+}
+
+/// Hex-encoded HMAC-SHA256 of the request body, so receivers can verify the sender
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}